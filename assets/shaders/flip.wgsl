@@ -0,0 +1,515 @@
+// FLIP/PIC/APIC on a MAC grid, the same steps as cpu::flip. The grid is this
+// frame's bounds grid (GridParams, cell size h) with an empty cell on every
+// side; node (i, j) holds cell (i, j), the u face on its left and the v face
+// below it.
+// One step: flip_clear_main, flip_p2g_main, flip_grid_main, then
+// FlipConfig::pressure.max_iterations rounds of flip_pressure_main ->
+// flip_check_main -> flip_update_main, flip_project_main and flip_g2p_main.
+// Grid passes run over the node capacity, the others over the particles.
+// grid_finish_main (sph_density.wgsl) adds the body forces, integrate_main moves.
+// A hashed grid has no bounds, the particles then only fall.
+
+struct Particle {
+    pos: vec2<f32>,
+    vel: vec2<f32>,
+    acc: vec2<f32>,
+    rho: f32,
+    p: f32,
+    flags: u32, // bit 0: dead (left through an open wall)
+    _pad: u32,
+    normal: vec2<f32>, // surface normal, normals_main (sph_density.wgsl)
+};
+const PARTICLE_DEAD: u32 = 1u;
+
+struct ParticleBuffer {
+    data: array<Particle>, // runtime-sized array must be last
+};
+
+@group(0) @binding(0)
+var<storage, read_write> particles : ParticleBuffer;
+
+struct GridParams {
+    min_world: vec2<f32>,
+    cell_size: f32,
+    _pad0: f32,
+    dims: vec2<u32>,
+    hash_size: u32, // 0 = dense grid
+    _pad1: u32,
+};
+
+@group(0) @binding(1)
+var<uniform> grid : GridParams;
+
+struct IntegrateParams {
+    dt: f32,                  // SimClock::dt when adaptive
+    dt_min: f32,
+    dt_max: f32,
+    _pad0: f32,
+    min: vec2<f32>,           // domain
+    max: vec2<f32>,
+    modes: vec4<u32>,         // left, right, bottom, top (WallMode::id)
+    restitution: vec4<f32>,
+    dt_safety: vec4<f32>,     // AdaptiveDt cfl, force, viscosity
+    adhesion: vec4<f32>,      // Domain::wall_adhesion
+};
+
+const WALL_OPEN: u32 = 3u;
+const WALL_PERIODIC: u32 = 4u;
+
+@group(0) @binding(2)
+var<uniform> integ : IntegrateParams;
+
+// same layout as in sph_density.wgsl
+struct SphParams {
+    mass: f32,
+    rho_0: f32,
+    k: f32,
+    mu: f32,
+    gravity: vec2<f32>,
+    num_colliders: u32,
+    eos: u32,
+    speed_of_sound: f32,
+    pressure_force: u32,
+    viscosity: u32,
+    av_alpha: f32,
+    av_speed_of_sound: f32,
+    solver: u32,
+    solver_tolerance: f32,      // FlipConfig::pressure
+    solver_min_iterations: u32,
+    pcisph_delta: f32,
+    divergence_tolerance: f32,
+    divergence_min_iterations: u32,
+    pbf_scale: f32,
+    pbf_relaxation: f32,
+    pbf_artificial_pressure: f32,
+    pbf_artificial_pressure_n: f32,
+    pbf_artificial_pressure_dq: f32,
+    pbf_xsph: f32,
+    pbf_vorticity: f32,
+    iisph_relaxation: f32,
+    flip_ratio: f32,
+    flip_relaxation: f32,
+    flip_apic: u32,
+    mpm_material: u32,
+    mpm_mu: f32,
+    mpm_lambda: f32,
+    mpm_bulk_modulus: f32,
+    mpm_critical_compression: f32,
+    mpm_critical_stretch: f32,
+    mpm_hardening: f32,
+    mpm_friction: f32,
+    sound_speed: f32,           // adaptive dt (timestep.wgsl)
+    kinematic_viscosity: f32,
+    integrator: u32,            // Integrator::id (sph_density.wgsl)
+    xsph: f32,                  // XSPH epsilon (sph_density.wgsl)
+    surface_tension: f32,       // (sph_density.wgsl)
+    num_boundary: u32,          // boundary particles, 0 = none
+    boundary_table: u32,        // their hash table size
+    _pad0: u32,
+    _pad1: u32,
+    _pad2: u32,
+};
+
+@group(0) @binding(3)
+var<uniform> sph : SphParams;
+
+// GPUMacNode; the splat sums are fixed point
+struct MacNode {
+    u_sum: atomic<i32>,
+    v_sum: atomic<i32>,
+    u_weight: atomic<i32>,
+    v_weight: atomic<i32>,
+    p_sum: atomic<i32>,
+    count: atomic<u32>,
+    u: f32,
+    v: f32,
+    u_old: f32,
+    v_old: f32,
+    p: f32,
+    p_next: f32,
+};
+const SPLAT_SCALE: f32 = 65536.0;  // velocities and weights
+const PRESSURE_SCALE: f32 = 16.0;  // warm start only
+
+@group(0) @binding(4)
+var<storage, read_write> mac : array<MacNode>;
+
+// APIC velocity gradient per particle: (grad u, grad v)
+@group(0) @binding(5)
+var<storage, read_write> affine : array<vec4<f32>>;
+
+// GPUSolverStats, shared with the SPH solvers
+struct SolverStats {
+    iterations: atomic<u32>,
+    converged: atomic<u32>,
+    error_sum: atomic<u32>, // fixed point
+    count: atomic<u32>,
+    density_error: f32,
+    divergence_iterations: u32,
+    divergence_error: f32,
+    _pad: u32,
+};
+const SOLVER_ERROR_SCALE: f32 = 65536.0;
+
+@group(0) @binding(6)
+var<storage, read_write> solver_stats : SolverStats;
+
+// ---------------- grid --------------------
+
+fn mac_cells() -> vec2<i32> {
+    return vec2<i32>(grid.dims) + vec2<i32>(2, 2);
+}
+
+// world cell of node (0, 0)
+fn mac_origin() -> vec2<i32> {
+    return vec2<i32>(round(grid.min_world / grid.cell_size)) - vec2<i32>(1, 1);
+}
+
+// nodes in use, zero without bounds
+fn mac_nodes() -> u32 {
+    if grid.hash_size != 0u { return 0u; }
+    let n = mac_cells() + vec2<i32>(1, 1);
+    return min(u32(n.x * n.y), arrayLength(&mac));
+}
+
+fn node_of(c: vec2<i32>) -> u32 {
+    return u32(c.y * (mac_cells().x + 1) + c.x);
+}
+
+fn node_cell(k: u32) -> vec2<i32> {
+    let w = u32(mac_cells().x + 1);
+    return vec2<i32>(i32(k % w), i32(k / w));
+}
+
+fn in_nodes(c: vec2<i32>) -> bool {
+    return all(c >= vec2<i32>(0, 0)) && all(c <= mac_cells()) && node_of(c) < mac_nodes();
+}
+
+fn cell_of(pos: vec2<f32>) -> vec2<i32> {
+    return vec2<i32>(floor(pos / grid.cell_size)) - mac_origin();
+}
+
+// behind a wall that holds the fluid back; works outside the grid too
+fn solid(c: vec2<i32>) -> bool {
+    let centre = (vec2<f32>(mac_origin() + c) + vec2<f32>(0.5, 0.5)) * grid.cell_size;
+    for (var axis = 0u; axis < 2u; axis++) {
+        var mode = 0u;
+        if centre[axis] < integ.min[axis] {
+            mode = integ.modes[2u * axis];
+        } else if centre[axis] > integ.max[axis] {
+            mode = integ.modes[2u * axis + 1u];
+        } else {
+            continue;
+        }
+        if mode != WALL_OPEN && mode != WALL_PERIODIC { return true; }
+    }
+    return false;
+}
+
+fn fluid(c: vec2<i32>) -> bool {
+    if any(c < vec2<i32>(0, 0)) || any(c >= mac_cells()) || node_of(c) >= mac_nodes() {
+        return false;
+    }
+    return atomicLoad(&mac[node_of(c)].count) > 0u && !solid(c);
+}
+
+// zero outside the fluid
+fn pressure_at(c: vec2<i32>) -> f32 {
+    if !fluid(c) { return 0.0; }
+    return mac[node_of(c)].p;
+}
+
+fn axis_dir(axis: u32) -> vec2<i32> {
+    return select(vec2<i32>(0, 1), vec2<i32>(1, 0), axis == 0u);
+}
+
+// bilinear stencil on the faces of one axis: lower node and fraction
+struct Stencil {
+    base: vec2<i32>,
+    f: vec2<f32>,
+    offset: vec2<f32>, // face position inside its cell
+};
+
+fn stencil(pos: vec2<f32>, axis: u32) -> Stencil {
+    let offset = select(vec2<f32>(0.5, 0.0), vec2<f32>(0.0, 0.5), axis == 0u);
+    let s = pos / grid.cell_size - vec2<f32>(mac_origin()) - offset;
+    let base = floor(s);
+    return Stencil(vec2<i32>(base), s - base, offset);
+}
+
+fn corner(k: u32) -> vec2<i32> {
+    return vec2<i32>(i32(k & 1u), i32(k >> 1u));
+}
+
+fn corner_weight(st: Stencil, c: vec2<i32>) -> f32 {
+    let w = select(vec2<f32>(1.0, 1.0) - st.f, st.f, c == vec2<i32>(1, 1));
+    return w.x * w.y;
+}
+
+fn corner_grad(st: Stencil, c: vec2<i32>) -> vec2<f32> {
+    let w = select(vec2<f32>(1.0, 1.0) - st.f, st.f, c == vec2<i32>(1, 1));
+    let s = select(vec2<f32>(-1.0, -1.0), vec2<f32>(1.0, 1.0), c == vec2<i32>(1, 1));
+    return vec2<f32>(s.x * w.y, w.x * s.y) / grid.cell_size;
+}
+
+fn face_pos(st: Stencil, c: vec2<i32>) -> vec2<f32> {
+    return (vec2<f32>(mac_origin() + st.base + c) + st.offset) * grid.cell_size;
+}
+
+fn to_fixed(x: f32, scale: f32) -> i32 {
+    return i32(round(clamp(x * scale, -2.0e9, 2.0e9)));
+}
+
+// ---------------- solver stats --------------------
+
+fn solver_converged() -> bool {
+    return atomicLoad(&solver_stats.converged) != 0u;
+}
+
+fn add_solver_error(err: f32) {
+    atomicAdd(&solver_stats.error_sum, u32(round(clamp(err, 0.0, 1.0) * SOLVER_ERROR_SCALE)));
+    atomicAdd(&solver_stats.count, 1u);
+}
+
+// same as close_round in sph_density.wgsl
+@compute @workgroup_size(1)
+fn flip_check_main() {
+    if solver_converged() { return; }
+
+    let iterations = atomicLoad(&solver_stats.iterations) + 1u;
+    atomicStore(&solver_stats.iterations, iterations);
+
+    let count = max(atomicLoad(&solver_stats.count), 1u);
+    let error = f32(atomicLoad(&solver_stats.error_sum)) / SOLVER_ERROR_SCALE / f32(count);
+    solver_stats.density_error = error;
+    atomicStore(&solver_stats.error_sum, 0u);
+    atomicStore(&solver_stats.count, 0u);
+
+    if iterations >= sph.solver_min_iterations && error <= sph.solver_tolerance {
+        atomicStore(&solver_stats.converged, 1u);
+    }
+}
+
+// ---------------- passes --------------------
+
+@compute @workgroup_size(256)
+fn flip_clear_main(@builtin(global_invocation_id) gid: vec3<u32>) {
+    let k = gid.x;
+    if k == 0u {
+        atomicStore(&solver_stats.iterations, 0u);
+        atomicStore(&solver_stats.converged, 0u);
+        atomicStore(&solver_stats.error_sum, 0u);
+        atomicStore(&solver_stats.count, 0u);
+        solver_stats.density_error = 0.0;
+        solver_stats.divergence_iterations = 0u;
+        solver_stats.divergence_error = 0.0;
+    }
+    if k >= arrayLength(&mac) { return; }
+
+    atomicStore(&mac[k].u_sum, 0);
+    atomicStore(&mac[k].v_sum, 0);
+    atomicStore(&mac[k].u_weight, 0);
+    atomicStore(&mac[k].v_weight, 0);
+    atomicStore(&mac[k].p_sum, 0);
+    atomicStore(&mac[k].count, 0u);
+}
+
+@compute @workgroup_size(256)
+fn flip_p2g_main(@builtin(global_invocation_id) gid: vec3<u32>) {
+    let i = gid.x;
+    if i >= arrayLength(&particles.data) { return; }
+    let p = particles.data[i];
+    if (p.flags & PARTICLE_DEAD) != 0u || mac_nodes() == 0u { return; }
+
+    let c_p = affine[i];
+    for (var axis = 0u; axis < 2u; axis++) {
+        let st = stencil(p.pos, axis);
+        // velocity gradient of this component
+        let grad = select(c_p.zw, c_p.xy, axis == 0u);
+        for (var k = 0u; k < 4u; k++) {
+            let c = st.base + corner(k);
+            if !in_nodes(c) { continue; }
+            let w = corner_weight(st, corner(k));
+            var v = p.vel[axis];
+            if sph.flip_apic != 0u {
+                v += dot(grad, face_pos(st, corner(k)) - p.pos);
+            }
+            let n = node_of(c);
+            if axis == 0u {
+                atomicAdd(&mac[n].u_sum, to_fixed(w * v, SPLAT_SCALE));
+                atomicAdd(&mac[n].u_weight, to_fixed(w, SPLAT_SCALE));
+            } else {
+                atomicAdd(&mac[n].v_sum, to_fixed(w * v, SPLAT_SCALE));
+                atomicAdd(&mac[n].v_weight, to_fixed(w, SPLAT_SCALE));
+            }
+        }
+    }
+
+    let c = cell_of(p.pos);
+    if all(c >= vec2<i32>(0, 0)) && all(c < mac_cells()) && node_of(c) < mac_nodes() {
+        let n = node_of(c);
+        atomicAdd(&mac[n].count, 1u);
+        atomicAdd(&mac[n].p_sum, to_fixed(p.p, PRESSURE_SCALE));
+    }
+}
+
+// face velocities from the splat, gravity, walls and the pressure warm start
+@compute @workgroup_size(256)
+fn flip_grid_main(@builtin(global_invocation_id) gid: vec3<u32>) {
+    let k = gid.x;
+    if k >= mac_nodes() { return; }
+    let c = node_cell(k);
+
+    let u_weight = atomicLoad(&mac[k].u_weight);
+    let v_weight = atomicLoad(&mac[k].v_weight);
+    var u = 0.0;
+    var v = 0.0;
+    if u_weight > 0 { u = f32(atomicLoad(&mac[k].u_sum)) / f32(u_weight); }
+    if v_weight > 0 { v = f32(atomicLoad(&mac[k].v_sum)) / f32(v_weight); }
+    mac[k].u_old = u;
+    mac[k].v_old = v;
+
+    u += integ.dt * sph.gravity.x;
+    v += integ.dt * sph.gravity.y;
+    // no flow through the walls; faces inside a wall keep the splat so
+    // particles next to it still slide along
+    if solid(c - vec2<i32>(1, 0)) != solid(c) { u = 0.0; }
+    if solid(c - vec2<i32>(0, 1)) != solid(c) { v = 0.0; }
+    mac[k].u = u;
+    mac[k].v = v;
+
+    // warm start from the pressures the particles carried out of the last step
+    var p = 0.0;
+    if fluid(c) {
+        let count = f32(atomicLoad(&mac[k].count));
+        p = f32(atomicLoad(&mac[k].p_sum)) / PRESSURE_SCALE / count;
+    }
+    mac[k].p = p;
+    mac[k].p_next = p;
+}
+
+// relaxed Jacobi on  sum_n (p_n - p_i) = rho_0 dx^2 / dt div v  into p_next,
+// flip_update_main copies it once every cell has read the old pressures
+@compute @workgroup_size(256)
+fn flip_pressure_main(@builtin(global_invocation_id) gid: vec3<u32>) {
+    let k = gid.x;
+    if k >= mac_nodes() { return; }
+    if solver_converged() { return; }
+    let c = node_cell(k);
+    if !fluid(c) { return; }
+
+    let dx = grid.cell_size;
+    let dt = integ.dt;
+    let scale = dt / (sph.rho_0 * dx * dx);
+    let pi = mac[k].p;
+    let div = (mac[node_of(c + vec2<i32>(1, 0))].u - mac[k].u
+        + mac[node_of(c + vec2<i32>(0, 1))].v - mac[k].v) / dx;
+
+    var sum = 0.0;
+    var open = 0.0;
+    for (var axis = 0u; axis < 2u; axis++) {
+        let e = axis_dir(axis);
+        if !solid(c - e) {
+            sum += pressure_at(c - e);
+            open += 1.0;
+        }
+        if !solid(c + e) {
+            sum += pressure_at(c + e);
+            open += 1.0;
+        }
+    }
+
+    // divergence left after projecting with the current pressures
+    let residual = div - scale * (sum - open * pi);
+    add_solver_error(abs(residual) * dt);
+
+    var p = 0.0; // walled in
+    if open > 0.0 {
+        let jacobi = (sum - div / scale) / open;
+        let omega = sph.flip_relaxation;
+        p = (1.0 - omega) * pi + omega * jacobi;
+    }
+    mac[k].p_next = p;
+}
+
+// also after convergence, p_next then holds the last pressure
+@compute @workgroup_size(256)
+fn flip_update_main(@builtin(global_invocation_id) gid: vec3<u32>) {
+    let k = gid.x;
+    if k >= mac_nodes() { return; }
+    mac[k].p = mac[k].p_next;
+}
+
+// v -= dt / (rho_0 dx) grad p on the faces of the fluid cells
+@compute @workgroup_size(256)
+fn flip_project_main(@builtin(global_invocation_id) gid: vec3<u32>) {
+    let k = gid.x;
+    if k >= mac_nodes() { return; }
+    let c = node_cell(k);
+    let s = integ.dt / (sph.rho_0 * grid.cell_size);
+
+    let left = c - vec2<i32>(1, 0);
+    if !solid(left) && !solid(c) && (fluid(left) || fluid(c)) {
+        mac[k].u -= s * (pressure_at(c) - pressure_at(left));
+    }
+    let below = c - vec2<i32>(0, 1);
+    if !solid(below) && !solid(c) && (fluid(below) || fluid(c)) {
+        mac[k].v -= s * (pressure_at(c) - pressure_at(below));
+    }
+}
+
+// particle velocity from the grid, handed on as acc = (v_new - v) / dt
+@compute @workgroup_size(256)
+fn flip_g2p_main(@builtin(global_invocation_id) gid: vec3<u32>) {
+    let i = gid.x;
+    if i >= arrayLength(&particles.data) { return; }
+    var p = particles.data[i];
+    if (p.flags & PARTICLE_DEAD) != 0u { return; }
+    if mac_nodes() == 0u {
+        p.acc = sph.gravity;
+        particles.data[i] = p;
+        return;
+    }
+
+    var v_pic = vec2<f32>(0.0, 0.0);
+    var change = vec2<f32>(0.0, 0.0);
+    var grad_u = vec2<f32>(0.0, 0.0);
+    var grad_v = vec2<f32>(0.0, 0.0);
+    for (var axis = 0u; axis < 2u; axis++) {
+        let st = stencil(p.pos, axis);
+        for (var k = 0u; k < 4u; k++) {
+            let c = st.base + corner(k);
+            if !in_nodes(c) { continue; }
+            let n = node_of(c);
+            let w = corner_weight(st, corner(k));
+            let dw = corner_grad(st, corner(k));
+            if axis == 0u {
+                v_pic.x += w * mac[n].u;
+                change.x += w * (mac[n].u - mac[n].u_old);
+                grad_u += dw * mac[n].u;
+            } else {
+                v_pic.y += w * mac[n].v;
+                change.y += w * (mac[n].v - mac[n].v_old);
+                grad_v += dw * mac[n].v;
+            }
+        }
+    }
+
+    let ratio = sph.flip_ratio;
+    let v_new = ratio * (p.vel + change) + (1.0 - ratio) * v_pic;
+    p.acc = (v_new - p.vel) / integ.dt;
+    if sph.flip_apic != 0u {
+        affine[i] = vec4<f32>(grad_u, grad_v);
+    } else {
+        affine[i] = vec4<f32>(0.0, 0.0, 0.0, 0.0);
+    }
+
+    let c = cell_of(p.pos);
+    p.p = pressure_at(c);
+    p.rho = 0.0;
+    if all(c >= vec2<i32>(0, 0)) && all(c < mac_cells()) && node_of(c) < mac_nodes() {
+        let count = f32(atomicLoad(&mac[node_of(c)].count));
+        p.rho = sph.mass * count / (grid.cell_size * grid.cell_size);
+    }
+    particles.data[i] = p;
+}
@@ -7,8 +7,10 @@ struct U32Buf {
 };
 
 struct GridBuildParams {
-    num_cells: u32,
-    _pad: vec3<u32>,
+    num_cells: u32, // allocated capacity
+    cell_size: f32,
+    hashed: u32,    // 1 = num_cells is a fixed hash table
+    _pad: u32,
 };
 
 struct Particle {
@@ -17,7 +19,11 @@ struct Particle {
     acc: vec2<f32>,
     rho: f32,
     p: f32,
+    flags: u32, // bit 0: dead (left through an open wall)
+    _pad: u32,
+    normal: vec2<f32>, // surface normal, normals_main (sph_density.wgsl)
 };
+const PARTICLE_DEAD: u32 = 1u;
 struct ParticleBuf {
     data: array<Particle>, // runtime-sized array must be last
 }
@@ -27,16 +33,126 @@ struct GridParams {
     cell_size: f32,
     _pad0: f32,
     dims: vec2<u32>,
-    _pad1: vec2<u32>,
+    hash_size: u32, // 0 = dense grid
+    _pad1: u32,
 };
 
 struct BlockSumsBuf {
     data: array<u32>,
 };
 
+struct GridBoundsStats {
+    required_cells: u32,
+    clamped: u32,
+    _pad0: u32,
+    _pad1: u32,
+};
+
 // ---------- Module-scope shared memory for block_scan ----------
 var<workgroup> wg_s: array<u32, 256u>;
 
+// =================== Bounds (group 0) ===================
+// A single workgroup reduces min/max cell over all particles and writes
+// GridParams, so the CPU never has to know where the particles are.
+@group(0) @binding(0) var<storage, read> particles_bounds : ParticleBuf;
+@group(0) @binding(1) var<storage, read_write> grid_out : GridParams;
+@group(0) @binding(2) var<uniform> gb_bounds : GridBuildParams;
+@group(0) @binding(3) var<storage, read_write> grid_stats : GridBoundsStats;
+
+const I32_MAX: i32 = 2147483647;
+const I32_MIN: i32 = -2147483647;
+
+var<workgroup> wg_min_x: atomic<i32>;
+var<workgroup> wg_min_y: atomic<i32>;
+var<workgroup> wg_max_x: atomic<i32>;
+var<workgroup> wg_max_y: atomic<i32>;
+
+@compute @workgroup_size(256)
+fn bounds(@builtin(local_invocation_id) lid: vec3<u32>) {
+    let li = lid.x;
+
+    // hashed cells do not depend on the bounds and the table never grows
+    if gb_bounds.hashed != 0u {
+        if li == 0u {
+            grid_out.min_world = vec2<f32>(0.0, 0.0);
+            grid_out.cell_size = gb_bounds.cell_size;
+            grid_out._pad0 = 0.0;
+            grid_out.dims = vec2<u32>(0u, 0u);
+            grid_out.hash_size = gb_bounds.num_cells;
+            grid_out._pad1 = 0u;
+            grid_stats.required_cells = 0u;
+            grid_stats.clamped = 0u;
+        }
+        return;
+    }
+
+    if li == 0u {
+        atomicStore(&wg_min_x, I32_MAX);
+        atomicStore(&wg_min_y, I32_MAX);
+        atomicStore(&wg_max_x, I32_MIN);
+        atomicStore(&wg_max_y, I32_MIN);
+    }
+    workgroupBarrier();
+
+    // every thread strides over the particles and keeps a local min/max
+    let h = gb_bounds.cell_size;
+    let n = arrayLength(&particles_bounds.data);
+    var lo = vec2<i32>(I32_MAX, I32_MAX);
+    var hi = vec2<i32>(I32_MIN, I32_MIN);
+    var i = li;
+    loop {
+        if i >= n { break; }
+        let p = particles_bounds.data[i];
+        if (p.flags & PARTICLE_DEAD) == 0u {
+            let c = vec2<i32>(floor(p.pos / h));
+            lo = min(lo, c);
+            hi = max(hi, c);
+        }
+        i += 256u;
+    }
+
+    atomicMin(&wg_min_x, lo.x);
+    atomicMin(&wg_min_y, lo.y);
+    atomicMax(&wg_max_x, hi.x);
+    atomicMax(&wg_max_y, hi.y);
+    workgroupBarrier();
+
+    if li != 0u { return; }
+
+    var min_c = vec2<i32>(atomicLoad(&wg_min_x), atomicLoad(&wg_min_y));
+    var max_c = vec2<i32>(atomicLoad(&wg_max_x), atomicLoad(&wg_max_y));
+    if min_c.x > max_c.x {
+        // no live particles
+        min_c = vec2<i32>(0, 0);
+        max_c = vec2<i32>(0, 0);
+    }
+
+    let want = vec2<u32>(max(max_c - min_c + vec2<i32>(1, 1), vec2<i32>(1, 1)));
+    // in f32 so a blown-up particle cannot wrap the product around
+    let want_f = f32(want.x) * f32(want.y);
+
+    // too big for the buffers: shrink the grid from the max side until the
+    // CPU has grown the capacity (a few frames later)
+    let cap = max(gb_bounds.num_cells, 1u);
+    var dims = want;
+    var clamped = 0u;
+    if want_f > f32(cap) {
+        dims.x = min(want.x, cap);
+        dims.y = max(min(want.y, cap / dims.x), 1u);
+        clamped = 1u;
+    }
+
+    grid_out.min_world = vec2<f32>(min_c) * h;
+    grid_out.cell_size = h;
+    grid_out._pad0 = 0.0;
+    grid_out.dims = dims;
+    grid_out.hash_size = 0u;
+    grid_out._pad1 = 0u;
+
+    grid_stats.required_cells = u32(min(want_f, 4294967040.0));
+    grid_stats.clamped = clamped;
+}
+
 // =================== ClearCounts (group 0) ===================
 @group(0) @binding(0) var<storage, read_write> counts_rw: U32AtomicBuf;
 @group(0) @binding(1) var<uniform> gb: GridBuildParams;
@@ -54,8 +170,16 @@ fn clear_counts(@builtin(global_invocation_id) gid: vec3<u32>) {
 @group(0) @binding(1) var<storage, read_write> counts_hist: U32AtomicBuf;
 @group(0) @binding(2) var<uniform> grid: GridParams;
 
+// same hash as the CPU (cpu::sph2d::hash_cell)
+fn hash_cell(c: vec2<i32>, table_size: u32) -> u32 {
+    return ((u32(c.x) * 73856093u) ^ (u32(c.y) * 19349663u)) % table_size;
+}
+
 fn cell_index(p: vec2<f32>) -> u32 {
     let h = grid.cell_size;
+    if grid.hash_size != 0u {
+        return hash_cell(vec2<i32>(floor(p / h)), grid.hash_size);
+    }
     // CPU-compatible: floor(pos / h) - round(min_world / h)
     let c = vec2<i32>(floor(p / h));
     let origin = vec2<i32>(round(grid.min_world / h));
@@ -69,6 +193,7 @@ fn cell_index(p: vec2<f32>) -> u32 {
 fn histogram(@builtin(global_invocation_id) gid: vec3<u32>) {
     let i = gid.x;
     if i >= arrayLength(&particles.data) { return; }
+    if (particles.data[i].flags & PARTICLE_DEAD) != 0u { return; }
     let idx = cell_index(particles.data[i].pos);
     _ = atomicAdd(&counts_hist.data[idx], 1u);
 }
@@ -213,6 +338,9 @@ fn write_sentinel(@builtin(global_invocation_id) gid: vec3<u32>) {
 
 fn cell_index_scatter(p: vec2<f32>) -> u32 {
     let h = grid_scatter.cell_size;
+    if grid_scatter.hash_size != 0u {
+        return hash_cell(vec2<i32>(floor(p / h)), grid_scatter.hash_size);
+    }
     let c = vec2<i32>(floor(p / h));
     let origin = vec2<i32>(round(grid_scatter.min_world / h));
 
@@ -226,6 +354,7 @@ fn scatter(@builtin(global_invocation_id) gid: vec3<u32>) {
     let i = gid.x;
     let n = arrayLength(&particles_scatter.data);
     if i >= n { return; }
+    if (particles_scatter.data[i].flags & PARTICLE_DEAD) != 0u { return; }
 
     let pos = particles_scatter.data[i].pos;
     let cell = cell_index_scatter(pos);
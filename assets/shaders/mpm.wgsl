@@ -0,0 +1,455 @@
+// MLS-MPM with quadratic B-splines, the same steps as cpu::mpm. The nodes sit
+// on the corners of this frame's bounds grid (GridParams, cell size h), from
+// one cell below the lowest particle cell to two above the highest.
+// One step: mpm_clear_main, mpm_p2g_main, mpm_grid_main, mpm_g2p_main; the
+// grid passes run over the node capacity, the others over the particles.
+// grid_finish_main (sph_density.wgsl) adds the body forces, integrate_main moves.
+// A hashed grid has no bounds, the particles then only fall.
+
+struct Particle {
+    pos: vec2<f32>,
+    vel: vec2<f32>,
+    acc: vec2<f32>,
+    rho: f32,
+    p: f32,
+    flags: u32, // bit 0: dead (left through an open wall)
+    _pad: u32,
+    normal: vec2<f32>, // surface normal, normals_main (sph_density.wgsl)
+};
+const PARTICLE_DEAD: u32 = 1u;
+
+struct ParticleBuffer {
+    data: array<Particle>, // runtime-sized array must be last
+};
+
+@group(0) @binding(0)
+var<storage, read_write> particles : ParticleBuffer;
+
+struct GridParams {
+    min_world: vec2<f32>,
+    cell_size: f32,
+    _pad0: f32,
+    dims: vec2<u32>,
+    hash_size: u32, // 0 = dense grid
+    _pad1: u32,
+};
+
+@group(0) @binding(1)
+var<uniform> grid : GridParams;
+
+struct IntegrateParams {
+    dt: f32,                  // SimClock::dt when adaptive
+    dt_min: f32,
+    dt_max: f32,
+    _pad0: f32,
+    min: vec2<f32>,           // domain
+    max: vec2<f32>,
+    modes: vec4<u32>,         // left, right, bottom, top (WallMode::id)
+    restitution: vec4<f32>,
+    dt_safety: vec4<f32>,     // AdaptiveDt cfl, force, viscosity
+    adhesion: vec4<f32>,      // Domain::wall_adhesion
+};
+
+const WALL_NO_SLIP: u32 = 2u;
+const WALL_OPEN: u32 = 3u;
+const WALL_PERIODIC: u32 = 4u;
+
+@group(0) @binding(2)
+var<uniform> integ : IntegrateParams;
+
+// same layout as in sph_density.wgsl
+struct SphParams {
+    mass: f32,
+    rho_0: f32,
+    k: f32,
+    mu: f32,
+    gravity: vec2<f32>,
+    num_colliders: u32,
+    eos: u32,
+    speed_of_sound: f32,
+    pressure_force: u32,
+    viscosity: u32,
+    av_alpha: f32,
+    av_speed_of_sound: f32,
+    solver: u32,
+    solver_tolerance: f32,
+    solver_min_iterations: u32,
+    pcisph_delta: f32,
+    divergence_tolerance: f32,
+    divergence_min_iterations: u32,
+    pbf_scale: f32,
+    pbf_relaxation: f32,
+    pbf_artificial_pressure: f32,
+    pbf_artificial_pressure_n: f32,
+    pbf_artificial_pressure_dq: f32,
+    pbf_xsph: f32,
+    pbf_vorticity: f32,
+    iisph_relaxation: f32,
+    flip_ratio: f32,
+    flip_relaxation: f32,
+    flip_apic: u32,
+    mpm_material: u32,          // MpmMaterial::id
+    mpm_mu: f32,                // Lame parameters
+    mpm_lambda: f32,
+    mpm_bulk_modulus: f32,      // Fluid
+    mpm_critical_compression: f32, // Snow
+    mpm_critical_stretch: f32,
+    mpm_hardening: f32,
+    mpm_friction: f32,          // Sand: Drucker-Prager alpha
+    sound_speed: f32,           // adaptive dt (timestep.wgsl)
+    kinematic_viscosity: f32,
+    integrator: u32,            // Integrator::id (sph_density.wgsl)
+    xsph: f32,                  // XSPH epsilon (sph_density.wgsl)
+    surface_tension: f32,       // (sph_density.wgsl)
+    num_boundary: u32,          // boundary particles, 0 = none
+    boundary_table: u32,        // their hash table size
+    _pad0: u32,
+    _pad1: u32,
+    _pad2: u32,
+};
+
+const MATERIAL_FLUID: u32 = 0u;
+const MATERIAL_JELLY: u32 = 1u;
+const MATERIAL_SNOW: u32 = 2u;
+const MATERIAL_SAND: u32 = 3u;
+
+@group(0) @binding(3)
+var<uniform> sph : SphParams;
+
+// GPUMpmNode; the splat is divided by the particle mass
+struct MpmNode {
+    weight: atomic<i32>,
+    momentum_x: atomic<i32>,
+    momentum_y: atomic<i32>,
+    vel_x: f32,
+    vel_y: f32,
+    _pad: u32,
+};
+const SPLAT_SCALE: f32 = 65536.0;
+
+@group(0) @binding(4)
+var<storage, read_write> nodes : array<MpmNode>;
+
+// GPUMpmParticle
+struct MpmParticle {
+    deformation: mat2x2<f32>, // F
+    affine: mat2x2<f32>,      // C
+    plastic_j: f32,
+    _pad0: f32,
+    _pad1: f32,
+    _pad2: f32,
+};
+
+@group(0) @binding(5)
+var<storage, read_write> state : array<MpmParticle>;
+
+// GPUSolverStats, shared with the SPH solvers; MPM only clears it
+struct SolverStats {
+    iterations: u32,
+    converged: u32,
+    error_sum: u32,
+    count: u32,
+    density_error: f32,
+    divergence_iterations: u32,
+    divergence_error: f32,
+    _pad: u32,
+};
+
+@group(0) @binding(6)
+var<storage, read_write> solver_stats : SolverStats;
+
+// ---------------- grid --------------------
+
+fn mpm_size() -> vec2<i32> {
+    return vec2<i32>(grid.dims) + vec2<i32>(3, 3);
+}
+
+// world node of index (0, 0)
+fn mpm_origin() -> vec2<i32> {
+    return vec2<i32>(round(grid.min_world / grid.cell_size)) - vec2<i32>(1, 1);
+}
+
+// nodes in use, zero without bounds
+fn mpm_nodes() -> u32 {
+    if grid.hash_size != 0u { return 0u; }
+    let n = mpm_size();
+    return min(u32(n.x * n.y), arrayLength(&nodes));
+}
+
+fn node_of(c: vec2<i32>) -> u32 {
+    return u32(c.y * mpm_size().x + c.x);
+}
+
+fn in_nodes(c: vec2<i32>) -> bool {
+    return all(c >= vec2<i32>(0, 0)) && all(c < mpm_size()) && node_of(c) < mpm_nodes();
+}
+
+// base node of the 3x3 stencil and the particle offset from it in cells
+fn stencil_base(pos: vec2<f32>) -> vec2<i32> {
+    return vec2<i32>(floor(pos / grid.cell_size - vec2<f32>(0.5, 0.5))) - mpm_origin();
+}
+
+fn stencil_fx(pos: vec2<f32>) -> vec2<f32> {
+    let s = pos / grid.cell_size;
+    return s - floor(s - vec2<f32>(0.5, 0.5));
+}
+
+// quadratic B-spline weight of node o (0..2) per axis
+fn bspline(fx: vec2<f32>, o: vec2<u32>) -> f32 {
+    var w = vec2<f32>(0.0, 0.0);
+    for (var axis = 0u; axis < 2u; axis++) {
+        let x = fx[axis];
+        if o[axis] == 0u {
+            w[axis] = 0.5 * (1.5 - x) * (1.5 - x);
+        } else if o[axis] == 1u {
+            w[axis] = 0.75 - (x - 1.0) * (x - 1.0);
+        } else {
+            w[axis] = 0.5 * (x - 0.5) * (x - 0.5);
+        }
+    }
+    return w.x * w.y;
+}
+
+fn to_fixed(x: f32) -> i32 {
+    return i32(round(clamp(x * SPLAT_SCALE, -2.0e9, 2.0e9)));
+}
+
+// ---------------- constitutive models --------------------
+
+fn rotation(angle: f32) -> mat2x2<f32> {
+    let c = cos(angle);
+    let s = sin(angle);
+    return mat2x2<f32>(c, s, -s, c);
+}
+
+fn diag(d: vec2<f32>) -> mat2x2<f32> {
+    return mat2x2<f32>(d.x, 0.0, 0.0, d.y);
+}
+
+fn identity_scaled(x: f32) -> mat2x2<f32> {
+    return diag(vec2<f32>(x, x));
+}
+
+struct Svd {
+    u: mat2x2<f32>,
+    sigma: vec2<f32>,
+    v: mat2x2<f32>,
+};
+
+// same as cpu::mpm::svd: polar decomposition, then the eigenvectors of S
+fn svd(f: mat2x2<f32>) -> Svd {
+    let r = rotation(atan2(f[0].y - f[1].x, f[0].x + f[1].y));
+    let s = transpose(r) * f;
+    let a = s[0].x;
+    let b = s[1].x;
+    let d = s[1].y;
+    var v = identity_scaled(1.0);
+    if abs(b) >= 1e-9 {
+        v = rotation(0.5 * atan2(2.0 * b, a - d));
+    }
+    let sigma = transpose(v) * s * v;
+    return Svd(r * v, vec2<f32>(sigma[0].x, sigma[1].y), v);
+}
+
+// fixed corotated: tau = 2 mu (F - R) F^T + lambda J (J - 1) I
+fn corotated(f: mat2x2<f32>, mu: f32, lambda: f32) -> mat2x2<f32> {
+    let d = svd(f);
+    let r = d.u * transpose(d.v);
+    let j = determinant(f);
+    return 2.0 * mu * (f - r) * transpose(f) + identity_scaled(lambda * j * (j - 1.0));
+}
+
+// Drucker-Prager return mapping of the log strain
+fn sand_projection(eps: vec2<f32>, mu: f32, lambda: f32, alpha: f32) -> vec2<f32> {
+    let trace = eps.x + eps.y;
+    if trace >= 0.0 { return vec2<f32>(0.0, 0.0); }
+    let dev = eps - vec2<f32>(0.5 * trace, 0.5 * trace);
+    let norm = length(dev);
+    let dgamma = norm + (2.0 * lambda + 2.0 * mu) / (2.0 * mu) * trace * alpha;
+    if dgamma <= 0.0 || norm == 0.0 { return eps; }
+    return eps - dgamma / norm * dev;
+}
+
+// plastic flow into st, then the Kirchhoff stress
+fn stress(st: ptr<function, MpmParticle>) -> mat2x2<f32> {
+    let mu = sph.mpm_mu;
+    let lambda = sph.mpm_lambda;
+    let f = (*st).deformation;
+    switch sph.mpm_material {
+        case MATERIAL_FLUID: {
+            let j = determinant(f);
+            (*st).deformation = identity_scaled(sqrt(max(j, 0.0)));
+            return identity_scaled(sph.mpm_bulk_modulus * (j - 1.0));
+        }
+        case MATERIAL_SNOW: {
+            let d = svd(f);
+            let clamped = clamp(
+                d.sigma,
+                vec2<f32>(1.0 - sph.mpm_critical_compression),
+                vec2<f32>(1.0 + sph.mpm_critical_stretch),
+            );
+            (*st).plastic_j *= d.sigma.x * d.sigma.y / (clamped.x * clamped.y);
+            (*st).deformation = d.u * diag(clamped) * transpose(d.v);
+            let h = exp(min(sph.mpm_hardening * (1.0 - (*st).plastic_j), 5.0));
+            return corotated((*st).deformation, mu * h, lambda * h);
+        }
+        case MATERIAL_SAND: {
+            let d = svd(f);
+            let eps = log(max(abs(d.sigma), vec2<f32>(1e-4, 1e-4)));
+            let projected = sand_projection(eps, mu, lambda, sph.mpm_friction);
+            let clamped = exp(projected);
+            (*st).plastic_j *= d.sigma.x * d.sigma.y / (clamped.x * clamped.y);
+            (*st).deformation = d.u * diag(clamped) * transpose(d.v);
+            let t = 2.0 * mu * projected + vec2<f32>(lambda * (projected.x + projected.y));
+            return d.u * diag(t) * transpose(d.u);
+        }
+        default: { // MATERIAL_JELLY
+            return corotated(f, mu, lambda);
+        }
+    }
+}
+
+// ---------------- passes --------------------
+
+@compute @workgroup_size(256)
+fn mpm_clear_main(@builtin(global_invocation_id) gid: vec3<u32>) {
+    let k = gid.x;
+    if k == 0u {
+        solver_stats.iterations = 0u;
+        solver_stats.converged = 0u;
+        solver_stats.error_sum = 0u;
+        solver_stats.count = 0u;
+        solver_stats.density_error = 0.0;
+        solver_stats.divergence_iterations = 0u;
+        solver_stats.divergence_error = 0.0;
+    }
+    if k >= arrayLength(&nodes) { return; }
+
+    atomicStore(&nodes[k].weight, 0);
+    atomicStore(&nodes[k].momentum_x, 0);
+    atomicStore(&nodes[k].momentum_y, 0);
+}
+
+// F = (I + dt C) F, plasticity, then mass, momentum and the stress impulse
+@compute @workgroup_size(256)
+fn mpm_p2g_main(@builtin(global_invocation_id) gid: vec3<u32>) {
+    let i = gid.x;
+    if i >= arrayLength(&particles.data) || i >= arrayLength(&state) { return; }
+    var p = particles.data[i];
+    if (p.flags & PARTICLE_DEAD) != 0u || mpm_nodes() == 0u { return; }
+
+    let dt = integ.dt;
+    let dx = grid.cell_size;
+    let d_inv = 4.0 / (dx * dx);
+    let volume = sph.mass / sph.rho_0;
+
+    var st = state[i];
+    st.deformation = (identity_scaled(1.0) + dt * st.affine) * st.deformation;
+    let tau = stress(&st);
+    state[i] = st;
+
+    let j = max(determinant(st.deformation), 1e-6);
+    p.rho = sph.rho_0 / j;
+    p.p = -0.5 * (tau[0].x + tau[1].y) / j;
+    particles.data[i] = p;
+
+    // per unit particle mass
+    let affine = (-dt * volume * d_inv / sph.mass) * tau + st.affine;
+    let base = stencil_base(p.pos);
+    let fx = stencil_fx(p.pos);
+    for (var oy = 0u; oy < 3u; oy++) {
+        for (var ox = 0u; ox < 3u; ox++) {
+            let o = vec2<u32>(ox, oy);
+            let c = base + vec2<i32>(o);
+            if !in_nodes(c) { continue; }
+            let w = bspline(fx, o);
+            let dpos = (vec2<f32>(o) - fx) * dx;
+            let m = w * (p.vel + affine * dpos);
+            let n = node_of(c);
+            atomicAdd(&nodes[n].weight, to_fixed(w));
+            atomicAdd(&nodes[n].momentum_x, to_fixed(m.x));
+            atomicAdd(&nodes[n].momentum_y, to_fixed(m.y));
+        }
+    }
+}
+
+// velocity, gravity, and nodes behind a closed wall only keep motion away from it
+@compute @workgroup_size(256)
+fn mpm_grid_main(@builtin(global_invocation_id) gid: vec3<u32>) {
+    let k = gid.x;
+    if k >= mpm_nodes() { return; }
+
+    let weight = atomicLoad(&nodes[k].weight);
+    if weight <= 0 {
+        nodes[k].vel_x = 0.0;
+        nodes[k].vel_y = 0.0;
+        return;
+    }
+    let momentum = vec2<f32>(
+        f32(atomicLoad(&nodes[k].momentum_x)),
+        f32(atomicLoad(&nodes[k].momentum_y)),
+    );
+    var v = momentum / f32(weight) + integ.dt * sph.gravity;
+
+    let w = mpm_size().x;
+    let c = vec2<i32>(i32(k) % w, i32(k) / w);
+    let pos = vec2<f32>(mpm_origin() + c) * grid.cell_size;
+    for (var axis = 0u; axis < 2u; axis++) {
+        var mode = 0u;
+        var inward = 1.0;
+        if pos[axis] < integ.min[axis] {
+            mode = integ.modes[2u * axis];
+        } else if pos[axis] > integ.max[axis] {
+            mode = integ.modes[2u * axis + 1u];
+            inward = -1.0;
+        } else {
+            continue;
+        }
+        if mode == WALL_OPEN || mode == WALL_PERIODIC { continue; }
+        if mode == WALL_NO_SLIP {
+            v = vec2<f32>(0.0, 0.0);
+        } else if v[axis] * inward < 0.0 {
+            v[axis] = 0.0;
+        }
+    }
+    nodes[k].vel_x = v.x;
+    nodes[k].vel_y = v.y;
+}
+
+// velocity and C back from the grid, handed on as acc = (v_new - v) / dt
+@compute @workgroup_size(256)
+fn mpm_g2p_main(@builtin(global_invocation_id) gid: vec3<u32>) {
+    let i = gid.x;
+    if i >= arrayLength(&particles.data) || i >= arrayLength(&state) { return; }
+    var p = particles.data[i];
+    if (p.flags & PARTICLE_DEAD) != 0u { return; }
+    if mpm_nodes() == 0u {
+        p.acc = sph.gravity;
+        particles.data[i] = p;
+        return;
+    }
+
+    let dx = grid.cell_size;
+    let d_inv = 4.0 / (dx * dx);
+    let base = stencil_base(p.pos);
+    let fx = stencil_fx(p.pos);
+    var v_new = vec2<f32>(0.0, 0.0);
+    var c_new = mat2x2<f32>(0.0, 0.0, 0.0, 0.0);
+    for (var oy = 0u; oy < 3u; oy++) {
+        for (var ox = 0u; ox < 3u; ox++) {
+            let o = vec2<u32>(ox, oy);
+            let c = base + vec2<i32>(o);
+            if !in_nodes(c) { continue; }
+            let w = bspline(fx, o);
+            let dpos = (vec2<f32>(o) - fx) * dx;
+            let n = node_of(c);
+            let v = vec2<f32>(nodes[n].vel_x, nodes[n].vel_y);
+            v_new += w * v;
+            // outer product v dpos^T
+            c_new += d_inv * w * mat2x2<f32>(v * dpos.x, v * dpos.y);
+        }
+    }
+    state[i].affine = c_new;
+    p.acc = (v_new - p.vel) / integ.dt;
+    particles.data[i] = p;
+}
@@ -0,0 +1,104 @@
+#import bevy_render::view::View
+
+struct Particle {
+    pos: vec2<f32>,
+    vel: vec2<f32>,
+    acc: vec2<f32>,
+    rho: f32,
+    p: f32,
+    flags: u32, // bit 0: dead (left through an open wall)
+    _pad: u32,
+    normal: vec2<f32>, // surface normal, normals_main (sph_density.wgsl)
+};
+const PARTICLE_DEAD: u32 = 1u;
+
+struct ParticleRenderParams {
+    color: vec4<f32>,
+    range: vec2<f32>,
+    radius: f32,
+    scale: f32,
+    mode: u32,
+    _pad0: u32,
+    _pad1: u32,
+    _pad2: u32,
+};
+
+@group(0) @binding(0) var<uniform> view: View;
+@group(0) @binding(1) var<storage, read> particles: array<Particle>;
+@group(0) @binding(2) var<uniform> params: ParticleRenderParams;
+
+struct VertexOut {
+    @builtin(position) clip: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+    @location(1) color: vec4<f32>,
+};
+
+// from blue to red, same ramp as the CPU demo
+fn ramp(t_in: f32) -> vec3<f32> {
+    let t = clamp(t_in, 0.0, 1.0);
+    if t < 0.5 {
+        return vec3<f32>(0.0, t * 2.0, 1.0);
+    } else if t < 0.75 {
+        let u = (t - 0.5) / 0.25;
+        return vec3<f32>(u, 1.0, 1.0 - u);
+    }
+    let u = (t - 0.75) / 0.25;
+    return vec3<f32>(1.0, 1.0 - u, 0.0);
+}
+
+// the ramp is authored in sRGB, the target expects linear values
+fn srgb_to_linear(c: vec3<f32>) -> vec3<f32> {
+    return pow(c, vec3<f32>(2.2));
+}
+
+fn particle_color(p: Particle) -> vec4<f32> {
+    var value: f32;
+    switch params.mode {
+        case 1u: { value = p.rho; }
+        case 2u: { value = p.p; }
+        case 3u: { value = length(p.vel); }
+        case 4u: { value = length(p.normal); }
+        default: { return params.color; }
+    }
+    let span = max(params.range.y - params.range.x, 1e-6);
+    let t = (value - params.range.x) / span;
+    return vec4<f32>(srgb_to_linear(ramp(t)), 1.0);
+}
+
+@vertex
+fn vertex(
+    @builtin(vertex_index) vertex_index: u32,
+    @builtin(instance_index) instance_index: u32,
+) -> VertexOut {
+    // two triangles per particle, no vertex buffer
+    var corners = array<vec2<f32>, 6>(
+        vec2<f32>(-1.0, -1.0),
+        vec2<f32>(1.0, -1.0),
+        vec2<f32>(1.0, 1.0),
+        vec2<f32>(-1.0, -1.0),
+        vec2<f32>(1.0, 1.0),
+        vec2<f32>(-1.0, 1.0),
+    );
+    let corner = corners[vertex_index % 6u];
+    let p = particles[instance_index];
+
+    let world = (p.pos + corner * params.radius) * params.scale;
+
+    var out: VertexOut;
+    out.clip = view.clip_from_world * vec4<f32>(world, 0.0, 1.0);
+    if (p.flags & PARTICLE_DEAD) != 0u {
+        out.clip = vec4<f32>(0.0, 0.0, 0.0, 0.0); // degenerate, nothing drawn
+    }
+    out.uv = corner;
+    out.color = particle_color(p);
+    return out;
+}
+
+@fragment
+fn fragment(in: VertexOut) -> @location(0) vec4<f32> {
+    // round points
+    if dot(in.uv, in.uv) > 1.0 {
+        discard;
+    }
+    return in.color;
+}
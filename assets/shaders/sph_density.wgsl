@@ -5,7 +5,11 @@ struct Particle {
     acc: vec2<f32>,
     rho: f32,
     p: f32,
+    flags: u32, // bit 0: dead (left through an open wall)
+    _pad: u32,
+    normal: vec2<f32>, // surface normal, normals_main
 };
+const PARTICLE_DEAD: u32 = 1u;
 
 struct ParticleBuffer {
     data: array<Particle> // runtime-sized array must be last
@@ -19,7 +23,8 @@ struct GridParams {
     cell_size: f32,
     _pad0: f32,          // keep alignment in sync with Rust
     dims: vec2<u32>,
-    _pad1: vec2<u32>,
+    hash_size: u32,      // 0 = dense grid, else hashed buckets
+    _pad1: u32,
 };
 
 @group(0) @binding(1)
@@ -32,21 +37,184 @@ var<storage, read> cell_entries : array<u32>;
 var<uniform> grid : GridParams;
 
 struct IntegrateParams {
-    dt: f32,
-    x_min: f32,
-    x_max: f32,
-    bounce: f32,
+    dt: f32,                  // SimClock::dt when adaptive
+    dt_min: f32,
+    dt_max: f32,
+    _pad0: f32,
+    min: vec2<f32>,           // domain
+    max: vec2<f32>,
+    modes: vec4<u32>,         // left, right, bottom, top (WallMode::id)
+    restitution: vec4<f32>,   // Reflect walls
+    dt_safety: vec4<f32>,     // AdaptiveDt cfl, force, viscosity
+    adhesion: vec4<f32>,      // Domain::wall_adhesion
 };
 
+const WALL_REFLECT: u32 = 0u;
+const WALL_FREE_SLIP: u32 = 1u;
+const WALL_NO_SLIP: u32 = 2u;
+const WALL_OPEN: u32 = 3u;
+const WALL_PERIODIC: u32 = 4u;
+
 @group(0) @binding(4)
 var<uniform> integ : IntegrateParams;
 
+// filled from SPHState on the CPU side
+struct SphParams {
+    mass: f32,
+    rho_0: f32,
+    k: f32,
+    mu: f32,
+    gravity: vec2<f32>,
+    num_colliders: u32,
+    eos: u32,            // EquationOfState::id
+    speed_of_sound: f32, // Tait
+    pressure_force: u32, // PressureForce::id
+    viscosity: u32,      // Viscosity::id
+    av_alpha: f32,       // artificial viscosity
+    av_speed_of_sound: f32,
+    solver: u32,                // Solver::id
+    solver_tolerance: f32,      // IterativeConfig
+    solver_min_iterations: u32,
+    pcisph_delta: f32,          // times dt^2
+    divergence_tolerance: f32,  // DFSPH
+    divergence_min_iterations: u32,
+    pbf_scale: f32,             // sum |grad C|^2 of a filled neighbourhood
+    pbf_relaxation: f32,        // PbfConfig, relative to pbf_scale
+    pbf_artificial_pressure: f32,
+    pbf_artificial_pressure_n: f32,
+    pbf_artificial_pressure_dq: f32,
+    pbf_xsph: f32,
+    pbf_vorticity: f32,
+    iisph_relaxation: f32,      // IisphConfig
+    flip_ratio: f32,            // FlipConfig (flip.wgsl)
+    flip_relaxation: f32,
+    flip_apic: u32,
+    mpm_material: u32,          // MpmMaterial::id (mpm.wgsl)
+    mpm_mu: f32,
+    mpm_lambda: f32,
+    mpm_bulk_modulus: f32,
+    mpm_critical_compression: f32,
+    mpm_critical_stretch: f32,
+    mpm_hardening: f32,
+    mpm_friction: f32,
+    sound_speed: f32,           // adaptive dt (timestep.wgsl)
+    kinematic_viscosity: f32,
+    integrator: u32,            // Integrator::id
+    xsph: f32,                  // SPHState::xsph, 0 unless xsph_active
+    surface_tension: f32,       // SPHState::surface_tension
+    num_boundary: u32,          // boundary particles, 0 = none
+    boundary_table: u32,        // their hash table size
+    _pad0: u32,
+    _pad1: u32,
+    _pad2: u32,
+};
+
+const EOS_LINEAR_CLAMPED: u32 = 0u;
+const EOS_LINEAR: u32 = 1u;
+const EOS_TAIT: u32 = 2u;
+
+const PRESSURE_AVERAGED: u32 = 0u;
+const PRESSURE_SYMMETRIC: u32 = 1u;
+const VISCOSITY_LAPLACIAN: u32 = 0u;
+const VISCOSITY_ARTIFICIAL: u32 = 1u;
+const TAIT_GAMMA: f32 = 7.0;
+
+const SOLVER_WCSPH: u32 = 0u;
+const SOLVER_PCISPH: u32 = 1u;
+const SOLVER_DFSPH: u32 = 2u;
+const SOLVER_PBF: u32 = 3u;
+const SOLVER_IISPH: u32 = 4u;
+const SOLVER_FLIP: u32 = 5u;
+const SOLVER_MPM: u32 = 6u;
+
+const INTEGRATOR_SYMPLECTIC_EULER: u32 = 0u;
+const INTEGRATOR_LEAPFROG: u32 = 1u;
+const INTEGRATOR_VELOCITY_VERLET: u32 = 2u;
+const INTEGRATOR_RK2: u32 = 3u;
+
+@group(0) @binding(5)
+var<uniform> sph : SphParams;
+
+// SDF colliders (cpu::collider::Collider)
+struct Collider {
+    kind: u32,
+    data_start: u32,     // polygon points / SDF values in collider_data
+    data_len: u32,
+    grid_width: u32,     // SDF grid columns
+    position: vec2<f32>,
+    rotation: f32,
+    restitution: f32,
+    shape: vec4<f32>,    // circle (r), box (hx, hy), capsule (a, b), sdf (origin, cell, rows)
+    friction: f32,
+    radius: f32,         // capsule
+    velocity: vec2<f32>, // kinematic colliders
+    angular_velocity: f32,
+    body: i32,           // index into body_forces, -1 if not a FluidBody
+    adhesion: f32,       // Akinci adhesion beta
+    _pad0: f32,
+};
+
+const COLLIDER_CIRCLE: u32 = 0u;
+const COLLIDER_BOX: u32 = 1u;
+const COLLIDER_CAPSULE: u32 = 2u;
+const COLLIDER_POLYGON: u32 = 3u;
+const COLLIDER_SDF: u32 = 4u;
+const SDF_EPS: f32 = 1e-3;
+const SDF_FAR: f32 = 1e30; // outside a baked grid
+
+@group(0) @binding(6)
+var<storage, read> colliders : array<Collider>;
+
+@group(0) @binding(7)
+var<storage, read> collider_data : array<f32>;
+
+// fluid force on every FluidBody, fixed point (GPUBodyForce)
+struct BodyForce {
+    force_x: atomic<i32>,
+    force_y: atomic<i32>,
+    torque: atomic<i32>,
+    _pad: i32,
+};
+const BODY_FORCE_SCALE: f32 = 1024.0;
+
+@group(0) @binding(8)
+var<storage, read_write> body_forces : array<BodyForce>;
+
+// per-particle state of the iterative solvers (GPUSolverScratch)
+struct SolverScratch {
+    pos_pred: vec2<f32>,
+    acc_np: vec2<f32>,   // viscosity + gravity
+    acc_p: vec2<f32>,    // pressure (PBF: position correction, then XSPH velocity)
+    rho_pred: f32,
+    alpha: f32,          // DFSPH factor (PBF: vorticity)
+    vel0: vec2<f32>,     // velocity at the start of the step (PBF: corrected velocity)
+    kappa: f32,          // (PBF: lambda)
+    kappa_sum: f32,      // p = kappa_sum * rho
+    acc_prev: vec2<f32>, // acceleration of the last step (VelocityVerlet)
+    dt_prev: f32,        // dt of the last step (VelocityVerlet)
+    _pad: f32,
+};
+
+@group(0) @binding(9)
+var<storage, read_write> scratch : array<SolverScratch>;
+
+// convergence of the iterative solvers (GPUSolverStats)
+struct SolverStats {
+    iterations: atomic<u32>,
+    converged: atomic<u32>,
+    error_sum: atomic<u32>, // fixed point
+    count: atomic<u32>,
+    density_error: f32,
+    divergence_iterations: u32,
+    divergence_error: f32,
+    _pad: u32,
+};
+const SOLVER_ERROR_SCALE: f32 = 65536.0;
+
+@group(0) @binding(10)
+var<storage, read_write> solver_stats : SolverStats;
+
 const PI : f32 = 3.141592653589793;
-const MASS : f32 = 1.6;
-const RHO0 : f32 = 1000.0;
-const K    : f32 = 3.0;
-const MU   : f32 = 0.2;
-const G    : vec2<f32> = vec2<f32>(0.0, -9.81);
 
 // ---------------- kernels --------------------
 
@@ -90,23 +258,159 @@ fn laplacian_visc(r_len: f32) -> f32 {
     return coeff * (h - r_len);
 }
 
+// Akinci cohesion spline, same as cohesion_kernel on the CPU
+fn cohesion_kernel(r_len: f32) -> f32 {
+    let h = grid.cell_size;
+    if r_len <= 0.0 || r_len >= h {
+        return 0.0;
+    }
+
+    let h2 = h * h;
+    let h6 = h2 * h2 * h2;
+    let coeff = 35840.0 / (209.0 * PI * h6 * h2);
+    let d = h - r_len;
+    let spline = d * d * d * r_len * r_len * r_len;
+    if 2.0 * r_len > h {
+        return coeff * spline;
+    }
+    return coeff * (2.0 * spline - h6 / 64.0);
+}
+
+// Akinci adhesion kernel, same as adhesion_kernel on the CPU
+fn adhesion_kernel(r_len: f32) -> f32 {
+    let h = grid.cell_size;
+    if 2.0 * r_len <= h || r_len >= h {
+        return 0.0;
+    }
+    return 0.007 / pow(h, 3.25) * pow(-4.0 * r_len * r_len / h + 6.0 * r_len - 2.0 * h, 0.25);
+}
+
 // density 
 
 fn cell_of_pos(pos: vec2<f32>) -> vec2<i32> {
     // cell index for the position (like CPU's floor(pos / h))
-    let c = vec2<i32>(floor(pos / grid.cell_size));
+    return vec2<i32>(floor(pos / grid.cell_size));
+}
+
+// same hash as the CPU (cpu::sph2d::hash_cell)
+fn hash_cell(c: vec2<i32>, table_size: u32) -> u32 {
+    return ((u32(c.x) * 73856093u) ^ (u32(c.y) * 19349663u)) % table_size;
+}
+
+// [start, end) of the entries for cell c; empty outside a dense grid
+fn cell_range(c: vec2<i32>) -> vec2<u32> {
+    if grid.hash_size != 0u {
+        let b = hash_cell(c, grid.hash_size);
+        return vec2<u32>(cell_starts[b], cell_starts[b + 1u]);
+    }
 
     // recover the integer min cell from the uniform using round to avoid -ε issues
     let origin = vec2<i32>(
         i32(round(grid.min_world.x / grid.cell_size)),
         i32(round(grid.min_world.y / grid.cell_size))
     );
+    let local = c - origin;
+
+    // skip cells outside the grid (no clamping -> no duplicates)
+    if local.x < 0 || local.x >= i32(grid.dims.x) || local.y < 0 || local.y >= i32(grid.dims.y) {
+        return vec2<u32>(0u, 0u);
+    }
+    let cid = u32(local.x) + u32(local.y) * grid.dims.x;
+    return vec2<u32>(cell_starts[cid], cell_starts[cid + 1u]);
+}
+
+// hashed buckets are shared by several cells; only count j in its own cell
+fn in_cell(xj: vec2<f32>, c: vec2<i32>) -> bool {
+    if grid.hash_size == 0u {
+        return true;
+    }
+    return all(cell_of_pos(xj) == c);
+}
+
+// ---------------- boundary particles --------------------
+
+// static particles on the walls and colliders (cpu::boundary), in front of
+// collider_data (pack_boundary): bucket starts, then x, y, psi in bucket order
+fn boundary_particle(k: u32) -> vec3<f32> {
+    let b = sph.boundary_table + 1u + 3u * k;
+    return vec3<f32>(collider_data[b], collider_data[b + 1u], collider_data[b + 2u]);
+}
+
+// density of the boundary particles at xi, they weigh psi instead of m
+fn boundary_density(xi: vec2<f32>) -> f32 {
+    let h2 = grid.cell_size * grid.cell_size;
+    let c0 = cell_of_pos(xi);
+    var rho = 0.0;
+    for (var oy = -1; oy <= 1; oy++) {
+        for (var ox = -1; ox <= 1; ox++) {
+            let c = c0 + vec2<i32>(ox, oy);
+            let b = hash_cell(c, sph.boundary_table);
+            for (var k = u32(collider_data[b]); k < u32(collider_data[b + 1u]); k++) {
+                let xb = boundary_particle(k);
+                let rvec = xi - xb.xy;
+                let r2 = dot(rvec, rvec);
+                // other cells can share the bucket
+                if r2 < h2 && all(cell_of_pos(xb.xy) == c) {
+                    rho += xb.z * w_poly6(r2);
+                }
+            }
+        }
+    }
+    return rho;
+}
 
-    return c - origin;
+// pressure of the boundary particles on i, each mirrors its pressure and
+// density (SPHState::accel_field_calc)
+fn boundary_pressure(xi: vec2<f32>, p_i: f32, rho_i: f32) -> vec2<f32> {
+    let h2 = grid.cell_size * grid.cell_size;
+    let c0 = cell_of_pos(xi);
+    var acc = vec2<f32>(0.0, 0.0);
+    for (var oy = -1; oy <= 1; oy++) {
+        for (var ox = -1; ox <= 1; ox++) {
+            let c = c0 + vec2<i32>(ox, oy);
+            let b = hash_cell(c, sph.boundary_table);
+            for (var k = u32(collider_data[b]); k < u32(collider_data[b + 1u]); k++) {
+                let xb = boundary_particle(k);
+                let rvec = xi - xb.xy;
+                if dot(rvec, rvec) < h2 && all(cell_of_pos(xb.xy) == c) {
+                    let a_p = pressure_accel(p_i, rho_i, p_i, rho_i, grad_spiky_kernel(rvec));
+                    acc += xb.z / sph.mass * a_p;
+                }
+            }
+        }
+    }
+    return acc;
+}
+
+// periodic axes: shift that moves xi next to the far side of the domain,
+// 0 if the axis does not wrap or xi is not within h of an edge
+// (same as Periodicity::images on the CPU)
+fn image_shift(xi: vec2<f32>) -> vec2<f32> {
+    let h = grid.cell_size;
+    var s = vec2<f32>(0.0, 0.0);
+    for (var axis = 0u; axis < 2u; axis++) {
+        let periodic = integ.modes[2u * axis] == WALL_PERIODIC
+            && integ.modes[2u * axis + 1u] == WALL_PERIODIC;
+        if !periodic { continue; }
+        let lo = integ.min[axis];
+        let hi = integ.max[axis];
+        if xi[axis] - lo < h {
+            s[axis] = hi - lo;
+        } else if hi - xi[axis] < h {
+            s[axis] = lo - hi;
+        }
+    }
+    return s;
 }
 
-fn cell_id(ix: i32, iy: i32) -> u32 {
-    return u32(ix) + u32(iy) * grid.dims.x;
+// image m (0..4) of the search position; bit 0 shifts x, bit 1 shifts y.
+// returns false for images that are not needed
+fn image_pos(xi: vec2<f32>, s: vec2<f32>, m: u32, out: ptr<function, vec2<f32>>) -> bool {
+    let use_x = (m & 1u) != 0u;
+    let use_y = (m & 2u) != 0u;
+    if (use_x && s.x == 0.0) || (use_y && s.y == 0.0) { return false; }
+    *out = xi + vec2<f32>(select(0.0, s.x, use_x), select(0.0, s.y, use_y));
+    return true;
 }
 
 @compute @workgroup_size(256)
@@ -116,62 +420,243 @@ fn main(@builtin(global_invocation_id) gid: vec3<u32>) {
     let h2 = h * h;
     let n = arrayLength(&particles.data);
     if i >= n { return; }
+    if (particles.data[i].flags & PARTICLE_DEAD) != 0u { return; }
 
-    let xi = particles.data[i].pos;
+    let x0 = particles.data[i].pos;
     var rho: f32 = 0.0;
 
-    let c0 = cell_of_pos(xi);
-    let nx = i32(grid.dims.x);
-    let ny = i32(grid.dims.y);
+    // periodic images see the neighbours across the domain edge
+    let shift = image_shift(x0);
+    for (var m = 0u; m < 4u; m++) {
+        var xi = x0;
+        if !image_pos(x0, shift, m, &xi) { continue; }
+        let c0 = cell_of_pos(xi);
 
-    var oy: i32 = -1;
-    loop {
-        if oy > 1 { break; }
-        var ox: i32 = -1;
+        var oy: i32 = -1;
         loop {
-            if ox > 1 { break; }
-
-            let cx_i = c0.x + ox;
-            let cy_i = c0.y + oy;
+            if oy > 1 { break; }
+            var ox: i32 = -1;
+            loop {
+                if ox > 1 { break; }
 
-            // skip cells outside the grid (no clamping -> no duplicates)
-            if cx_i >= 0 && cx_i < nx && cy_i >= 0 && cy_i < ny {
-                let cid = cell_id(cx_i, cy_i);
+                let c = c0 + vec2<i32>(ox, oy);
+                let range = cell_range(c);
 
-                let start = cell_starts[cid];
-                let end = cell_starts[cid + 1u];
-
-                var k = start;
+                var k = range.x;
                 loop {
-                    if k >= end { break; }
+                    if k >= range.y { break; }
                     let j = cell_entries[k];
-                    let rvec = xi - particles.data[j].pos;
+                    let xj = particles.data[j].pos;
+                    let rvec = xi - xj;
                     let r2 = dot(rvec, rvec);
-                    if r2 < h2 {
-                        rho += MASS * w_poly6(r2);
+                    if r2 < h2 && in_cell(xj, c) {
+                        rho += sph.mass * w_poly6(r2);
                     }
                     k = k + 1u;
                 }
-            }
 
-            ox = ox + 1;
+                ox = ox + 1;
+            }
+            oy = oy + 1;
+        }
+        if sph.num_boundary > 0u {
+            rho += boundary_density(xi);
         }
-        oy = oy + 1;
     }
 
     particles.data[i].rho = rho;
 }
 
+// same as EquationOfState::pressure on the CPU
+fn eos_pressure(rho: f32) -> f32 {
+    switch sph.eos {
+        case EOS_LINEAR: {
+            return sph.k * (rho - sph.rho_0);
+        }
+        case EOS_TAIT: {
+            let b = sph.rho_0 * sph.speed_of_sound * sph.speed_of_sound / TAIT_GAMMA;
+            return b * (pow(rho / sph.rho_0, TAIT_GAMMA) - 1.0);
+        }
+        default: {
+            // linear clamped: no tension
+            return max(0.0, sph.k * (rho - sph.rho_0));
+        }
+    }
+}
+
 @compute @workgroup_size(256)
 fn pressure_main(@builtin(global_invocation_id) gid: vec3<u32>) {
     let i = gid.x;
     let n = arrayLength(&particles.data);
     if i >= n { return; }
+    if (particles.data[i].flags & PARTICLE_DEAD) != 0u { return; }
 
     let rho_i = particles.data[i].rho;
-    // CPU clamps to non-negative
-    let p_i = max(0.0, K * (rho_i - RHO0));
-    particles.data[i].p = p_i;
+    particles.data[i].p = eos_pressure(rho_i);
+}
+
+// outward unit normal of a collider (zero if the gradient vanishes)
+fn collider_normal(c: Collider, x: vec2<f32>) -> vec2<f32> {
+    let dx = vec2<f32>(SDF_EPS, 0.0);
+    let dy = vec2<f32>(0.0, SDF_EPS);
+    let grad = vec2<f32>(
+        collider_distance(c, x + dx) - collider_distance(c, x - dx),
+        collider_distance(c, x + dy) - collider_distance(c, x - dy)
+    );
+    if dot(grad, grad) == 0.0 { return vec2<f32>(0.0, 0.0); }
+    return normalize(grad);
+}
+
+// two-way coupling (RigidBody::fluid_accel on the CPU): the closest surface
+// point acts like a particle with the same pressure and density. The reaction
+// goes to body_forces.
+// pressure acceleration on i from j (SPHState::pressure_accel)
+fn pressure_accel(p_i: f32, rho_i: f32, p_j: f32, rho_j: f32, grad: vec2<f32>) -> vec2<f32> {
+    if sph.pressure_force == PRESSURE_SYMMETRIC {
+        return -sph.mass * (p_i / (rho_i * rho_i) + p_j / (rho_j * rho_j)) * grad;
+    }
+    return -sph.mass * (p_i + p_j) / (2.0 * rho_j) * grad;
+}
+
+// viscous acceleration on i from j, r = x_i - x_j, v_ij = v_i - v_j
+// (SPHState::viscosity_accel)
+fn viscosity_accel(r: vec2<f32>, v_ij: vec2<f32>, rho_i: f32, rho_j: f32, grad: vec2<f32>) -> vec2<f32> {
+    let h = grid.cell_size;
+    if sph.viscosity == VISCOSITY_ARTIFICIAL {
+        let vr = dot(v_ij, r);
+        if vr >= 0.0 { return vec2<f32>(0.0, 0.0); } // separating
+        let mu_ij = h * vr / (dot(r, r) + 0.01 * h * h);
+        let pi_ij = -sph.av_alpha * sph.av_speed_of_sound * mu_ij / (0.5 * (rho_i + rho_j));
+        return -sph.mass * pi_ij * grad;
+    }
+    return -sph.mu * sph.mass * v_ij / rho_j * laplacian_visc(length(r));
+}
+
+fn body_accel(c: Collider, xi: vec2<f32>, vi: vec2<f32>, rho_i: f32, p_i: f32) -> vec2<f32> {
+    let h = grid.cell_size;
+    let d = collider_distance(c, xi);
+    if d >= h || rho_i <= 0.0 { return vec2<f32>(0.0, 0.0); }
+
+    let n = collider_normal(c, xi);
+    let r = n * max(d, 0.01 * h);
+    let surface = xi - n * d;
+    let arm = surface - c.position;
+    let wall = c.velocity + c.angular_velocity * vec2<f32>(-arm.y, arm.x);
+
+    let grad = grad_spiky_kernel(r);
+    let a_p = pressure_accel(p_i, rho_i, p_i, rho_i, grad);
+    let a_v = viscosity_accel(r, vi - wall, rho_i, rho_i, grad);
+    let a = a_p + a_v + adhesion_accel(c.adhesion, d, n);
+
+    let f = -sph.mass * a * BODY_FORCE_SCALE;
+    let b = u32(c.body);
+    atomicAdd(&body_forces[b].force_x, i32(round(f.x)));
+    atomicAdd(&body_forces[b].force_y, i32(round(f.y)));
+    atomicAdd(&body_forces[b].torque, i32(round(arm.x * f.y - arm.y * f.x)));
+    return a;
+}
+
+// adhesion of a surface at distance d with outward normal n, a row of
+// boundary particles at spacing h/4 (SPHState::adhesion_accel)
+fn adhesion_accel(adhesion: f32, d: f32, n: vec2<f32>) -> vec2<f32> {
+    let h = grid.cell_size;
+    if adhesion == 0.0 || d <= 0.0 || d >= h {
+        return vec2<f32>(0.0, 0.0);
+    }
+    let dx = 0.25 * h;
+    var sum = 0.0;
+    for (var k = -3; k <= 3; k++) {
+        let s = f32(k) * dx;
+        let r = sqrt(d * d + s * s);
+        sum += adhesion_kernel(r) * d / r;
+    }
+    return -adhesion * sph.rho_0 * dx * dx * sum * n;
+}
+
+// adhesion of the domain walls and the colliders that are not bodies
+// (SPHState::boundary_adhesion); bodies add theirs in body_accel
+fn boundary_adhesion(xi: vec2<f32>) -> vec2<f32> {
+    var acc = vec2<f32>(0.0, 0.0);
+    for (var axis = 0u; axis < 2u; axis++) {
+        var n = vec2<f32>(0.0, 0.0);
+        n[axis] = 1.0;
+        acc += adhesion_accel(integ.adhesion[2u * axis], xi[axis] - integ.min[axis], n);
+        acc += adhesion_accel(integ.adhesion[2u * axis + 1u], integ.max[axis] - xi[axis], -n);
+    }
+    for (var c = 0u; c < sph.num_colliders; c++) {
+        let col = colliders[c];
+        if col.body >= 0 || col.adhesion == 0.0 {
+            continue;
+        }
+        let d = collider_distance(col, xi);
+        if d < grid.cell_size {
+            acc += adhesion_accel(col.adhesion, d, collider_normal(col, xi));
+        }
+    }
+    return acc;
+}
+
+// cohesion and curvature on i from j (Akinci et al. 2013), same as
+// SPHState::surface_tension_accel; rvec = x_i - x_j, dn = n_i - n_j
+fn surface_tension_accel(rvec: vec2<f32>, rhoi: f32, rhoj: f32, dn: vec2<f32>) -> vec2<f32> {
+    let r_len = length(rvec);
+    if r_len < EPS {
+        return vec2<f32>(0.0, 0.0);
+    }
+    let k_ij = 2.0 * sph.rho_0 / (rhoi + rhoj);
+    let cohesion = sph.mass * cohesion_kernel(r_len) * rvec / r_len;
+    return -sph.surface_tension * k_ij * (cohesion + dn);
+}
+
+// n_i = h sum m / rho_j grad W into Particle::normal, pointing into the fluid
+// at the surface. Runs after the density pass when the surface tension or
+// the Surface colour mode needs it
+@compute @workgroup_size(256)
+fn normals_main(@builtin(global_invocation_id) gid: vec3<u32>) {
+    let i = gid.x;
+    let h = grid.cell_size;
+    let h2 = h * h;
+    if i >= arrayLength(&particles.data) { return; }
+    if (particles.data[i].flags & PARTICLE_DEAD) != 0u { return; }
+
+    let x0 = particles.data[i].pos;
+    var n = vec2<f32>(0.0, 0.0);
+
+    let shift = image_shift(x0);
+    for (var m = 0u; m < 4u; m++) {
+        var xi = x0;
+        if !image_pos(x0, shift, m, &xi) { continue; }
+        let c0 = cell_of_pos(xi);
+
+        var oy: i32 = -1;
+        loop {
+            if oy > 1 { break; }
+            var ox: i32 = -1;
+            loop {
+                if ox > 1 { break; }
+
+                let c = c0 + vec2<i32>(ox, oy);
+                let range = cell_range(c);
+
+                var k = range.x;
+                loop {
+                    if k >= range.y { break; }
+                    let j = cell_entries[k];
+                    let xj = particles.data[j].pos;
+                    let rvec = xi - xj;
+                    if dot(rvec, rvec) < h2 && in_cell(xj, c) {
+                        n += sph.mass / particles.data[j].rho * grad_spiky_kernel(rvec);
+                    }
+                    k = k + 1u;
+                }
+
+                ox = ox + 1;
+            }
+            oy = oy + 1;
+        }
+    }
+
+    particles.data[i].normal = h * n;
 }
 
 @compute @workgroup_size(256)
@@ -181,40 +666,41 @@ fn forces_main(@builtin(global_invocation_id) gid: vec3<u32>) {
     let h2 = h * h;
     let n = arrayLength(&particles.data);
     if i >= n { return; }
+    if (particles.data[i].flags & PARTICLE_DEAD) != 0u { return; }
 
-    let xi = particles.data[i].pos;
+    let x0 = particles.data[i].pos;
     let vi = particles.data[i].vel;
     let pi = particles.data[i].p;
+    let rhoi = particles.data[i].rho;
+    let ni = particles.data[i].normal;
 
     var acc_i: vec2<f32> = vec2<f32>(0.0, 0.0);
 
-    // --- 3×3 neighbor cells (same as density) ---
-    let c0 = cell_of_pos(xi);
-    let nx = i32(grid.dims.x);
-    let ny = i32(grid.dims.y);
+    let shift = image_shift(x0);
+    for (var m = 0u; m < 4u; m++) {
+        var xi = x0;
+        if !image_pos(x0, shift, m, &xi) { continue; }
 
-    var oy: i32 = -1;
-    loop {
-        if oy > 1 { break; }
-        var ox: i32 = -1;
-        loop {
-            if ox > 1 { break; }
+        // --- 3×3 neighbor cells (same as density) ---
+        let c0 = cell_of_pos(xi);
 
-            let cx_i = c0.x + ox;
-            let cy_i = c0.y + oy;
+        var oy: i32 = -1;
+        loop {
+            if oy > 1 { break; }
+            var ox: i32 = -1;
+            loop {
+                if ox > 1 { break; }
 
-            if cx_i >= 0 && cx_i < nx && cy_i >= 0 && cy_i < ny {
-                let cid = cell_id(cx_i, cy_i);
-                let start = cell_starts[cid];
-                let end = cell_starts[cid + 1u];
+                let c = c0 + vec2<i32>(ox, oy);
+                let range = cell_range(c);
 
-                var k = start;
+                var k = range.x;
                 loop {
-                    if k >= end { break; }
+                    if k >= range.y { break; }
                     let j = cell_entries[k];
+                    let xj = particles.data[j].pos;
 
-                    if j != i {
-                        let xj = particles.data[j].pos;
+                    if j != i && in_cell(xj, c) {
                         let vj = particles.data[j].vel;
                         let rhoj = particles.data[j].rho;
                         let pj = particles.data[j].p;
@@ -222,33 +708,294 @@ fn forces_main(@builtin(global_invocation_id) gid: vec3<u32>) {
                         let rvec = xi - xj;
                         let r2 = dot(rvec, rvec);
                         if r2 < h2 {
-                            let r_len = sqrt(max(r2, 1e-12));
-
                             let grad = grad_spiky_kernel(rvec);
-                            let a_p = -MASS * (pi + pj) / (2.0 * rhoj) * grad;
-
-                            let lap = laplacian_visc(r_len);
-                            let a_v = MU * MASS * (vj - vi) / rhoj * lap;
-
-                            acc_i += a_p + a_v;
+                            // the iterative solvers add pressure later
+                            if sph.solver == SOLVER_WCSPH {
+                                acc_i += pressure_accel(pi, rhoi, pj, rhoj, grad);
+                            }
+                            acc_i += viscosity_accel(rvec, vi - vj, rhoi, rhoj, grad);
+                            if sph.surface_tension > 0.0 {
+                                let nj = particles.data[j].normal;
+                                acc_i += surface_tension_accel(rvec, rhoi, rhoj, ni - nj);
+                            }
                         }
                     }
 
                     k = k + 1u;
                 }
-            }
 
-            ox = ox + 1;
+                ox = ox + 1;
+            }
+            oy = oy + 1;
+        }
+        // only set for Wcsph (SPHState::active_boundary)
+        if sph.num_boundary > 0u {
+            acc_i += boundary_pressure(xi, pi, rhoi);
         }
-        oy = oy + 1;
     }
 
-    // gravity
-    acc_i += G;
+    // gravity and adhesion
+    acc_i += sph.gravity + boundary_adhesion(x0);
+
+    // fluid <-> body forces (after the pressure solve otherwise)
+    if sph.solver == SOLVER_WCSPH {
+        acc_i += body_forces_on(x0, vi, rhoi, pi);
+    }
 
     particles.data[i].acc = acc_i;
 }
 
+fn body_forces_on(xi: vec2<f32>, vi: vec2<f32>, rho_i: f32, p_i: f32) -> vec2<f32> {
+    var acc = vec2<f32>(0.0, 0.0);
+    for (var c = 0u; c < sph.num_colliders; c++) {
+        if colliders[c].body >= 0 {
+            acc += body_accel(colliders[c], xi, vi, rho_i, p_i);
+        }
+    }
+    return acc;
+}
+
+// ---------------- colliders --------------------
+
+fn polygon_point(c: Collider, i: u32) -> vec2<f32> {
+    let k = c.data_start + 2u * i;
+    return vec2<f32>(collider_data[k], collider_data[k + 1u]);
+}
+
+// distance to the outline, negative inside (same as the CPU)
+fn sd_polygon(c: Collider, p: vec2<f32>) -> f32 {
+    let n = c.data_len / 2u;
+    if n < 3u { return SDF_FAR; }
+    var d2 = dot(p - polygon_point(c, 0u), p - polygon_point(c, 0u));
+    var s = 1.0;
+    var j = n - 1u;
+    for (var i = 0u; i < n; i++) {
+        let vi = polygon_point(c, i);
+        let vj = polygon_point(c, j);
+        let e = vj - vi;
+        let w = p - vi;
+        let b = w - e * clamp(dot(w, e) / max(dot(e, e), EPS), 0.0, 1.0);
+        d2 = min(d2, dot(b, b));
+        let c0 = p.y >= vi.y;
+        let c1 = p.y < vj.y;
+        let c2 = e.x * w.y > e.y * w.x;
+        if (c0 && c1 && c2) || (!c0 && !c1 && !c2) { s = -s; }
+        j = i;
+    }
+    return s * sqrt(d2);
+}
+
+// bilinear sample of a baked grid (SdfGrid::sample)
+fn sd_grid(c: Collider, p: vec2<f32>) -> f32 {
+    let dims = vec2<f32>(f32(c.grid_width), c.shape.w);
+    let g = (p - c.shape.xy) / c.shape.z;
+    if any(g < vec2<f32>(0.0)) || any(g > dims - 1.0) { return SDF_FAR; }
+    let cell = min(floor(g), dims - 2.0);
+    let t = g - cell;
+    let i = c.data_start + u32(cell.x) + u32(cell.y) * c.grid_width;
+    let w = c.grid_width;
+    let bottom = mix(collider_data[i], collider_data[i + 1u], t.x);
+    let top = mix(collider_data[i + w], collider_data[i + w + 1u], t.x);
+    return mix(bottom, top, t.y);
+}
+
+fn collider_distance(c: Collider, world: vec2<f32>) -> f32 {
+    // into the collider frame
+    let cs = cos(c.rotation);
+    let sn = sin(c.rotation);
+    let q = world - c.position;
+    let p = vec2<f32>(cs * q.x + sn * q.y, -sn * q.x + cs * q.y);
+
+    switch c.kind {
+        case COLLIDER_CIRCLE: {
+            return length(p) - c.shape.x;
+        }
+        case COLLIDER_BOX: {
+            let d = abs(p) - c.shape.xy;
+            return length(max(d, vec2<f32>(0.0))) + min(max(d.x, d.y), 0.0);
+        }
+        case COLLIDER_CAPSULE: {
+            let pa = p - c.shape.xy;
+            let ba = c.shape.zw - c.shape.xy;
+            let t = clamp(dot(pa, ba) / max(dot(ba, ba), EPS), 0.0, 1.0);
+            return length(pa - ba * t) - c.radius;
+        }
+        case COLLIDER_POLYGON: {
+            return sd_polygon(c, p);
+        }
+        case COLLIDER_SDF: {
+            return sd_grid(c, p);
+        }
+        default: {
+            return SDF_FAR;
+        }
+    }
+}
+
+// projects the particle out along the SDF gradient (Collider::apply)
+fn apply_collider(p: ptr<function, Particle>, c: Collider) {
+    let x = (*p).pos;
+    let d = collider_distance(c, x);
+    if d >= 0.0 { return; }
+
+    let n = collider_normal(c, x);
+    if all(n == vec2<f32>(0.0, 0.0)) { return; }
+    (*p).pos = x - n * d;
+
+    // restitution and friction act relative to the moving wall
+    let r = (*p).pos - c.position;
+    let wall = c.velocity + c.angular_velocity * vec2<f32>(-r.y, r.x);
+    let rel = (*p).vel - wall;
+    let vn = dot(rel, n);
+    if vn < 0.0 {
+        let normal = n * vn;
+        let tangent = rel - normal;
+        (*p).vel = wall + tangent * (1.0 - c.friction) - normal * c.restitution;
+    }
+}
+
+// one wall hit along `axis`; returns false if the particle has to be deleted
+fn hit_wall(p: ptr<function, Particle>, axis: u32, wall: u32, lo: f32, hi: f32) -> bool {
+    let mode = integ.modes[wall];
+    let at = select(hi, lo, (wall & 1u) == 0u); // even walls are the min side
+
+    switch mode {
+        case WALL_REFLECT: {
+            (*p).pos[axis] = at;
+            (*p).vel[axis] *= -integ.restitution[wall];
+        }
+        case WALL_FREE_SLIP: {
+            (*p).pos[axis] = at;
+            (*p).vel[axis] = 0.0;
+        }
+        case WALL_NO_SLIP: {
+            (*p).pos[axis] = at;
+            (*p).vel = vec2<f32>(0.0, 0.0);
+        }
+        case WALL_OPEN: {
+            return false;
+        }
+        case WALL_PERIODIC: {
+            let len = hi - lo;
+            let x = (*p).pos[axis] - lo;
+            (*p).pos[axis] = lo + (x - len * floor(x / len));
+        }
+        default: {}
+    }
+    return true;
+}
+
+// same as Domain::apply on the CPU: x walls first, then y
+fn apply_domain(p: ptr<function, Particle>) -> bool {
+    for (var axis = 0u; axis < 2u; axis++) {
+        let lo = integ.min[axis];
+        let hi = integ.max[axis];
+        let x = (*p).pos[axis];
+        if x < lo {
+            if !hit_wall(p, axis, 2u * axis, lo, hi) { return false; }
+        } else if x > hi {
+            if !hit_wall(p, axis, 2u * axis + 1u, lo, hi) { return false; }
+        }
+    }
+    return true;
+}
+
+// ---------------- XSPH --------------------
+// after the forces (and the solver rounds), before integrate_main:
+// xsph_main smooths the velocities into scratch.acc_np, which no solver
+// reads past its init pass, then xsph_apply_main copies them back. Two
+// passes so no thread reads a neighbour that was already smoothed
+
+// v_i + eps sum m / rho_ij (v_j - v_i) W, rho_ij the mean density
+@compute @workgroup_size(256)
+fn xsph_main(@builtin(global_invocation_id) gid: vec3<u32>) {
+    let i = gid.x;
+    let h = grid.cell_size;
+    let h2 = h * h;
+    if i >= arrayLength(&particles.data) { return; }
+    if (particles.data[i].flags & PARTICLE_DEAD) != 0u { return; }
+
+    let x0 = particles.data[i].pos;
+    let vi = particles.data[i].vel;
+    let rhoi = particles.data[i].rho;
+    var sum = vec2<f32>(0.0, 0.0);
+
+    let shift = image_shift(x0);
+    for (var m = 0u; m < 4u; m++) {
+        var xi = x0;
+        if !image_pos(x0, shift, m, &xi) { continue; }
+        let c0 = cell_of_pos(xi);
+
+        var oy: i32 = -1;
+        loop {
+            if oy > 1 { break; }
+            var ox: i32 = -1;
+            loop {
+                if ox > 1 { break; }
+
+                let c = c0 + vec2<i32>(ox, oy);
+                let range = cell_range(c);
+
+                var k = range.x;
+                loop {
+                    if k >= range.y { break; }
+                    let j = cell_entries[k];
+                    let xj = particles.data[j].pos;
+                    let rvec = xi - xj;
+                    let r2 = dot(rvec, rvec);
+                    if r2 < h2 && in_cell(xj, c) {
+                        let rho_ij = 0.5 * (rhoi + particles.data[j].rho);
+                        sum += sph.mass / rho_ij * (particles.data[j].vel - vi) * w_poly6(r2);
+                    }
+                    k = k + 1u;
+                }
+
+                ox = ox + 1;
+            }
+            oy = oy + 1;
+        }
+    }
+
+    scratch[i].acc_np = vi + sph.xsph * sum;
+}
+
+@compute @workgroup_size(256)
+fn xsph_apply_main(@builtin(global_invocation_id) gid: vec3<u32>) {
+    let i = gid.x;
+    if i >= arrayLength(&particles.data) { return; }
+    if (particles.data[i].flags & PARTICLE_DEAD) != 0u { return; }
+    particles.data[i].vel = scratch[i].acc_np;
+}
+
+// Integrator::id is 0 (symplectic Euler) for every solver but Wcsph, which
+// keeps the integrator state in the solver scratch: pos_pred and vel0 hold
+// the start of an Rk2 step, acc_prev and dt_prev the acceleration and dt of
+// the last VelocityVerlet step.
+
+// first stage of Leapfrog and Rk2, the step sub graph then runs again with
+// the forces at the new positions and ends in integrate_main. No domain or
+// colliders here (match CPU)
+@compute @workgroup_size(256)
+fn integrate_predict_main(@builtin(global_invocation_id) gid: vec3<u32>) {
+    let i = gid.x;
+    if i >= arrayLength(&particles.data) { return; }
+
+    var p = particles.data[i];
+    if (p.flags & PARTICLE_DEAD) != 0u { return; }
+
+    let dt = integ.dt;
+    scratch[i].pos_pred = p.pos;
+    scratch[i].vel0 = p.vel;
+    if sph.integrator == INTEGRATOR_LEAPFROG {
+        p.vel += 0.5 * p.acc * dt;
+        p.pos += p.vel * dt;
+    } else {
+        p.pos += 0.5 * p.vel * dt;
+        p.vel += 0.5 * p.acc * dt;
+    }
+    particles.data[i] = p;
+}
+
 @compute @workgroup_size(256)
 fn integrate_main(@builtin(global_invocation_id) gid: vec3<u32>) {
     let i = gid.x;
@@ -256,23 +1003,1105 @@ fn integrate_main(@builtin(global_invocation_id) gid: vec3<u32>) {
     if i >= n { return; }
 
     var p = particles.data[i];
+    if (p.flags & PARTICLE_DEAD) != 0u { return; }
 
-    p.vel += p.acc * integ.dt;
-    p.pos += p.vel * integ.dt;
+    let dt = integ.dt;
+    // a later switch to velocity Verlet starts over
+    if sph.integrator != INTEGRATOR_VELOCITY_VERLET {
+        scratch[i].acc_prev = vec2<f32>(0.0);
+        scratch[i].dt_prev = 0.0;
+    }
+    switch sph.integrator {
+        case INTEGRATOR_LEAPFROG: {
+            p.vel += 0.5 * p.acc * dt;
+        }
+        case INTEGRATOR_VELOCITY_VERLET: {
+            // finish the last step with the new forces and its own dt,
+            // kick, drift. dt_prev is zero before the first step
+            let dt_prev = scratch[i].dt_prev;
+            if dt_prev > 0.0 {
+                p.vel += 0.5 * (p.acc - scratch[i].acc_prev) * dt_prev;
+            }
+            p.pos += p.vel * dt + 0.5 * p.acc * dt * dt;
+            p.vel += p.acc * dt;
+            scratch[i].acc_prev = p.acc;
+            scratch[i].dt_prev = dt;
+        }
+        case INTEGRATOR_RK2: {
+            p.pos = scratch[i].pos_pred + p.vel * dt;
+            p.vel = scratch[i].vel0 + p.acc * dt;
+        }
+        default: {
+            // symplectic Euler
+            p.vel += p.acc * dt;
+            p.pos += p.vel * dt;
+        }
+    }
 
     // boundaries (match CPU)
-    if p.pos.y < 0.0 {
-        p.pos.y = 0.0;
-        p.vel.y *= integ.bounce;
-    }
-    if p.pos.x > integ.x_max {
-        p.pos.x = integ.x_max;
-        p.vel.x *= integ.bounce;
+    if !apply_domain(&p) {
+        p.flags |= PARTICLE_DEAD;
     }
-    if p.pos.x < integ.x_min {
-        p.pos.x = integ.x_min;
-        p.vel.x *= integ.bounce;
+
+    for (var c = 0u; c < sph.num_colliders; c++) {
+        apply_collider(&p, colliders[c]);
     }
 
     particles.data[i] = p;
-}
\ No newline at end of file
+}
+// ---------------- PCISPH --------------------
+// one step: main, forces_main (no pressure), pcisph_init_main, then
+// IterativeConfig::max_iterations rounds of predict -> density -> pressure ->
+// solver_check_main, pcisph_finish_main and integrate_main. Rounds after convergence
+// return right away.
+
+fn solver_converged() -> bool {
+    return atomicLoad(&solver_stats.converged) != 0u;
+}
+
+@compute @workgroup_size(256)
+fn pcisph_init_main(@builtin(global_invocation_id) gid: vec3<u32>) {
+    let i = gid.x;
+    if i == 0u {
+        reset_solver_stats();
+        solver_stats.divergence_iterations = 0u;
+        solver_stats.divergence_error = 0.0;
+    }
+    if i >= arrayLength(&particles.data) { return; }
+    if (particles.data[i].flags & PARTICLE_DEAD) != 0u { return; }
+
+    scratch[i].acc_np = particles.data[i].acc;
+    scratch[i].acc_p = vec2<f32>(0.0, 0.0);
+    particles.data[i].p = 0.0;
+}
+
+@compute @workgroup_size(256)
+fn pcisph_predict_main(@builtin(global_invocation_id) gid: vec3<u32>) {
+    let i = gid.x;
+    if i >= arrayLength(&particles.data) { return; }
+    if (particles.data[i].flags & PARTICLE_DEAD) != 0u { return; }
+    if solver_converged() { return; }
+
+    let dt = integ.dt;
+    let acc = scratch[i].acc_np + scratch[i].acc_p;
+    scratch[i].pos_pred = particles.data[i].pos + dt * (particles.data[i].vel + dt * acc);
+}
+
+// predicted density corrects the pressure; neighbours are still found from
+// the current positions (the grid was built for those)
+@compute @workgroup_size(256)
+fn pcisph_density_main(@builtin(global_invocation_id) gid: vec3<u32>) {
+    let i = gid.x;
+    let h = grid.cell_size;
+    let h2 = h * h;
+    if i >= arrayLength(&particles.data) { return; }
+    if (particles.data[i].flags & PARTICLE_DEAD) != 0u { return; }
+    if solver_converged() { return; }
+
+    let x0 = particles.data[i].pos;
+    let pred_i = scratch[i].pos_pred;
+    var rho: f32 = 0.0;
+
+    let shift = image_shift(x0);
+    for (var m = 0u; m < 4u; m++) {
+        var xi = x0;
+        if !image_pos(x0, shift, m, &xi) { continue; }
+        let c0 = cell_of_pos(xi);
+        let offset = xi - x0;
+
+        var oy: i32 = -1;
+        loop {
+            if oy > 1 { break; }
+            var ox: i32 = -1;
+            loop {
+                if ox > 1 { break; }
+
+                let c = c0 + vec2<i32>(ox, oy);
+                let range = cell_range(c);
+
+                var k = range.x;
+                loop {
+                    if k >= range.y { break; }
+                    let j = cell_entries[k];
+                    let xj = particles.data[j].pos;
+                    let rvec = xi - xj;
+                    if dot(rvec, rvec) < h2 && in_cell(xj, c) {
+                        let r_pred = pred_i + offset - scratch[j].pos_pred;
+                        rho += sph.mass * w_poly6(dot(r_pred, r_pred));
+                    }
+                    k = k + 1u;
+                }
+
+                ox = ox + 1;
+            }
+            oy = oy + 1;
+        }
+    }
+
+    scratch[i].rho_pred = rho;
+    let rho_err = rho - sph.rho_0;
+    let delta = sph.pcisph_delta / (integ.dt * integ.dt);
+    // no tension at the surface
+    particles.data[i].p = max(particles.data[i].p + delta * rho_err, 0.0);
+
+    add_solver_error(rho_err / sph.rho_0);
+}
+
+// symmetric pressure force with rho_0 (as in the derivation of delta)
+@compute @workgroup_size(256)
+fn pcisph_pressure_main(@builtin(global_invocation_id) gid: vec3<u32>) {
+    let i = gid.x;
+    let h = grid.cell_size;
+    let h2 = h * h;
+    if i >= arrayLength(&particles.data) { return; }
+    if (particles.data[i].flags & PARTICLE_DEAD) != 0u { return; }
+    if solver_converged() { return; }
+
+    let x0 = particles.data[i].pos;
+    let pi = particles.data[i].p;
+    var acc = vec2<f32>(0.0, 0.0);
+
+    let shift = image_shift(x0);
+    for (var m = 0u; m < 4u; m++) {
+        var xi = x0;
+        if !image_pos(x0, shift, m, &xi) { continue; }
+        let c0 = cell_of_pos(xi);
+
+        var oy: i32 = -1;
+        loop {
+            if oy > 1 { break; }
+            var ox: i32 = -1;
+            loop {
+                if ox > 1 { break; }
+
+                let c = c0 + vec2<i32>(ox, oy);
+                let range = cell_range(c);
+
+                var k = range.x;
+                loop {
+                    if k >= range.y { break; }
+                    let j = cell_entries[k];
+                    let xj = particles.data[j].pos;
+                    let rvec = xi - xj;
+                    if j != i && dot(rvec, rvec) < h2 && in_cell(xj, c) {
+                        let pj = particles.data[j].p;
+                        acc -= sph.mass * (pi + pj) / (sph.rho_0 * sph.rho_0)
+                            * grad_spiky_kernel(rvec);
+                    }
+                    k = k + 1u;
+                }
+
+                ox = ox + 1;
+            }
+            oy = oy + 1;
+        }
+    }
+
+    scratch[i].acc_p = acc;
+}
+
+// closes a round of an iterative solve and decides whether to stop
+fn close_round(tolerance: f32, min_iterations: u32) {
+    if solver_converged() { return; }
+
+    let iterations = atomicLoad(&solver_stats.iterations) + 1u;
+    atomicStore(&solver_stats.iterations, iterations);
+
+    let count = max(atomicLoad(&solver_stats.count), 1u);
+    let error = f32(atomicLoad(&solver_stats.error_sum)) / SOLVER_ERROR_SCALE / f32(count);
+    solver_stats.density_error = error;
+    atomicStore(&solver_stats.error_sum, 0u);
+    atomicStore(&solver_stats.count, 0u);
+
+    if iterations >= min_iterations && error <= tolerance {
+        atomicStore(&solver_stats.converged, 1u);
+    }
+}
+
+// single thread, density solves (PCISPH, DFSPH)
+@compute @workgroup_size(1)
+fn solver_check_main() {
+    close_round(sph.solver_tolerance, sph.solver_min_iterations);
+}
+
+fn add_solver_error(err: f32) {
+    atomicAdd(&solver_stats.error_sum, u32(round(clamp(err, 0.0, 1.0) * SOLVER_ERROR_SCALE)));
+    atomicAdd(&solver_stats.count, 1u);
+}
+
+fn reset_solver_stats() {
+    atomicStore(&solver_stats.iterations, 0u);
+    atomicStore(&solver_stats.converged, 0u);
+    atomicStore(&solver_stats.error_sum, 0u);
+    atomicStore(&solver_stats.count, 0u);
+}
+
+@compute @workgroup_size(256)
+fn pcisph_finish_main(@builtin(global_invocation_id) gid: vec3<u32>) {
+    let i = gid.x;
+    if i >= arrayLength(&particles.data) { return; }
+    if (particles.data[i].flags & PARTICLE_DEAD) != 0u { return; }
+
+    let p = particles.data[i];
+    var acc = scratch[i].acc_np + scratch[i].acc_p;
+    acc += body_forces_on(p.pos, p.vel, p.rho, p.p);
+    particles.data[i].acc = acc;
+}
+
+// ---------------- DFSPH --------------------
+// one step: main, dfsph_alpha_main, divergence rounds (dfsph_divergence_main ->
+// dfsph_divergence_check_main -> dfsph_update_main), forces_main (no pressure),
+// dfsph_predict_main, density rounds (dfsph_density_main -> solver_check_main ->
+// dfsph_update_main), dfsph_finish_main and integrate_main. The solves change
+// velocities; finish turns that into acc so integrate_main stays the same.
+
+@compute @workgroup_size(256)
+fn dfsph_alpha_main(@builtin(global_invocation_id) gid: vec3<u32>) {
+    let i = gid.x;
+    let h = grid.cell_size;
+    let h2 = h * h;
+    if i == 0u {
+        reset_solver_stats();
+        solver_stats.divergence_iterations = 0u;
+        solver_stats.divergence_error = 0.0;
+    }
+    if i >= arrayLength(&particles.data) { return; }
+    if (particles.data[i].flags & PARTICLE_DEAD) != 0u { return; }
+
+    let x0 = particles.data[i].pos;
+    var sum = vec2<f32>(0.0, 0.0);
+    var sum_sq: f32 = 0.0;
+
+    let shift = image_shift(x0);
+    for (var m = 0u; m < 4u; m++) {
+        var xi = x0;
+        if !image_pos(x0, shift, m, &xi) { continue; }
+        let c0 = cell_of_pos(xi);
+
+        var oy: i32 = -1;
+        loop {
+            if oy > 1 { break; }
+            var ox: i32 = -1;
+            loop {
+                if ox > 1 { break; }
+
+                let c = c0 + vec2<i32>(ox, oy);
+                let range = cell_range(c);
+
+                var k = range.x;
+                loop {
+                    if k >= range.y { break; }
+                    let j = cell_entries[k];
+                    let xj = particles.data[j].pos;
+                    let rvec = xi - xj;
+                    if dot(rvec, rvec) < h2 && in_cell(xj, c) {
+                        let g = sph.mass * grad_spiky_kernel(rvec);
+                        sum += g;
+                        sum_sq += dot(g, g);
+                    }
+                    k = k + 1u;
+                }
+
+                ox = ox + 1;
+            }
+            oy = oy + 1;
+        }
+    }
+
+    let denom = dot(sum, sum) + sum_sq;
+    var alpha = 0.0;
+    if denom > 1e-6 {
+        alpha = particles.data[i].rho / denom;
+    }
+    scratch[i].alpha = alpha;
+    scratch[i].vel0 = particles.data[i].vel;
+    scratch[i].kappa_sum = 0.0;
+}
+
+// density change over the step from the current velocities; only compression
+// is corrected. `density` adds the current density error (density solve)
+fn dfsph_source(i: u32, density: bool) {
+    let h = grid.cell_size;
+    let h2 = h * h;
+    let dt = integ.dt;
+    let x0 = particles.data[i].pos;
+    let vi = particles.data[i].vel;
+    var div: f32 = 0.0;
+
+    let shift = image_shift(x0);
+    for (var m = 0u; m < 4u; m++) {
+        var xi = x0;
+        if !image_pos(x0, shift, m, &xi) { continue; }
+        let c0 = cell_of_pos(xi);
+
+        var oy: i32 = -1;
+        loop {
+            if oy > 1 { break; }
+            var ox: i32 = -1;
+            loop {
+                if ox > 1 { break; }
+
+                let c = c0 + vec2<i32>(ox, oy);
+                let range = cell_range(c);
+
+                var k = range.x;
+                loop {
+                    if k >= range.y { break; }
+                    let j = cell_entries[k];
+                    let xj = particles.data[j].pos;
+                    let rvec = xi - xj;
+                    if dot(rvec, rvec) < h2 && in_cell(xj, c) {
+                        let vj = particles.data[j].vel;
+                        div += sph.mass * dot(vi - vj, grad_spiky_kernel(rvec));
+                    }
+                    k = k + 1u;
+                }
+
+                ox = ox + 1;
+            }
+            oy = oy + 1;
+        }
+    }
+
+    var source = dt * div;
+    if density {
+        source += particles.data[i].rho - sph.rho_0;
+    }
+    source = max(source, 0.0);
+    scratch[i].kappa = source / (dt * dt) * scratch[i].alpha;
+    add_solver_error(source / sph.rho_0);
+}
+
+@compute @workgroup_size(256)
+fn dfsph_divergence_main(@builtin(global_invocation_id) gid: vec3<u32>) {
+    let i = gid.x;
+    if i >= arrayLength(&particles.data) { return; }
+    if (particles.data[i].flags & PARTICLE_DEAD) != 0u { return; }
+    if solver_converged() { return; }
+    dfsph_source(i, false);
+}
+
+@compute @workgroup_size(256)
+fn dfsph_density_main(@builtin(global_invocation_id) gid: vec3<u32>) {
+    let i = gid.x;
+    if i >= arrayLength(&particles.data) { return; }
+    if (particles.data[i].flags & PARTICLE_DEAD) != 0u { return; }
+    if solver_converged() { return; }
+    dfsph_source(i, true);
+}
+
+// single thread, divergence solve
+@compute @workgroup_size(1)
+fn dfsph_divergence_check_main() {
+    close_round(sph.divergence_tolerance, sph.divergence_min_iterations);
+}
+
+// v_i -= dt sum m (kappa_i / rho_i + kappa_j / rho_j) grad W
+@compute @workgroup_size(256)
+fn dfsph_update_main(@builtin(global_invocation_id) gid: vec3<u32>) {
+    let i = gid.x;
+    let h = grid.cell_size;
+    let h2 = h * h;
+    if i >= arrayLength(&particles.data) { return; }
+    if (particles.data[i].flags & PARTICLE_DEAD) != 0u { return; }
+    if solver_converged() { return; }
+
+    let x0 = particles.data[i].pos;
+    let ki = scratch[i].kappa / particles.data[i].rho;
+    var dv = vec2<f32>(0.0, 0.0);
+
+    let shift = image_shift(x0);
+    for (var m = 0u; m < 4u; m++) {
+        var xi = x0;
+        if !image_pos(x0, shift, m, &xi) { continue; }
+        let c0 = cell_of_pos(xi);
+
+        var oy: i32 = -1;
+        loop {
+            if oy > 1 { break; }
+            var ox: i32 = -1;
+            loop {
+                if ox > 1 { break; }
+
+                let c = c0 + vec2<i32>(ox, oy);
+                let range = cell_range(c);
+
+                var k = range.x;
+                loop {
+                    if k >= range.y { break; }
+                    let j = cell_entries[k];
+                    let xj = particles.data[j].pos;
+                    let rvec = xi - xj;
+                    if dot(rvec, rvec) < h2 && in_cell(xj, c) {
+                        let kj = scratch[j].kappa / particles.data[j].rho;
+                        dv += sph.mass * (ki + kj) * grad_spiky_kernel(rvec);
+                    }
+                    k = k + 1u;
+                }
+
+                ox = ox + 1;
+            }
+            oy = oy + 1;
+        }
+    }
+
+    particles.data[i].vel -= integ.dt * dv;
+    scratch[i].kappa_sum += scratch[i].kappa;
+}
+
+// between the solves: viscosity and gravity from forces_main, then the
+// density solve starts with fresh stats
+@compute @workgroup_size(256)
+fn dfsph_predict_main(@builtin(global_invocation_id) gid: vec3<u32>) {
+    let i = gid.x;
+    if i == 0u {
+        solver_stats.divergence_iterations = atomicLoad(&solver_stats.iterations);
+        solver_stats.divergence_error = solver_stats.density_error;
+        reset_solver_stats();
+    }
+    if i >= arrayLength(&particles.data) { return; }
+    if (particles.data[i].flags & PARTICLE_DEAD) != 0u { return; }
+
+    particles.data[i].vel += integ.dt * particles.data[i].acc;
+    scratch[i].kappa_sum = 0.0; // only the density solve becomes p
+}
+
+@compute @workgroup_size(256)
+fn dfsph_finish_main(@builtin(global_invocation_id) gid: vec3<u32>) {
+    let i = gid.x;
+    if i >= arrayLength(&particles.data) { return; }
+    if (particles.data[i].flags & PARTICLE_DEAD) != 0u { return; }
+
+    var p = particles.data[i];
+    let v0 = scratch[i].vel0;
+    p.p = scratch[i].kappa_sum * p.rho; // kappa = p / rho
+    p.acc = (p.vel - v0) / integ.dt + body_forces_on(p.pos, v0, p.rho, p.p);
+    p.vel = v0;
+    particles.data[i] = p;
+}
+
+// ---------------- PBF --------------------
+// one step: main, forces_main (no pressure), pbf_predict_main, then
+// PbfConfig::iterations rounds of pbf_lambda_main -> solver_check_main ->
+// pbf_delta_main -> pbf_apply_main, pbf_velocity_main, pbf_xsph_main,
+// pbf_vorticity_main, pbf_finish_main and integrate_main. There is no
+// tolerance, every round runs (min_iterations = max_iterations).
+
+@compute @workgroup_size(256)
+fn pbf_predict_main(@builtin(global_invocation_id) gid: vec3<u32>) {
+    let i = gid.x;
+    if i == 0u {
+        reset_solver_stats();
+        solver_stats.divergence_iterations = 0u;
+        solver_stats.divergence_error = 0.0;
+    }
+    if i >= arrayLength(&particles.data) { return; }
+    if (particles.data[i].flags & PARTICLE_DEAD) != 0u { return; }
+
+    let p = particles.data[i];
+    let dt = integ.dt;
+    scratch[i].pos_pred = p.pos + dt * (p.vel + dt * p.acc);
+    scratch[i].kappa_sum = 0.0;
+}
+
+// artificial pressure between two predicted positions (zero when disabled)
+fn pbf_s_corr(r2: f32) -> f32 {
+    let dq = sph.pbf_artificial_pressure_dq * grid.cell_size;
+    let w_dq = w_poly6(dq * dq);
+    if w_dq <= 0.0 || sph.pbf_scale <= 0.0 {
+        return 0.0;
+    }
+    let ratio = w_poly6(r2) / w_dq;
+    return -sph.pbf_artificial_pressure * pow(ratio, sph.pbf_artificial_pressure_n) / sph.pbf_scale;
+}
+
+// lambda_i = -C_i / (sum_k |grad_k C_i|^2 + epsilon), C_i = rho_i / rho_0 - 1
+@compute @workgroup_size(256)
+fn pbf_lambda_main(@builtin(global_invocation_id) gid: vec3<u32>) {
+    let i = gid.x;
+    let h = grid.cell_size;
+    let h2 = h * h;
+    if i >= arrayLength(&particles.data) { return; }
+    if (particles.data[i].flags & PARTICLE_DEAD) != 0u { return; }
+
+    let x0 = particles.data[i].pos;
+    let pred_i = scratch[i].pos_pred;
+    let m_rho_0 = sph.mass / sph.rho_0;
+    var rho: f32 = 0.0;
+    var grad_i = vec2<f32>(0.0, 0.0);
+    var sum_sq: f32 = 0.0;
+
+    let shift = image_shift(x0);
+    for (var m = 0u; m < 4u; m++) {
+        var xi = x0;
+        if !image_pos(x0, shift, m, &xi) { continue; }
+        let c0 = cell_of_pos(xi);
+        let offset = xi - x0;
+
+        var oy: i32 = -1;
+        loop {
+            if oy > 1 { break; }
+            var ox: i32 = -1;
+            loop {
+                if ox > 1 { break; }
+
+                let c = c0 + vec2<i32>(ox, oy);
+                let range = cell_range(c);
+
+                var k = range.x;
+                loop {
+                    if k >= range.y { break; }
+                    let j = cell_entries[k];
+                    let xj = particles.data[j].pos;
+                    let rvec = xi - xj;
+                    if dot(rvec, rvec) < h2 && in_cell(xj, c) {
+                        let r_pred = pred_i + offset - scratch[j].pos_pred;
+                        rho += sph.mass * w_poly6(dot(r_pred, r_pred));
+                        let g = m_rho_0 * grad_spiky_kernel(r_pred);
+                        grad_i += g;
+                        sum_sq += dot(g, g);
+                    }
+                    k = k + 1u;
+                }
+
+                ox = ox + 1;
+            }
+            oy = oy + 1;
+        }
+    }
+
+    scratch[i].rho_pred = rho;
+    let c = max(rho / sph.rho_0 - 1.0, 0.0); // only compression
+    let epsilon = sph.pbf_relaxation * sph.pbf_scale;
+    let lambda = -c / (dot(grad_i, grad_i) + sum_sq + epsilon);
+    scratch[i].kappa = lambda;
+    scratch[i].kappa_sum += lambda;
+
+    add_solver_error(c);
+}
+
+// dx_i = m / rho_0 sum (lambda_i + lambda_j + s_corr) grad W, applied by
+// pbf_apply_main once every particle has its correction
+@compute @workgroup_size(256)
+fn pbf_delta_main(@builtin(global_invocation_id) gid: vec3<u32>) {
+    let i = gid.x;
+    let h = grid.cell_size;
+    let h2 = h * h;
+    if i >= arrayLength(&particles.data) { return; }
+    if (particles.data[i].flags & PARTICLE_DEAD) != 0u { return; }
+
+    let x0 = particles.data[i].pos;
+    let pred_i = scratch[i].pos_pred;
+    let lambda_i = scratch[i].kappa;
+    var delta = vec2<f32>(0.0, 0.0);
+
+    let shift = image_shift(x0);
+    for (var m = 0u; m < 4u; m++) {
+        var xi = x0;
+        if !image_pos(x0, shift, m, &xi) { continue; }
+        let c0 = cell_of_pos(xi);
+        let offset = xi - x0;
+
+        var oy: i32 = -1;
+        loop {
+            if oy > 1 { break; }
+            var ox: i32 = -1;
+            loop {
+                if ox > 1 { break; }
+
+                let c = c0 + vec2<i32>(ox, oy);
+                let range = cell_range(c);
+
+                var k = range.x;
+                loop {
+                    if k >= range.y { break; }
+                    let j = cell_entries[k];
+                    let xj = particles.data[j].pos;
+                    let rvec = xi - xj;
+                    if dot(rvec, rvec) < h2 && in_cell(xj, c) {
+                        let r_pred = pred_i + offset - scratch[j].pos_pred;
+                        let s_corr = pbf_s_corr(dot(r_pred, r_pred));
+                        delta += (lambda_i + scratch[j].kappa + s_corr)
+                            * grad_spiky_kernel(r_pred);
+                    }
+                    k = k + 1u;
+                }
+
+                ox = ox + 1;
+            }
+            oy = oy + 1;
+        }
+    }
+
+    scratch[i].acc_p = sph.mass / sph.rho_0 * delta;
+}
+
+@compute @workgroup_size(256)
+fn pbf_apply_main(@builtin(global_invocation_id) gid: vec3<u32>) {
+    let i = gid.x;
+    if i >= arrayLength(&particles.data) { return; }
+    if (particles.data[i].flags & PARTICLE_DEAD) != 0u { return; }
+    scratch[i].pos_pred += scratch[i].acc_p;
+}
+
+@compute @workgroup_size(256)
+fn pbf_velocity_main(@builtin(global_invocation_id) gid: vec3<u32>) {
+    let i = gid.x;
+    if i >= arrayLength(&particles.data) { return; }
+    if (particles.data[i].flags & PARTICLE_DEAD) != 0u { return; }
+    scratch[i].vel0 = (scratch[i].pos_pred - particles.data[i].pos) / integ.dt;
+}
+
+// v_i + c sum m / rho_j (v_j - v_i) W, into acc_p
+@compute @workgroup_size(256)
+fn pbf_xsph_main(@builtin(global_invocation_id) gid: vec3<u32>) {
+    let i = gid.x;
+    let h = grid.cell_size;
+    let h2 = h * h;
+    if i >= arrayLength(&particles.data) { return; }
+    if (particles.data[i].flags & PARTICLE_DEAD) != 0u { return; }
+
+    let x0 = particles.data[i].pos;
+    let pred_i = scratch[i].pos_pred;
+    let vi = scratch[i].vel0;
+    var sum = vec2<f32>(0.0, 0.0);
+
+    let shift = image_shift(x0);
+    for (var m = 0u; m < 4u; m++) {
+        var xi = x0;
+        if !image_pos(x0, shift, m, &xi) { continue; }
+        let c0 = cell_of_pos(xi);
+        let offset = xi - x0;
+
+        var oy: i32 = -1;
+        loop {
+            if oy > 1 { break; }
+            var ox: i32 = -1;
+            loop {
+                if ox > 1 { break; }
+
+                let c = c0 + vec2<i32>(ox, oy);
+                let range = cell_range(c);
+
+                var k = range.x;
+                loop {
+                    if k >= range.y { break; }
+                    let j = cell_entries[k];
+                    let xj = particles.data[j].pos;
+                    let rvec = xi - xj;
+                    if dot(rvec, rvec) < h2 && in_cell(xj, c) {
+                        let r_pred = pred_i + offset - scratch[j].pos_pred;
+                        sum += sph.mass / particles.data[j].rho * (scratch[j].vel0 - vi)
+                            * w_poly6(dot(r_pred, r_pred));
+                    }
+                    k = k + 1u;
+                }
+
+                ox = ox + 1;
+            }
+            oy = oy + 1;
+        }
+    }
+
+    scratch[i].acc_p = vi + sph.pbf_xsph * sum;
+}
+
+// z component of the curl of the XSPH velocity, into alpha
+@compute @workgroup_size(256)
+fn pbf_vorticity_main(@builtin(global_invocation_id) gid: vec3<u32>) {
+    let i = gid.x;
+    let h = grid.cell_size;
+    let h2 = h * h;
+    if i >= arrayLength(&particles.data) { return; }
+    if (particles.data[i].flags & PARTICLE_DEAD) != 0u { return; }
+
+    let x0 = particles.data[i].pos;
+    let pred_i = scratch[i].pos_pred;
+    let vi = scratch[i].acc_p;
+    var curl: f32 = 0.0;
+
+    let shift = image_shift(x0);
+    for (var m = 0u; m < 4u; m++) {
+        var xi = x0;
+        if !image_pos(x0, shift, m, &xi) { continue; }
+        let c0 = cell_of_pos(xi);
+        let offset = xi - x0;
+
+        var oy: i32 = -1;
+        loop {
+            if oy > 1 { break; }
+            var ox: i32 = -1;
+            loop {
+                if ox > 1 { break; }
+
+                let c = c0 + vec2<i32>(ox, oy);
+                let range = cell_range(c);
+
+                var k = range.x;
+                loop {
+                    if k >= range.y { break; }
+                    let j = cell_entries[k];
+                    let xj = particles.data[j].pos;
+                    let rvec = xi - xj;
+                    if dot(rvec, rvec) < h2 && in_cell(xj, c) {
+                        let g = grad_spiky_kernel(pred_i + offset - scratch[j].pos_pred);
+                        let v_ij = vi - scratch[j].acc_p;
+                        curl += sph.mass / particles.data[j].rho * (v_ij.x * g.y - v_ij.y * g.x);
+                    }
+                    k = k + 1u;
+                }
+
+                ox = ox + 1;
+            }
+            oy = oy + 1;
+        }
+    }
+
+    scratch[i].alpha = curl;
+}
+
+// vorticity confinement eps (N x omega), N = grad |omega| / |grad |omega||,
+// then acc = (v_new - v) / dt for integrate_main
+@compute @workgroup_size(256)
+fn pbf_finish_main(@builtin(global_invocation_id) gid: vec3<u32>) {
+    let i = gid.x;
+    let h = grid.cell_size;
+    let h2 = h * h;
+    if i >= arrayLength(&particles.data) { return; }
+    if (particles.data[i].flags & PARTICLE_DEAD) != 0u { return; }
+
+    let x0 = particles.data[i].pos;
+    let pred_i = scratch[i].pos_pred;
+    let omega_i = scratch[i].alpha;
+    var eta = vec2<f32>(0.0, 0.0);
+
+    let shift = image_shift(x0);
+    for (var m = 0u; m < 4u; m++) {
+        var xi = x0;
+        if !image_pos(x0, shift, m, &xi) { continue; }
+        let c0 = cell_of_pos(xi);
+        let offset = xi - x0;
+
+        var oy: i32 = -1;
+        loop {
+            if oy > 1 { break; }
+            var ox: i32 = -1;
+            loop {
+                if ox > 1 { break; }
+
+                let c = c0 + vec2<i32>(ox, oy);
+                let range = cell_range(c);
+
+                var k = range.x;
+                loop {
+                    if k >= range.y { break; }
+                    let j = cell_entries[k];
+                    let xj = particles.data[j].pos;
+                    let rvec = xi - xj;
+                    if dot(rvec, rvec) < h2 && in_cell(xj, c) {
+                        let g = grad_spiky_kernel(pred_i + offset - scratch[j].pos_pred);
+                        eta += sph.mass / particles.data[j].rho
+                            * (abs(scratch[j].alpha) - abs(omega_i)) * g;
+                    }
+                    k = k + 1u;
+                }
+
+                ox = ox + 1;
+            }
+            oy = oy + 1;
+        }
+    }
+
+    var n = vec2<f32>(0.0, 0.0);
+    if dot(eta, eta) > 0.0 {
+        n = normalize(eta);
+    }
+    let dt = integ.dt;
+    let v_new = scratch[i].acc_p + dt * sph.pbf_vorticity * vec2<f32>(n.y, -n.x) * omega_i;
+
+    var p = particles.data[i];
+    // the pressure that moves the particle as far in one step
+    p.p = -scratch[i].kappa_sum * sph.rho_0 / (dt * dt);
+    p.acc = (v_new - p.vel) / dt + body_forces_on(p.pos, p.vel, p.rho, p.p);
+    particles.data[i] = p;
+}
+
+// ---------------- IISPH --------------------
+// one step: main, forces_main (no pressure), iisph_init_main, then
+// IterativeConfig::max_iterations rounds of iisph_sum_main -> iisph_pressure_main
+// -> solver_check_main -> iisph_update_main, iisph_finish_main and
+// integrate_main. Scratch: pos_pred = d_ii, rho_pred = advected density,
+// alpha = a_ii, vel0 = sum_j d_ij p_j, kappa = next pressure.
+
+@compute @workgroup_size(256)
+fn iisph_init_main(@builtin(global_invocation_id) gid: vec3<u32>) {
+    let i = gid.x;
+    let h = grid.cell_size;
+    let h2 = h * h;
+    if i == 0u {
+        reset_solver_stats();
+        solver_stats.divergence_iterations = 0u;
+        solver_stats.divergence_error = 0.0;
+    }
+    if i >= arrayLength(&particles.data) { return; }
+    if (particles.data[i].flags & PARTICLE_DEAD) != 0u { return; }
+
+    let dt = integ.dt;
+    let x0 = particles.data[i].pos;
+    let rho_i = particles.data[i].rho;
+    let v_adv_i = particles.data[i].vel + dt * particles.data[i].acc;
+    var sum_g = vec2<f32>(0.0, 0.0);
+    var sum_g2: f32 = 0.0;
+    var drho: f32 = 0.0;
+
+    let shift = image_shift(x0);
+    for (var m = 0u; m < 4u; m++) {
+        var xi = x0;
+        if !image_pos(x0, shift, m, &xi) { continue; }
+        let c0 = cell_of_pos(xi);
+
+        var oy: i32 = -1;
+        loop {
+            if oy > 1 { break; }
+            var ox: i32 = -1;
+            loop {
+                if ox > 1 { break; }
+
+                let c = c0 + vec2<i32>(ox, oy);
+                let range = cell_range(c);
+
+                var k = range.x;
+                loop {
+                    if k >= range.y { break; }
+                    let j = cell_entries[k];
+                    let xj = particles.data[j].pos;
+                    let rvec = xi - xj;
+                    if dot(rvec, rvec) < h2 && in_cell(xj, c) {
+                        let g = grad_spiky_kernel(rvec);
+                        let v_adv_j = particles.data[j].vel + dt * particles.data[j].acc;
+                        sum_g += sph.mass * g;
+                        sum_g2 += sph.mass * sph.mass * dot(g, g);
+                        drho += dt * sph.mass * dot(v_adv_i - v_adv_j, g);
+                    }
+                    k = k + 1u;
+                }
+
+                ox = ox + 1;
+            }
+            oy = oy + 1;
+        }
+    }
+
+    // d_ii = -dt^2 sum m / rho_i^2 grad W, a_ii = sum m (d_ii - d_ji) . grad W
+    let c_i = dt * dt / (rho_i * rho_i);
+    let d_ii = -c_i * sum_g;
+    scratch[i].acc_np = particles.data[i].acc;
+    scratch[i].pos_pred = d_ii;
+    scratch[i].rho_pred = rho_i + drho;
+    scratch[i].alpha = dot(d_ii, sum_g) - c_i * sum_g2;
+
+    // warm start from the last step
+    let p = 0.5 * particles.data[i].p;
+    particles.data[i].p = p;
+    scratch[i].kappa = p;
+}
+
+// sum_j d_ij p_j = -dt^2 sum m p_j / rho_j^2 grad W
+@compute @workgroup_size(256)
+fn iisph_sum_main(@builtin(global_invocation_id) gid: vec3<u32>) {
+    let i = gid.x;
+    let h = grid.cell_size;
+    let h2 = h * h;
+    if i >= arrayLength(&particles.data) { return; }
+    if (particles.data[i].flags & PARTICLE_DEAD) != 0u { return; }
+    if solver_converged() { return; }
+
+    let x0 = particles.data[i].pos;
+    var sum = vec2<f32>(0.0, 0.0);
+
+    let shift = image_shift(x0);
+    for (var m = 0u; m < 4u; m++) {
+        var xi = x0;
+        if !image_pos(x0, shift, m, &xi) { continue; }
+        let c0 = cell_of_pos(xi);
+
+        var oy: i32 = -1;
+        loop {
+            if oy > 1 { break; }
+            var ox: i32 = -1;
+            loop {
+                if ox > 1 { break; }
+
+                let c = c0 + vec2<i32>(ox, oy);
+                let range = cell_range(c);
+
+                var k = range.x;
+                loop {
+                    if k >= range.y { break; }
+                    let j = cell_entries[k];
+                    let xj = particles.data[j].pos;
+                    let rvec = xi - xj;
+                    if dot(rvec, rvec) < h2 && in_cell(xj, c) {
+                        let pj = particles.data[j].p;
+                        let rho_j = particles.data[j].rho;
+                        sum -= sph.mass * pj / (rho_j * rho_j) * grad_spiky_kernel(rvec);
+                    }
+                    k = k + 1u;
+                }
+
+                ox = ox + 1;
+            }
+            oy = oy + 1;
+        }
+    }
+
+    scratch[i].vel0 = integ.dt * integ.dt * sum;
+}
+
+// relaxed Jacobi update of p_i into kappa, iisph_update_main copies it once
+// every particle has read the old pressures
+@compute @workgroup_size(256)
+fn iisph_pressure_main(@builtin(global_invocation_id) gid: vec3<u32>) {
+    let i = gid.x;
+    let h = grid.cell_size;
+    let h2 = h * h;
+    if i >= arrayLength(&particles.data) { return; }
+    if (particles.data[i].flags & PARTICLE_DEAD) != 0u { return; }
+    if solver_converged() { return; }
+
+    let x0 = particles.data[i].pos;
+    let pi = particles.data[i].p;
+    let rho_i = particles.data[i].rho;
+    let c_i = integ.dt * integ.dt * sph.mass / (rho_i * rho_i);
+    let sum_dp_i = scratch[i].vel0;
+    var sum: f32 = 0.0;
+
+    let shift = image_shift(x0);
+    for (var m = 0u; m < 4u; m++) {
+        var xi = x0;
+        if !image_pos(x0, shift, m, &xi) { continue; }
+        let c0 = cell_of_pos(xi);
+
+        var oy: i32 = -1;
+        loop {
+            if oy > 1 { break; }
+            var ox: i32 = -1;
+            loop {
+                if ox > 1 { break; }
+
+                let c = c0 + vec2<i32>(ox, oy);
+                let range = cell_range(c);
+
+                var k = range.x;
+                loop {
+                    if k >= range.y { break; }
+                    let j = cell_entries[k];
+                    let xj = particles.data[j].pos;
+                    let rvec = xi - xj;
+                    if j != i && dot(rvec, rvec) < h2 && in_cell(xj, c) {
+                        let g = grad_spiky_kernel(rvec);
+                        let d_jj_pj = scratch[j].pos_pred * particles.data[j].p;
+                        let others = scratch[j].vel0 - c_i * g * pi; // k != i
+                        sum += sph.mass * dot(sum_dp_i - d_jj_pj - others, g);
+                    }
+                    k = k + 1u;
+                }
+
+                ox = ox + 1;
+            }
+            oy = oy + 1;
+        }
+    }
+
+    let rho_adv = scratch[i].rho_pred;
+    let a_ii = scratch[i].alpha;
+    // density with the current pressures
+    let rho_pred = rho_adv + a_ii * pi + sum;
+    add_solver_error((rho_pred - sph.rho_0) / sph.rho_0);
+
+    var p = 0.0;
+    if a_ii < 0.0 { // zero without neighbours
+        let jacobi = (sph.rho_0 - rho_adv - sum) / a_ii;
+        let omega = sph.iisph_relaxation;
+        p = max((1.0 - omega) * pi + omega * jacobi, 0.0);
+    }
+    scratch[i].kappa = p;
+}
+
+// also after convergence, kappa then holds the last pressure
+@compute @workgroup_size(256)
+fn iisph_update_main(@builtin(global_invocation_id) gid: vec3<u32>) {
+    let i = gid.x;
+    if i >= arrayLength(&particles.data) { return; }
+    if (particles.data[i].flags & PARTICLE_DEAD) != 0u { return; }
+    particles.data[i].p = scratch[i].kappa;
+}
+
+// symmetric pressure force with the solved pressures
+@compute @workgroup_size(256)
+fn iisph_finish_main(@builtin(global_invocation_id) gid: vec3<u32>) {
+    let i = gid.x;
+    let h = grid.cell_size;
+    let h2 = h * h;
+    if i >= arrayLength(&particles.data) { return; }
+    if (particles.data[i].flags & PARTICLE_DEAD) != 0u { return; }
+
+    let x0 = particles.data[i].pos;
+    let pi = particles.data[i].p;
+    let rho_i = particles.data[i].rho;
+    var acc = scratch[i].acc_np;
+
+    let shift = image_shift(x0);
+    for (var m = 0u; m < 4u; m++) {
+        var xi = x0;
+        if !image_pos(x0, shift, m, &xi) { continue; }
+        let c0 = cell_of_pos(xi);
+
+        var oy: i32 = -1;
+        loop {
+            if oy > 1 { break; }
+            var ox: i32 = -1;
+            loop {
+                if ox > 1 { break; }
+
+                let c = c0 + vec2<i32>(ox, oy);
+                let range = cell_range(c);
+
+                var k = range.x;
+                loop {
+                    if k >= range.y { break; }
+                    let j = cell_entries[k];
+                    let xj = particles.data[j].pos;
+                    let rvec = xi - xj;
+                    if j != i && dot(rvec, rvec) < h2 && in_cell(xj, c) {
+                        let pj = particles.data[j].p;
+                        let rho_j = particles.data[j].rho;
+                        acc -= sph.mass * (pi / (rho_i * rho_i) + pj / (rho_j * rho_j))
+                            * grad_spiky_kernel(rvec);
+                    }
+                    k = k + 1u;
+                }
+
+                ox = ox + 1;
+            }
+            oy = oy + 1;
+        }
+    }
+
+    let p = particles.data[i];
+    particles.data[i].acc = acc + body_forces_on(p.pos, p.vel, p.rho, p.p);
+}
+
+// ---------------- FLIP and MPM --------------------
+// the grid passes live in flip.wgsl and mpm.wgsl (own bind groups); they
+// leave the new velocity in acc, rho and p from the grid
+
+@compute @workgroup_size(256)
+fn grid_finish_main(@builtin(global_invocation_id) gid: vec3<u32>) {
+    let i = gid.x;
+    if i >= arrayLength(&particles.data) { return; }
+    if (particles.data[i].flags & PARTICLE_DEAD) != 0u { return; }
+
+    let p = particles.data[i];
+    particles.data[i].acc = p.acc + body_forces_on(p.pos, p.vel, p.rho, p.p);
+}
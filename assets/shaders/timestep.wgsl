@@ -0,0 +1,183 @@
+// Adaptive time step, the same limits as cpu::timestep::AdaptiveDt.
+// timestep_max_main reduces max |v| and |a| of the last step, timestep_dt_main
+// turns them into dt. GPUTimestep::dt is read back and the App paces the next
+// steps with it, writing it into IntegrateParams::dt.
+
+struct Particle {
+    pos: vec2<f32>,
+    vel: vec2<f32>,
+    acc: vec2<f32>,
+    rho: f32,
+    p: f32,
+    flags: u32, // bit 0: dead (left through an open wall)
+    _pad: u32,
+    normal: vec2<f32>, // surface normal, normals_main (sph_density.wgsl)
+};
+const PARTICLE_DEAD: u32 = 1u;
+
+struct ParticleBuffer {
+    data: array<Particle>, // runtime-sized array must be last
+};
+
+@group(0) @binding(0)
+var<storage, read> particles : ParticleBuffer;
+
+struct GridParams {
+    min_world: vec2<f32>,
+    cell_size: f32,
+    _pad0: f32,
+    dims: vec2<u32>,
+    hash_size: u32, // 0 = dense grid
+    _pad1: u32,
+};
+
+@group(0) @binding(1)
+var<uniform> grid : GridParams;
+
+struct IntegrateParams {
+    dt: f32,                  // SimClock::dt when adaptive
+    dt_min: f32,
+    dt_max: f32,
+    _pad0: f32,
+    min: vec2<f32>,           // domain
+    max: vec2<f32>,
+    modes: vec4<u32>,         // left, right, bottom, top (WallMode::id)
+    restitution: vec4<f32>,
+    dt_safety: vec4<f32>,     // AdaptiveDt cfl, force, viscosity
+    adhesion: vec4<f32>,      // Domain::wall_adhesion
+};
+
+@group(0) @binding(2)
+var<uniform> integ : IntegrateParams;
+
+struct SphParams {
+    mass: f32,
+    rho_0: f32,
+    k: f32,
+    mu: f32,
+    gravity: vec2<f32>,
+    num_colliders: u32,
+    eos: u32,
+    speed_of_sound: f32,
+    pressure_force: u32,
+    viscosity: u32,
+    av_alpha: f32,
+    av_speed_of_sound: f32,
+    solver: u32,
+    solver_tolerance: f32,
+    solver_min_iterations: u32,
+    pcisph_delta: f32,
+    divergence_tolerance: f32,
+    divergence_min_iterations: u32,
+    pbf_scale: f32,
+    pbf_relaxation: f32,
+    pbf_artificial_pressure: f32,
+    pbf_artificial_pressure_n: f32,
+    pbf_artificial_pressure_dq: f32,
+    pbf_xsph: f32,
+    pbf_vorticity: f32,
+    iisph_relaxation: f32,
+    flip_ratio: f32,
+    flip_relaxation: f32,
+    flip_apic: u32,
+    mpm_material: u32,
+    mpm_mu: f32,
+    mpm_lambda: f32,
+    mpm_bulk_modulus: f32,
+    mpm_critical_compression: f32,
+    mpm_critical_stretch: f32,
+    mpm_hardening: f32,
+    mpm_friction: f32,
+    sound_speed: f32,           // SPHState::sound_speed
+    kinematic_viscosity: f32,   // SPHState::kinematic_viscosity
+    integrator: u32,            // Integrator::id (sph_density.wgsl)
+    xsph: f32,                  // XSPH epsilon (sph_density.wgsl)
+    surface_tension: f32,       // (sph_density.wgsl)
+    num_boundary: u32,          // boundary particles, 0 = none
+    boundary_table: u32,        // their hash table size
+    _pad0: u32,
+    _pad1: u32,
+    _pad2: u32,
+};
+
+@group(0) @binding(3)
+var<uniform> sph : SphParams;
+
+// GPUTimestep
+struct Timestep {
+    dt: f32,
+    max_speed: f32,
+    max_accel: f32,
+    _pad: u32,
+    speed_bits: atomic<u32>, // bits of non-negative floats order like the floats
+    accel_bits: atomic<u32>,
+    _pad1: vec2<u32>,
+};
+
+@group(0) @binding(4)
+var<storage, read_write> timestep : Timestep;
+
+// large but finite, so a NaN or inf particle still leaves a usable dt
+const MAX_VALUE: f32 = 1e30;
+
+var<workgroup> wg_speed: atomic<u32>;
+var<workgroup> wg_accel: atomic<u32>;
+
+fn finite_bits(x: f32) -> u32 {
+    // NaN fails the comparison
+    if !(x <= MAX_VALUE) {
+        return bitcast<u32>(MAX_VALUE);
+    }
+    return bitcast<u32>(x);
+}
+
+@compute @workgroup_size(256)
+fn timestep_max_main(
+    @builtin(global_invocation_id) gid: vec3<u32>,
+    @builtin(local_invocation_id) lid: vec3<u32>,
+) {
+    if lid.x == 0u {
+        atomicStore(&wg_speed, 0u);
+        atomicStore(&wg_accel, 0u);
+    }
+    workgroupBarrier();
+
+    let i = gid.x;
+    if i < arrayLength(&particles.data) {
+        let p = particles.data[i];
+        if (p.flags & PARTICLE_DEAD) == 0u {
+            atomicMax(&wg_speed, finite_bits(length(p.vel)));
+            atomicMax(&wg_accel, finite_bits(length(p.acc)));
+        }
+    }
+    workgroupBarrier();
+
+    if lid.x == 0u {
+        atomicMax(&timestep.speed_bits, atomicLoad(&wg_speed));
+        atomicMax(&timestep.accel_bits, atomicLoad(&wg_accel));
+    }
+}
+
+@compute @workgroup_size(1)
+fn timestep_dt_main() {
+    // reset for the next step
+    let max_speed = bitcast<f32>(atomicExchange(&timestep.speed_bits, 0u));
+    let max_accel = bitcast<f32>(atomicExchange(&timestep.accel_bits, 0u));
+
+    let h = grid.cell_size;
+    var dt = integ.dt_max;
+    let speed = max_speed + sph.sound_speed;
+    if speed > 0.0 {
+        dt = min(dt, integ.dt_safety.x * h / speed);
+    }
+    if max_accel > 0.0 {
+        dt = min(dt, integ.dt_safety.y * sqrt(h / max_accel));
+    }
+    if sph.kinematic_viscosity > 0.0 {
+        dt = min(dt, integ.dt_safety.z * h * h / sph.kinematic_viscosity);
+    }
+
+    timestep.dt = max(dt, integ.dt_min);
+    timestep.max_speed = max_speed;
+    timestep.max_accel = max_accel;
+}
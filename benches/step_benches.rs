@@ -13,7 +13,7 @@ fn bench_step(c: &mut Criterion) {
 
     sph.init_grid(70, 70, spacing);
 
-    c.bench_function("step_4.9k", |b| b.iter(|| sph.step(0.001)));
+    c.bench_function("step_4.9k", |b| b.iter(|| sph.step(0.001, 3.0, -3.0, -0.5)));
 }
 
 criterion_group!(benches, bench_step);
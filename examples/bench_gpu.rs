@@ -1,6 +1,7 @@
 use bevy::diagnostic::{DiagnosticsStore, FrameTimeDiagnosticsPlugin};
 use bevy::prelude::*;
 use bevy_gpu_fluid::cpu::sph2d::SPHState;
+use bevy_gpu_fluid::cpu::timestep::SimClock;
 use bevy_gpu_fluid::gpu::buffers::{GPUSPHPlugin, UseGpuIntegration};
 
 const DURATION_SEC: f32 = 3.0;
@@ -45,7 +46,9 @@ fn main() {
             accum_fps: 0.0,
             frames: 0,
         })
-        .insert_resource(UseGpuIntegration(false))
+        // the first case is seeded by the plugin; one step per frame to compare
+        .insert_resource(UseGpuIntegration(true))
+        .insert_resource(SimClock::lockstep())
         .insert_resource(SeedRequest::default())
         .insert_resource(make_state(first_n))
         .add_plugins(GPUSPHPlugin)
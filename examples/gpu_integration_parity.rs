@@ -1,11 +1,10 @@
 use bevy::app::AppExit;
 use bevy::prelude::*;
-use bevy::render::render_resource::{Maintain, MapMode};
-use bevy_gpu_fluid::gpu::buffers::update_grid_buffers;
 use bevy_gpu_fluid::{
     cpu::sph2d::SPHState,
-    gpu::buffers::{AllowCopy, GPUSPHPlugin, ReadbackBuffer, UseGpuIntegration},
-    gpu::ffi::GPUParticle,
+    cpu::timestep::SimClock,
+    gpu::buffers::{GPUSPHPlugin, UseGpuIntegration},
+    gpu::readback::{ParticleReadback, ParticleSnapshot, ParticleSnapshotReady},
 };
 
 const DT: f32 = 0.0005;
@@ -27,27 +26,26 @@ fn main() {
         .add_plugins(DefaultPlugins)
         .insert_resource(SPHState::demo_block_5k())
         .insert_resource(UseGpuIntegration(false))
-        .insert_resource(AllowCopy(false))
+        .insert_resource(SimClock::lockstep()) // one GPU step per CPU step
         .add_plugins(GPUSPHPlugin)
         .add_systems(Startup, |mut commands: Commands| {
             commands.spawn(Camera2d::default());
         })
-        .add_systems(Update, orchestrate_100.before(update_grid_buffers))
+        .add_systems(Update, orchestrate_100)
         .run();
 }
 
 fn orchestrate_100(
-    mut allow_copy: ResMut<AllowCopy>,
     mut use_gpu: ResMut<UseGpuIntegration>,
     mut sph: ResMut<SPHState>,
-    readback: Option<Res<ReadbackBuffer>>,
-    render_device: Res<bevy::render::renderer::RenderDevice>,
+    mut readback: ResMut<ParticleReadback>,
+    snapshot: Res<ParticleSnapshot>,
+    mut ready: EventReader<ParticleSnapshotReady>,
     mut exit: EventWriter<AppExit>,
     mut frame: Local<u32>,
     mut state: Local<u8>,
     mut cpu_steps: Local<u32>,
 ) {
-    let Some(readback) = readback else { return };
     *frame += 1;
 
     match *state {
@@ -65,58 +63,21 @@ fn orchestrate_100(
                 sph.step(DT, X_MAX, X_MIN, BOUNCE);
                 *cpu_steps += 1;
                 if *cpu_steps == STEPS {
-                    info!("Reached {} CPU steps; preparing readback.", STEPS);
+                    info!("Reached {} CPU steps; requesting readback.", STEPS);
+                    readback.request();
                     *state = 1;
                 }
             }
         }
 
-        // copy this frame
         1 => {
-            allow_copy.0 = true;
-            *state = 2;
-        }
-
-        // avoid mapping race
-        2 => {
-            allow_copy.0 = false;
-            *state = 3;
-        }
-
-        3 => {
-            render_device.poll(Maintain::Wait);
-            let slice = readback.buffer.slice(..);
-
-            // async map
-            let status = std::sync::Arc::new(std::sync::atomic::AtomicU8::new(0));
-            let cb = status.clone();
-            slice.map_async(MapMode::Read, move |r| {
-                cb.store(
-                    if r.is_ok() { 1 } else { 2 },
-                    std::sync::atomic::Ordering::SeqCst,
-                )
-            });
-
-            // wait for map completion
-            loop {
-                render_device.poll(Maintain::Poll);
-                match status.load(std::sync::atomic::Ordering::SeqCst) {
-                    0 => std::thread::yield_now(),
-                    1 => break,
-                    2 => {
-                        error!("map_async failed; unmapping and exiting");
-                        readback.buffer.unmap();
-                        exit.write(AppExit::Success);
-                        return;
-                    }
-                    _ => unreachable!(),
-                }
+            if ready.read().last().is_none() {
+                return;
             }
 
             // compute diffs
             {
-                let data = slice.get_mapped_range();
-                let gpu: &[GPUParticle] = bytemuck::cast_slice(&data);
+                let gpu = &snapshot.particles;
                 assert_eq!(
                     gpu.len(),
                     sph.particles.len(),
@@ -187,9 +148,9 @@ fn orchestrate_100(
                 }
             }
 
-            readback.buffer.unmap();
             info!("Done. Exiting.");
             exit.write(AppExit::Success);
+            *state = 2;
         }
 
         _ => {}
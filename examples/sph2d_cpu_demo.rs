@@ -5,11 +5,11 @@ use bevy::window::PrimaryWindow;
 use glam::Vec2 as GVec2;
 
 use bevy_gpu_fluid::cpu::sph2d::SPHState;
-use bevy_gpu_fluid::gpu::buffers::{SimStep, readback_and_compare};
+use bevy_gpu_fluid::cpu::timestep::{AdaptiveDt, SimClock};
+use bevy_gpu_fluid::gpu::buffers::{UseGpuIntegration, readback_and_compare};
 
 const RENDER_SCALE: f32 = 100.0;
 const PARTICLE_SIZE: f32 = 15.0;
-const DT: f32 = 0.0005;
 const X_MAX: f32 = 3.0;
 const X_MIN: f32 = -5.0;
 const BOUNCINESS: f32 = -3.0;
@@ -40,7 +40,9 @@ fn main() {
         .insert_resource(SPHState::demo_block_5k())
         .insert_resource(DragInput::default())
         .insert_resource(ViewMode::DensityColor)
-        .insert_resource(SimStep::default())
+        .insert_resource(SimClock::default())
+        // the GPU only recomputes the streamed CPU state (readback_and_compare)
+        .insert_resource(UseGpuIntegration(false))
         .add_systems(Startup, setup)
         .add_systems(
             Update,
@@ -49,6 +51,7 @@ fn main() {
                 sph_step,
                 apply_drag,
                 toggle_view,
+                clock_controls,
                 sync_particles,
                 // readback_and_compare,
             ),
@@ -66,6 +69,22 @@ fn toggle_view(keys: Res<ButtonInput<KeyCode>>, mut view: ResMut<ViewMode>) {
     }
 }
 
+// P pauses, N takes a single step, [ and ] halve and double the time scale
+fn clock_controls(keys: Res<ButtonInput<KeyCode>>, mut clock: ResMut<SimClock>) {
+    if keys.just_pressed(KeyCode::KeyP) {
+        clock.paused = !clock.paused;
+    }
+    if keys.just_pressed(KeyCode::KeyN) {
+        clock.step_once();
+    }
+    if keys.just_pressed(KeyCode::BracketLeft) {
+        clock.time_scale *= 0.5;
+    }
+    if keys.just_pressed(KeyCode::BracketRight) {
+        clock.time_scale *= 2.0;
+    }
+}
+
 // from blue to red based on the density
 fn density_color(t: f32) -> Color {
     let t = t.clamp(0.0, 1.0);
@@ -141,10 +160,18 @@ fn apply_drag(
 }
 
 // all the mathematic happens here!
-fn sph_step(mut sph: ResMut<SPHState>, time: Res<Time>, mut step: ResMut<SimStep>) {
-    let dt = time.delta_secs().min(DT);
-    sph.step(dt, X_MAX, X_MIN, BOUNCINESS); // integral
-    step.0 += 1;
+// the clock hands out real time, in steps as large as the CFL, force and
+// viscosity limits allow
+fn sph_step(mut sph: ResMut<SPHState>, time: Res<Time>, mut clock: ResMut<SimClock>) {
+    let config = AdaptiveDt::default();
+    clock.accumulate(time.delta_secs());
+    loop {
+        let dt = sph.adaptive_dt(&config);
+        if !clock.consume(dt) {
+            break;
+        }
+        sph.step(dt, X_MAX, X_MIN, BOUNCINESS); // integral
+    }
 }
 
 fn sync_particles(
@@ -0,0 +1,194 @@
+// dynamic rigid bodies that float in the fluid and push it around (two-way coupling)
+use bevy::prelude::*;
+use glam::Vec2;
+
+use crate::cpu::collider::{Collider, ColliderShape};
+use crate::cpu::domain::Domain;
+use crate::cpu::sph2d::{SPHState, grad_spiky_kernel};
+
+// the body is also a collider: particles inside are projected out and take
+// over the body velocity. Its pose and velocity live in `collider`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RigidBody {
+    pub collider: Collider,
+    pub mass: f32,
+    pub inertia: f32, // about Collider::position
+    // sum of the fluid forces of the last step (world space, about the centre)
+    pub force: Vec2,
+    pub torque: f32,
+    pub entity: Option<Entity>, // FluidBody it was synced from
+}
+
+impl RigidBody {
+    pub fn new(collider: Collider, mass: f32) -> Self {
+        let inertia = inertia_of(&collider.shape, mass);
+        Self {
+            collider,
+            mass,
+            inertia,
+            force: Vec2::ZERO,
+            torque: 0.0,
+            entity: None,
+        }
+    }
+
+    // Fluid acceleration on a particle near the surface, treating the closest
+    // surface point as a particle with the same pressure and density (same
+    // pressure/viscosity terms as accel_field_calc), plus the adhesion of the
+    // surface. None if out of reach.
+    pub fn fluid_accel(
+        &self,
+        sph: &SPHState,
+        pos: Vec2,
+        vel: Vec2,
+        rho: f32,
+        p: f32,
+    ) -> Option<Vec2> {
+        let d = self.collider.distance(pos);
+        if d >= sph.h || rho <= 0.0 {
+            return None;
+        }
+        let n = self.collider.normal(pos);
+        // from the surface point to the particle; keep a minimum length so
+        // particles pushed onto the surface still feel the pressure
+        let r = n * d.max(0.01 * sph.h);
+        let surface = pos - n * d;
+
+        let grad = grad_spiky_kernel(r, sph.h);
+        let a_p = sph.pressure_accel((p, rho), (p, rho), grad);
+        let v_ij = vel - self.collider.velocity_at(surface);
+        let a_v = sph.viscosity_accel(r, v_ij, (rho, rho), grad);
+        let a_adh = sph.adhesion_accel(self.collider.adhesion, d, n);
+        Some(a_p + a_v + a_adh)
+    }
+
+    // adds the reaction of `accel` (on a particle of mass m at pos) to the body
+    pub fn add_reaction(&mut self, m: f32, pos: Vec2, accel: Vec2) {
+        let d = self.collider.distance(pos);
+        let surface = pos - self.collider.normal(pos) * d;
+        let f = -m * accel;
+        self.force += f;
+        self.torque += (surface - self.collider.position).perp_dot(f);
+    }
+
+    // symplectic Euler with the stored fluid force; the walls only see the centre
+    pub fn integrate(&mut self, dt: f32, gravity: Vec2, domain: &Domain) {
+        let c = &mut self.collider;
+        c.velocity += (self.force / self.mass + gravity) * dt;
+        c.angular_velocity += self.torque / self.inertia * dt;
+        c.position += c.velocity * dt;
+        c.rotation += c.angular_velocity * dt;
+        domain.apply(&mut c.position, &mut c.velocity);
+    }
+}
+
+// moment of inertia of a uniform body about its origin
+pub fn inertia_of(shape: &ColliderShape, mass: f32) -> f32 {
+    match shape {
+        ColliderShape::Circle { radius } => 0.5 * mass * radius * radius,
+        ColliderShape::Box { half_extents } => mass * half_extents.length_squared() / 3.0,
+        // as its bounding box
+        ColliderShape::Capsule { a, b, radius } => {
+            let half = Vec2::new(0.5 * a.distance(*b) + radius, *radius);
+            mass * half.length_squared() / 3.0
+        }
+        ColliderShape::Polygon { points } => {
+            let mut num = 0.0;
+            let mut den = 0.0;
+            for (i, p) in points.iter().enumerate() {
+                let q = points[(i + 1) % points.len()];
+                let cross = p.perp_dot(q); // signed, so concave outlines work
+                num += cross * (p.dot(*p) + p.dot(q) + q.dot(q));
+                den += cross;
+            }
+            if den != 0.0 {
+                mass * num / (6.0 * den)
+            } else {
+                mass
+            }
+        }
+        ColliderShape::Sdf(grid) => {
+            let half = 0.5 * grid.dims.as_vec2() * grid.cell_size;
+            mass * half.length_squared() / 3.0
+        }
+    }
+}
+
+// body that is pushed around by the fluid; its Transform is written back
+// after every frame. set `inertia` after `new` for a non-uniform body
+#[derive(Component, Clone, Debug)]
+pub struct FluidBody {
+    pub shape: ColliderShape,
+    pub mass: f32,
+    pub inertia: f32,
+    pub restitution: f32,
+    pub friction: f32,
+    pub adhesion: f32,
+    pub velocity: Vec2,
+    pub angular_velocity: f32,
+}
+
+impl FluidBody {
+    pub fn new(shape: ColliderShape, mass: f32) -> Self {
+        Self {
+            inertia: inertia_of(&shape, mass),
+            shape,
+            mass,
+            restitution: 0.0,
+            friction: 0.0,
+            adhesion: 0.0,
+            velocity: Vec2::ZERO,
+            angular_velocity: 0.0,
+        }
+    }
+}
+
+// rebuilds SPHState::bodies from the FluidBody entities (pose from Transform)
+pub fn sync_fluid_bodies(
+    mut sph: ResMut<SPHState>,
+    query: Query<(Entity, &FluidBody, &Transform)>,
+) {
+    if query.is_empty() && sph.bodies.is_empty() {
+        return;
+    }
+    sph.bodies = query
+        .iter()
+        .map(|(entity, fb, transform)| {
+            let t = transform.translation; // bevy's glam, not ours
+            let (_, _, rotation) = transform.rotation.to_euler(EulerRot::XYZ);
+            let mut collider = Collider::new(fb.shape.clone(), Vec2::new(t.x, t.y));
+            collider.rotation = rotation;
+            collider.restitution = fb.restitution;
+            collider.friction = fb.friction;
+            collider.adhesion = fb.adhesion;
+            collider.velocity = fb.velocity;
+            collider.angular_velocity = fb.angular_velocity;
+            RigidBody {
+                collider,
+                mass: fb.mass,
+                inertia: fb.inertia,
+                force: Vec2::ZERO,
+                torque: 0.0,
+                entity: Some(entity),
+            }
+        })
+        .collect();
+}
+
+// copies the simulated pose and velocity back to the entities
+pub fn write_back_fluid_bodies(
+    sph: Res<SPHState>,
+    mut query: Query<(&mut FluidBody, &mut Transform)>,
+) {
+    for body in &sph.bodies {
+        let Some(Ok((mut fb, mut transform))) = body.entity.map(|e| query.get_mut(e)) else {
+            continue;
+        };
+        let c = &body.collider;
+        transform.translation.x = c.position.x;
+        transform.translation.y = c.position.y;
+        transform.rotation = Quat::from_rotation_z(c.rotation);
+        fb.velocity = c.velocity;
+        fb.angular_velocity = c.angular_velocity;
+    }
+}
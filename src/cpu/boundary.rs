@@ -0,0 +1,167 @@
+// boundary particles after Akinci et al. 2012. Walls and static colliders are
+// sampled into one layer of particles that stay put and add to the density
+// and pressure sums of the fluid next to them, so fluid at a wall sees a full
+// neighbourhood instead of piling up against the position clamp. Wcsph only;
+// the clamps stay as a backstop for particles that get through.
+use std::collections::HashMap;
+
+use glam::{IVec2, Vec2};
+
+use crate::cpu::collider::Collider;
+use crate::cpu::domain::{Domain, WallMode};
+use crate::cpu::sph2d::{HashedGrid, SPHState, Solver, cell, w_poly6};
+
+// samples closer than this (times the spacing) are merged
+const MIN_GAP: f32 = 0.5;
+
+// a collider surface is projected from an unordered grid four times finer
+// than the spacing, so its samples are thinned to almost a full spacing;
+// MIN_GAP would keep up to twice as many as a wall of the same length has
+const COLLIDER_GAP: f32 = 0.9;
+
+#[derive(Clone, Debug)]
+pub struct BoundaryParticles {
+    pub pos: Vec<Vec2>,
+    // pseudo-mass rho_0 / sum_k W(x_b - x_k) over the boundary neighbours,
+    // so dense and sparse samplings push the same
+    pub psi: Vec<f32>,
+    pub grid: HashedGrid, // built once, uploaded as is to the GPU
+}
+
+impl BoundaryParticles {
+    pub fn new(pos: Vec<Vec2>, h: f32, rho_0: f32) -> Self {
+        let table_size = (pos.len().max(1) * 2).next_power_of_two() as u32;
+        let grid = HashedGrid::build(pos.iter().copied(), h, table_size);
+        let mut boundary = Self {
+            psi: vec![0.0; pos.len()],
+            pos,
+            grid,
+        };
+        for b in 0..boundary.pos.len() {
+            let mut sum = 0.0;
+            boundary.for_each_near(boundary.pos[b], h, |k| {
+                sum += w_poly6((boundary.pos[b] - boundary.pos[k]).length_squared(), h);
+            });
+            boundary.psi[b] = rho_0 / sum;
+        }
+        boundary
+    }
+
+    // calls `f` for every boundary particle within h of x
+    pub fn for_each_near(&self, x: Vec2, h: f32, mut f: impl FnMut(usize)) {
+        let c = cell(x, h);
+        for ox in -1..=1 {
+            for oy in -1..=1 {
+                let c = c + IVec2::new(ox, oy);
+                for &k in self.grid.bucket(c) {
+                    let k = k as usize;
+                    // other cells can share the bucket
+                    if cell(self.pos[k], h) == c && (x - self.pos[k]).length_squared() < h * h {
+                        f(k);
+                    }
+                }
+            }
+        }
+    }
+}
+
+// samples along the walls of `domain` that fluid can touch. Walls at infinity
+// are skipped, walls of infinite length end at `extent`
+pub fn sample_walls(domain: &Domain, spacing: f32, extent: (Vec2, Vec2)) -> Vec<Vec2> {
+    let mut points = Vec::new();
+    let walls = domain.walls();
+    for axis in 0..2 {
+        let along = 1 - axis;
+        let finite_or = |x: f32, or: f32| if x.is_finite() { x } else { or };
+        let lo = finite_or(domain.min[along], extent.0[along]);
+        let hi = finite_or(domain.max[along], extent.1[along]);
+        if !(hi - lo).is_finite() || hi < lo {
+            continue;
+        }
+        let n = ((hi - lo) / spacing).ceil().max(1.0) as usize;
+        for (mode, at) in [
+            (walls[2 * axis], domain.min[axis]),
+            (walls[2 * axis + 1], domain.max[axis]),
+        ] {
+            if matches!(mode, WallMode::Open | WallMode::Periodic) || !at.is_finite() {
+                continue;
+            }
+            for k in 0..=n {
+                let mut p = Vec2::ZERO;
+                p[axis] = at;
+                p[along] = lo + (hi - lo) * k as f32 / n as f32;
+                points.push(p);
+            }
+        }
+    }
+    points
+}
+
+// samples on the surface of a collider: points of a fine grid near the
+// surface are projected onto it
+pub fn sample_collider(collider: &Collider, spacing: f32) -> Vec<Vec2> {
+    let step = 0.25 * spacing;
+    let r = collider.shape.bounding_radius() + spacing;
+    let n = (2.0 * r / step).ceil() as i32;
+    let mut points = Vec::new();
+    for iy in 0..=n {
+        for ix in 0..=n {
+            let p = collider.position - Vec2::splat(r) + Vec2::new(ix as f32, iy as f32) * step;
+            let d = collider.distance(p);
+            if d.abs() < step {
+                points.push(p - collider.normal(p) * d);
+            }
+        }
+    }
+    thin_out(points, spacing, COLLIDER_GAP)
+}
+
+// drops the points closer than gap * spacing to one already kept
+fn thin_out(points: Vec<Vec2>, spacing: f32, gap: f32) -> Vec<Vec2> {
+    let min_dist = gap * spacing;
+    let mut kept: Vec<Vec2> = Vec::with_capacity(points.len());
+    let mut cells: HashMap<IVec2, Vec<usize>> = HashMap::new();
+    for p in points {
+        let c = cell(p, spacing);
+        let close = (-1..=1).any(|ox| {
+            (-1..=1).any(|oy| {
+                cells.get(&(c + IVec2::new(ox, oy))).is_some_and(|list| {
+                    list.iter()
+                        .any(|&k| (kept[k] - p).length_squared() < min_dist * min_dist)
+                })
+            })
+        });
+        if !close {
+            cells.entry(c).or_default().push(kept.len());
+            kept.push(p);
+        }
+    }
+    kept
+}
+
+impl SPHState {
+    // boundary particles of the current solver
+    pub fn active_boundary(&self) -> Option<&BoundaryParticles> {
+        match self.solver {
+            Solver::Wcsph => self.boundary.as_ref(),
+            _ => None,
+        }
+    }
+
+    // samples the walls of `domain` and the static colliders into
+    // SPHState::boundary, about `spacing` apart (the particle spacing).
+    // Walls of infinite length end at the particle bounds grown by their size
+    pub fn sample_boundary(&mut self, domain: &Domain, spacing: f32) {
+        let (lo, hi) = self.particles.iter().fold(
+            (Vec2::splat(f32::INFINITY), Vec2::splat(f32::NEG_INFINITY)),
+            |(lo, hi), p| (lo.min(p.pos), hi.max(p.pos)),
+        );
+        let grow = (hi - lo).max(Vec2::splat(self.h));
+        let mut points = sample_walls(domain, spacing, (lo - grow, hi + grow));
+        for collider in &self.colliders {
+            points.extend(sample_collider(collider, spacing));
+        }
+        let points = thin_out(points, spacing, MIN_GAP);
+        self.boundary = Some(BoundaryParticles::new(points, self.h, self.rho_0));
+    }
+}
@@ -0,0 +1,296 @@
+// obstacles described by signed distance functions (negative inside)
+use bevy::prelude::*;
+use glam::{UVec2, Vec2};
+
+use crate::cpu::body::{sync_fluid_bodies, write_back_fluid_bodies};
+use crate::cpu::sph2d::SPHState;
+
+// step for the finite-difference SDF gradient
+const SDF_EPS: f32 = 1e-3;
+
+// distance sampled on a regular grid, e.g. baked from a level mesh
+#[derive(Clone, Debug, PartialEq)]
+pub struct SdfGrid {
+    pub origin: Vec2, // position of sample (0, 0) in the collider frame
+    pub cell_size: f32,
+    pub dims: UVec2,
+    pub values: Vec<f32>, // row-major, x + y * dims.x
+}
+
+impl SdfGrid {
+    // samples `shape` at every grid point of [min, max]
+    pub fn bake(shape: &ColliderShape, min: Vec2, max: Vec2, cell_size: f32) -> Self {
+        let dims = (((max - min) / cell_size).ceil().as_uvec2() + 1).max(UVec2::splat(2));
+        let mut values = Vec::with_capacity((dims.x * dims.y) as usize);
+        for y in 0..dims.y {
+            for x in 0..dims.x {
+                let p = min + Vec2::new(x as f32, y as f32) * cell_size;
+                values.push(shape.distance(p));
+            }
+        }
+        Self {
+            origin: min,
+            cell_size,
+            dims,
+            values,
+        }
+    }
+
+    // bilinear; outside the grid there is no obstacle
+    pub fn sample(&self, p: Vec2) -> f32 {
+        let g = (p - self.origin) / self.cell_size;
+        let max = (self.dims - 1).as_vec2();
+        if g.x < 0.0 || g.y < 0.0 || g.x > max.x || g.y > max.y {
+            return f32::INFINITY;
+        }
+        let c = g.floor().min(max - 1.0);
+        let t = g - c;
+        let (x, y) = (c.x as u32, c.y as u32);
+        let at = |x: u32, y: u32| self.values[(x + y * self.dims.x) as usize];
+
+        let bottom = at(x, y) * (1.0 - t.x) + at(x + 1, y) * t.x;
+        let top = at(x, y + 1) * (1.0 - t.x) + at(x + 1, y + 1) * t.x;
+        bottom * (1.0 - t.y) + top * t.y
+    }
+}
+
+// shapes in the collider frame (centred on Collider::position)
+#[derive(Clone, Debug, PartialEq)]
+pub enum ColliderShape {
+    Circle { radius: f32 },
+    Box { half_extents: Vec2 },
+    Capsule { a: Vec2, b: Vec2, radius: f32 },
+    // closed outline, any winding, may be concave (cups)
+    Polygon { points: Vec<Vec2> },
+    Sdf(SdfGrid),
+}
+
+impl ColliderShape {
+    pub fn distance(&self, p: Vec2) -> f32 {
+        match self {
+            ColliderShape::Circle { radius } => p.length() - radius,
+            ColliderShape::Box { half_extents } => {
+                let d = p.abs() - *half_extents;
+                d.max(Vec2::ZERO).length() + d.x.max(d.y).min(0.0)
+            }
+            ColliderShape::Capsule { a, b, radius } => {
+                let pa = p - *a;
+                let ba = *b - *a;
+                let t = (pa.dot(ba) / ba.length_squared().max(f32::EPSILON)).clamp(0.0, 1.0);
+                (pa - ba * t).length() - radius
+            }
+            ColliderShape::Polygon { points } => polygon_distance(p, points),
+            ColliderShape::Sdf(grid) => grid.sample(p),
+        }
+    }
+
+    // radius of a circle around the collider origin that holds the shape
+    pub fn bounding_radius(&self) -> f32 {
+        match self {
+            ColliderShape::Circle { radius } => *radius,
+            ColliderShape::Box { half_extents } => half_extents.length(),
+            ColliderShape::Capsule { a, b, radius } => a.length().max(b.length()) + radius,
+            ColliderShape::Polygon { points } => {
+                points.iter().map(|p| p.length()).fold(0.0, f32::max)
+            }
+            ColliderShape::Sdf(grid) => {
+                let far = grid.origin + (grid.dims - 1).as_vec2() * grid.cell_size;
+                let corners = [
+                    grid.origin,
+                    far,
+                    Vec2::new(grid.origin.x, far.y),
+                    Vec2::new(far.x, grid.origin.y),
+                ];
+                corners.iter().map(|p| p.length()).fold(0.0, f32::max)
+            }
+        }
+    }
+}
+
+// distance to the outline, negative inside (even-odd rule)
+fn polygon_distance(p: Vec2, v: &[Vec2]) -> f32 {
+    if v.len() < 3 {
+        return f32::INFINITY;
+    }
+    let mut d2 = (p - v[0]).length_squared();
+    let mut sign = 1.0;
+    let mut j = v.len() - 1;
+    for i in 0..v.len() {
+        let e = v[j] - v[i];
+        let w = p - v[i];
+        let b = w - e * (w.dot(e) / e.length_squared().max(f32::EPSILON)).clamp(0.0, 1.0);
+        d2 = d2.min(b.length_squared());
+
+        // does the edge cross the horizontal ray from p
+        let c = [p.y >= v[i].y, p.y < v[j].y, e.x * w.y > e.y * w.x];
+        if c.iter().all(|&x| x) || c.iter().all(|&x| !x) {
+            sign = -sign;
+        }
+        j = i;
+    }
+    sign * d2.sqrt()
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Collider {
+    pub shape: ColliderShape,
+    pub position: Vec2,
+    pub rotation: f32, // radians, counter-clockwise
+    // normal velocity kept after a hit (0 = inelastic, 1 = elastic)
+    pub restitution: f32,
+    // tangential velocity removed on contact (0 = frictionless, 1 = sticky)
+    pub friction: f32,
+    // pull on fluid within h of the surface (Akinci adhesion beta, 0 = none)
+    pub adhesion: f32,
+    // motion of a kinematic collider, handed to the particles it touches
+    pub velocity: Vec2,
+    pub angular_velocity: f32, // radians per second
+}
+
+impl Collider {
+    pub fn new(shape: ColliderShape, position: Vec2) -> Self {
+        Self {
+            shape,
+            position,
+            rotation: 0.0,
+            restitution: 0.0,
+            friction: 0.0,
+            adhesion: 0.0,
+            velocity: Vec2::ZERO,
+            angular_velocity: 0.0,
+        }
+    }
+
+    // velocity of the collider surface at p
+    pub fn velocity_at(&self, p: Vec2) -> Vec2 {
+        self.velocity + self.angular_velocity * (p - self.position).perp()
+    }
+
+    fn to_local(&self, p: Vec2) -> Vec2 {
+        Vec2::from_angle(-self.rotation).rotate(p - self.position)
+    }
+
+    pub fn distance(&self, p: Vec2) -> f32 {
+        self.shape.distance(self.to_local(p))
+    }
+
+    // outward unit normal (SDF gradient) in world space
+    pub fn normal(&self, p: Vec2) -> Vec2 {
+        let dx = Vec2::new(SDF_EPS, 0.0);
+        let dy = Vec2::new(0.0, SDF_EPS);
+        let grad = Vec2::new(
+            self.distance(p + dx) - self.distance(p - dx),
+            self.distance(p + dy) - self.distance(p - dy),
+        );
+        grad.normalize_or_zero()
+    }
+
+    // pushes a particle inside the shape back to its surface; true on contact.
+    // restitution and friction act on the velocity relative to the wall
+    pub fn apply(&self, pos: &mut Vec2, vel: &mut Vec2) -> bool {
+        let d = self.distance(*pos);
+        if d >= 0.0 {
+            return false;
+        }
+        let n = self.normal(*pos);
+        *pos -= n * d;
+
+        let wall = self.velocity_at(*pos);
+        let rel = *vel - wall;
+        let vn = rel.dot(n);
+        if vn < 0.0 {
+            let normal = n * vn;
+            let tangent = rel - normal;
+            *vel =
+                wall + tangent * (1.0 - self.friction.clamp(0.0, 1.0)) - normal * self.restitution;
+        }
+        true
+    }
+}
+
+// collider that follows the entity's Transform (xy translation, rotation about z).
+// scale is ignored, size the shape instead
+#[derive(Component, Clone, Debug)]
+pub struct FluidCollider {
+    pub shape: ColliderShape,
+    pub restitution: f32,
+    pub friction: f32,
+    pub adhesion: f32,
+    last_pose: Option<(Vec2, f32)>, // to estimate the wall velocity
+}
+
+impl FluidCollider {
+    pub fn new(shape: ColliderShape) -> Self {
+        Self {
+            shape,
+            restitution: 0.0,
+            friction: 0.0,
+            adhesion: 0.0,
+            last_pose: None,
+        }
+    }
+}
+
+fn pose_of(transform: &Transform) -> (Vec2, f32) {
+    let (_, _, angle) = transform.rotation.to_euler(EulerRot::XYZ);
+    let t = transform.translation; // bevy's glam, not ours
+    (Vec2::new(t.x, t.y), angle)
+}
+
+// rebuilds SPHState::moving_colliders from the FluidCollider entities.
+// the wall velocity is the pose change since the last frame
+pub fn sync_fluid_colliders(
+    time: Res<Time>,
+    mut sph: ResMut<SPHState>,
+    mut query: Query<(&mut FluidCollider, &Transform)>,
+) {
+    let dt = time.delta_secs();
+    let mut moving = Vec::with_capacity(query.iter().len());
+
+    for (mut fc, transform) in &mut query {
+        let (position, rotation) = pose_of(transform);
+        let (velocity, angular_velocity) = match fc.last_pose {
+            Some((last_pos, last_rot)) if dt > 0.0 => {
+                // shortest way round so +pi -> -pi is not a full spin
+                let turn = (rotation - last_rot + std::f32::consts::PI)
+                    .rem_euclid(std::f32::consts::TAU)
+                    - std::f32::consts::PI;
+                ((position - last_pos) / dt, turn / dt)
+            }
+            _ => (Vec2::ZERO, 0.0),
+        };
+        fc.last_pose = Some((position, rotation));
+
+        moving.push(Collider {
+            shape: fc.shape.clone(),
+            position,
+            rotation,
+            restitution: fc.restitution,
+            friction: fc.friction,
+            adhesion: fc.adhesion,
+            velocity,
+            angular_velocity,
+        });
+    }
+
+    // keep SPHState unchanged (no GPU re-upload) if nothing is there
+    if moving.is_empty() && sph.moving_colliders.is_empty() {
+        return;
+    }
+    sph.moving_colliders = moving;
+}
+
+// FluidCollider and FluidBody entities. Syncs run before Update so the
+// simulation step of this frame sees the new poses; bodies are written back
+// before the transforms are propagated.
+pub struct FluidColliderPlugin;
+
+impl Plugin for FluidColliderPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(PreUpdate, (sync_fluid_colliders, sync_fluid_bodies))
+            .add_systems(
+                PostUpdate,
+                write_back_fluid_bodies.before(TransformSystem::TransformPropagate),
+            );
+    }
+}
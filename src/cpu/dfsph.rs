@@ -0,0 +1,142 @@
+// divergence-free SPH (Bender & Koschier 2015). Two pressure solves per step:
+// one keeps the velocity field divergence-free, the other corrects the
+// predicted density. Both change velocities directly; the result is handed to
+// integrate() as acc = (v_new - v) / dt, so domain walls and colliders work as
+// for the other solvers.
+use glam::Vec2;
+
+use crate::cpu::sph2d::{
+    DfsphConfig, IterativeConfig, Neighbor, SPHState, SolverStats, grad_spiky_kernel,
+};
+
+// what one of the two solves works on
+struct Solve<'a> {
+    neighbors: &'a [Vec<Neighbor>],
+    grads: &'a [Vec<Vec2>], // grad W(x_i - x_j), same layout as neighbors
+    rho: &'a [f32],
+    alpha: &'a [f32],
+    dt: f32,
+}
+
+impl SPHState {
+    // fills rho, p and acc like density_pressure_calc + accel_field_calc
+    pub(crate) fn dfsph_accel(&mut self, dt: f32, config: DfsphConfig) {
+        let neighbors = self.neighbor_lists();
+        let rho = self.densities(&neighbors);
+        let grads: Vec<Vec<Vec2>> = neighbors
+            .iter()
+            .enumerate()
+            .map(|(i, list)| {
+                let x_i = self.particles[i].pos;
+                list.iter()
+                    .map(|nb| grad_spiky_kernel(x_i + nb.shift - self.particles[nb.j].pos, self.h))
+                    .collect()
+            })
+            .collect();
+
+        // alpha_i = rho_i / (|sum m grad W|^2 + sum |m grad W|^2)
+        let alpha: Vec<f32> = grads
+            .iter()
+            .enumerate()
+            .map(|(i, list)| {
+                let sum: Vec2 = list.iter().map(|g| self.m * *g).sum();
+                let sum_sq: f32 = list.iter().map(|g| (self.m * *g).length_squared()).sum();
+                let denom = sum.length_squared() + sum_sq;
+                if denom > 1e-6 { rho[i] / denom } else { 0.0 }
+            })
+            .collect();
+
+        let solve = Solve {
+            neighbors: &neighbors,
+            grads: &grads,
+            rho: &rho,
+            alpha: &alpha,
+            dt,
+        };
+        let v0: Vec<Vec2> = self.particles.iter().map(|p| p.vel).collect();
+        let mut vel = v0.clone();
+        let mut kappa_sum = vec![0.0; vel.len()];
+        let mut stats = SolverStats::default();
+
+        (stats.divergence_iterations, stats.divergence_error) =
+            self.dfsph_solve(&solve, config.divergence, false, &mut vel, &mut kappa_sum);
+
+        let acc_np = self.non_pressure_accel(&neighbors, &rho, &vel);
+        for (v, a) in vel.iter_mut().zip(&acc_np) {
+            *v += dt * *a;
+        }
+
+        // only the density solve becomes the particle pressure
+        kappa_sum.fill(0.0);
+        (stats.iterations, stats.density_error) =
+            self.dfsph_solve(&solve, config.density, true, &mut vel, &mut kappa_sum);
+
+        let mut acc_vec = Vec::with_capacity(vel.len());
+        for (i, particle) in self.particles.iter_mut().enumerate() {
+            particle.rho = rho[i];
+            particle.p = kappa_sum[i] * rho[i]; // kappa = p / rho
+            acc_vec.push((vel[i] - v0[i]) / dt);
+        }
+        self.body_forces(&mut acc_vec);
+        for (particle, acc) in self.particles.iter_mut().zip(acc_vec) {
+            particle.acc = acc;
+        }
+        self.stats = stats;
+    }
+
+    // Jacobi iterations on vel until the density change over one step (or the
+    // predicted compression for `density`) is within the tolerance.
+    // Returns the iterations and the final error
+    fn dfsph_solve(
+        &self,
+        solve: &Solve,
+        config: IterativeConfig,
+        density: bool,
+        vel: &mut [Vec2],
+        kappa_sum: &mut [f32],
+    ) -> (u32, f32) {
+        let n = vel.len();
+        let dt = solve.dt;
+        let mut kappa = vec![0.0; n];
+        let mut iterations = 0;
+        let mut error = 0.0;
+
+        while iterations < config.max_iterations {
+            let mut sum = 0.0;
+            for (i, list) in solve.neighbors.iter().enumerate() {
+                // density change over the step, only compression is corrected
+                let mut div = 0.0;
+                for (nb, grad) in list.iter().zip(&solve.grads[i]) {
+                    div += self.m * (vel[i] - vel[nb.j]).dot(*grad);
+                }
+                let mut source = dt * div;
+                if density {
+                    source += solve.rho[i] - self.rho_0;
+                }
+                let source = source.max(0.0);
+                kappa[i] = source / (dt * dt) * solve.alpha[i];
+                sum += source;
+            }
+
+            iterations += 1;
+            error = if n > 0 {
+                sum / (n as f32 * self.rho_0)
+            } else {
+                0.0
+            };
+            if iterations >= config.min_iterations && error <= config.tolerance {
+                break;
+            }
+
+            for (i, list) in solve.neighbors.iter().enumerate() {
+                let k_i = kappa[i] / solve.rho[i];
+                for (nb, grad) in list.iter().zip(&solve.grads[i]) {
+                    let k_j = kappa[nb.j] / solve.rho[nb.j];
+                    vel[i] -= dt * self.m * (k_i + k_j) * *grad;
+                }
+                kappa_sum[i] += kappa[i];
+            }
+        }
+        (iterations, error)
+    }
+}
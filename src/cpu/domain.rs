@@ -0,0 +1,184 @@
+// rectangular simulation domain with one behaviour per wall
+use glam::Vec2;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum WallMode {
+    // mirror the normal velocity, scaled by the restitution (1 = elastic)
+    Reflect { restitution: f32 },
+    // drop the normal velocity, keep the tangential one
+    FreeSlip,
+    // particle sticks to the wall
+    NoSlip,
+    // particles leaving through this wall are deleted
+    Open,
+    // particles leave and re-enter on the opposite wall (set both sides)
+    Periodic,
+}
+
+impl WallMode {
+    // id used by the GPU (IntegrateParams::modes)
+    pub fn id(self) -> u32 {
+        match self {
+            WallMode::Reflect { .. } => 0,
+            WallMode::FreeSlip => 1,
+            WallMode::NoSlip => 2,
+            WallMode::Open => 3,
+            WallMode::Periodic => 4,
+        }
+    }
+
+    pub fn restitution(self) -> f32 {
+        match self {
+            WallMode::Reflect { restitution } => restitution,
+            _ => 0.0,
+        }
+    }
+}
+
+// axes on which the neighbour search wraps around
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Periodicity {
+    pub min: Vec2,
+    pub max: Vec2,
+    pub x: bool,
+    pub y: bool,
+}
+
+impl Periodicity {
+    // Offsets to add to `pos` so the 3x3 cell search also covers the far side
+    // of a periodic axis. The first entry is always zero (no shift).
+    // Assumes the period is larger than 2h.
+    pub fn images(&self, pos: Vec2, h: f32) -> ([Vec2; 4], usize) {
+        let shift = |on: bool, x: f32, lo: f32, hi: f32| {
+            if !on {
+                0.0
+            } else if x - lo < h {
+                hi - lo
+            } else if hi - x < h {
+                lo - hi
+            } else {
+                0.0
+            }
+        };
+        let sx = shift(self.x, pos.x, self.min.x, self.max.x);
+        let sy = shift(self.y, pos.y, self.min.y, self.max.y);
+
+        let mut images = [Vec2::ZERO; 4];
+        let mut n = 1;
+        if sx != 0.0 {
+            images[n] = Vec2::new(sx, 0.0);
+            n += 1;
+        }
+        if sy != 0.0 {
+            images[n] = Vec2::new(0.0, sy);
+            n += 1;
+        }
+        if sx != 0.0 && sy != 0.0 {
+            images[n] = Vec2::new(sx, sy);
+            n += 1;
+        }
+        (images, n)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Domain {
+    pub min: Vec2,
+    pub max: Vec2,
+    pub left: WallMode,
+    pub right: WallMode,
+    pub bottom: WallMode,
+    pub top: WallMode,
+    // pull of each wall on fluid within h, [left, right, bottom, top]
+    // (Akinci adhesion beta, 0 = none)
+    pub adhesion: [f32; 4],
+}
+
+impl Domain {
+    pub fn new(min: Vec2, max: Vec2, mode: WallMode) -> Self {
+        Self {
+            min,
+            max,
+            left: mode,
+            right: mode,
+            bottom: mode,
+            top: mode,
+            adhesion: [0.0; 4],
+        }
+    }
+
+    // the original scene: floor at y = 0, side walls, no ceiling.
+    // bounce is the (negative) factor the normal velocity is multiplied with
+    pub fn floor_and_walls(x_min: f32, x_max: f32, bounce: f32) -> Self {
+        Self::new(
+            Vec2::new(x_min, 0.0),
+            Vec2::new(x_max, f32::INFINITY),
+            WallMode::Reflect {
+                restitution: -bounce,
+            },
+        )
+    }
+
+    // [left, right, bottom, top]
+    pub fn walls(&self) -> [WallMode; 4] {
+        [self.left, self.right, self.bottom, self.top]
+    }
+
+    // adhesion of the walls fluid can touch, open and periodic ones have none
+    pub fn wall_adhesion(&self) -> [f32; 4] {
+        let walls = self.walls();
+        std::array::from_fn(|i| match walls[i] {
+            WallMode::Open | WallMode::Periodic => 0.0,
+            _ => self.adhesion[i],
+        })
+    }
+
+    // an axis wraps only if both of its walls are periodic
+    pub fn periodicity(&self) -> Periodicity {
+        Periodicity {
+            min: self.min,
+            max: self.max,
+            x: self.left == WallMode::Periodic && self.right == WallMode::Periodic,
+            y: self.bottom == WallMode::Periodic && self.top == WallMode::Periodic,
+        }
+    }
+
+    pub fn size(&self) -> Vec2 {
+        self.max - self.min
+    }
+
+    // applies the walls to one particle; false if it left through an open wall
+    pub fn apply(&self, pos: &mut Vec2, vel: &mut Vec2) -> bool {
+        let walls = self.walls();
+        for axis in 0..2 {
+            let (lo, hi) = (self.min[axis], self.max[axis]);
+            let (mode, wall) = if pos[axis] < lo {
+                (walls[2 * axis], lo)
+            } else if pos[axis] > hi {
+                (walls[2 * axis + 1], hi)
+            } else {
+                continue;
+            };
+
+            match mode {
+                WallMode::Reflect { restitution } => {
+                    pos[axis] = wall;
+                    vel[axis] *= -restitution;
+                }
+                WallMode::FreeSlip => {
+                    pos[axis] = wall;
+                    vel[axis] = 0.0;
+                }
+                WallMode::NoSlip => {
+                    pos[axis] = wall;
+                    *vel = Vec2::ZERO;
+                }
+                WallMode::Open => return false,
+                WallMode::Periodic => {
+                    pos[axis] = lo + (pos[axis] - lo).rem_euclid(hi - lo);
+                }
+            }
+        }
+        true
+    }
+}
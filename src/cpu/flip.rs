@@ -0,0 +1,301 @@
+// FLIP/PIC/APIC (Zhu & Bridson 2005, Jiang et al. 2015). The particle
+// velocities are splatted onto a staggered (MAC) grid of cell size h, gravity is
+// added and the pressure is projected out with relaxed Jacobi iterations. The
+// particles then take the grid velocity (PIC/APIC) or keep their own and add
+// the grid change (FLIP), blended by FlipConfig::flip_ratio. Domain walls are
+// solid cells unless open or periodic; colliders and bodies act on the
+// particles as for the SPH solvers. The result is handed to integrate() as
+// acc = (v_new - v) / dt.
+use glam::{IVec2, Mat2, Vec2};
+
+use crate::cpu::domain::{Domain, WallMode};
+use crate::cpu::sph2d::{FlipConfig, SPHState, SolverStats};
+
+const AXES: [IVec2; 2] = [IVec2::X, IVec2::Y];
+
+// Grid around the particles with an empty cell on every side. Node (i, j)
+// holds cell (i, j), the u face on its left and the v face below it, so every
+// axis has one node more than cells (the GPU MacNode uses the same layout).
+struct MacGrid {
+    origin: IVec2, // world cell of node (0, 0)
+    cells: IVec2,
+    dx: f32,
+    domain: Domain,
+    vel: [Vec<f32>; 2], // u and v faces
+    vel_old: [Vec<f32>; 2],
+    count: Vec<u32>, // particles per cell
+    p: Vec<f32>,     // zero outside the fluid cells
+}
+
+impl MacGrid {
+    fn around(sph: &SPHState, domain: &Domain) -> Self {
+        let dx = sph.h;
+        let mut lo = IVec2::MAX;
+        let mut hi = IVec2::MIN;
+        for p in &sph.particles {
+            let c = (p.pos / dx).floor().as_ivec2();
+            lo = lo.min(c);
+            hi = hi.max(c);
+        }
+        let origin = lo - IVec2::ONE;
+        let cells = hi - lo + IVec2::splat(3);
+        let nodes = ((cells.x + 1) * (cells.y + 1)) as usize;
+        Self {
+            origin,
+            cells,
+            dx,
+            domain: *domain,
+            vel: [vec![0.0; nodes], vec![0.0; nodes]],
+            vel_old: [vec![0.0; nodes], vec![0.0; nodes]],
+            count: vec![0; nodes],
+            p: vec![0.0; nodes],
+        }
+    }
+
+    fn node(&self, c: IVec2) -> usize {
+        (c.y * (self.cells.x + 1) + c.x) as usize
+    }
+
+    fn cell_of(&self, pos: Vec2) -> IVec2 {
+        (pos / self.dx).floor().as_ivec2() - self.origin
+    }
+
+    // behind a wall that holds the fluid back; works outside the grid too
+    fn solid(&self, c: IVec2) -> bool {
+        let centre = (self.origin + c).as_vec2() * self.dx + 0.5 * self.dx;
+        let walls = self.domain.walls();
+        (0..2).any(|axis| {
+            let mode = if centre[axis] < self.domain.min[axis] {
+                walls[2 * axis]
+            } else if centre[axis] > self.domain.max[axis] {
+                walls[2 * axis + 1]
+            } else {
+                return false;
+            };
+            !matches!(mode, WallMode::Open | WallMode::Periodic)
+        })
+    }
+
+    fn fluid(&self, c: IVec2) -> bool {
+        self.count[self.node(c)] > 0 && !self.solid(c)
+    }
+
+    // bilinear stencil on the faces normal to e: node, weight, weight
+    // gradient and the face position
+    fn stencil(&self, pos: Vec2, e: IVec2) -> [(usize, f32, Vec2, Vec2); 4] {
+        let offset = 0.5 * (Vec2::ONE - e.as_vec2());
+        let s = pos / self.dx - self.origin.as_vec2() - offset;
+        let base = s.floor();
+        let f = s - base;
+        let base = base.as_ivec2();
+        let mut out = [(0, 0.0, Vec2::ZERO, Vec2::ZERO); 4];
+        for (k, corner) in [IVec2::ZERO, IVec2::X, IVec2::Y, IVec2::ONE]
+            .iter()
+            .enumerate()
+        {
+            let wx = if corner.x == 0 { 1.0 - f.x } else { f.x };
+            let wy = if corner.y == 0 { 1.0 - f.y } else { f.y };
+            let sx = if corner.x == 0 { -1.0 } else { 1.0 };
+            let sy = if corner.y == 0 { -1.0 } else { 1.0 };
+            let c = base + *corner;
+            let face = (self.origin + c).as_vec2() + offset;
+            out[k] = (
+                self.node(c),
+                wx * wy,
+                Vec2::new(sx * wy, wx * sy) / self.dx,
+                face * self.dx,
+            );
+        }
+        out
+    }
+
+    fn particles_to_grid(&mut self, sph: &SPHState, apic: bool) {
+        let nodes = self.count.len();
+        let mut weight = [vec![0.0; nodes], vec![0.0; nodes]];
+        let mut p_sum = vec![0.0; nodes];
+        for particle in &sph.particles {
+            for (axis, e) in AXES.into_iter().enumerate() {
+                for (k, w, _, face) in self.stencil(particle.pos, e) {
+                    let mut v = particle.vel[axis];
+                    if apic {
+                        v += (particle.affine * (face - particle.pos))[axis];
+                    }
+                    self.vel[axis][k] += w * v;
+                    weight[axis][k] += w;
+                }
+            }
+            let k = self.node(self.cell_of(particle.pos));
+            self.count[k] += 1;
+            p_sum[k] += particle.p;
+        }
+
+        for (vel, weight) in self.vel.iter_mut().zip(&weight) {
+            for (v, w) in vel.iter_mut().zip(weight) {
+                *v = if *w > 0.0 { *v / w } else { 0.0 };
+            }
+        }
+        // warm start from the pressures the particles carried out of the last step
+        for j in 0..self.cells.y {
+            for i in 0..self.cells.x {
+                let c = IVec2::new(i, j);
+                if self.fluid(c) {
+                    let k = self.node(c);
+                    self.p[k] = p_sum[k] / self.count[k] as f32;
+                }
+            }
+        }
+    }
+
+    // no flow through the walls (they are static). Faces inside a wall keep
+    // the splatted velocity, so particles next to it still slide along
+    fn enforce_solid(&mut self) {
+        for (axis, e) in AXES.into_iter().enumerate() {
+            for j in 0..=self.cells.y {
+                for i in 0..=self.cells.x {
+                    let c = IVec2::new(i, j);
+                    if self.solid(c - e) != self.solid(c) {
+                        let k = self.node(c);
+                        self.vel[axis][k] = 0.0;
+                    }
+                }
+            }
+        }
+    }
+
+    // Jacobi on  sum_n (p_n - p_i) = rho_0 dx^2 / dt div v  over the fluid
+    // cells (air has p = 0, solid neighbours drop out), then the update
+    // v -= dt / (rho_0 dx) grad p
+    fn project(&mut self, dt: f32, rho_0: f32, config: FlipConfig) -> SolverStats {
+        let scale = dt / (rho_0 * self.dx * self.dx);
+        let mut fluid = Vec::new();
+        for j in 0..self.cells.y {
+            for i in 0..self.cells.x {
+                let c = IVec2::new(i, j);
+                if self.fluid(c) {
+                    fluid.push(c);
+                }
+            }
+        }
+
+        let mut stats = SolverStats::default();
+        let mut p_new = self.p.clone();
+        while !fluid.is_empty() && stats.iterations < config.pressure.max_iterations {
+            let mut error = 0.0;
+            for &c in &fluid {
+                let k = self.node(c);
+                let mut div = 0.0;
+                let mut sum = 0.0;
+                let mut open = 0.0;
+                for (axis, e) in AXES.into_iter().enumerate() {
+                    div += (self.vel[axis][self.node(c + e)] - self.vel[axis][k]) / self.dx;
+                    for n in [c - e, c + e] {
+                        if !self.solid(n) {
+                            sum += self.p[self.node(n)];
+                            open += 1.0;
+                        }
+                    }
+                }
+
+                // divergence left after projecting with the current pressures
+                let residual = div - scale * (sum - open * self.p[k]);
+                error += residual.abs() * dt;
+
+                p_new[k] = if open > 0.0 {
+                    let jacobi = (sum - div / scale) / open;
+                    let omega = config.relaxation;
+                    (1.0 - omega) * self.p[k] + omega * jacobi
+                } else {
+                    0.0 // walled in
+                };
+            }
+            self.p.copy_from_slice(&p_new);
+
+            stats.iterations += 1;
+            stats.density_error = error / fluid.len() as f32;
+            if stats.iterations >= config.pressure.min_iterations
+                && stats.density_error <= config.pressure.tolerance
+            {
+                break;
+            }
+        }
+
+        for (axis, e) in AXES.into_iter().enumerate() {
+            for j in 0..=self.cells.y {
+                for i in 0..=self.cells.x {
+                    let c = IVec2::new(i, j);
+                    let lo = c - e;
+                    if self.solid(lo) || self.solid(c) {
+                        continue;
+                    }
+                    if (self.in_grid(lo) && self.fluid(lo)) || (self.in_grid(c) && self.fluid(c)) {
+                        let p_lo = self.p[self.node(lo)];
+                        let k = self.node(c);
+                        self.vel[axis][k] -= dt / (rho_0 * self.dx) * (self.p[k] - p_lo);
+                    }
+                }
+            }
+        }
+        stats
+    }
+
+    fn in_grid(&self, c: IVec2) -> bool {
+        c.cmpge(IVec2::ZERO).all() && c.cmplt(self.cells).all()
+    }
+}
+
+impl SPHState {
+    // fills rho, p and acc like density_pressure_calc + accel_field_calc
+    pub(crate) fn flip_accel(&mut self, dt: f32, config: FlipConfig, domain: &Domain) {
+        if self.particles.is_empty() {
+            self.stats = SolverStats::default();
+            return;
+        }
+        let mut grid = MacGrid::around(self, domain);
+        grid.particles_to_grid(self, config.apic);
+        grid.vel_old = grid.vel.clone();
+        for (vel, g) in grid.vel.iter_mut().zip(self.gravity.to_array()) {
+            for v in vel {
+                *v += dt * g;
+            }
+        }
+        grid.enforce_solid();
+        let stats = grid.project(dt, self.rho_0, config);
+
+        // grid to particles
+        let ratio = config.flip_ratio;
+        let mut acc_vec = Vec::with_capacity(self.particles.len());
+        for particle in &mut self.particles {
+            let mut v_pic = Vec2::ZERO;
+            let mut change = Vec2::ZERO;
+            let mut grad = [Vec2::ZERO; 2];
+            for (axis, e) in AXES.into_iter().enumerate() {
+                for (k, w, dw, _) in grid.stencil(particle.pos, e) {
+                    v_pic[axis] += w * grid.vel[axis][k];
+                    change[axis] += w * (grid.vel[axis][k] - grid.vel_old[axis][k]);
+                    grad[axis] += dw * grid.vel[axis][k];
+                }
+            }
+            let v_new = ratio * (particle.vel + change) + (1.0 - ratio) * v_pic;
+            acc_vec.push((v_new - particle.vel) / dt);
+
+            particle.affine = if config.apic {
+                // rows are grad u and grad v
+                Mat2::from_cols(
+                    Vec2::new(grad[0].x, grad[1].x),
+                    Vec2::new(grad[0].y, grad[1].y),
+                )
+            } else {
+                Mat2::ZERO
+            };
+            let k = grid.node(grid.cell_of(particle.pos));
+            particle.p = grid.p[k];
+            particle.rho = self.m * grid.count[k] as f32 / (grid.dx * grid.dx);
+        }
+
+        self.body_forces(&mut acc_vec);
+        for (particle, acc) in self.particles.iter_mut().zip(acc_vec) {
+            particle.acc = acc;
+        }
+        self.stats = stats;
+    }
+}
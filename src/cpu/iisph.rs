@@ -0,0 +1,134 @@
+// implicit incompressible SPH (Ihmsen et al. 2014). The pressure Poisson
+// equation
+//   a_ii p_i + sum_j m (sum_k d_ik p_k - d_jj p_j - sum_{k != i} d_jk p_k) . grad W_ij
+//     = rho_0 - rho_adv_i
+// is solved with relaxed Jacobi iterations, where d_ij p_j is the displacement
+// dt^2 a_p the pressure of j causes on i. Neighbours come from neighbor_lists
+// (build_grid or the hashed grid, like the other solvers).
+use glam::Vec2;
+
+use crate::cpu::sph2d::{IisphConfig, SPHState, SolverStats, grad_spiky_kernel};
+
+impl SPHState {
+    // fills rho, p and acc like density_pressure_calc + accel_field_calc
+    pub(crate) fn iisph_accel(&mut self, dt: f32, config: IisphConfig) {
+        let n = self.particles.len();
+        let m = self.m;
+        let dt2 = dt * dt;
+        let neighbors = self.neighbor_lists();
+        let rho = self.densities(&neighbors);
+        let vel: Vec<Vec2> = self.particles.iter().map(|p| p.vel).collect();
+        let acc_np = self.non_pressure_accel(&neighbors, &rho, &vel);
+        let grads: Vec<Vec<Vec2>> = neighbors
+            .iter()
+            .enumerate()
+            .map(|(i, list)| {
+                let x_i = self.particles[i].pos;
+                list.iter()
+                    .map(|nb| grad_spiky_kernel(x_i + nb.shift - self.particles[nb.j].pos, self.h))
+                    .collect()
+            })
+            .collect();
+
+        // advected velocity and density, d_ii = -dt^2 sum m / rho_i^2 grad W
+        let v_adv: Vec<Vec2> = (0..n).map(|i| vel[i] + dt * acc_np[i]).collect();
+        let mut rho_adv = rho.clone();
+        let mut d_ii = vec![Vec2::ZERO; n];
+        for (i, list) in neighbors.iter().enumerate() {
+            for (nb, grad) in list.iter().zip(&grads[i]) {
+                d_ii[i] -= dt2 * m / (rho[i] * rho[i]) * *grad;
+                rho_adv[i] += dt * m * (v_adv[i] - v_adv[nb.j]).dot(*grad);
+            }
+        }
+
+        // a_ii = sum m (d_ii - d_ji) . grad W, d_ji = dt^2 m / rho_i^2 grad W_ij
+        let a_ii: Vec<f32> = (0..n)
+            .map(|i| {
+                let d_ji = dt2 * m / (rho[i] * rho[i]);
+                grads[i]
+                    .iter()
+                    .map(|g| m * (d_ii[i] - d_ji * *g).dot(*g))
+                    .sum()
+            })
+            .collect();
+
+        // warm start from the last step
+        let mut p: Vec<f32> = self.particles.iter().map(|p| 0.5 * p.p).collect();
+        let mut sum_dp = vec![Vec2::ZERO; n];
+        let mut stats = SolverStats::default();
+
+        while stats.iterations < config.density.max_iterations {
+            // sum_j d_ij p_j = -dt^2 sum m p_j / rho_j^2 grad W
+            for (i, list) in neighbors.iter().enumerate() {
+                sum_dp[i] = Vec2::ZERO;
+                for (nb, grad) in list.iter().zip(&grads[i]) {
+                    sum_dp[i] -= dt2 * m * p[nb.j] / (rho[nb.j] * rho[nb.j]) * *grad;
+                }
+            }
+
+            let mut error = 0.0;
+            let p_new: Vec<f32> = neighbors
+                .iter()
+                .enumerate()
+                .map(|(i, list)| {
+                    let d_ji = dt2 * m / (rho[i] * rho[i]);
+                    let mut sum = 0.0;
+                    for (nb, grad) in list.iter().zip(&grads[i]) {
+                        let j = nb.j;
+                        if j == i {
+                            continue;
+                        }
+                        let others = sum_dp[j] - d_ji * *grad * p[i]; // k != i
+                        sum += m * (sum_dp[i] - d_ii[j] * p[j] - others).dot(*grad);
+                    }
+
+                    // density with the current pressures
+                    let rho_pred = rho_adv[i] + a_ii[i] * p[i] + sum;
+                    error += (rho_pred - self.rho_0).max(0.0);
+
+                    // a_ii < 0 unless there are no neighbours
+                    if a_ii[i] < 0.0 {
+                        let jacobi = (self.rho_0 - rho_adv[i] - sum) / a_ii[i];
+                        let omega = config.relaxation;
+                        ((1.0 - omega) * p[i] + omega * jacobi).max(0.0)
+                    } else {
+                        0.0
+                    }
+                })
+                .collect();
+            p = p_new;
+
+            stats.iterations += 1;
+            stats.density_error = if n > 0 {
+                error / (n as f32 * self.rho_0)
+            } else {
+                0.0
+            };
+            if stats.iterations >= config.density.min_iterations
+                && stats.density_error <= config.density.tolerance
+            {
+                break;
+            }
+        }
+
+        // symmetric pressure force with the solved pressures
+        let mut acc_vec = Vec::with_capacity(n);
+        for (i, list) in neighbors.iter().enumerate() {
+            let mut acc = acc_np[i];
+            for (nb, grad) in list.iter().zip(&grads[i]) {
+                let j = nb.j;
+                acc -= m * (p[i] / (rho[i] * rho[i]) + p[j] / (rho[j] * rho[j])) * *grad;
+            }
+            acc_vec.push(acc);
+        }
+        for (i, particle) in self.particles.iter_mut().enumerate() {
+            particle.rho = rho[i];
+            particle.p = p[i];
+        }
+        self.body_forces(&mut acc_vec);
+        for (particle, acc) in self.particles.iter_mut().zip(acc_vec) {
+            particle.acc = acc;
+        }
+        self.stats = stats;
+    }
+}
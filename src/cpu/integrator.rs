@@ -0,0 +1,108 @@
+// time integrators of the Wcsph step. The pressure solvers and the grid
+// solvers build their velocity update around symplectic Euler and keep it.
+use glam::Vec2;
+
+use crate::cpu::sph2d::{SPHState, Solver};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Integrator {
+    // v += a dt, x += v dt
+    #[default]
+    SymplecticEuler,
+    // kick-drift-kick: half kick, drift, forces at the new positions, half
+    // kick. Two force evaluations per step
+    Leapfrog,
+    // one force evaluation: the velocity gets a full kick and is corrected
+    // by half the change of acceleration at the start of the next step
+    VelocityVerlet,
+    // explicit midpoint: forces at the half step, then a full step from the
+    // start with them. Two force evaluations, not symplectic
+    Rk2,
+}
+
+impl Integrator {
+    // SphParams::integrator
+    pub fn id(self) -> u32 {
+        match self {
+            Integrator::SymplecticEuler => 0,
+            Integrator::Leapfrog => 1,
+            Integrator::VelocityVerlet => 2,
+            Integrator::Rk2 => 3,
+        }
+    }
+
+    // force evaluations per step, the GPU runs the step sub graph this often
+    pub fn stages(self) -> u32 {
+        match self {
+            Integrator::Leapfrog | Integrator::Rk2 => 2,
+            Integrator::SymplecticEuler | Integrator::VelocityVerlet => 1,
+        }
+    }
+}
+
+impl SPHState {
+    // the integrator of the current solver
+    pub fn active_integrator(&self) -> Integrator {
+        match self.solver {
+            Solver::Wcsph => self.integrator,
+            _ => Integrator::SymplecticEuler,
+        }
+    }
+
+    // first stage of Leapfrog and Rk2, before the second force evaluation.
+    // No domain or colliders here, they only see the end of the step.
+    // Returns position and velocity at the start of the step
+    pub fn integrate_predict(&mut self, dt: f32) -> Vec<(Vec2, Vec2)> {
+        let integrator = self.active_integrator();
+        let start = self.particles.iter().map(|p| (p.pos, p.vel)).collect();
+        for p in &mut self.particles {
+            match integrator {
+                Integrator::Leapfrog => {
+                    p.vel += 0.5 * p.acc * dt;
+                    p.pos += p.vel * dt;
+                }
+                Integrator::Rk2 => {
+                    p.pos += 0.5 * p.vel * dt;
+                    p.vel += 0.5 * p.acc * dt;
+                }
+                Integrator::SymplecticEuler | Integrator::VelocityVerlet => {}
+            }
+        }
+        start
+    }
+
+    // last stage; `start` comes from integrate_predict (Rk2 only)
+    pub fn integrate(&mut self, dt: f32, start: &[(Vec2, Vec2)]) {
+        let integrator = self.active_integrator();
+        for (i, p) in self.particles.iter_mut().enumerate() {
+            // a later switch to VelocityVerlet starts over
+            if integrator != Integrator::VelocityVerlet {
+                p.acc_prev = Vec2::ZERO;
+                p.dt_prev = 0.0;
+            }
+            match integrator {
+                Integrator::SymplecticEuler => {
+                    p.vel += p.acc * dt;
+                    p.pos += p.vel * dt;
+                }
+                Integrator::Leapfrog => p.vel += 0.5 * p.acc * dt,
+                Integrator::VelocityVerlet => {
+                    // finish the last step with the new forces and its own dt,
+                    // kick, drift. dt_prev is zero before the first step
+                    if p.dt_prev > 0.0 {
+                        p.vel += 0.5 * (p.acc - p.acc_prev) * p.dt_prev;
+                    }
+                    p.pos += p.vel * dt + 0.5 * p.acc * dt * dt;
+                    p.vel += p.acc * dt;
+                    p.acc_prev = p.acc;
+                    p.dt_prev = dt;
+                }
+                Integrator::Rk2 => {
+                    let (pos, vel) = start[i];
+                    p.pos = pos + p.vel * dt;
+                    p.vel = vel + p.acc * dt;
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,252 @@
+// MLS-MPM (Hu et al. 2018) with quadratic B-splines on a grid of cell size h.
+// Every particle carries its deformation gradient F and the affine velocity
+// C (Particle::affine). A step updates F = (I + dt C) F, applies the
+// plasticity of the material, splats mass and momentum plus the stress
+// impulse onto the grid, adds gravity, stops the nodes at the domain walls
+// and reads the velocity and C back. Like Flip the result is handed to
+// integrate() as acc = (v_new - v) / dt. Open and periodic walls are empty space.
+use glam::{IVec2, Mat2, Vec2};
+
+use crate::cpu::domain::{Domain, WallMode};
+use crate::cpu::sph2d::{MpmConfig, MpmMaterial, SPHState, SolverStats};
+
+// quadratic B-spline weights of the three nodes from base (node at
+// floor(x / dx - 0.5)), per axis
+fn weights(fx: Vec2) -> [Vec2; 3] {
+    [
+        0.5 * (Vec2::splat(1.5) - fx) * (Vec2::splat(1.5) - fx),
+        Vec2::splat(0.75) - (fx - Vec2::ONE) * (fx - Vec2::ONE),
+        0.5 * (fx - Vec2::splat(0.5)) * (fx - Vec2::splat(0.5)),
+    ]
+}
+
+// F = U diag(sigma) V^T from the polar decomposition F = R S and the
+// eigenvectors of the symmetric S. Singular values keep the sign of det F
+pub(crate) fn svd(f: Mat2) -> (Mat2, Vec2, Mat2) {
+    let r = Mat2::from_angle((f.x_axis.y - f.y_axis.x).atan2(f.x_axis.x + f.y_axis.y));
+    let s = r.transpose() * f;
+    let (a, b, d) = (s.x_axis.x, s.y_axis.x, s.y_axis.y);
+    let v = if b.abs() < 1e-9 {
+        Mat2::IDENTITY
+    } else {
+        Mat2::from_angle(0.5 * (2.0 * b).atan2(a - d))
+    };
+    let sigma = v.transpose() * s * v;
+    (r * v, Vec2::new(sigma.x_axis.x, sigma.y_axis.y), v)
+}
+
+// plastic flow of snow and sand, then the Kirchhoff stress tau = P F^T
+pub(crate) fn stress(config: &MpmConfig, f: &mut Mat2, plastic_j: &mut f32) -> Mat2 {
+    let (mu, lambda) = config.lame();
+    match config.material {
+        MpmMaterial::Fluid { bulk_modulus } => {
+            // p = bulk_modulus (1 / J - 1); only the volume is kept in F
+            let j = f.determinant();
+            *f = Mat2::from_diagonal(Vec2::splat(j.max(0.0).sqrt()));
+            Mat2::from_diagonal(Vec2::splat(bulk_modulus * (j - 1.0)))
+        }
+        MpmMaterial::Jelly => corotated(*f, mu, lambda),
+        MpmMaterial::Snow {
+            critical_compression,
+            critical_stretch,
+            hardening,
+        } => {
+            let (u, sigma, v) = svd(*f);
+            let clamped = sigma.clamp(
+                Vec2::splat(1.0 - critical_compression),
+                Vec2::splat(1.0 + critical_stretch),
+            );
+            *plastic_j *= sigma.x * sigma.y / (clamped.x * clamped.y);
+            *f = u * Mat2::from_diagonal(clamped) * v.transpose();
+            // packed snow gets harder, stretched snow softer
+            let h = (hardening * (1.0 - *plastic_j)).min(5.0).exp();
+            corotated(*f, mu * h, lambda * h)
+        }
+        MpmMaterial::Sand { friction_angle } => {
+            let (u, sigma, v) = svd(*f);
+            let alpha = sand_alpha(friction_angle);
+            let eps = sigma.abs().max(Vec2::splat(1e-4)).ln();
+            let projected = sand_projection(eps, mu, lambda, alpha);
+            let clamped = projected.exp();
+            *plastic_j *= sigma.x * sigma.y / (clamped.x * clamped.y);
+            *f = u * Mat2::from_diagonal(clamped) * v.transpose();
+            // Hencky: tau = U (2 mu eps + lambda tr(eps) I) U^T
+            let tau = 2.0 * mu * projected + Vec2::splat(lambda * projected.element_sum());
+            u * Mat2::from_diagonal(tau) * u.transpose()
+        }
+    }
+}
+
+// Drucker-Prager cone from the friction angle in degrees (SphParams::mpm_friction)
+pub fn sand_alpha(friction_angle: f32) -> f32 {
+    let sin = friction_angle.to_radians().sin();
+    (2.0f32 / 3.0).sqrt() * 2.0 * sin / (3.0 - sin)
+}
+
+// fixed corotated: tau = 2 mu (F - R) F^T + lambda J (J - 1) I
+fn corotated(f: Mat2, mu: f32, lambda: f32) -> Mat2 {
+    let (u, _, v) = svd(f);
+    let r = u * v.transpose();
+    let j = f.determinant();
+    2.0 * mu * (f - r) * f.transpose() + Mat2::from_diagonal(Vec2::splat(lambda * j * (j - 1.0)))
+}
+
+// return mapping of the log strain onto the Drucker-Prager cone; sand under
+// tension falls apart (zero strain)
+fn sand_projection(eps: Vec2, mu: f32, lambda: f32, alpha: f32) -> Vec2 {
+    let trace = eps.element_sum();
+    if trace >= 0.0 {
+        return Vec2::ZERO;
+    }
+    let dev = eps - Vec2::splat(0.5 * trace);
+    let norm = dev.length();
+    let dgamma = norm + (2.0 * lambda + 2.0 * mu) / (2.0 * mu) * trace * alpha;
+    if dgamma <= 0.0 || norm == 0.0 {
+        return eps; // inside the cone
+    }
+    eps - dgamma / norm * dev
+}
+
+// collocated nodes around the particles: the stencil of a particle in cell
+// c covers the nodes c - 1 .. c + 2
+struct MpmGrid {
+    origin: IVec2, // world node of index (0, 0)
+    size: IVec2,
+    dx: f32,
+    mass: Vec<f32>,
+    momentum: Vec<Vec2>, // velocity after grid_update
+}
+
+impl MpmGrid {
+    fn around(sph: &SPHState) -> Self {
+        let dx = sph.h;
+        let mut lo = IVec2::MAX;
+        let mut hi = IVec2::MIN;
+        for p in &sph.particles {
+            let c = (p.pos / dx).floor().as_ivec2();
+            lo = lo.min(c);
+            hi = hi.max(c);
+        }
+        let size = hi - lo + IVec2::splat(4);
+        let nodes = (size.x * size.y) as usize;
+        Self {
+            origin: lo - IVec2::ONE,
+            size,
+            dx,
+            mass: vec![0.0; nodes],
+            momentum: vec![Vec2::ZERO; nodes],
+        }
+    }
+
+    // base node, offset of the particle from it in cells, and the weights
+    fn stencil(&self, pos: Vec2) -> (IVec2, Vec2, [Vec2; 3]) {
+        let s = pos / self.dx - Vec2::splat(0.5);
+        let base = s.floor();
+        let fx = pos / self.dx - base;
+        (base.as_ivec2() - self.origin, fx, weights(fx))
+    }
+
+    fn node(&self, c: IVec2) -> usize {
+        (c.y * self.size.x + c.x) as usize
+    }
+
+    // nodes behind a closed wall only keep motion away from it
+    fn grid_update(&mut self, dt: f32, gravity: Vec2, domain: &Domain) {
+        let walls = domain.walls();
+        for y in 0..self.size.y {
+            for x in 0..self.size.x {
+                let c = IVec2::new(x, y);
+                let k = self.node(c);
+                if self.mass[k] <= 0.0 {
+                    continue;
+                }
+                let mut v = self.momentum[k] / self.mass[k] + dt * gravity;
+                let pos = (self.origin + c).as_vec2() * self.dx;
+                for axis in [0, 1] {
+                    let (mode, inward) = if pos[axis] < domain.min[axis] {
+                        (walls[2 * axis], 1.0)
+                    } else if pos[axis] > domain.max[axis] {
+                        (walls[2 * axis + 1], -1.0)
+                    } else {
+                        continue;
+                    };
+                    match mode {
+                        WallMode::Open | WallMode::Periodic => {}
+                        WallMode::NoSlip => v = Vec2::ZERO,
+                        _ => {
+                            if v[axis] * inward < 0.0 {
+                                v[axis] = 0.0;
+                            }
+                        }
+                    }
+                }
+                self.momentum[k] = v;
+            }
+        }
+    }
+}
+
+impl SPHState {
+    // fills rho, p and acc like density_pressure_calc + accel_field_calc
+    pub(crate) fn mpm_accel(&mut self, dt: f32, config: MpmConfig, domain: &Domain) {
+        self.stats = SolverStats::default();
+        if self.particles.is_empty() {
+            return;
+        }
+        let mut grid = MpmGrid::around(self);
+        let dx = grid.dx;
+        let volume = self.m / self.rho_0;
+        // D^-1 = 4 / dx^2 for quadratic B-splines
+        let d_inv = 4.0 / (dx * dx);
+
+        // deformation, plasticity, particles to grid
+        for particle in &mut self.particles {
+            particle.deformation = (Mat2::IDENTITY + dt * particle.affine) * particle.deformation;
+            let tau = stress(&config, &mut particle.deformation, &mut particle.plastic_j);
+            let j = particle.deformation.determinant();
+            particle.rho = self.rho_0 / j.max(1e-6);
+            particle.p = -0.5 * (tau.x_axis.x + tau.y_axis.y) / j.max(1e-6);
+
+            let affine = -dt * volume * d_inv * tau + self.m * particle.affine;
+            let (base, fx, w) = grid.stencil(particle.pos);
+            for oy in 0..3 {
+                for ox in 0..3 {
+                    let offset = IVec2::new(ox, oy);
+                    let weight = w[ox as usize].x * w[oy as usize].y;
+                    let dpos = (offset.as_vec2() - fx) * dx;
+                    let k = grid.node(base + offset);
+                    grid.mass[k] += weight * self.m;
+                    grid.momentum[k] += weight * (self.m * particle.vel + affine * dpos);
+                }
+            }
+        }
+
+        grid.grid_update(dt, self.gravity, domain);
+
+        // grid to particles
+        let mut acc_vec = Vec::with_capacity(self.particles.len());
+        for particle in &mut self.particles {
+            let (base, fx, w) = grid.stencil(particle.pos);
+            let mut v_new = Vec2::ZERO;
+            let mut c = Mat2::ZERO;
+            for oy in 0..3 {
+                for ox in 0..3 {
+                    let offset = IVec2::new(ox, oy);
+                    let weight = w[ox as usize].x * w[oy as usize].y;
+                    let dpos = (offset.as_vec2() - fx) * dx;
+                    let v = grid.momentum[grid.node(base + offset)];
+                    v_new += weight * v;
+                    // outer product v dpos^T
+                    c += d_inv * weight * Mat2::from_cols(v * dpos.x, v * dpos.y);
+                }
+            }
+            acc_vec.push((v_new - particle.vel) / dt);
+            particle.affine = c;
+        }
+
+        self.body_forces(&mut acc_vec);
+        for (particle, acc) in self.particles.iter_mut().zip(acc_vec) {
+            particle.acc = acc;
+        }
+    }
+}
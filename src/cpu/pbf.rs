@@ -0,0 +1,158 @@
+// position based fluids (Macklin & Müller 2013). The density constraint
+// C_i = rho_i / rho_0 - 1 is solved on predicted positions with a fixed number
+// of Jacobi rounds, then the velocity is smoothed (XSPH) and vorticity
+// confinement puts back some of the lost rotation. The result is handed to
+// integrate() as acc = (v_new - v) / dt like the other incompressible solvers.
+use glam::Vec2;
+
+use crate::cpu::pcisph::prototype_grad_sq;
+use crate::cpu::sph2d::{Neighbor, PbfConfig, SPHState, SolverStats, grad_spiky_kernel, w_poly6};
+
+impl SPHState {
+    // fills rho, p and acc like density_pressure_calc + accel_field_calc
+    pub(crate) fn pbf_accel(&mut self, dt: f32, config: PbfConfig) {
+        let n = self.particles.len();
+        let neighbors = self.neighbor_lists();
+        let rho = self.densities(&neighbors);
+        let vel: Vec<Vec2> = self.particles.iter().map(|p| p.vel).collect();
+        let acc_np = self.non_pressure_accel(&neighbors, &rho, &vel);
+
+        let scale = prototype_grad_sq(self.h, self.m, self.rho_0);
+        let epsilon = config.relaxation * scale;
+        let w_dq = w_poly6((config.artificial_pressure_dq * self.h).powi(2), self.h);
+        let m_rho_0 = self.m / self.rho_0;
+
+        let mut predicted: Vec<Vec2> = (0..n)
+            .map(|i| self.particles[i].pos + dt * (vel[i] + dt * acc_np[i]))
+            .collect();
+        let mut lambda = vec![0.0; n];
+        let mut lambda_sum = vec![0.0; n];
+        let mut delta = vec![Vec2::ZERO; n];
+        let mut stats = SolverStats::default();
+
+        for _ in 0..config.iterations {
+            // lambda_i = -C_i / (sum_k |grad_k C_i|^2 + epsilon)
+            let mut error = 0.0;
+            for (i, list) in neighbors.iter().enumerate() {
+                let mut rho_pred = 0.0;
+                let mut grad_i = Vec2::ZERO;
+                let mut sum_sq = 0.0;
+                for nb in list {
+                    let r = predicted[i] + nb.shift - predicted[nb.j];
+                    rho_pred += self.m * w_poly6(r.length_squared(), self.h);
+                    let grad = m_rho_0 * grad_spiky_kernel(r, self.h);
+                    grad_i += grad;
+                    sum_sq += grad.length_squared();
+                }
+                let c = (rho_pred / self.rho_0 - 1.0).max(0.0); // only compression
+                lambda[i] = -c / (grad_i.length_squared() + sum_sq + epsilon);
+                lambda_sum[i] += lambda[i];
+                error += c;
+            }
+
+            // all corrections from the same lambdas, then move
+            for (i, list) in neighbors.iter().enumerate() {
+                delta[i] = Vec2::ZERO;
+                for nb in list {
+                    // zero for i itself
+                    let r = predicted[i] + nb.shift - predicted[nb.j];
+                    let s_corr = if w_dq > 0.0 && scale > 0.0 {
+                        let ratio = w_poly6(r.length_squared(), self.h) / w_dq;
+                        -config.artificial_pressure * ratio.powf(config.artificial_pressure_n)
+                            / scale
+                    } else {
+                        0.0
+                    };
+                    delta[i] += m_rho_0
+                        * (lambda[i] + lambda[nb.j] + s_corr)
+                        * grad_spiky_kernel(r, self.h);
+                }
+            }
+            for (x, d) in predicted.iter_mut().zip(&delta) {
+                *x += *d;
+            }
+
+            stats.iterations += 1;
+            stats.density_error = if n > 0 { error / n as f32 } else { 0.0 };
+        }
+
+        // integrate() moves by dt * v_new, which is the predicted position up
+        // to the XSPH and vorticity changes
+        let mut vel_new: Vec<Vec2> = (0..n)
+            .map(|i| (predicted[i] - self.particles[i].pos) / dt)
+            .collect();
+        self.xsph(&neighbors, &predicted, &rho, config.xsph, &mut vel_new);
+        let vorticity = self.vorticity_accel(&neighbors, &predicted, &rho, &vel_new);
+        for (v, a) in vel_new.iter_mut().zip(vorticity) {
+            *v += dt * config.vorticity * a;
+        }
+
+        let mut acc_vec = Vec::with_capacity(n);
+        for (i, particle) in self.particles.iter_mut().enumerate() {
+            particle.rho = rho[i];
+            // the pressure that moves the particle as far in one step
+            // (dx = -dt^2 m sum (p_i + p_j) / rho_0^2 grad W)
+            particle.p = -lambda_sum[i] * self.rho_0 / (dt * dt);
+            acc_vec.push((vel_new[i] - vel[i]) / dt);
+        }
+        self.body_forces(&mut acc_vec);
+        for (particle, acc) in self.particles.iter_mut().zip(acc_vec) {
+            particle.acc = acc;
+        }
+        self.stats = stats;
+    }
+
+    // v_i += c sum m / rho_j (v_j - v_i) W
+    fn xsph(&self, neighbors: &[Vec<Neighbor>], x: &[Vec2], rho: &[f32], c: f32, vel: &mut [Vec2]) {
+        let smoothed: Vec<Vec2> = neighbors
+            .iter()
+            .enumerate()
+            .map(|(i, list)| {
+                let mut sum = Vec2::ZERO;
+                for nb in list {
+                    let r2 = (x[i] + nb.shift - x[nb.j]).length_squared();
+                    sum += self.m / rho[nb.j] * (vel[nb.j] - vel[i]) * w_poly6(r2, self.h);
+                }
+                vel[i] + c * sum
+            })
+            .collect();
+        vel.copy_from_slice(&smoothed);
+    }
+
+    // N x omega per unit epsilon, N = grad |omega| / |grad |omega||. In 2D
+    // omega is the z component of the curl
+    fn vorticity_accel(
+        &self,
+        neighbors: &[Vec<Neighbor>],
+        x: &[Vec2],
+        rho: &[f32],
+        vel: &[Vec2],
+    ) -> Vec<Vec2> {
+        let omega: Vec<f32> = neighbors
+            .iter()
+            .enumerate()
+            .map(|(i, list)| {
+                let mut curl = 0.0;
+                for nb in list {
+                    let grad = grad_spiky_kernel(x[i] + nb.shift - x[nb.j], self.h);
+                    curl += self.m / rho[nb.j] * (vel[i] - vel[nb.j]).perp_dot(grad);
+                }
+                curl
+            })
+            .collect();
+
+        neighbors
+            .iter()
+            .enumerate()
+            .map(|(i, list)| {
+                let mut eta = Vec2::ZERO;
+                for nb in list {
+                    let grad = grad_spiky_kernel(x[i] + nb.shift - x[nb.j], self.h);
+                    eta += self.m / rho[nb.j] * (omega[nb.j].abs() - omega[i].abs()) * grad;
+                }
+                let n = eta.normalize_or_zero();
+                Vec2::new(n.y, -n.x) * omega[i]
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,103 @@
+// predictive-corrective incompressible SPH (Solenthaler & Pajarola 2009).
+// Pressure is not taken from the equation of state but corrected until the
+// predicted density is within the tolerance of rho_0.
+use glam::Vec2;
+
+use crate::cpu::sph2d::{IterativeConfig, SPHState, SolverStats, grad_spiky_kernel, w_poly6};
+
+// PCISPH stiffness times dt^2, from a particle with a filled neighbourhood on a
+// square lattice of spacing sqrt(m / rho_0). The solver divides it by dt^2
+pub fn pcisph_delta(h: f32, m: f32, rho_0: f32) -> f32 {
+    let denom = 2.0 * prototype_grad_sq(h, m, rho_0);
+    if denom > 0.0 { 1.0 / denom } else { 0.0 }
+}
+
+// sum over k of |grad_k C|^2 for the density constraint C = rho / rho_0 - 1
+// of that lattice particle (the k = i term included)
+pub fn prototype_grad_sq(h: f32, m: f32, rho_0: f32) -> f32 {
+    let spacing = (m / rho_0).sqrt();
+    let n = (h / spacing).ceil() as i32;
+    let mut sum_grad = Vec2::ZERO;
+    let mut sum_dot = 0.0;
+    for y in -n..=n {
+        for x in -n..=n {
+            let grad = grad_spiky_kernel(Vec2::new(x as f32, y as f32) * spacing, h);
+            sum_grad += grad;
+            sum_dot += grad.dot(grad);
+        }
+    }
+    (m / rho_0).powi(2) * (sum_grad.dot(sum_grad) + sum_dot)
+}
+
+impl SPHState {
+    // fills rho, p and acc like density_pressure_calc + accel_field_calc
+    pub(crate) fn pcisph_accel(&mut self, dt: f32, config: IterativeConfig) {
+        let n = self.particles.len();
+        let neighbors = self.neighbor_lists();
+        let rho = self.densities(&neighbors);
+
+        // viscosity and gravity stay fixed during the iterations
+        let vel: Vec<Vec2> = self.particles.iter().map(|p| p.vel).collect();
+        let acc_np = self.non_pressure_accel(&neighbors, &rho, &vel);
+
+        let delta = pcisph_delta(self.h, self.m, self.rho_0) / (dt * dt);
+        let mut p = vec![0.0; n];
+        let mut acc_p = vec![Vec2::ZERO; n];
+        let mut predicted = vec![Vec2::ZERO; n];
+        let mut stats = SolverStats::default();
+
+        while stats.iterations < config.max_iterations {
+            for (i, x) in predicted.iter_mut().enumerate() {
+                let particle = &self.particles[i];
+                *x = particle.pos + dt * (particle.vel + dt * (acc_np[i] + acc_p[i]));
+            }
+
+            // density error of the predicted positions corrects the pressure
+            let mut error = 0.0;
+            for (i, list) in neighbors.iter().enumerate() {
+                let mut rho_pred = 0.0;
+                for nb in list {
+                    let r2 = (predicted[i] + nb.shift - predicted[nb.j]).length_squared();
+                    rho_pred += self.m * w_poly6(r2, self.h);
+                }
+                let rho_err = rho_pred - self.rho_0;
+                p[i] = (p[i] + delta * rho_err).max(0.0); // no tension at the surface
+                error += rho_err.max(0.0);
+            }
+
+            // symmetric pressure force, with rho_0 as in the derivation of delta
+            for (i, list) in neighbors.iter().enumerate() {
+                let x_i = self.particles[i].pos;
+                acc_p[i] = Vec2::ZERO;
+                for nb in list {
+                    // zero for i itself
+                    let grad = grad_spiky_kernel(x_i + nb.shift - self.particles[nb.j].pos, self.h);
+                    acc_p[i] -= self.m * (p[i] + p[nb.j]) / (self.rho_0 * self.rho_0) * grad;
+                }
+            }
+
+            stats.iterations += 1;
+            stats.density_error = if n > 0 {
+                error / (n as f32 * self.rho_0)
+            } else {
+                0.0
+            };
+            if stats.iterations >= config.min_iterations && stats.density_error <= config.tolerance
+            {
+                break;
+            }
+        }
+
+        let mut acc_vec = Vec::with_capacity(n);
+        for (i, particle) in self.particles.iter_mut().enumerate() {
+            particle.rho = rho[i];
+            particle.p = p[i];
+            acc_vec.push(acc_np[i] + acc_p[i]);
+        }
+        self.body_forces(&mut acc_vec);
+        for (particle, acc) in self.particles.iter_mut().zip(acc_vec) {
+            particle.acc = acc;
+        }
+        self.stats = stats;
+    }
+}
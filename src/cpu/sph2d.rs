@@ -2,24 +2,467 @@
 use std::{collections::HashMap, f32::consts::PI};
 
 use bevy::prelude::Resource;
-use glam::{IVec2, Vec2};
+use glam::{IVec2, Mat2, Vec2};
 
-#[derive(Resource, Default)] // to make sure GPU step and CPU step are the same
-pub struct SimStep(pub u64);
+use crate::cpu::body::RigidBody;
+use crate::cpu::boundary::BoundaryParticles;
+use crate::cpu::collider::Collider;
+use crate::cpu::domain::{Domain, Periodicity};
+use crate::cpu::integrator::Integrator;
 
 type Cell = IVec2;
 
 const GRAVITY: Vec2 = Vec2::new(0.0, -9.81);
 
 #[inline]
-fn cell(pos: Vec2, h: f32) -> IVec2 {
+pub(crate) fn cell(pos: Vec2, h: f32) -> IVec2 {
     (pos / h).floor().as_ivec2()
 }
 
+// large primes from Teschner et al. 2003; the WGSL side uses the same hash
+const HASH_P1: u32 = 73856093;
+const HASH_P2: u32 = 19349663;
+
+#[inline]
+pub fn hash_cell(c: IVec2, table_size: u32) -> u32 {
+    ((c.x as u32).wrapping_mul(HASH_P1) ^ (c.y as u32).wrapping_mul(HASH_P2)) % table_size
+}
+
+// how particles are bucketed for the neighbour search
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum GridMode {
+    // one bucket per occupied cell (dense on the GPU, sized from the particle bounds)
+    #[default]
+    Dense,
+    // fixed number of buckets, cells are hashed into them; works for unbounded domains
+    Hashed {
+        table_size: u32,
+    },
+}
+
+impl GridMode {
+    // about two buckets per particle keeps collisions rare
+    pub fn hashed_for(num_particles: usize) -> Self {
+        Self::Hashed {
+            table_size: (num_particles.max(1) * 2).next_power_of_two() as u32,
+        }
+    }
+}
+
+// Tait exponent for water
+pub const TAIT_GAMMA: f32 = 7.0;
+
+// how density turns into pressure
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum EquationOfState {
+    // p = k * max(rho - rho_0, 0), no tension (the original spongy fluid)
+    #[default]
+    LinearClamped,
+    // p = k * (rho - rho_0), negative pressure pulls particles together
+    Linear,
+    // weakly compressible: p = B * ((rho / rho_0)^7 - 1), B = rho_0 * c^2 / 7.
+    // c is usually ~10x the fastest flow speed (density error ~1%)
+    Tait {
+        speed_of_sound: f32,
+    },
+}
+
+impl EquationOfState {
+    // id used by the GPU (SphParams::eos)
+    pub fn id(self) -> u32 {
+        match self {
+            EquationOfState::LinearClamped => 0,
+            EquationOfState::Linear => 1,
+            EquationOfState::Tait { .. } => 2,
+        }
+    }
+
+    pub fn speed_of_sound(self) -> f32 {
+        match self {
+            EquationOfState::Tait { speed_of_sound } => speed_of_sound,
+            _ => 0.0,
+        }
+    }
+
+    pub fn pressure(self, rho: f32, rho_0: f32, k: f32) -> f32 {
+        match self {
+            EquationOfState::LinearClamped => k * (rho - rho_0).max(0.0),
+            EquationOfState::Linear => k * (rho - rho_0),
+            EquationOfState::Tait { speed_of_sound } => {
+                let b = rho_0 * speed_of_sound * speed_of_sound / TAIT_GAMMA;
+                b * ((rho / rho_0).powi(TAIT_GAMMA as i32) - 1.0)
+            }
+        }
+    }
+}
+
+// pressure term of the momentum equation
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum PressureForce {
+    // -m (p_i + p_j) / (2 rho_j) grad W, the original prototype term.
+    // cheap but not symmetric, so momentum drifts
+    #[default]
+    Averaged,
+    // -m (p_i / rho_i^2 + p_j / rho_j^2) grad W, conserves linear momentum.
+    // about rho_0 times weaker than Averaged for the same k
+    Symmetric,
+}
+
+impl PressureForce {
+    // id used by the GPU (SphParams::pressure_force)
+    pub fn id(self) -> u32 {
+        match self {
+            PressureForce::Averaged => 0,
+            PressureForce::Symmetric => 1,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Viscosity {
+    // mu m (v_j - v_i) / rho_j laplacian W (uses SPHState::mu)
+    #[default]
+    Laplacian,
+    // Monaghan artificial viscosity, only between approaching particles;
+    // symmetric like PressureForce::Symmetric. alpha ~ 0.01 - 0.1
+    Artificial {
+        alpha: f32,
+        speed_of_sound: f32,
+    },
+}
+
+impl Viscosity {
+    // id used by the GPU (SphParams::viscosity)
+    pub fn id(self) -> u32 {
+        match self {
+            Viscosity::Laplacian => 0,
+            Viscosity::Artificial { .. } => 1,
+        }
+    }
+}
+
+// how step_domain turns density into pressure forces
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Solver {
+    // weakly compressible: pressure from SPHState::eos, one pass per step
+    #[default]
+    Wcsph,
+    // predictive-corrective incompressible SPH (Solenthaler & Pajarola 2009)
+    Pcisph(IterativeConfig),
+    // divergence-free SPH (Bender & Koschier 2015), for large steps
+    Dfsph(DfsphConfig),
+    // position based fluids (Macklin & Müller 2013), stable at any step
+    Pbf(PbfConfig),
+    // implicit incompressible SPH (Ihmsen et al. 2014)
+    Iisph(IisphConfig),
+    // FLIP/PIC/APIC on a MAC grid (Zhu & Bridson 2005), no SPH sums at all
+    Flip(FlipConfig),
+    // MLS-MPM (Hu et al. 2018) for liquids and granular materials
+    Mpm(MpmConfig),
+}
+
+impl Solver {
+    // id used by the GPU (SphParams::solver)
+    pub fn id(self) -> u32 {
+        match self {
+            Solver::Wcsph => 0,
+            Solver::Pcisph(_) => 1,
+            Solver::Dfsph(_) => 2,
+            Solver::Pbf(_) => 3,
+            Solver::Iisph(_) => 4,
+            Solver::Flip(_) => 5,
+            Solver::Mpm(_) => 6,
+        }
+    }
+
+    // the density solve of the iterative solvers
+    pub fn iterative(self) -> Option<IterativeConfig> {
+        match self {
+            Solver::Wcsph => None,
+            Solver::Pcisph(config) => Some(config),
+            Solver::Dfsph(config) => Some(config.density),
+            // a fixed number of rounds
+            Solver::Pbf(config) => Some(IterativeConfig {
+                tolerance: 0.0,
+                min_iterations: config.iterations,
+                max_iterations: config.iterations,
+            }),
+            Solver::Iisph(config) => Some(config.density),
+            Solver::Flip(config) => Some(config.pressure),
+            Solver::Mpm(_) => None, // explicit
+        }
+    }
+}
+
+// convergence settings shared by the iterative pressure solvers
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct IterativeConfig {
+    pub tolerance: f32, // average density error, relative to rho_0
+    pub min_iterations: u32,
+    pub max_iterations: u32, // also the number of rounds dispatched on the GPU
+}
+
+impl Default for IterativeConfig {
+    fn default() -> Self {
+        Self {
+            tolerance: 0.01,
+            min_iterations: 3,
+            max_iterations: 50,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DfsphConfig {
+    pub density: IterativeConfig,
+    // error: average density growth over one step, relative to rho_0
+    pub divergence: IterativeConfig,
+}
+
+impl Default for DfsphConfig {
+    fn default() -> Self {
+        Self {
+            density: IterativeConfig::default(),
+            divergence: IterativeConfig {
+                tolerance: 0.01,
+                min_iterations: 1,
+                max_iterations: 50,
+            },
+        }
+    }
+}
+
+// Pbf. relaxation and artificial_pressure are relative to the sum of
+// |grad C|^2 of a particle with a filled neighbourhood, so they do not depend
+// on the particle spacing
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PbfConfig {
+    pub iterations: u32, // density constraint rounds, no tolerance
+    // epsilon in the lambda denominator (CFM). 1 halves the correction of a
+    // filled neighbourhood and keeps sparse ones from overshooting
+    pub relaxation: f32,
+    // -k (W(r) / W(dq h))^n between all neighbours, against clumping
+    pub artificial_pressure: f32,
+    pub artificial_pressure_n: f32,
+    pub artificial_pressure_dq: f32,
+    pub xsph: f32,      // velocity smoothing c
+    pub vorticity: f32, // confinement epsilon
+}
+
+impl Default for PbfConfig {
+    fn default() -> Self {
+        Self {
+            iterations: 4,
+            relaxation: 1.0,
+            artificial_pressure: 0.1,
+            artificial_pressure_n: 4.0,
+            artificial_pressure_dq: 0.2,
+            xsph: 0.01,
+            vorticity: 0.01,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct IisphConfig {
+    pub density: IterativeConfig,
+    pub relaxation: f32, // Jacobi weight omega, 0.5 in the paper
+}
+
+impl Default for IisphConfig {
+    fn default() -> Self {
+        Self {
+            density: IterativeConfig {
+                tolerance: 0.001,
+                min_iterations: 2,
+                max_iterations: 100,
+            },
+            relaxation: 0.5,
+        }
+    }
+}
+
+// Flip. The grid cells are h wide, so a spacing of about h / 2 puts four
+// particles in a cell
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FlipConfig {
+    // error: average velocity divergence of the fluid cells times dt
+    pub pressure: IterativeConfig,
+    pub relaxation: f32, // Jacobi weight
+    // share of the particle velocity that is kept and only changed by the
+    // grid update (FLIP); the rest is the grid velocity (PIC, or APIC).
+    // 0 is dissipative but stable, 1 is lively but noisy
+    pub flip_ratio: f32,
+    pub apic: bool, // transfer the velocity gradient too (Jiang et al. 2015)
+}
+
+impl Default for FlipConfig {
+    fn default() -> Self {
+        Self {
+            pressure: IterativeConfig {
+                tolerance: 0.0001,
+                min_iterations: 1,
+                max_iterations: 100,
+            },
+            relaxation: 1.0,
+            flip_ratio: 0.95,
+            apic: false,
+        }
+    }
+}
+
+// Mpm. Elastic materials use the fixed corotated model with Lame parameters
+// from youngs_modulus and poisson_ratio, the fluid only resists compression.
+// The grid cells are h wide like for Flip; dt has to stay below about
+// h / sqrt(youngs_modulus / rho_0)
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MpmConfig {
+    pub material: MpmMaterial,
+    pub youngs_modulus: f32,
+    pub poisson_ratio: f32,
+}
+
+impl Default for MpmConfig {
+    fn default() -> Self {
+        Self {
+            material: MpmMaterial::Fluid {
+                bulk_modulus: 50000.0,
+            },
+            youngs_modulus: 20000.0,
+            poisson_ratio: 0.2,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MpmMaterial {
+    // pressure bulk_modulus * (1 / J - 1), no shear
+    Fluid {
+        bulk_modulus: f32,
+    },
+    // elastic, returns to its rest shape
+    Jelly,
+    // Stomakhin et al. 2013: singular values of F clamped to
+    // [1 - critical_compression, 1 + critical_stretch], stiffer when packed
+    Snow {
+        critical_compression: f32,
+        critical_stretch: f32,
+        hardening: f32,
+    },
+    // Drucker-Prager plasticity (Klar et al. 2016), no cohesion
+    Sand {
+        friction_angle: f32,
+    }, // degrees
+}
+
+impl MpmMaterial {
+    pub fn snow() -> Self {
+        MpmMaterial::Snow {
+            critical_compression: 0.025,
+            critical_stretch: 0.0075,
+            hardening: 10.0,
+        }
+    }
+
+    pub fn sand() -> Self {
+        MpmMaterial::Sand {
+            friction_angle: 30.0,
+        }
+    }
+
+    // id used by the GPU (SphParams::mpm_material)
+    pub fn id(self) -> u32 {
+        match self {
+            MpmMaterial::Fluid { .. } => 0,
+            MpmMaterial::Jelly => 1,
+            MpmMaterial::Snow { .. } => 2,
+            MpmMaterial::Sand { .. } => 3,
+        }
+    }
+}
+
+impl MpmConfig {
+    // shear modulus mu and lambda
+    pub fn lame(&self) -> (f32, f32) {
+        let e = self.youngs_modulus;
+        let nu = self.poisson_ratio;
+        (
+            e / (2.0 * (1.0 + nu)),
+            e * nu / ((1.0 + nu) * (1.0 - 2.0 * nu)),
+        )
+    }
+}
+
+// what the pressure solver did in the last step (zero for Wcsph)
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct SolverStats {
+    pub iterations: u32,
+    pub density_error: f32, // average compression, relative to rho_0 (Flip: div v dt)
+    pub divergence_iterations: u32, // Dfsph only
+    pub divergence_error: f32,
+}
+
+// neighbour of a particle: x_i + shift - x_j is the distance vector (shift
+// is the periodic image offset, zero otherwise)
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Neighbor {
+    pub j: usize,
+    pub shift: Vec2,
+}
+
+// compressed (CSR) hash table: particles of bucket b are entries[starts[b]..starts[b + 1]]
+#[derive(Clone, Debug)]
+pub struct HashedGrid {
+    pub table_size: u32,
+    pub starts: Vec<u32>,
+    pub entries: Vec<u32>,
+}
+
+impl HashedGrid {
+    pub fn build(positions: impl Iterator<Item = Vec2>, h: f32, table_size: u32) -> Self {
+        let n_buckets = table_size.max(1) as usize;
+        let buckets: Vec<u32> = positions
+            .map(|pos| hash_cell(cell(pos, h), n_buckets as u32))
+            .collect();
+
+        let mut starts = vec![0u32; n_buckets + 1];
+        for &b in &buckets {
+            starts[b as usize + 1] += 1;
+        }
+        for b in 0..n_buckets {
+            starts[b + 1] += starts[b];
+        }
+
+        let mut cursor = starts.clone();
+        let mut entries = vec![0u32; buckets.len()];
+        for (i, &b) in buckets.iter().enumerate() {
+            entries[cursor[b as usize] as usize] = i as u32;
+            cursor[b as usize] += 1;
+        }
+
+        Self {
+            table_size: n_buckets as u32,
+            starts,
+            entries,
+        }
+    }
+
+    // entries of the bucket cell c hashes to; other cells can share it
+    pub fn bucket(&self, c: IVec2) -> &[u32] {
+        let b = hash_cell(c, self.table_size) as usize;
+        &self.entries[self.starts[b] as usize..self.starts[b + 1] as usize]
+    }
+}
+
+enum NeighborGrid {
+    Map(HashMap<Cell, Vec<usize>>),
+    Hashed(HashedGrid),
+}
+
 // define 2D Kernels
 
 #[inline]
-fn w_poly6(r2: f32, h: f32) -> f32 {
+pub(crate) fn w_poly6(r2: f32, h: f32) -> f32 {
     let k: f32 = 4.0 / (PI * h.powi(8));
     if r2 >= 0.0 && r2 <= h * h {
         k * (h * h - r2).powi(3)
@@ -29,7 +472,7 @@ fn w_poly6(r2: f32, h: f32) -> f32 {
 }
 
 #[inline]
-fn grad_spiky_kernel(r: Vec2, h: f32) -> Vec2 {
+pub(crate) fn grad_spiky_kernel(r: Vec2, h: f32) -> Vec2 {
     let r_len = r.length();
     let k = -10.0 / (PI * h.powi(5));
     if r_len == 0.0 || r_len >= h {
@@ -40,18 +483,23 @@ fn grad_spiky_kernel(r: Vec2, h: f32) -> Vec2 {
 }
 
 #[inline]
-fn laplacian_visc(r: f32, h: f32) -> f32 {
+pub(crate) fn laplacian_visc(r: f32, h: f32) -> f32 {
     let k: f32 = 40.0 / (PI * h.powi(5));
     if r == 0.0 || r >= h { 0.0 } else { k * (h - r) }
 }
 
 #[derive(Clone, Debug)]
 pub struct Particle {
-    pub pos: Vec2, // position
-    pub vel: Vec2, // velocity
-    pub acc: Vec2, // acceleration
-    pub rho: f32,  // density
-    pub p: f32,    // pressure
+    pub pos: Vec2,         // position
+    pub vel: Vec2,         // velocity
+    pub acc: Vec2,         // acceleration
+    pub rho: f32,          // density
+    pub p: f32,            // pressure
+    pub affine: Mat2,      // velocity gradient carried by APIC (Flip) and Mpm, zero otherwise
+    pub deformation: Mat2, // elastic deformation gradient F (Mpm)
+    pub plastic_j: f32,    // volume change absorbed by plasticity (Mpm snow and sand)
+    pub acc_prev: Vec2,    // acceleration of the last step (VelocityVerlet)
+    pub dt_prev: f32,      // dt of the last step, acc_prev was kicked with it
 }
 
 impl Particle {
@@ -62,6 +510,11 @@ impl Particle {
             acc: Vec2::ZERO,
             rho: 0.0,
             p: 0.0,
+            affine: Mat2::ZERO,
+            deformation: Mat2::IDENTITY,
+            plastic_j: 1.0,
+            acc_prev: Vec2::ZERO,
+            dt_prev: 0.0,
         }
     }
 }
@@ -70,9 +523,25 @@ impl Particle {
 pub struct SPHState {
     pub h: f32, // smoothing length
     pub rho_0: f32,
-    pub k: f32,  // stiffness
+    pub k: f32,  // stiffness (linear equations of state)
     pub mu: f32, // viscosity
     pub m: f32,  // mass
+    pub gravity: Vec2,
+    pub eos: EquationOfState,
+    pub pressure_force: PressureForce,
+    pub viscosity: Viscosity,
+    pub solver: Solver,
+    pub integrator: Integrator, // Wcsph only, see active_integrator
+    pub xsph: f32,              // XSPH velocity smoothing epsilon, 0 = off
+    pub surface_tension: f32,   // Akinci cohesion and curvature gamma, 0 = off
+    pub stats: SolverStats,     // written by every step
+    pub grid_mode: GridMode,
+    pub periodic: Periodicity,    // set from the domain in step_domain
+    pub walls: Option<Domain>,    // same, for the wall adhesion
+    pub colliders: Vec<Collider>, // static, owned by the user
+    pub moving_colliders: Vec<Collider>, // from FluidCollider entities, rebuilt every frame
+    pub bodies: Vec<RigidBody>,   // two-way coupled, also act as colliders
+    pub boundary: Option<BoundaryParticles>, // static, see sample_boundary
     pub particles: Vec<Particle>,
 }
 
@@ -84,6 +553,22 @@ impl SPHState {
             k,
             mu,
             m,
+            gravity: GRAVITY,
+            eos: EquationOfState::LinearClamped,
+            pressure_force: PressureForce::Averaged,
+            viscosity: Viscosity::Laplacian,
+            solver: Solver::Wcsph,
+            integrator: Integrator::SymplecticEuler,
+            xsph: 0.0,
+            surface_tension: 0.0,
+            stats: SolverStats::default(),
+            grid_mode: GridMode::Dense,
+            periodic: Periodicity::default(),
+            walls: None,
+            colliders: Vec::new(),
+            moving_colliders: Vec::new(),
+            bodies: Vec::new(),
+            boundary: None,
             particles: Vec::new(),
         }
     }
@@ -109,121 +594,381 @@ impl SPHState {
         grid
     }
 
+    pub fn build_hashed_grid(&self, table_size: u32) -> HashedGrid {
+        HashedGrid::build(self.particles.iter().map(|p| p.pos), self.h, table_size)
+    }
+
+    fn build_neighbor_grid(&self) -> NeighborGrid {
+        match self.grid_mode {
+            GridMode::Dense => NeighborGrid::Map(self.build_grid()),
+            GridMode::Hashed { table_size } => {
+                NeighborGrid::Hashed(self.build_hashed_grid(table_size))
+            }
+        }
+    }
+
+    // calls `f` for every particle in cell `c`
+    fn for_each_in_cell(&self, grid: &NeighborGrid, c: Cell, mut f: impl FnMut(usize)) {
+        match grid {
+            NeighborGrid::Map(map) => {
+                if let Some(list) = map.get(&c) {
+                    list.iter().for_each(|&j| f(j));
+                }
+            }
+            NeighborGrid::Hashed(table) => {
+                for &j in table.bucket(c) {
+                    let j = j as usize;
+                    // other cells can share the bucket
+                    if cell(self.particles[j].pos, self.h) == c {
+                        f(j);
+                    }
+                }
+            }
+        }
+    }
+
+    // everything within h of every particle (itself included), found once per
+    // step so the iterative solvers don't rebuild the grid
+    pub(crate) fn neighbor_lists(&self) -> Vec<Vec<Neighbor>> {
+        let grid = self.build_neighbor_grid();
+        let h2 = self.h * self.h;
+        let mut lists = Vec::with_capacity(self.particles.len());
+
+        for particle_i in &self.particles {
+            let mut list = Vec::new();
+            let (images, n_images) = self.periodic.images(particle_i.pos, self.h);
+            for shift in &images[..n_images] {
+                let x_i = particle_i.pos + *shift;
+                let c = cell(x_i, self.h);
+                for ox in -1..=1 {
+                    for oy in -1..=1 {
+                        self.for_each_in_cell(&grid, c + IVec2::new(ox, oy), |j| {
+                            if (x_i - self.particles[j].pos).length_squared() < h2 {
+                                list.push(Neighbor { j, shift: *shift });
+                            }
+                        });
+                    }
+                }
+            }
+            lists.push(list);
+        }
+        lists
+    }
+
+    // SPH density of every particle from its neighbor_lists entry
+    pub(crate) fn densities(&self, neighbors: &[Vec<Neighbor>]) -> Vec<f32> {
+        let mut rho = vec![0.0; neighbors.len()];
+        for (i, list) in neighbors.iter().enumerate() {
+            let x_i = self.particles[i].pos;
+            for nb in list {
+                let r2 = (x_i + nb.shift - self.particles[nb.j].pos).length_squared();
+                rho[i] += self.m * w_poly6(r2, self.h);
+            }
+        }
+        rho
+    }
+
+    // viscosity, gravity, surface tension and adhesion for the given
+    // velocities (the iterative solvers add pressure on top)
+    pub(crate) fn non_pressure_accel(
+        &self,
+        neighbors: &[Vec<Neighbor>],
+        rho: &[f32],
+        vel: &[Vec2],
+    ) -> Vec<Vec2> {
+        let mut acc = vec![self.gravity; neighbors.len()];
+        for (i, list) in neighbors.iter().enumerate() {
+            for nb in list {
+                if nb.j == i {
+                    continue;
+                }
+                let r = self.particles[i].pos + nb.shift - self.particles[nb.j].pos;
+                let grad = grad_spiky_kernel(r, self.h);
+                acc[i] += self.viscosity_accel(r, vel[i] - vel[nb.j], (rho[i], rho[nb.j]), grad);
+            }
+        }
+        if self.surface_tension > 0.0 {
+            self.surface_tension_accel(neighbors, rho, &mut acc);
+        }
+        for (a, p) in acc.iter_mut().zip(&self.particles) {
+            *a += self.boundary_adhesion(p.pos);
+        }
+        acc
+    }
+
     pub fn density_pressure_calc(&mut self) {
         let mut rho_vec = vec![0.0; self.particles.len()];
-        let grid = self.build_grid();
+        let grid = self.build_neighbor_grid();
+        let boundary = self.active_boundary();
         let h2 = self.h * self.h;
 
         for i in 0..self.particles.len() {
             let particle_i_po = self.particles[i].pos;
-            let c = cell(particle_i_po, self.h);
             let mut rho = 0.0;
 
-            // covering a 3 x 3 surrounding cells
-            for ox in -1..=1 {
-                for oy in -1..=1 {
-                    if let Some(list) = grid.get(&(c + IVec2::new(ox, oy))) {
-                        for &j in list {
-                            let r2 = (particle_i_po - self.particles[j].pos).length_squared();
+            // periodic images see the neighbours across the domain edge
+            let (images, n_images) = self.periodic.images(particle_i_po, self.h);
+            for shift in &images[..n_images] {
+                let x_i = particle_i_po + *shift;
+                let c = cell(x_i, self.h);
+
+                // covering a 3 x 3 surrounding cells
+                for ox in -1..=1 {
+                    for oy in -1..=1 {
+                        self.for_each_in_cell(&grid, c + IVec2::new(ox, oy), |j| {
+                            let r2 = (x_i - self.particles[j].pos).length_squared();
                             if r2 < h2 {
                                 rho += self.m * w_poly6(r2, self.h);
                             }
-                        }
+                        });
                     }
                 }
+                // boundary particles weigh psi instead of m
+                if let Some(b) = boundary {
+                    b.for_each_near(x_i, self.h, |k| {
+                        rho += b.psi[k] * w_poly6((x_i - b.pos[k]).length_squared(), self.h);
+                    });
+                }
             }
             rho_vec[i] = rho;
         }
         for i in 0..self.particles.len() {
             self.particles[i].rho = rho_vec[i];
-            self.particles[i].p = self.k * (rho_vec[i] - self.rho_0).max(0.0);
+            self.particles[i].p = self.eos.pressure(rho_vec[i], self.rho_0, self.k);
+        }
+    }
+
+    // pressure acceleration on i from j, (p, rho) of both, grad W(x_i - x_j)
+    pub fn pressure_accel(
+        &self,
+        (p_i, rho_i): (f32, f32),
+        (p_j, rho_j): (f32, f32),
+        grad: Vec2,
+    ) -> Vec2 {
+        match self.pressure_force {
+            // not text book but cheap to claculate
+            PressureForce::Averaged => -self.m * (p_i + p_j) / (2.0 * rho_j) * grad,
+            PressureForce::Symmetric => {
+                -self.m * (p_i / (rho_i * rho_i) + p_j / (rho_j * rho_j)) * grad
+            }
+        }
+    }
+
+    // viscous acceleration on i from j; r = x_i - x_j, v_ij = v_i - v_j
+    pub fn viscosity_accel(
+        &self,
+        r: Vec2,
+        v_ij: Vec2,
+        (rho_i, rho_j): (f32, f32),
+        grad: Vec2,
+    ) -> Vec2 {
+        match self.viscosity {
+            Viscosity::Laplacian => {
+                self.mu * self.m * -v_ij / rho_j * laplacian_visc(r.length(), self.h)
+            }
+            Viscosity::Artificial {
+                alpha,
+                speed_of_sound,
+            } => {
+                let vr = v_ij.dot(r);
+                if vr >= 0.0 {
+                    return Vec2::ZERO; // separating
+                }
+                let mu_ij = self.h * vr / (r.length_squared() + 0.01 * self.h * self.h);
+                let pi_ij = -alpha * speed_of_sound * mu_ij / (0.5 * (rho_i + rho_j));
+                -self.m * pi_ij * grad
+            }
         }
     }
 
     fn accel_field_calc(&mut self) {
-        let grid = self.build_grid();
+        let grid = self.build_neighbor_grid();
+        let boundary = self.active_boundary();
 
         let mut acc_vec = vec![Vec2::ZERO; self.particles.len()];
 
         for i in 0..self.particles.len() {
             let particle_i = &self.particles[i];
-            let pos_i = particle_i.pos;
             let p_i = particle_i.p;
+            let rho_i = particle_i.rho;
             let vel_i = particle_i.vel;
-            let cell_i = cell(pos_i, self.h);
 
-            for ox in -1..=1 {
-                for oy in -1..=1 {
-                    if let Some(list) = grid.get(&(cell_i + IVec2::new(ox, oy))) {
-                        for &j in list {
+            let (images, n_images) = self.periodic.images(particle_i.pos, self.h);
+            for shift in &images[..n_images] {
+                let pos_i = particle_i.pos + *shift;
+                let cell_i = cell(pos_i, self.h);
+
+                for ox in -1..=1 {
+                    for oy in -1..=1 {
+                        self.for_each_in_cell(&grid, cell_i + IVec2::new(ox, oy), |j| {
                             if i == j {
-                                continue;
+                                return;
                             }
                             let particle_j = &self.particles[j];
                             let r = pos_i - particle_j.pos;
-                            let r2 = r.length_squared();
+                            let grad = grad_spiky_kernel(r, self.h);
 
                             // acceleration due to pressure
-                            let grad_spiky = grad_spiky_kernel(r, self.h);
-                            // not text book but cheap to claculate for now
-                            let a_p = -self.m * (p_i + particle_j.p) / (2.0 * particle_j.rho)
-                                * grad_spiky;
-
+                            let a_p = self.pressure_accel(
+                                (p_i, rho_i),
+                                (particle_j.p, particle_j.rho),
+                                grad,
+                            );
                             // acceleration because of viscosity (fraction)
-                            let r_mag = r2.sqrt(); // not len so not confused with len()
-                            let laplacian = laplacian_visc(r_mag, self.h);
-                            let a_v = self.mu * self.m * (particle_j.vel - vel_i) / particle_j.rho
-                                * laplacian;
+                            let a_v = self.viscosity_accel(
+                                r,
+                                vel_i - particle_j.vel,
+                                (rho_i, particle_j.rho),
+                                grad,
+                            );
 
                             acc_vec[i] += a_p + a_v;
-                        }
+                        });
                     }
                 }
+                // a boundary particle mirrors the pressure and density of i
+                if let Some(b) = boundary {
+                    b.for_each_near(pos_i, self.h, |k| {
+                        let grad = grad_spiky_kernel(pos_i - b.pos[k], self.h);
+                        let a_p = self.pressure_accel((p_i, rho_i), (p_i, rho_i), grad);
+                        acc_vec[i] += b.psi[k] / self.m * a_p;
+                    });
+                }
             }
 
-            acc_vec[i] += GRAVITY;
+            acc_vec[i] += self.gravity + self.boundary_adhesion(particle_i.pos);
+        }
+        if self.surface_tension > 0.0 {
+            let rho: Vec<f32> = self.particles.iter().map(|p| p.rho).collect();
+            self.surface_tension_accel(&self.neighbor_lists(), &rho, &mut acc_vec);
         }
+        self.body_forces(&mut acc_vec);
 
         for i in 0..self.particles.len() {
             self.particles[i].acc = acc_vec[i];
         }
     }
 
-    pub fn integrate(&mut self, dt: f32) {
-        for p in &mut self.particles {
-            p.vel += p.acc * dt;
-            p.pos += p.vel * dt;
-        }
-    }
-
     pub fn apply_boundaries(&mut self, x_max: f32, x_min: f32, bounce: f32) {
         // bounciness must be a negative number
-        for p in &mut self.particles {
-            // floor
-            if p.pos.y < 0.0 {
-                p.pos.y = 0.0;
-                p.vel.y *= bounce;
-            }
+        self.apply_domain(&Domain::floor_and_walls(x_min, x_max, bounce));
+    }
+
+    pub fn apply_domain(&mut self, domain: &Domain) {
+        // open walls delete the particles that crossed them
+        self.particles
+            .retain_mut(|p| domain.apply(&mut p.pos, &mut p.vel));
+    }
+
+    // static, moving, then bodies (the GPU collider buffer uses this order)
+    pub fn all_colliders(&self) -> impl Iterator<Item = &Collider> + Clone {
+        self.colliders
+            .iter()
+            .chain(&self.moving_colliders)
+            .chain(self.bodies.iter().map(|b| &b.collider))
+    }
 
-            // right wall
-            if p.pos.x > x_max {
-                p.pos.x = x_max;
-                p.vel.x *= bounce;
+    pub fn apply_colliders(&mut self) {
+        let mut particles = std::mem::take(&mut self.particles);
+        for p in &mut particles {
+            for collider in self.all_colliders() {
+                collider.apply(&mut p.pos, &mut p.vel);
             }
+        }
+        self.particles = particles;
+    }
 
-            // left wall
-            if p.pos.x < x_min {
-                p.pos.x = x_min;
-                p.vel.x *= bounce;
+    // fluid forces on the bodies and their reaction on the particles
+    pub(crate) fn body_forces(&mut self, acc_vec: &mut [Vec2]) {
+        let mut bodies = std::mem::take(&mut self.bodies);
+        for body in &mut bodies {
+            body.force = Vec2::ZERO;
+            body.torque = 0.0;
+            for (i, p) in self.particles.iter().enumerate() {
+                if let Some(a) = body.fluid_accel(self, p.pos, p.vel, p.rho, p.p) {
+                    acc_vec[i] += a;
+                    body.add_reaction(self.m, p.pos, a);
+                }
             }
         }
+        self.bodies = bodies;
+    }
+
+    // PBF smooths in its own pass, the grid solvers have no SPH neighbourhoods
+    pub fn xsph_active(&self) -> bool {
+        let own_pass = matches!(
+            self.solver,
+            Solver::Pbf(_) | Solver::Flip(_) | Solver::Mpm(_)
+        );
+        self.xsph > 0.0 && !own_pass
+    }
+
+    // XSPH (Monaghan 1989), v_i += eps sum m / rho_ij (v_j - v_i) W with
+    // rho_ij the mean density. Smoothed into a copy, so every particle sees
+    // the velocities the forces were computed with
+    pub fn xsph_smooth(&mut self) {
+        let neighbors = self.neighbor_lists();
+        let smoothed: Vec<Vec2> = neighbors
+            .iter()
+            .enumerate()
+            .map(|(i, list)| {
+                let p_i = &self.particles[i];
+                let mut sum = Vec2::ZERO;
+                for nb in list {
+                    let p_j = &self.particles[nb.j];
+                    let r2 = (p_i.pos + nb.shift - p_j.pos).length_squared();
+                    let rho_ij = 0.5 * (p_i.rho + p_j.rho);
+                    sum += self.m / rho_ij * (p_j.vel - p_i.vel) * w_poly6(r2, self.h);
+                }
+                p_i.vel + self.xsph * sum
+            })
+            .collect();
+        for (p, vel) in self.particles.iter_mut().zip(smoothed) {
+            p.vel = vel;
+        }
+    }
+
+    pub fn integrate_bodies(&mut self, dt: f32, domain: &Domain) {
+        let gravity = self.gravity;
+        for body in &mut self.bodies {
+            body.integrate(dt, gravity, domain);
+        }
     }
 
     pub fn step(&mut self, dt: f32, x_max: f32, x_min: f32, bounce: f32) {
-        self.density_pressure_calc();
-        self.accel_field_calc();
-        self.integrate(dt);
-        self.apply_boundaries(x_max, x_min, bounce)
+        self.step_domain(dt, &Domain::floor_and_walls(x_min, x_max, bounce))
+    }
+
+    pub fn step_domain(&mut self, dt: f32, domain: &Domain) {
+        self.periodic = domain.periodicity();
+        self.walls = Some(*domain);
+        let mut start = Vec::new();
+        match self.solver {
+            Solver::Wcsph => {
+                self.density_pressure_calc();
+                self.accel_field_calc();
+                // Leapfrog and Rk2 evaluate the forces again after a first stage
+                if self.integrator.stages() == 2 {
+                    start = self.integrate_predict(dt);
+                    self.density_pressure_calc();
+                    self.accel_field_calc();
+                }
+                self.stats = SolverStats::default();
+            }
+            Solver::Pcisph(config) => self.pcisph_accel(dt, config),
+            Solver::Dfsph(config) => self.dfsph_accel(dt, config),
+            Solver::Pbf(config) => self.pbf_accel(dt, config),
+            Solver::Iisph(config) => self.iisph_accel(dt, config),
+            Solver::Flip(config) => self.flip_accel(dt, config, domain),
+            Solver::Mpm(config) => self.mpm_accel(dt, config, domain),
+        }
+        if self.xsph_active() {
+            self.xsph_smooth();
+        }
+        self.integrate(dt, &start);
+        self.integrate_bodies(dt, domain);
+        self.apply_domain(domain);
+        self.apply_colliders();
     }
 
     // demo function ----------------------------------------------
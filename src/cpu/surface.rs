@@ -0,0 +1,131 @@
+// surface tension and adhesion after Akinci et al. 2013. Surface tension is
+// a cohesion force between neighbours plus a curvature force from the
+// surface normals, both scaled by 2 rho_0 / (rho_i + rho_j) so particles at
+// the surface (too few neighbours) are not pulled harder than those inside.
+// Adhesion pulls fluid towards walls and colliders.
+use std::f32::consts::PI;
+
+use glam::Vec2;
+
+use crate::cpu::sph2d::{Neighbor, SPHState, grad_spiky_kernel};
+
+// cohesion spline: attracting for r > h/2, repulsing closer. Normalised
+// over the disk, the 2D version of Akinci's 32 / (pi h^9)
+#[inline]
+pub(crate) fn cohesion_kernel(r: f32, h: f32) -> f32 {
+    if r <= 0.0 || r >= h {
+        return 0.0;
+    }
+    let k = 35840.0 / (209.0 * PI * h.powi(8));
+    let spline = (h - r).powi(3) * r.powi(3);
+    if 2.0 * r > h {
+        k * spline
+    } else {
+        k * (2.0 * spline - h.powi(6) / 64.0)
+    }
+}
+
+// Akinci adhesion kernel, only non-zero for h/2 < r < h so particles
+// touching the wall are left to the wall handling
+#[inline]
+pub(crate) fn adhesion_kernel(r: f32, h: f32) -> f32 {
+    if 2.0 * r <= h || r >= h {
+        return 0.0;
+    }
+    0.007 / h.powf(3.25) * (-4.0 * r * r / h + 6.0 * r - 2.0 * h).powf(0.25)
+}
+
+impl SPHState {
+    // adhesion of a surface at distance d with normal n (out of the surface),
+    // seen as a row of boundary particles at spacing h/4 through the closest
+    // point. Their pulls along the surface cancel, the normal parts add up
+    pub(crate) fn adhesion_accel(&self, adhesion: f32, d: f32, n: Vec2) -> Vec2 {
+        if adhesion == 0.0 || d <= 0.0 || d >= self.h {
+            return Vec2::ZERO;
+        }
+        let dx = 0.25 * self.h;
+        let mut sum = 0.0;
+        for k in -3..=3 {
+            let r = (d * d + (k as f32 * dx).powi(2)).sqrt();
+            sum += adhesion_kernel(r, self.h) * d / r;
+        }
+        -adhesion * self.rho_0 * dx * dx * sum * n
+    }
+
+    // adhesion of the domain walls and the static and moving colliders on a
+    // particle at pos. Bodies add theirs in RigidBody::fluid_accel
+    pub(crate) fn boundary_adhesion(&self, pos: Vec2) -> Vec2 {
+        let mut acc = Vec2::ZERO;
+        if let Some(domain) = &self.walls {
+            for (wall, adhesion) in domain.wall_adhesion().into_iter().enumerate() {
+                // even walls are the min side, their normal points up the axis
+                let axis = wall / 2;
+                let (d, sign) = if wall % 2 == 0 {
+                    (pos[axis] - domain.min[axis], 1.0)
+                } else {
+                    (domain.max[axis] - pos[axis], -1.0)
+                };
+                let mut n = Vec2::ZERO;
+                n[axis] = sign;
+                acc += self.adhesion_accel(adhesion, d, n);
+            }
+        }
+        let colliders = self.colliders.iter().chain(&self.moving_colliders);
+        for collider in colliders.filter(|c| c.adhesion != 0.0) {
+            let d = collider.distance(pos);
+            if d < self.h {
+                acc += self.adhesion_accel(collider.adhesion, d, collider.normal(pos));
+            }
+        }
+        acc
+    }
+
+    // n_i = h sum m / rho_j grad W(x_i - x_j), the gradient of the colour
+    // field: about zero inside the fluid, pointing into it at the surface
+    pub(crate) fn surface_normals(&self, neighbors: &[Vec<Neighbor>], rho: &[f32]) -> Vec<Vec2> {
+        neighbors
+            .iter()
+            .enumerate()
+            .map(|(i, list)| {
+                let mut n = Vec2::ZERO;
+                for nb in list {
+                    let r = self.particles[i].pos + nb.shift - self.particles[nb.j].pos;
+                    n += self.m / rho[nb.j] * grad_spiky_kernel(r, self.h);
+                }
+                self.h * n
+            })
+            .collect()
+    }
+
+    // normals of the current particles (densities from the last density pass)
+    pub fn normals(&self) -> Vec<Vec2> {
+        let rho: Vec<f32> = self.particles.iter().map(|p| p.rho).collect();
+        self.surface_normals(&self.neighbor_lists(), &rho)
+    }
+
+    // adds cohesion and curvature, a_i = -gamma sum K_ij (m C(r) r / |r| + n_i - n_j)
+    pub(crate) fn surface_tension_accel(
+        &self,
+        neighbors: &[Vec<Neighbor>],
+        rho: &[f32],
+        acc: &mut [Vec2],
+    ) {
+        let normals = self.surface_normals(neighbors, rho);
+        for (i, list) in neighbors.iter().enumerate() {
+            for nb in list {
+                if nb.j == i {
+                    continue;
+                }
+                let r = self.particles[i].pos + nb.shift - self.particles[nb.j].pos;
+                let r_len = r.length();
+                if r_len == 0.0 {
+                    continue;
+                }
+                let k_ij = 2.0 * self.rho_0 / (rho[i] + rho[nb.j]);
+                let cohesion = self.m * cohesion_kernel(r_len, self.h) * r / r_len;
+                let curvature = normals[i] - normals[nb.j];
+                acc[i] -= self.surface_tension * k_ij * (cohesion + curvature);
+            }
+        }
+    }
+}
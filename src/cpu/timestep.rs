@@ -0,0 +1,192 @@
+// adaptive time step from the usual SPH stability limits (Monaghan 1992):
+//   CFL:       dt <= cfl * h / (v_max + c)
+//   forces:    dt <= force * sqrt(h / a_max)
+//   viscosity: dt <= viscosity * h^2 / nu
+// clamped to [min_dt, max_dt]. c is the speed of sound of the weakly
+// compressible solvers (0 for the incompressible ones) and a_max comes from
+// the accelerations of the last step. timestep.wgsl does the same on the GPU.
+//
+// SimClock paces the steps: frame time fills an accumulator and whole steps
+// are taken out of it, so simulated time does not depend on the frame rate.
+use bevy::prelude::Resource;
+
+use crate::cpu::sph2d::{EquationOfState, MpmMaterial, SPHState, Solver, Viscosity};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AdaptiveDt {
+    pub cfl: f32, // safety factors of the three limits
+    pub force: f32,
+    pub viscosity: f32,
+    pub min_dt: f32,
+    pub max_dt: f32,
+}
+
+impl Default for AdaptiveDt {
+    fn default() -> Self {
+        Self {
+            cfl: 0.4,
+            force: 0.25,
+            viscosity: 0.125,
+            min_dt: 1e-5,
+            max_dt: 0.005,
+        }
+    }
+}
+
+impl AdaptiveDt {
+    pub fn dt(&self, h: f32, max_speed: f32, max_accel: f32, sound_speed: f32, nu: f32) -> f32 {
+        let mut dt = self.max_dt;
+        let speed = max_speed + sound_speed;
+        if speed > 0.0 {
+            dt = dt.min(self.cfl * h / speed);
+        }
+        if max_accel > 0.0 {
+            dt = dt.min(self.force * (h / max_accel).sqrt());
+        }
+        if nu > 0.0 {
+            dt = dt.min(self.viscosity * h * h / nu);
+        }
+        dt.max(self.min_dt)
+    }
+}
+
+impl SPHState {
+    // how fast density changes travel through the material; the pressure
+    // solvers and FLIP keep the fluid incompressible instead
+    pub fn sound_speed(&self) -> f32 {
+        match self.solver {
+            Solver::Wcsph => match self.eos {
+                EquationOfState::Tait { speed_of_sound } => speed_of_sound,
+                // dp/drho = k
+                _ => self.k.max(0.0).sqrt(),
+            },
+            Solver::Mpm(config) => match config.material {
+                MpmMaterial::Fluid { bulk_modulus } => (bulk_modulus / self.rho_0).sqrt(),
+                // pressure waves of the elastic materials
+                _ => {
+                    let (mu, lambda) = config.lame();
+                    ((lambda + 2.0 * mu) / self.rho_0).sqrt()
+                }
+            },
+            _ => 0.0,
+        }
+    }
+
+    // nu of the viscosity term; artificial viscosity acts like
+    // alpha * c * h / 8 in 2D
+    pub fn kinematic_viscosity(&self) -> f32 {
+        match self.viscosity {
+            Viscosity::Laplacian => self.mu,
+            Viscosity::Artificial {
+                alpha,
+                speed_of_sound,
+            } => alpha * speed_of_sound * self.h / 8.0,
+        }
+    }
+
+    // largest |v| and |a| (of the last step)
+    pub fn max_speed_accel(&self) -> (f32, f32) {
+        self.particles.iter().fold((0.0f32, 0.0f32), |(v, a), p| {
+            (v.max(p.vel.length()), a.max(p.acc.length()))
+        })
+    }
+
+    pub fn adaptive_dt(&self, config: &AdaptiveDt) -> f32 {
+        let (max_speed, max_accel) = self.max_speed_accel();
+        config.dt(
+            self.h,
+            max_speed,
+            max_accel,
+            self.sound_speed(),
+            self.kinematic_viscosity(),
+        )
+    }
+}
+
+// shared by the CPU stepping and the GPU graph (which runs steps_this_frame
+// rounds of its passes)
+#[derive(Resource, Clone, Debug)]
+pub struct SimClock {
+    pub time_scale: f32, // simulated seconds per real second
+    pub paused: bool,
+    pub max_steps_per_frame: u32, // the backlog beyond is dropped (slow motion, no spiral)
+    pub lockstep: bool,           // one step per frame whatever the frame time (parity runs)
+    pub step: u64,                // steps taken so far
+    pub time: f64,                // simulated seconds
+    pub steps_this_frame: u32,
+    pub dt: f32,           // of the last step taken
+    pub accumulator: f32,  // unspent simulated seconds
+    pub single_step: bool, // taken while paused, see step_once
+}
+
+impl Default for SimClock {
+    fn default() -> Self {
+        Self {
+            time_scale: 1.0,
+            paused: false,
+            max_steps_per_frame: 16,
+            lockstep: false,
+            step: 0,
+            time: 0.0,
+            steps_this_frame: 0,
+            dt: 0.0,
+            accumulator: 0.0,
+            single_step: false,
+        }
+    }
+}
+
+impl SimClock {
+    pub fn lockstep() -> Self {
+        Self {
+            lockstep: true,
+            ..Default::default()
+        }
+    }
+
+    // pauses and takes exactly one step next frame
+    pub fn step_once(&mut self) {
+        self.paused = true;
+        self.single_step = true;
+    }
+
+    // start of a frame, with the real seconds since the last one
+    pub fn accumulate(&mut self, frame_dt: f32) {
+        self.steps_this_frame = 0;
+        if !self.paused && !self.lockstep {
+            self.accumulator += frame_dt.max(0.0) * self.time_scale.max(0.0);
+        }
+    }
+
+    // whether a step of dt is due this frame; counts it if so. dt may change
+    // from step to step (adaptive)
+    pub fn consume(&mut self, dt: f32) -> bool {
+        let due = if self.paused {
+            std::mem::take(&mut self.single_step)
+        } else if self.lockstep {
+            self.steps_this_frame == 0
+        } else if self.accumulator < dt {
+            false
+        } else if self.steps_this_frame >= self.max_steps_per_frame {
+            self.accumulator = 0.0;
+            false
+        } else {
+            self.accumulator -= dt;
+            true
+        };
+        if due {
+            self.steps_this_frame += 1;
+            self.step += 1;
+            self.time += dt as f64;
+            self.dt = dt;
+        }
+        due
+    }
+
+    // accumulate, then take every step of dt that is due
+    pub fn advance(&mut self, frame_dt: f32, dt: f32) -> u32 {
+        self.accumulate(frame_dt);
+        while self.consume(dt) {}
+        self.steps_this_frame
+    }
+}
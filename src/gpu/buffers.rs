@@ -1,34 +1,62 @@
-use std::sync::Arc;
-use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::atomic::{AtomicU32, Ordering};
 
 use bevy::prelude::*;
 use bevy::render::extract_resource::ExtractResource;
 use bevy::render::render_resource::{
     BindGroup, BindGroupEntry, BindGroupLayout, BindGroupLayoutEntry, BindingType, Buffer,
-    BufferBindingType, BufferDescriptor, BufferInitDescriptor, BufferUsages, Maintain, MapMode,
-    ShaderStages,
+    BufferBindingType, BufferInitDescriptor, BufferUsages, ShaderStages,
 };
 use bevy::render::renderer::{RenderDevice, RenderQueue};
 use bevy::render::{Extract, ExtractSchedule, Render, RenderApp, RenderSet};
 
-use crate::cpu::sph2d::SPHState;
-use crate::gpu::ffi::{GPUParticle, GridParams, IntegrateParams};
+use crate::cpu::domain::{Domain, WallMode};
+use crate::cpu::mpm::sand_alpha;
+use crate::cpu::pcisph::{pcisph_delta, prototype_grad_sq};
+use crate::cpu::sph2d::{
+    FlipConfig, GridMode, IisphConfig, IterativeConfig, MpmConfig, MpmMaterial, PbfConfig,
+    SPHState, Solver, Viscosity,
+};
+use crate::cpu::timestep::{AdaptiveDt, SimClock};
+use crate::gpu::collider::{
+    ExtractedColliderBuffers, extract_collider_buffers, init_collider_buffers,
+    integrate_gpu_bodies, update_collider_buffers,
+};
+use crate::gpu::ffi::{GPUParticle, GridParams, IntegrateParams, PARTICLE_DEAD, SphParams};
+use crate::gpu::flip::{init_flip_bind_group_layout, init_flip_buffers, prepare_flip_bind_group};
 use crate::gpu::grid_build::{
     GridEntriesGpuBuffer, GridStartsBuffer, init_add_back_bg, init_add_back_bgl,
     init_block_scan_bgl, init_block_sums_and_bg, init_block_sums_scan_bg, init_block_sums_scan_bgl,
     init_counts_to_starts_bgl, init_cursor_buffer_and_clear_bg, init_gpu_entries_buffer,
+    init_grid_bounds_bg, init_grid_bounds_bgl, init_grid_bounds_buffers,
     init_grid_build_bind_group_layout, init_grid_build_buffers, init_grid_histogram_bind_group,
     init_grid_histogram_bind_group_layout, init_scatter_bg, init_scatter_bgl,
     init_starts_buffer_and_bg,
 };
+use crate::gpu::mpm::{init_mpm_bind_group_layout, init_mpm_buffers, prepare_mpm_bind_group};
 use crate::gpu::pipeline::{
     add_add_back_node_to_graph, add_block_scan_node_to_graph, add_block_sums_scan_node_to_graph,
     add_clear_counts_node_to_graph, add_clear_cursor_node_to_graph, add_density_node_to_graph,
-    add_histogram_node_to_graph, add_scatter_node_to_graph, add_write_sentinel_node_to_graph,
-    prepare_add_back_pipeline, prepare_block_scan_pipeline, prepare_block_sums_scan_pipeline,
-    prepare_clear_counts_pipeline, prepare_density_pipeline, prepare_forces_pipeline,
-    prepare_histogram_pipeline, prepare_integrate_pipeline, prepare_pressure_pipeline,
-    prepare_scatter_pipeline, prepare_write_sentinel_pipeline,
+    add_grid_bounds_node_to_graph, add_histogram_node_to_graph, add_scatter_node_to_graph,
+    add_sim_steps_node_to_graph, add_write_sentinel_node_to_graph, prepare_add_back_pipeline,
+    prepare_block_scan_pipeline, prepare_block_sums_scan_pipeline, prepare_clear_counts_pipeline,
+    prepare_density_pipeline, prepare_flip_pipelines, prepare_forces_pipeline,
+    prepare_grid_bounds_pipeline, prepare_histogram_pipeline, prepare_integrate_pipeline,
+    prepare_mpm_pipelines, prepare_pressure_pipeline, prepare_scatter_pipeline,
+    prepare_solver_pipelines, prepare_timestep_pipelines, prepare_write_sentinel_pipeline,
+};
+use crate::gpu::readback::{
+    BodyForceReport, GridBoundsReport, ParticleReadback, ParticleReadbackPlugin, ParticleSnapshot,
+    ParticleSnapshotReady, SolverStatsReport, TimestepReport, extract_body_force_readback,
+    extract_grid_stats_readback, extract_solver_stats_readback, extract_timestep_readback,
+    init_body_force_readback, init_grid_stats_readback, init_solver_stats_readback,
+    init_timestep_readback, map_body_force_readback, map_grid_stats_readback,
+    map_solver_stats_readback, map_timestep_readback, poll_body_force_readback,
+    poll_grid_stats_readback, poll_solver_stats_readback, poll_timestep_readback,
+};
+use crate::gpu::solver::{ExtractedSolverBuffers, extract_solver_buffers, init_solver_buffers};
+use crate::gpu::timestep::{
+    extract_timestep, init_timestep_bind_group_layout, init_timestep_buffers,
+    prepare_timestep_bind_group,
 };
 use glam::{IVec2, Vec2};
 
@@ -56,40 +84,22 @@ pub struct ExtractedParticleBuffer {
     pub num_particles: u32,
 }
 
-// need information back on CPU
-#[derive(Resource)]
-pub struct ReadbackBuffer {
-    pub buffer: Buffer,
-    pub size_bytes: u64,
-}
-
-#[derive(Resource, Clone, ExtractResource)]
-pub struct ExtractedReadbackBuffer {
-    pub buffer: Buffer,
-    pub size_bytes: u64,
-}
-
-#[derive(Resource, Clone, Copy, Default)]
-pub struct AllowCopy(pub bool);
-
-#[derive(Resource, Clone, ExtractResource, Default)]
-pub struct ExtractedAllowCopy(pub bool);
-
+// GridParams are written by the GPU bounds pass every step; the App world only
+// decides how many cells the grid buffers can hold.
 #[derive(Resource)]
 pub struct GridBuffers {
-    pub params_buf: Buffer,  // UNIFORM
-    pub starts_buf: Buffer,  // STORAGE
-    pub entries_buf: Buffer, // STORAGE
-    pub num_cells: usize,
-    pub num_particles: usize,
+    pub params_buf: Buffer, // UNIFORM
+    pub num_cells: usize,   // capacity, the grid in use is usually smaller
+    pub cell_size: f32,
+    pub hashed: bool, // num_cells is a fixed hash table (GridMode::Hashed)
 }
 
 #[derive(Resource, Clone)]
 pub struct ExtractedGrid {
     pub params_buf: Buffer,
-    pub starts_buf: Buffer,
-    pub entries_buf: Buffer,
     pub num_cells: usize,
+    pub cell_size: f32,
+    pub hashed: bool,
 }
 
 #[derive(Resource)]
@@ -101,27 +111,53 @@ pub struct IntegrateParamsBuffer {
 pub struct ExtractedIntegrateParamsBuffer {
     pub buffer: Buffer,
 }
+
+#[derive(Resource)]
+pub struct SphParamsBuffer {
+    pub buffer: Buffer,
+}
+
+#[derive(Resource, Clone, ExtractResource)]
+pub struct ExtractedSphParamsBuffer {
+    pub buffer: Buffer,
+}
+
 #[derive(Resource, Default, Clone, Copy)]
 pub struct UseGpuIntegration(pub bool);
 
-#[derive(Resource, Default)]
-pub struct SimStep(pub u64);
+// GPU steps of this frame, SimClock::steps_this_frame. Every step runs the
+// step sub graph once per Integrator::stages; DensityNode counts the runs
+#[derive(Resource)]
+pub struct ExtractedSimClock {
+    pub steps: u32,
+    pub stages: u32,
+    pub runs: AtomicU32,
+}
+
+impl ExtractedSimClock {
+    // stage of the sub graph run that calls this, 0 starts a step
+    pub fn next_stage(&self) -> u32 {
+        self.runs.fetch_add(1, Ordering::Relaxed) % self.stages.max(1)
+    }
+
+    pub fn is_last_stage(&self, stage: u32) -> bool {
+        stage + 1 >= self.stages
+    }
+}
 
 #[derive(Resource, Clone, Copy, Debug)]
 pub struct IntegrateConfig {
-    pub dt: f32,
-    pub x_min: f32,
-    pub x_max: f32,
-    pub bounce: f32,
+    pub dt: f32, // fixed step, or the first steps until an adaptive dt is read back
+    pub adaptive: Option<AdaptiveDt>,
+    pub domain: Domain,
 }
 
 impl Default for IntegrateConfig {
     fn default() -> Self {
         Self {
             dt: 0.0005,
-            x_min: -5.0,
-            x_max: 3.0,
-            bounce: -3.0,
+            adaptive: None,
+            domain: Domain::floor_and_walls(-5.0, 3.0, -3.0),
         }
     }
 }
@@ -196,34 +232,77 @@ fn init_particle_bind_group_layout(mut commands: Commands, render_device: Res<Re
                 },
                 count: None,
             },
+            // binding 5: sph params (uniform)
+            BindGroupLayoutEntry {
+                binding: 5,
+                visibility: ShaderStages::COMPUTE,
+                ty: BindingType::Buffer {
+                    ty: BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            },
+            // binding 6: colliders (read-only)
+            BindGroupLayoutEntry {
+                binding: 6,
+                visibility: ShaderStages::COMPUTE,
+                ty: BindingType::Buffer {
+                    ty: BufferBindingType::Storage { read_only: true },
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            },
+            // binding 7: collider data, polygon points and SDF values (read-only)
+            BindGroupLayoutEntry {
+                binding: 7,
+                visibility: ShaderStages::COMPUTE,
+                ty: BindingType::Buffer {
+                    ty: BufferBindingType::Storage { read_only: true },
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            },
+            // binding 8: body forces (read_write, atomics)
+            BindGroupLayoutEntry {
+                binding: 8,
+                visibility: ShaderStages::COMPUTE,
+                ty: BindingType::Buffer {
+                    ty: BufferBindingType::Storage { read_only: false },
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            },
+            // binding 9: solver scratch, one per particle (read_write)
+            BindGroupLayoutEntry {
+                binding: 9,
+                visibility: ShaderStages::COMPUTE,
+                ty: BindingType::Buffer {
+                    ty: BufferBindingType::Storage { read_only: false },
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            },
+            // binding 10: solver stats (read_write, atomics)
+            BindGroupLayoutEntry {
+                binding: 10,
+                visibility: ShaderStages::COMPUTE,
+                ty: BindingType::Buffer {
+                    ty: BufferBindingType::Storage { read_only: false },
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            },
         ],
     );
     commands.insert_resource(ParticleBindGroupLayout(layout));
 }
 
-fn init_readback_buffer(
-    mut commands: Commands,
-    render_device: Res<RenderDevice>,
-    particle_buffers: Option<Res<ParticleBuffers>>, // solving the panic problem
-) {
-    let Some(particle_buffers) = particle_buffers else {
-        return;
-    };
-    let size_bytes =
-        (particle_buffers.num_particles as u64) * (std::mem::size_of::<GPUParticle>() as u64);
-    let buffer = render_device.create_buffer(&BufferDescriptor {
-        label: Some("readback_buffer"),
-        size: size_bytes,
-        usage: BufferUsages::COPY_DST | BufferUsages::MAP_READ,
-        mapped_at_creation: false,
-    });
-    commands.insert_resource(ReadbackBuffer { buffer, size_bytes });
-}
-
-fn init_allow_copy(mut commands: Commands) {
-    commands.insert_resource(AllowCopy(true));
-}
-
 pub fn init_grid_buffers(
     mut commands: Commands,
     render_device: Res<RenderDevice>,
@@ -237,22 +316,35 @@ fn init_integrate_params_buffer(
     render_device: Res<RenderDevice>,
     config: Res<IntegrateConfig>,
 ) {
-    let params = IntegrateParams {
-        dt: config.dt,
-        x_min: config.x_min,
-        x_max: config.x_max,
-        bounce: config.bounce,
-    };
+    let params = IntegrateParams::from_config(&config);
     let buffer = render_device.create_buffer_with_data(&BufferInitDescriptor {
         label: Some("integrate_params_uniform"),
         contents: bytemuck::bytes_of(&params),
-        usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+        // COPY_SRC: the dt of a step goes out with the body forces
+        usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST | BufferUsages::COPY_SRC,
     });
     commands.insert_resource(IntegrateParamsBuffer { buffer });
 }
 
-fn init_use_gpu_integration(mut commands: Commands) {
-    commands.insert_resource(UseGpuIntegration(true)); // had to become true for gpu demo to work
+fn init_sph_params_buffer(
+    mut commands: Commands,
+    render_device: Res<RenderDevice>,
+    sph: Res<SPHState>,
+) {
+    let params = SphParams::from_state(&sph);
+    let buffer = render_device.create_buffer_with_data(&BufferInitDescriptor {
+        label: Some("sph_params_uniform"),
+        contents: bytemuck::bytes_of(&params),
+        usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+    });
+    commands.insert_resource(SphParamsBuffer { buffer });
+}
+
+// keeps a value the app inserted, GPU integration otherwise
+fn init_use_gpu_integration(mut commands: Commands, existing: Option<Res<UseGpuIntegration>>) {
+    if existing.is_none() {
+        commands.insert_resource(UseGpuIntegration(true));
+    }
 }
 
 // Update systems that have to run per frame
@@ -277,8 +369,19 @@ fn queue_particle_buffer(
             acc: [particle.acc.x, particle.acc.y],
             rho: particle.rho,
             p: particle.p,
+            flags: 0,
+            _pad: 0,
+            normal: [0.0; 2],
         });
     }
+    // particles deleted on the CPU (open walls) must not stay alive on the GPU
+    gpu_particles.resize(
+        particle_buffers.num_particles as usize,
+        GPUParticle {
+            flags: PARTICLE_DEAD,
+            ..bytemuck::Zeroable::zeroed()
+        },
+    );
 
     // writing the slice into the whole buffer
     render_queue.write_buffer(
@@ -288,29 +391,70 @@ fn queue_particle_buffer(
     );
 }
 
-pub fn update_grid_buffers(
-    render_device: Res<RenderDevice>,
-    render_queue: Res<RenderQueue>,
-    sph: Res<SPHState>,
-    mut grid: ResMut<GridBuffers>,
-) {
-    grid.update(&render_device, &render_queue, &sph);
+// grow-only: the render world reallocates counts/starts/cursor when this changes
+pub fn grow_grid_capacity(report: Res<GridBoundsReport>, mut grid: ResMut<GridBuffers>) {
+    let required = report.required_cells as usize;
+    if grid.hashed || required <= grid.num_cells {
+        return;
+    }
+    let capacity = grid_capacity_for(required);
+    if capacity <= grid.num_cells {
+        return; // already at MAX_GRID_CELLS
+    }
+    info!(
+        "grid capacity {} -> {} cells (bounds need {})",
+        grid.num_cells, capacity, required
+    );
+    grid.num_cells = capacity;
 }
 
 fn update_integrate_params_buffer(
     render_queue: Res<RenderQueue>,
     ub: Res<IntegrateParamsBuffer>,
     config: Res<IntegrateConfig>,
+    clock: Res<SimClock>,
 ) {
-    let params = IntegrateParams {
-        dt: config.dt,
-        x_min: config.x_min,
-        x_max: config.x_max,
-        bounce: config.bounce,
-    };
+    let mut params = IntegrateParams::from_config(&config);
+    // the GPU steps with the dt the clock paced them with
+    if config.adaptive.is_some() && clock.dt > 0.0 {
+        params.dt = clock.dt;
+    }
     render_queue.write_buffer(&ub.buffer, 0, bytemuck::bytes_of(&params));
 }
 
+fn update_sph_params_buffer(
+    render_queue: Res<RenderQueue>,
+    ub: Res<SphParamsBuffer>,
+    sph: Res<SPHState>,
+) {
+    if !sph.is_changed() {
+        return;
+    }
+    let params = SphParams::from_state(&sph);
+    render_queue.write_buffer(&ub.buffer, 0, bytemuck::bytes_of(&params));
+}
+
+// GPU steps due this frame while the GPU integrates on its own. An adaptive
+// step is paced by the last dt read back (a few frames old), and the steps
+// run with that same dt, so SimClock::time is what the GPU simulated
+fn advance_gpu_clock(
+    use_gpu_integration: Res<UseGpuIntegration>,
+    time: Res<Time>,
+    config: Res<IntegrateConfig>,
+    timestep: Res<TimestepReport>,
+    mut clock: ResMut<SimClock>,
+) {
+    if !use_gpu_integration.0 {
+        return;
+    }
+    let dt = if config.adaptive.is_some() && timestep.dt > 0.0 {
+        timestep.dt
+    } else {
+        config.dt
+    };
+    clock.advance(time.delta_secs(), dt);
+}
+
 // Extract systems that send from App to Render
 
 fn extract_particle_buffer(
@@ -341,6 +485,9 @@ fn prepare_particle_bind_group(
     starts_gpu: Res<GridStartsBuffer>,
     entries_gpu: Res<GridEntriesGpuBuffer>,
     integ: Res<ExtractedIntegrateParamsBuffer>,
+    sph_params: Res<ExtractedSphParamsBuffer>,
+    colliders: Res<ExtractedColliderBuffers>,
+    solver: Res<ExtractedSolverBuffers>,
 ) {
     let bind_group = render_device.create_bind_group(
         Some("particle_bind_group"),
@@ -361,7 +508,7 @@ fn prepare_particle_bind_group(
                 binding: 2,
                 resource: entries_gpu.buffer.as_entire_binding(),
             },
-            // binding(3): GridParams UBO (CPU-built for now; provides dims/min_world/h)
+            // binding(3): GridParams UBO (written by the GPU bounds pass)
             BindGroupEntry {
                 binding: 3,
                 resource: grid_params_only.params_buf.as_entire_binding(),
@@ -371,85 +518,106 @@ fn prepare_particle_bind_group(
                 binding: 4,
                 resource: integ.buffer.as_entire_binding(),
             },
+            // binding(5): SphParams UBO
+            BindGroupEntry {
+                binding: 5,
+                resource: sph_params.buffer.as_entire_binding(),
+            },
+            // binding(6): colliders SSBO (ro)
+            BindGroupEntry {
+                binding: 6,
+                resource: colliders.colliders.as_entire_binding(),
+            },
+            // binding(7): collider data SSBO (ro)
+            BindGroupEntry {
+                binding: 7,
+                resource: colliders.data.as_entire_binding(),
+            },
+            // binding(8): body forces SSBO (rw, fixed point)
+            BindGroupEntry {
+                binding: 8,
+                resource: colliders.body_forces.as_entire_binding(),
+            },
+            // binding(9): solver scratch SSBO (rw)
+            BindGroupEntry {
+                binding: 9,
+                resource: solver.scratch.as_entire_binding(),
+            },
+            // binding(10): solver stats SSBO (rw, atomics)
+            BindGroupEntry {
+                binding: 10,
+                resource: solver.stats.as_entire_binding(),
+            },
         ],
     );
     commands.insert_resource(ParticleBindGroup(bind_group));
     info!("particle_bind_group is READY (SPH wired to GPU CSR)");
 }
 
-fn extract_readback_buffer(mut commands: Commands, readback: Extract<Res<ReadbackBuffer>>) {
-    commands.insert_resource(ExtractedReadbackBuffer {
-        buffer: readback.buffer.clone(),
-        size_bytes: readback.size_bytes,
-    });
-}
-
-fn extract_allow_copy(mut commands: Commands, allow: Extract<Res<AllowCopy>>) {
-    commands.insert_resource(ExtractedAllowCopy(allow.0));
-}
-
 fn cell_ix(pos: Vec2, h: f32) -> IVec2 {
     (pos / h).floor().as_ivec2()
 }
 
-fn build_compressed_grid(sph: &SPHState) -> (GridParams, Vec<u32>, Vec<u32>) {
+// headroom so a spreading fluid does not trigger a reallocation right away
+pub const GRID_CAPACITY_HEADROOM: usize = 2;
+pub const MIN_GRID_CELLS: usize = 1024;
+// beyond this the particles have most likely blown up; keep the grid clamped
+pub const MAX_GRID_CELLS: usize = 1 << 24;
+
+// power of two so repeated growth stays rare
+pub fn grid_capacity_for(required_cells: usize) -> usize {
+    (required_cells.max(1) * GRID_CAPACITY_HEADROOM)
+        .next_power_of_two()
+        .clamp(MIN_GRID_CELLS, MAX_GRID_CELLS)
+}
+
+// bounds of the initial particles, used until the first GPU bounds pass ran
+fn initial_grid_params(sph: &SPHState) -> GridParams {
     let h = sph.h;
+    if let GridMode::Hashed { table_size } = sph.grid_mode {
+        return GridParams {
+            min_world: [0.0, 0.0],
+            cell_size: h,
+            _pad0: 0.0,
+            dims: [0, 0],
+            hash_size: table_size.max(1),
+            _pad1: 0,
+        };
+    }
 
     let mut min_c = IVec2::new(i32::MAX, i32::MAX);
     let mut max_c = IVec2::new(i32::MIN, i32::MIN);
     for p in &sph.particles {
         let c = cell_ix(p.pos, h);
-        min_c = IVec2::new(min_c.x.min(c.x), min_c.y.min(c.y));
-        max_c = IVec2::new(max_c.x.max(c.x), max_c.y.max(c.y));
+        min_c = min_c.min(c);
+        max_c = max_c.max(c);
     }
-    let dims = IVec2::new(max_c.x - min_c.x + 1, max_c.y - min_c.y + 1);
-    let nx = dims.x.max(1) as usize;
-    let ny = dims.y.max(1) as usize;
-    let num_cells = nx * ny;
-    let n = sph.particles.len();
-
-    let mut counts = vec![0u32; num_cells];
-    for (_i, p) in sph.particles.iter().enumerate() {
-        let c = cell_ix(p.pos, h);
-        let ix = (c.x - min_c.x) as usize;
-        let iy = (c.y - min_c.y) as usize;
-        let id = ix + iy * nx;
-        debug_assert!(id < num_cells);
-        counts[id] += 1;
-    }
-
-    let mut starts = vec![0u32; num_cells + 1];
-    for i in 0..num_cells {
-        starts[i + 1] = starts[i] + counts[i];
-    }
-
-    let mut offsets = starts.clone();
-    let mut entries = vec![0u32; n];
-    for (pi, p) in sph.particles.iter().enumerate() {
-        let c = cell_ix(p.pos, h);
-        let ix = (c.x - min_c.x) as usize;
-        let iy = (c.y - min_c.y) as usize;
-        let id = ix + iy * nx;
-        let dst = &mut offsets[id];
-        let idx = *dst as usize;
-        entries[idx] = pi as u32;
-        *dst += 1;
+    if sph.particles.is_empty() {
+        min_c = IVec2::ZERO;
+        max_c = IVec2::ZERO;
     }
+    let dims = (max_c - min_c + IVec2::ONE).max(IVec2::ONE);
 
-    let params = GridParams {
+    GridParams {
         min_world: [min_c.x as f32 * h, min_c.y as f32 * h],
         cell_size: h,
         _pad0: 0.0,
-        dims: [nx as u32, ny as u32],
-        _pad1: [0, 0],
-    };
-
-    (params, starts, entries)
+        dims: [dims.x as u32, dims.y as u32],
+        hash_size: 0,
+        _pad1: 0,
+    }
 }
 
 impl GridBuffers {
     pub fn new(render_device: &RenderDevice, sph: &SPHState) -> Self {
-        let (params, starts, entries) = build_compressed_grid(sph);
+        let params = initial_grid_params(sph);
+        let hashed = params.hash_size != 0;
+        let used_cells = (params.dims[0] as usize) * (params.dims[1] as usize);
+        let num_cells = if hashed {
+            params.hash_size as usize
+        } else {
+            grid_capacity_for(used_cells)
+        };
 
         let params_buf = render_device.create_buffer_with_data(&BufferInitDescriptor {
             label: Some("Grid Params"),
@@ -457,86 +625,50 @@ impl GridBuffers {
             usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST | BufferUsages::COPY_SRC,
         });
 
-        let starts_buf = render_device.create_buffer_with_data(&BufferInitDescriptor {
-            label: Some("Grid Starts"),
-            contents: bytemuck::cast_slice(&starts),
-            usage: BufferUsages::STORAGE | BufferUsages::COPY_DST,
-        });
-
-        let entries_buf = render_device.create_buffer_with_data(&BufferInitDescriptor {
-            label: Some("Grid entries"),
-            contents: bytemuck::cast_slice(&entries),
-            usage: BufferUsages::STORAGE | BufferUsages::COPY_DST,
-        });
-
-        info!(
-            "Grid Init: cells={} ({}x{}), starts.len={}, entries.len={}",
-            (params.dims[0] as usize) * (params.dims[1] as usize),
-            params.dims[0],
-            params.dims[1],
-            starts.len(),
-            entries.len()
-        );
-
-        Self {
-            params_buf,
-            starts_buf,
-            entries_buf,
-            num_cells: starts.len() - 1,
-            num_particles: entries.len(),
-        }
-    }
-
-    pub fn update(&mut self, render_device: &RenderDevice, queue: &RenderQueue, sph: &SPHState) {
-        let (params, starts, entries) = build_compressed_grid(sph);
-
-        let new_num_cells = starts.len() - 1;
-        let new_num_particles = entries.len();
-
-        if new_num_cells != self.num_cells {
-            self.starts_buf = render_device.create_buffer_with_data(&BufferInitDescriptor {
-                label: Some("Grid Starts"),
-                contents: bytemuck::cast_slice(&starts),
-                usage: BufferUsages::STORAGE | BufferUsages::COPY_DST,
-            });
-            self.num_cells = new_num_cells;
+        if hashed {
+            info!("Grid Init: hashed, buckets={}", num_cells);
         } else {
-            queue.write_buffer(&self.starts_buf, 0, bytemuck::cast_slice(&starts));
+            info!(
+                "Grid Init: cells={} ({}x{}), capacity={}",
+                used_cells, params.dims[0], params.dims[1], num_cells
+            );
         }
 
-        if new_num_particles != self.num_particles {
-            self.entries_buf = render_device.create_buffer_with_data(&BufferInitDescriptor {
-                label: Some("Grid Entries"),
-                contents: bytemuck::cast_slice(&entries),
-                usage: BufferUsages::STORAGE | BufferUsages::COPY_DST,
-            });
-            self.num_particles = new_num_particles;
-        } else {
-            queue.write_buffer(&self.entries_buf, 0, bytemuck::cast_slice(&entries));
+        Self {
+            params_buf,
+            num_cells,
+            cell_size: sph.h,
+            hashed,
         }
-
-        queue.write_buffer(&self.params_buf, 0, bytemuck::bytes_of(&params));
-
-        let nx = params.dims[0];
-        let ny = params.dims[1];
-
-        info!(
-            "grid update: dims=({}x{}), cells={}, entries={}, starts[0..5]={:?}",
-            nx,
-            ny,
-            self.num_cells,
-            self.num_particles,
-            &starts[0..starts.len().min(5)]
-        );
     }
 }
 
 pub fn extract_grid_buffers(mut commands: Commands, grid: Extract<Res<GridBuffers>>) {
     commands.insert_resource(ExtractedGrid {
         params_buf: grid.params_buf.clone(),
-        starts_buf: grid.starts_buf.clone(),
-        entries_buf: grid.entries_buf.clone(),
         num_cells: grid.num_cells,
+        cell_size: grid.cell_size,
+        hashed: grid.hashed,
+    });
+}
+
+// the CPU streams its state every frame without GPU integration; one step on
+// it keeps the comparisons going
+fn extract_sim_clock(
+    mut commands: Commands,
+    clock: Extract<Res<SimClock>>,
+    use_gpu_integration: Extract<Res<UseGpuIntegration>>,
+    sph: Extract<Res<SPHState>>,
+) {
+    let steps = if use_gpu_integration.0 {
+        clock.steps_this_frame
+    } else {
+        1
+    };
+    commands.insert_resource(ExtractedSimClock {
+        steps,
+        stages: sph.active_integrator().stages(),
+        runs: AtomicU32::new(0),
     });
 }
 
@@ -550,16 +682,21 @@ fn extract_integrate_params_buffer(
     });
 }
 
+fn extract_sph_params_buffer(mut commands: Commands, ub: Extract<Res<SphParamsBuffer>>) {
+    commands.insert_resource(ExtractedSphParamsBuffer {
+        buffer: ub.buffer.clone(),
+    });
+}
+
 // comparison between GPU results and CPU
 pub fn readback_and_compare(
-    render_device: Res<RenderDevice>,
-    readback: Res<ReadbackBuffer>,
     sph: Res<SPHState>,
-    mut allow_copy: ResMut<AllowCopy>,
+    mut readback: ResMut<ParticleReadback>,
+    snapshot: Res<ParticleSnapshot>,
+    mut ready: EventReader<ParticleSnapshotReady>,
     mut done: Local<bool>,
     mut frames_seen: Local<u32>,
-    mut state: Local<u8>,
-    step: Res<SimStep>,
+    clock: Res<SimClock>,
 ) {
     const EPS: f32 = 1e-6;
     const MAX_REL: f32 = 0.01; // 1 % for rho, p, a
@@ -576,126 +713,207 @@ pub fn readback_and_compare(
     }
 
     *frames_seen += 1;
-    info!("frame {}, sim step {}", *frames_seen, step.0);
+    info!("frame {}, sim step {}", *frames_seen, clock.step);
 
     if *frames_seen < FRAMES_BEFORE_RD {
         return;
     }
 
-    match *state {
-        0 => {
-            allow_copy.0 = false; // skip copy next render frame
-            *state = 1;
-            return;
-        }
-
-        1 => {
-            let slice = readback.buffer.slice(..);
-
-            // async map
-            let status = Arc::new(AtomicU8::new(0)); // 0=pending 1=ok 2=err
-            let cb = status.clone();
-            slice.map_async(MapMode::Read, move |r| {
-                cb.store(if r.is_ok() { 1 } else { 2 }, Ordering::SeqCst);
-            });
+    if *frames_seen == FRAMES_BEFORE_RD {
+        readback.request();
+        return;
+    }
 
-            // spin-wait: RenderSchedule runs on the main thread anyway
-            loop {
-                render_device.poll(Maintain::Poll);
-                match status.load(Ordering::SeqCst) {
-                    0 => std::thread::yield_now(),
-                    1 => break,
-                    2 => {
-                        error!("GPU buffer map failed");
-                        readback.buffer.unmap();
-                        *done = true;
-                        *state = 2;
-                        return;
-                    }
-                    _ => unreachable!(),
-                }
-            }
+    // wait (without blocking) for the snapshot to arrive
+    if ready.read().last().is_none() {
+        return;
+    }
+    *done = true;
 
-            // comparison in one pass
-            let data = slice.get_mapped_range();
-            let gpu: &[GPUParticle] = bytemuck::cast_slice(&data);
+    // comparison in one pass
+    let gpu = &snapshot.particles;
 
-            let mut max_rel_rho: f32 = 0.0;
-            let mut max_rel_p: f32 = 0.0;
-            let mut max_rel_a: f32 = 0.0;
-            let mut max_abs_a: f32 = 0.0;
+    let mut max_rel_rho: f32 = 0.0;
+    let mut max_rel_p: f32 = 0.0;
+    let mut max_rel_a: f32 = 0.0;
+    let mut max_abs_a: f32 = 0.0;
 
-            for (cpu, g) in sph.particles.iter().zip(gpu) {
-                max_rel_rho = max_rel_rho.max(rel_err(cpu.rho, g.rho));
-                max_rel_p = max_rel_p.max(rel_err(cpu.p, g.p));
+    for (cpu, g) in sph.particles.iter().zip(gpu) {
+        max_rel_rho = max_rel_rho.max(rel_err(cpu.rho, g.rho));
+        max_rel_p = max_rel_p.max(rel_err(cpu.p, g.p));
 
-                let cpu_a = glam::Vec2::new(cpu.acc.x, cpu.acc.y);
-                let gpu_a = glam::Vec2::new(g.acc[0], g.acc[1]);
-                let diff = (gpu_a - cpu_a).length();
-                max_abs_a = max_abs_a.max(diff);
-                max_rel_a = max_rel_a.max(diff / cpu_a.length().max(EPS));
-            }
+        let cpu_a = glam::Vec2::new(cpu.acc.x, cpu.acc.y);
+        let gpu_a = glam::Vec2::new(g.acc[0], g.acc[1]);
+        let diff = (gpu_a - cpu_a).length();
+        max_abs_a = max_abs_a.max(diff);
+        max_rel_a = max_rel_a.max(diff / cpu_a.length().max(EPS));
+    }
 
-            // helper macro so we don’t repeat boilerplate
-            macro_rules! check {
-                ($label:literal, $err:expr, $lim:expr) => {
-                    if $err > $lim {
-                        error!(
-                            "FAIL: {} error {:.3} % > {:.1} %",
-                            $label,
-                            $err * 100.0,
-                            $lim * 100.0
-                        );
-                        return Err(());
-                    } else {
-                        info!(
-                            "PASS: {} within {:.1} % (max {:.3} %)",
-                            $label,
-                            $lim * 100.0,
-                            $err * 100.0
-                        );
-                    }
-                };
+    // helper macro so we don’t repeat boilerplate
+    macro_rules! check {
+        ($label:literal, $err:expr, $lim:expr) => {
+            if $err > $lim {
+                error!(
+                    "FAIL: {} error {:.3} % > {:.1} %",
+                    $label,
+                    $err * 100.0,
+                    $lim * 100.0
+                );
+                return Err(());
+            } else {
+                info!(
+                    "PASS: {} within {:.1} % (max {:.3} %)",
+                    $label,
+                    $lim * 100.0,
+                    $err * 100.0
+                );
             }
+        };
+    }
 
-            let res: Result<(), ()> = (|| {
-                check!("density", max_rel_rho, MAX_REL);
-                check!("pressure", max_rel_p, MAX_REL);
-                if max_rel_a > MAX_REL || max_abs_a > MAX_ABS_ACC {
-                    error!(
-                        "FAIL: accel rel {:.3} %, abs {:.3} (limits {:.1} %, {:.2})",
-                        max_rel_a * 100.0,
-                        max_abs_a,
-                        MAX_REL * 100.0,
-                        MAX_ABS_ACC
-                    );
-                    return Err(());
-                } else {
-                    info!(
-                        "PASS: accel within limits (rel {:.3} %, abs {:.3})",
-                        max_rel_a * 100.0,
-                        max_abs_a
-                    );
-                }
-                Ok(())
-            })();
-
-            drop(data);
-            readback.buffer.unmap();
-            *done = true;
-            *state = 2;
-
-            if res.is_err() {
-                panic!("GPU <-> CPU validation failed; see log above");
-            }
+    let res: Result<(), ()> = (|| {
+        check!("density", max_rel_rho, MAX_REL);
+        check!("pressure", max_rel_p, MAX_REL);
+        if max_rel_a > MAX_REL || max_abs_a > MAX_ABS_ACC {
+            error!(
+                "FAIL: accel rel {:.3} %, abs {:.3} (limits {:.1} %, {:.2})",
+                max_rel_a * 100.0,
+                max_abs_a,
+                MAX_REL * 100.0,
+                MAX_ABS_ACC
+            );
+            return Err(());
+        } else {
+            info!(
+                "PASS: accel within limits (rel {:.3} %, abs {:.3})",
+                max_rel_a * 100.0,
+                max_abs_a
+            );
         }
+        Ok(())
+    })();
 
-        _ => {}
+    if res.is_err() {
+        panic!("GPU <-> CPU validation failed; see log above");
     }
 }
 
 // Implementations
 
+impl IntegrateParams {
+    pub fn from_config(config: &IntegrateConfig) -> Self {
+        let walls = config.domain.walls();
+        let adaptive = config.adaptive.unwrap_or_default();
+        Self {
+            dt: config.dt,
+            dt_min: adaptive.min_dt,
+            dt_max: adaptive.max_dt,
+            _pad: 0.0,
+            min: config.domain.min.to_array(),
+            max: config.domain.max.to_array(),
+            modes: walls.map(WallMode::id),
+            restitution: walls.map(WallMode::restitution),
+            dt_safety: [adaptive.cfl, adaptive.force, adaptive.viscosity, 0.0],
+            adhesion: config.domain.wall_adhesion(),
+        }
+    }
+}
+
+impl SphParams {
+    pub fn from_state(sph: &SPHState) -> Self {
+        let (av_alpha, av_speed_of_sound) = match sph.viscosity {
+            Viscosity::Artificial {
+                alpha,
+                speed_of_sound,
+            } => (alpha, speed_of_sound),
+            Viscosity::Laplacian => (0.0, 0.0),
+        };
+        let iterative = sph.solver.iterative().unwrap_or_default();
+        let divergence = match sph.solver {
+            Solver::Dfsph(config) => config.divergence,
+            _ => IterativeConfig::default(),
+        };
+        let pbf = match sph.solver {
+            Solver::Pbf(config) => config,
+            _ => PbfConfig::default(),
+        };
+        let iisph = match sph.solver {
+            Solver::Iisph(config) => config,
+            _ => IisphConfig::default(),
+        };
+        let boundary = sph.active_boundary();
+        let flip = match sph.solver {
+            Solver::Flip(config) => config,
+            _ => FlipConfig::default(),
+        };
+        let mpm = match sph.solver {
+            Solver::Mpm(config) => config,
+            _ => MpmConfig::default(),
+        };
+        let (mpm_mu, mpm_lambda) = mpm.lame();
+        let mut mpm_bulk_modulus = 0.0;
+        let mut snow = [0.0; 3];
+        let mut mpm_friction = 0.0;
+        match mpm.material {
+            MpmMaterial::Fluid { bulk_modulus } => mpm_bulk_modulus = bulk_modulus,
+            MpmMaterial::Jelly => {}
+            MpmMaterial::Snow {
+                critical_compression,
+                critical_stretch,
+                hardening,
+            } => snow = [critical_compression, critical_stretch, hardening],
+            MpmMaterial::Sand { friction_angle } => mpm_friction = sand_alpha(friction_angle),
+        }
+        Self {
+            mass: sph.m,
+            rho_0: sph.rho_0,
+            k: sph.k,
+            mu: sph.mu,
+            gravity: [sph.gravity.x, sph.gravity.y],
+            num_colliders: sph.all_colliders().count() as u32,
+            eos: sph.eos.id(),
+            speed_of_sound: sph.eos.speed_of_sound(),
+            pressure_force: sph.pressure_force.id(),
+            viscosity: sph.viscosity.id(),
+            av_alpha,
+            av_speed_of_sound,
+            solver: sph.solver.id(),
+            solver_tolerance: iterative.tolerance,
+            solver_min_iterations: iterative.min_iterations,
+            pcisph_delta: pcisph_delta(sph.h, sph.m, sph.rho_0),
+            divergence_tolerance: divergence.tolerance,
+            divergence_min_iterations: divergence.min_iterations,
+            pbf_scale: prototype_grad_sq(sph.h, sph.m, sph.rho_0),
+            pbf_relaxation: pbf.relaxation,
+            pbf_artificial_pressure: pbf.artificial_pressure,
+            pbf_artificial_pressure_n: pbf.artificial_pressure_n,
+            pbf_artificial_pressure_dq: pbf.artificial_pressure_dq,
+            pbf_xsph: pbf.xsph,
+            pbf_vorticity: pbf.vorticity,
+            iisph_relaxation: iisph.relaxation,
+            flip_ratio: flip.flip_ratio,
+            flip_relaxation: flip.relaxation,
+            flip_apic: flip.apic as u32,
+            mpm_material: mpm.material.id(),
+            mpm_mu,
+            mpm_lambda,
+            mpm_bulk_modulus,
+            mpm_critical_compression: snow[0],
+            mpm_critical_stretch: snow[1],
+            mpm_hardening: snow[2],
+            mpm_friction,
+            sound_speed: sph.sound_speed(),
+            kinematic_viscosity: sph.kinematic_viscosity(),
+            integrator: sph.active_integrator().id(),
+            xsph: if sph.xsph_active() { sph.xsph } else { 0.0 },
+            surface_tension: sph.surface_tension,
+            num_boundary: boundary.map_or(0, |b| b.pos.len() as u32),
+            boundary_table: boundary.map_or(0, |b| b.grid.table_size),
+            _pad: [0; 3],
+        }
+    }
+}
+
 impl ParticleBuffers {
     pub fn new(render_device: &RenderDevice, sph: &SPHState) -> Self {
         // converting the cpu particle to gpu
@@ -707,6 +925,9 @@ impl ParticleBuffers {
                 acc: [particle.acc.x, particle.acc.y],
                 rho: particle.rho,
                 p: particle.p,
+                flags: 0,
+                _pad: 0,
+                normal: [0.0; 2],
             });
         }
 
@@ -733,26 +954,52 @@ pub struct GPUSPHPlugin;
 impl Plugin for GPUSPHPlugin {
     fn build(&self, app: &mut App) {
         // ================== App world ==================
-        app.init_resource::<IntegrateConfig>();
+        app.init_resource::<IntegrateConfig>()
+            .init_resource::<SimClock>()
+            .init_resource::<GridBoundsReport>()
+            .init_resource::<BodyForceReport>()
+            .init_resource::<SolverStatsReport>()
+            .init_resource::<TimestepReport>();
         app.add_systems(
             Startup,
             (
                 init_gpu_buffers,
-                init_readback_buffer,
                 init_particle_bind_group_layout,
-                init_allow_copy,
                 init_grid_buffers,
                 init_integrate_params_buffer,
+                init_sph_params_buffer,
+                init_collider_buffers,
                 init_use_gpu_integration,
+                init_grid_stats_readback,
+                init_body_force_readback,
+                init_solver_buffers,
+                init_solver_stats_readback,
+                init_timestep_buffers,
+                init_timestep_readback,
             )
                 .chain(),
         )
+        .add_systems(
+            First,
+            (
+                poll_grid_stats_readback,
+                poll_body_force_readback,
+                poll_solver_stats_readback,
+                poll_timestep_readback,
+            ),
+        )
         .add_systems(
             Update,
             (
                 queue_particle_buffer,
-                update_grid_buffers,
-                update_integrate_params_buffer,
+                grow_grid_capacity,
+                update_integrate_params_buffer.after(advance_gpu_clock),
+                update_sph_params_buffer,
+                advance_gpu_clock,
+                integrate_gpu_bodies
+                    .after(advance_gpu_clock)
+                    .before(update_collider_buffers),
+                update_collider_buffers,
             ),
         );
 
@@ -765,12 +1012,29 @@ impl Plugin for GPUSPHPlugin {
             (
                 extract_particle_buffer,
                 extract_bind_group_layout,
-                extract_readback_buffer,
-                extract_allow_copy,
                 extract_grid_buffers,
                 extract_integrate_params_buffer,
+                extract_sph_params_buffer,
+                extract_collider_buffers,
+                extract_grid_stats_readback,
+                extract_body_force_readback,
+                extract_solver_buffers,
+                extract_solver_stats_readback,
+                extract_timestep,
+                extract_timestep_readback,
+                extract_sim_clock,
             ),
         );
+        render_app.add_systems(
+            Render,
+            (
+                map_grid_stats_readback,
+                map_body_force_readback,
+                map_solver_stats_readback,
+                map_timestep_readback,
+            )
+                .in_set(RenderSet::Cleanup),
+        );
 
         // ---- Prepare (pipelines, bind groups) ----
         render_app.add_systems(
@@ -784,6 +1048,21 @@ impl Plugin for GPUSPHPlugin {
                 prepare_pressure_pipeline,
                 prepare_forces_pipeline,
                 prepare_integrate_pipeline,
+                prepare_solver_pipelines,
+                // FLIP grid passes
+                init_flip_bind_group_layout,
+                init_flip_buffers,
+                prepare_flip_bind_group
+                    .after(init_flip_bind_group_layout)
+                    .after(init_flip_buffers),
+                prepare_flip_pipelines.after(init_flip_bind_group_layout),
+                // MPM
+                init_mpm_bind_group_layout,
+                init_mpm_buffers,
+                prepare_mpm_bind_group
+                    .after(init_mpm_bind_group_layout)
+                    .after(init_mpm_buffers),
+                prepare_mpm_pipelines.after(init_mpm_bind_group_layout),
                 // Grid build: counts & params
                 init_grid_build_bind_group_layout,
                 init_grid_build_buffers.after(init_grid_build_bind_group_layout),
@@ -799,6 +1078,17 @@ impl Plugin for GPUSPHPlugin {
                 .in_set(RenderSet::Prepare),
         );
 
+        // Render — adaptive dt
+        render_app.add_systems(
+            Render,
+            (
+                init_timestep_bind_group_layout,
+                prepare_timestep_bind_group.after(init_timestep_bind_group_layout),
+                prepare_timestep_pipelines.after(init_timestep_bind_group_layout),
+            )
+                .in_set(RenderSet::Prepare),
+        );
+
         // Render — block B (starts + block scan)
         render_app.add_systems(
             Render,
@@ -838,6 +1128,21 @@ impl Plugin for GPUSPHPlugin {
                 .in_set(RenderSet::Prepare),
         );
 
+        // Render — bounds (before histogram; writes GridParams on the GPU)
+        render_app.add_systems(
+            Render,
+            (
+                init_grid_bounds_bgl,
+                init_grid_bounds_buffers,
+                init_grid_bounds_bg
+                    .after(init_grid_bounds_bgl)
+                    .after(init_grid_bounds_buffers)
+                    .after(init_grid_build_buffers),
+                prepare_grid_bounds_pipeline.after(init_grid_bounds_bgl),
+            )
+                .in_set(RenderSet::Prepare),
+        );
+
         // Render — block D (cursor + scatter)
         render_app.add_systems(
             Render,
@@ -856,6 +1161,8 @@ impl Plugin for GPUSPHPlugin {
         );
 
         // ---- Render Graph nodes (order via edges) ----
+        // one step's passes live in SimStepGraph, run once per clock step
+        add_sim_steps_node_to_graph(render_app);
         add_density_node_to_graph(render_app);
         add_clear_counts_node_to_graph(render_app);
         add_histogram_node_to_graph(render_app);
@@ -866,5 +1173,10 @@ impl Plugin for GPUSPHPlugin {
         add_write_sentinel_node_to_graph(render_app);
         add_clear_cursor_node_to_graph(render_app);
         add_scatter_node_to_graph(render_app);
+        // ordered before histogram and scatter, so after them
+        add_grid_bounds_node_to_graph(render_app);
+
+        // its node copies after the step nodes, which have to exist first
+        app.add_plugins(ParticleReadbackPlugin);
     }
 }
@@ -0,0 +1,249 @@
+use bevy::prelude::*;
+use bevy::render::Extract;
+use bevy::render::render_resource::{Buffer, BufferDescriptor, BufferUsages};
+use bevy::render::renderer::{RenderDevice, RenderQueue};
+
+use crate::cpu::boundary::BoundaryParticles;
+use crate::cpu::collider::{Collider, ColliderShape};
+use crate::cpu::sph2d::SPHState;
+use crate::cpu::timestep::SimClock;
+use crate::gpu::buffers::{IntegrateConfig, UseGpuIntegration};
+use crate::gpu::ffi::{
+    COLLIDER_BOX, COLLIDER_CAPSULE, COLLIDER_CIRCLE, COLLIDER_POLYGON, COLLIDER_SDF, GPUBodyForce,
+    GPUCollider,
+};
+use crate::gpu::readback::BodyForceReport;
+
+// ==================== resources ======================================
+
+// SPHState::colliders and moving_colliders on the GPU: one GPUCollider per collider plus the
+// variable-sized data (polygon points, SDF grid values) in a flat f32 buffer
+#[derive(Resource)]
+pub struct ColliderBuffers {
+    pub colliders: Buffer,
+    pub data: Buffer,
+    pub body_forces: Buffer, // GPUBodyForce per FluidBody, cleared every step
+    pub num_bodies: u32,
+}
+
+#[derive(Resource, Clone)]
+pub struct ExtractedColliderBuffers {
+    pub colliders: Buffer,
+    pub data: Buffer,
+    pub body_forces: Buffer,
+    pub num_bodies: u32,
+}
+
+// =====================================================================
+
+pub fn pack_colliders<'a>(
+    colliders: impl IntoIterator<Item = &'a Collider>,
+) -> (Vec<GPUCollider>, Vec<f32>) {
+    let mut packed = Vec::new();
+    let mut data = Vec::new();
+
+    for c in colliders {
+        let mut g = GPUCollider {
+            position: c.position.to_array(),
+            rotation: c.rotation,
+            restitution: c.restitution,
+            friction: c.friction.clamp(0.0, 1.0),
+            adhesion: c.adhesion,
+            velocity: c.velocity.to_array(),
+            angular_velocity: c.angular_velocity,
+            body: -1,
+            data_start: data.len() as u32,
+            ..Default::default()
+        };
+        match &c.shape {
+            ColliderShape::Circle { radius } => {
+                g.kind = COLLIDER_CIRCLE;
+                g.shape[0] = *radius;
+            }
+            ColliderShape::Box { half_extents } => {
+                g.kind = COLLIDER_BOX;
+                g.shape[..2].copy_from_slice(&half_extents.to_array());
+            }
+            ColliderShape::Capsule { a, b, radius } => {
+                g.kind = COLLIDER_CAPSULE;
+                g.shape = [a.x, a.y, b.x, b.y];
+                g.radius = *radius;
+            }
+            ColliderShape::Polygon { points } => {
+                g.kind = COLLIDER_POLYGON;
+                data.extend(points.iter().flat_map(|p| p.to_array()));
+            }
+            ColliderShape::Sdf(grid) => {
+                g.kind = COLLIDER_SDF;
+                g.shape = [
+                    grid.origin.x,
+                    grid.origin.y,
+                    grid.cell_size,
+                    grid.dims.y as f32,
+                ];
+                g.grid_width = grid.dims.x;
+                data.extend_from_slice(&grid.values);
+            }
+        }
+        g.data_len = data.len() as u32 - g.data_start;
+        packed.push(g);
+    }
+    (packed, data)
+}
+
+// boundary particles as f32: the bucket starts (table size + 1), then x, y
+// and psi of every particle in bucket order, so the starts index them directly
+pub fn pack_boundary(boundary: &BoundaryParticles) -> Vec<f32> {
+    let grid = &boundary.grid;
+    let mut data = Vec::with_capacity(grid.starts.len() + 3 * grid.entries.len());
+    data.extend(grid.starts.iter().map(|&s| s as f32));
+    for &k in &grid.entries {
+        let k = k as usize;
+        data.extend([boundary.pos[k].x, boundary.pos[k].y, boundary.psi[k]]);
+    }
+    data
+}
+
+// all colliders of the scene; bodies come last and get their force slot.
+// The boundary particles of the active solver go in front of the collider data
+pub fn pack_scene(sph: &SPHState) -> (Vec<GPUCollider>, Vec<f32>) {
+    let (mut packed, data) = pack_colliders(sph.all_colliders());
+    let first_body = packed.len() - sph.bodies.len();
+    for (i, g) in packed[first_body..].iter_mut().enumerate() {
+        g.body = i as i32;
+    }
+    let Some(boundary) = sph.active_boundary() else {
+        return (packed, data);
+    };
+    let mut boundary = pack_boundary(boundary);
+    for g in &mut packed {
+        g.data_start += boundary.len() as u32;
+    }
+    boundary.extend(data);
+    (packed, boundary)
+}
+
+fn body_forces_size(num_bodies: usize) -> u64 {
+    (num_bodies.max(1) * std::mem::size_of::<GPUBodyForce>()) as u64
+}
+
+fn body_forces_buffer(render_device: &RenderDevice, num_bodies: usize) -> Buffer {
+    render_device.create_buffer(&BufferDescriptor {
+        label: Some("body_forces_buffer"),
+        size: body_forces_size(num_bodies),
+        usage: BufferUsages::STORAGE | BufferUsages::COPY_DST | BufferUsages::COPY_SRC,
+        mapped_at_creation: false,
+    })
+}
+
+// bindings can not be empty; keep room for at least one (zeroed) collider
+fn storage_buffer(render_device: &RenderDevice, label: &str, size: u64) -> Buffer {
+    render_device.create_buffer(&BufferDescriptor {
+        label: Some(label),
+        size: size.max(std::mem::size_of::<GPUCollider>() as u64),
+        usage: BufferUsages::STORAGE | BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    })
+}
+
+fn write_nonempty(render_queue: &RenderQueue, buffer: &Buffer, bytes: &[u8]) {
+    if !bytes.is_empty() {
+        render_queue.write_buffer(buffer, 0, bytes);
+    }
+}
+
+// ========================== systems ==================================
+
+pub fn init_collider_buffers(
+    mut commands: Commands,
+    render_device: Res<RenderDevice>,
+    render_queue: Res<RenderQueue>,
+    sph: Res<SPHState>,
+) {
+    let (packed, data) = pack_scene(&sph);
+    let colliders = storage_buffer(
+        &render_device,
+        "collider_buffer",
+        std::mem::size_of_val(packed.as_slice()) as u64,
+    );
+    let data_buf = storage_buffer(
+        &render_device,
+        "collider_data_buffer",
+        std::mem::size_of_val(data.as_slice()) as u64,
+    );
+    write_nonempty(&render_queue, &colliders, bytemuck::cast_slice(&packed));
+    write_nonempty(&render_queue, &data_buf, bytemuck::cast_slice(&data));
+    commands.insert_resource(ColliderBuffers {
+        colliders,
+        data: data_buf,
+        body_forces: body_forces_buffer(&render_device, sph.bodies.len()),
+        num_bodies: sph.bodies.len() as u32,
+    });
+}
+
+// re-uploads when the SPHState changed; grows the buffers if needed
+pub fn update_collider_buffers(
+    render_device: Res<RenderDevice>,
+    render_queue: Res<RenderQueue>,
+    sph: Res<SPHState>,
+    mut buffers: ResMut<ColliderBuffers>,
+) {
+    if !sph.is_changed() {
+        return;
+    }
+    let (packed, data) = pack_scene(&sph);
+    let packed: &[u8] = bytemuck::cast_slice(&packed);
+    let data: &[u8] = bytemuck::cast_slice(&data);
+
+    if packed.len() as u64 > buffers.colliders.size() {
+        buffers.colliders = storage_buffer(&render_device, "collider_buffer", packed.len() as u64);
+    }
+    if data.len() as u64 > buffers.data.size() {
+        buffers.data = storage_buffer(&render_device, "collider_data_buffer", data.len() as u64);
+    }
+    write_nonempty(&render_queue, &buffers.colliders, packed);
+    write_nonempty(&render_queue, &buffers.data, data);
+
+    if body_forces_size(sph.bodies.len()) > buffers.body_forces.size() {
+        buffers.body_forces = body_forces_buffer(&render_device, sph.bodies.len());
+    }
+    buffers.num_bodies = sph.bodies.len() as u32;
+}
+
+// GPU mode: the bodies are integrated here from the forces the GPU reported
+// (a few frames old), as many steps per frame as the fluid. They take the dt
+// the GPU used for those forces, so the impulses on fluid and body balance
+pub fn integrate_gpu_bodies(
+    use_gpu_integration: Res<UseGpuIntegration>,
+    config: Res<IntegrateConfig>,
+    clock: Res<SimClock>,
+    report: Res<BodyForceReport>,
+    mut sph: ResMut<SPHState>,
+) {
+    if !use_gpu_integration.0 || sph.bodies.is_empty() {
+        return;
+    }
+    let dt = if report.dt > 0.0 {
+        report.dt
+    } else {
+        config.dt
+    };
+    let gravity = sph.gravity;
+    for (i, body) in sph.bodies.iter_mut().enumerate() {
+        let (force, torque) = report.forces.get(i).copied().unwrap_or_default();
+        body.force = force;
+        body.torque = torque;
+        for _ in 0..clock.steps_this_frame {
+            body.integrate(dt, gravity, &config.domain);
+        }
+    }
+}
+
+pub fn extract_collider_buffers(mut commands: Commands, buffers: Extract<Res<ColliderBuffers>>) {
+    commands.insert_resource(ExtractedColliderBuffers {
+        colliders: buffers.colliders.clone(),
+        data: buffers.data.clone(),
+        body_forces: buffers.body_forces.clone(),
+        num_bodies: buffers.num_bodies,
+    });
+}
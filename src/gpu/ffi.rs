@@ -9,8 +9,14 @@ pub struct GPUParticle {
     pub acc: [f32; 2],
     pub rho: f32,
     pub p: f32,
+    pub flags: u32,       // PARTICLE_DEAD, ...
+    pub _pad: u32,        // 8B alignment (vec2 in WGSL)
+    pub normal: [f32; 2], // h sum m / rho_j grad W, written by the normals pass
 }
 
+// particle left through an open wall; skipped by every pass
+pub const PARTICLE_DEAD: u32 = 1;
+
 #[repr(C)]
 #[derive(Clone, Copy, Debug, Pod, Zeroable)]
 pub struct GridParams {
@@ -18,22 +24,244 @@ pub struct GridParams {
     pub cell_size: f32,
     pub _pad0: f32, // 16B alignment
     pub dims: [u32; 2],
-    pub _pad1: [u32; 2], // 16B alignment
+    pub hash_size: u32, // 0 = dense grid, else number of hash buckets
+    pub _pad1: u32,     // 16B alignment
 }
 // 16B alignment for uniform buffers
 
+// physical constants, mirrored from SPHState so CPU and GPU run the same scene
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Pod, Zeroable)]
+pub struct SphParams {
+    pub mass: f32,
+    pub rho_0: f32,
+    pub k: f32,  // stiffness
+    pub mu: f32, // viscosity
+    pub gravity: [f32; 2],
+    pub num_colliders: u32,  // entries used in the collider buffer
+    pub eos: u32,            // EquationOfState::id
+    pub speed_of_sound: f32, // Tait
+    pub pressure_force: u32, // PressureForce::id
+    pub viscosity: u32,      // Viscosity::id
+    pub av_alpha: f32,       // Viscosity::Artificial
+    pub av_speed_of_sound: f32,
+    pub solver: u32,                // Solver::id
+    pub solver_tolerance: f32,      // IterativeConfig
+    pub solver_min_iterations: u32, // IterativeConfig
+    pub pcisph_delta: f32,          // pcisph_delta(), divided by dt^2 in the shader
+    pub divergence_tolerance: f32,  // DfsphConfig::divergence
+    pub divergence_min_iterations: u32,
+    pub pbf_scale: f32, // prototype_grad_sq(), PbfConfig is relative to it
+    pub pbf_relaxation: f32,
+    pub pbf_artificial_pressure: f32,
+    pub pbf_artificial_pressure_n: f32,
+    pub pbf_artificial_pressure_dq: f32,
+    pub pbf_xsph: f32,
+    pub pbf_vorticity: f32,
+    pub iisph_relaxation: f32, // IisphConfig
+    pub flip_ratio: f32,       // FlipConfig
+    pub flip_relaxation: f32,
+    pub flip_apic: u32,
+    pub mpm_material: u32, // MpmMaterial::id
+    pub mpm_mu: f32,       // MpmConfig::lame
+    pub mpm_lambda: f32,
+    pub mpm_bulk_modulus: f32,         // Fluid
+    pub mpm_critical_compression: f32, // Snow
+    pub mpm_critical_stretch: f32,
+    pub mpm_hardening: f32,
+    pub mpm_friction: f32, // Sand, sand_alpha()
+    pub sound_speed: f32,  // SPHState::sound_speed, for the adaptive dt
+    pub kinematic_viscosity: f32,
+    pub integrator: u32, // Integrator::id, of the active solver
+    pub xsph: f32,       // SPHState::xsph, 0 unless xsph_active
+    pub surface_tension: f32,
+    pub num_boundary: u32, // boundary particles of the active solver, 0 = none
+    pub boundary_table: u32, // their hash table size (pack_boundary)
+    pub _pad: [u32; 3],    // 16B alignment
+}
+
 #[repr(C)]
 #[derive(Clone, Copy, Pod, Zeroable)]
 pub struct IntegrateParams {
-    pub dt: f32,
-    pub x_min: f32,
-    pub x_max: f32,
-    pub bounce: f32, // mast be negative
+    pub dt: f32,     // SimClock::dt when adaptive
+    pub dt_min: f32, // AdaptiveDt
+    pub dt_max: f32,
+    pub _pad: f32,     // 16B alignment
+    pub min: [f32; 2], // domain
+    pub max: [f32; 2],
+    pub modes: [u32; 4],       // WallMode::id of left, right, bottom, top
+    pub restitution: [f32; 4], // for Reflect walls, same order
+    pub dt_safety: [f32; 4],   // AdaptiveDt cfl, force, viscosity, unused
+    pub adhesion: [f32; 4],    // Domain::wall_adhesion, same order as modes
+}
+
+// one collider (cpu::collider::Collider), storage buffer element
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Pod, Zeroable)]
+pub struct GPUCollider {
+    pub kind: u32,       // COLLIDER_*
+    pub data_start: u32, // polygon points / SDF values in the collider data buffer
+    pub data_len: u32,   // number of f32 used there
+    pub grid_width: u32, // SDF grid columns
+    pub position: [f32; 2],
+    pub rotation: f32,
+    pub restitution: f32,
+    // circle: (radius), box: (hx, hy), capsule: (ax, ay, bx, by),
+    // sdf: (origin x, origin y, cell size, rows)
+    pub shape: [f32; 4],
+    pub friction: f32,
+    pub radius: f32, // capsule
+    pub velocity: [f32; 2],
+    pub angular_velocity: f32,
+    pub body: i32, // index into the body forces, -1 if not a FluidBody
+    pub adhesion: f32,
+    pub _pad: f32, // 16B alignment
+}
+
+pub const COLLIDER_CIRCLE: u32 = 0;
+pub const COLLIDER_BOX: u32 = 1;
+pub const COLLIDER_CAPSULE: u32 = 2;
+pub const COLLIDER_POLYGON: u32 = 3;
+pub const COLLIDER_SDF: u32 = 4;
+
+// fluid force on one body, summed with atomics in fixed point
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, Pod, Zeroable)]
+pub struct GPUBodyForce {
+    pub force: [i32; 2],
+    pub torque: i32,
+    pub _pad: i32, // 16B alignment
+}
+
+// fixed-point scale of GPUBodyForce (same constant in sph_density.wgsl)
+pub const BODY_FORCE_SCALE: f32 = 1024.0;
+
+// per-particle state of the iterative pressure solvers (storage buffer
+// element, one per GPUParticle)
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, Pod, Zeroable)]
+pub struct GPUSolverScratch {
+    pub pos_pred: [f32; 2], // predicted position
+    pub acc_np: [f32; 2],   // non-pressure acceleration (viscosity, gravity)
+    pub acc_p: [f32; 2],    // pressure acceleration (PBF: position correction, then XSPH velocity)
+    pub rho_pred: f32,      // predicted density
+    pub alpha: f32,         // DFSPH factor (PBF: vorticity)
+    pub vel0: [f32; 2],     // velocity at the start of the step (PBF: corrected velocity)
+    pub kappa: f32,         // DFSPH stiffness of the current iteration (PBF: lambda)
+    pub kappa_sum: f32,     // summed over the density solve, p = kappa_sum * rho
+    pub acc_prev: [f32; 2], // acceleration of the last step (VelocityVerlet)
+    pub dt_prev: f32,       // dt of the last step (VelocityVerlet)
+    pub _pad: f32,
+}
+
+// convergence of the iterative solvers, reset at the start of every step
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, Pod, Zeroable)]
+pub struct GPUSolverStats {
+    pub iterations: u32,
+    pub converged: u32, // 1 once the tolerance is met; later rounds do nothing
+    pub error_sum: u32, // fixed point, SOLVER_ERROR_SCALE per rho_0
+    pub count: u32,     // particles summed into error_sum
+    pub density_error: f32,
+    pub divergence_iterations: u32, // DFSPH, moved here before the density solve
+    pub divergence_error: f32,
+    pub _pad: u32, // 16B alignment
+}
+
+// adaptive time step (timestep.wgsl). The maxima are reduced with atomicMax
+// on the bits of non-negative floats, which order like the floats
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, Pod, Zeroable)]
+pub struct GPUTimestep {
+    pub dt: f32, // read back, paces the next steps
+    pub max_speed: f32,
+    pub max_accel: f32,
+    pub _pad: u32,
+    pub speed_bits: u32, // running maxima, reset once dt is computed
+    pub accel_bits: u32,
+    pub _pad1: [u32; 2], // 16B alignment
+}
+
+// fixed-point scale of GPUSolverStats::error_sum (same constant in sph_density.wgsl)
+pub const SOLVER_ERROR_SCALE: f32 = 65536.0;
+
+// one node of the FLIP MAC grid (flip.wgsl): cell (i, j), the u face on its
+// left and the v face below it
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, Pod, Zeroable)]
+pub struct GPUMacNode {
+    pub u_sum: i32, // particle to grid splat, fixed point (weight * velocity)
+    pub v_sum: i32,
+    pub u_weight: i32,
+    pub v_weight: i32,
+    pub p_sum: i32, // particle pressures for the warm start, fixed point
+    pub count: u32, // particles in the cell
+    pub u: f32,     // face velocities
+    pub v: f32,
+    pub u_old: f32, // before gravity and projection (FLIP change)
+    pub v_old: f32,
+    pub p: f32,      // cell pressure
+    pub p_next: f32, // Jacobi
+}
+
+// one node of the MPM grid (mpm.wgsl). The splat is divided by the particle
+// mass, so the fixed point does not depend on it
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, Pod, Zeroable)]
+pub struct GPUMpmNode {
+    pub weight: i32,        // sum of the weights, fixed point
+    pub momentum: [i32; 2], // weight * (v + C dpos) + stress impulse / mass
+    pub vel: [f32; 2],      // after gravity and the walls
+    pub _pad: u32,
+}
+
+// MPM state per GPUParticle (mpm.wgsl), columns like glam::Mat2
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Pod, Zeroable)]
+pub struct GPUMpmParticle {
+    pub deformation: [f32; 4], // F
+    pub affine: [f32; 4],      // C
+    pub plastic_j: f32,
+    pub _pad: [f32; 3], // 16B alignment
+}
+
+impl Default for GPUMpmParticle {
+    // undeformed, like Particle::new
+    fn default() -> Self {
+        Self {
+            deformation: [1.0, 0.0, 0.0, 1.0],
+            affine: [0.0; 4],
+            plastic_j: 1.0,
+            _pad: [0.0; 3],
+        }
+    }
 }
 
 #[repr(C)]
 #[derive(Clone, Copy, Debug, Pod, Zeroable)]
 pub struct GridBuildParams {
-    pub num_cells: u32,
-    pub _pad: [u32; 7], // 16B alignment
+    pub num_cells: u32, // allocated cell capacity, not the cells in use
+    pub cell_size: f32,
+    pub hashed: u32,    // 1 = num_cells is a fixed hash table
+    pub _pad: [u32; 5], // 16B alignment
+}
+
+// written by the bounds pass, read back to grow the grid buffers
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, Pod, Zeroable)]
+pub struct GridBoundsStats {
+    pub required_cells: u32, // cells the particle bounds need this frame
+    pub clamped: u32,        // 1 if the grid did not fit the capacity
+    pub _pad: [u32; 2],      // 16B alignment
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Pod, Zeroable)]
+pub struct ParticleRenderParams {
+    pub color: [f32; 4], // solid colour (linear)
+    pub range: [f32; 2], // (min, max) of the coloured quantity
+    pub radius: f32,     // world units
+    pub scale: f32,      // world -> pixels
+    pub mode: u32,       // 0 solid, 1 density, 2 pressure, 3 speed
+    pub _pad: [u32; 3],  // 16B alignment
 }
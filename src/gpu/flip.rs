@@ -0,0 +1,197 @@
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use bevy::render::render_resource::{
+    BindGroup, BindGroupEntry, BindGroupLayout, BindGroupLayoutEntry, BindingType, Buffer,
+    BufferBindingType, BufferDescriptor, BufferUsages, ShaderStages,
+};
+use bevy::render::renderer::RenderDevice;
+
+use crate::gpu::buffers::{
+    ExtractedGrid, ExtractedIntegrateParamsBuffer, ExtractedParticleBuffer,
+    ExtractedSphParamsBuffer,
+};
+use crate::gpu::ffi::GPUMacNode;
+use crate::gpu::solver::ExtractedSolverBuffers;
+
+// ==================== resources ======================================
+
+// flip.wgsl: 0 = particles (rw), 1 = GridParams, 2 = IntegrateParams,
+// 3 = SphParams (uniforms), 4 = MAC nodes (rw, atomics), 5 = APIC gradients
+// (rw), 6 = solver stats (rw). The particle bind group has no storage slot
+// left, so the grid passes get their own
+#[derive(Resource, Clone)]
+pub struct FlipBindGroupLayout(pub BindGroupLayout);
+
+// render world only, sized from the grid capacity like the grid-build buffers
+#[derive(Resource)]
+pub struct FlipBuffers {
+    pub nodes: Buffer,  // GPUMacNode
+    pub affine: Buffer, // [f32; 4] per particle
+    pub capacity: u32,  // nodes
+    pub num_particles: u32,
+}
+
+#[derive(Resource)]
+pub struct FlipBindGroup(pub BindGroup);
+
+// =====================================================================
+
+// nodes for the bounds grid plus the empty ring around it (one node more
+// than cells per axis); grid_capacity_for keeps num_cells >= 2 * dims.x * dims.y
+pub fn mac_capacity(num_cells: usize) -> usize {
+    2 * num_cells + 64
+}
+
+// layout of flip.wgsl and mpm.wgsl, which differ only in slots 4 and 5
+pub fn grid_solver_layout(render_device: &RenderDevice, label: &str) -> BindGroupLayout {
+    let entry = |binding: u32, ty: BufferBindingType| BindGroupLayoutEntry {
+        binding,
+        visibility: ShaderStages::COMPUTE,
+        ty: BindingType::Buffer {
+            ty,
+            has_dynamic_offset: false,
+            min_binding_size: None,
+        },
+        count: None,
+    };
+    let storage = BufferBindingType::Storage { read_only: false };
+    render_device.create_bind_group_layout(
+        Some(label),
+        &[
+            entry(0, storage),
+            entry(1, BufferBindingType::Uniform),
+            entry(2, BufferBindingType::Uniform),
+            entry(3, BufferBindingType::Uniform),
+            entry(4, storage),
+            entry(5, storage),
+            entry(6, storage),
+        ],
+    )
+}
+
+// the shared buffers of the grid solver bind groups (slots 0 to 3 also of the
+// timestep bind group)
+#[derive(SystemParam)]
+pub struct GridSolverInputs<'w> {
+    particles: Res<'w, ExtractedParticleBuffer>,
+    grid: Res<'w, ExtractedGrid>,
+    integ: Res<'w, ExtractedIntegrateParamsBuffer>,
+    sph_params: Res<'w, ExtractedSphParamsBuffer>,
+    solver: Res<'w, ExtractedSolverBuffers>,
+}
+
+impl GridSolverInputs<'_> {
+    // nodes go to slot 4, the per-particle state to slot 5
+    pub fn bind_group(
+        &self,
+        render_device: &RenderDevice,
+        label: &str,
+        layout: &BindGroupLayout,
+        nodes: &Buffer,
+        per_particle: &Buffer,
+    ) -> BindGroup {
+        let rest = [nodes, per_particle, &self.solver.stats];
+        self.bind_group_with(render_device, label, layout, &rest)
+    }
+
+    // particles and the uniforms, then `rest` from slot 4 on
+    pub fn bind_group_with(
+        &self,
+        render_device: &RenderDevice,
+        label: &str,
+        layout: &BindGroupLayout,
+        rest: &[&Buffer],
+    ) -> BindGroup {
+        let shared = [
+            &self.particles.buffer,
+            &self.grid.params_buf,
+            &self.integ.buffer,
+            &self.sph_params.buffer,
+        ];
+        let entries: Vec<BindGroupEntry> = (0u32..)
+            .zip(shared.into_iter().chain(rest.iter().copied()))
+            .map(|(binding, buffer)| BindGroupEntry {
+                binding,
+                resource: buffer.as_entire_binding(),
+            })
+            .collect();
+        render_device.create_bind_group(Some(label), layout, &entries)
+    }
+}
+
+// ========================== systems ==================================
+
+pub fn init_flip_bind_group_layout(
+    mut commands: Commands,
+    render_device: Res<RenderDevice>,
+    existing: Option<Res<FlipBindGroupLayout>>,
+) {
+    if existing.is_some() {
+        return;
+    }
+    let layout = grid_solver_layout(&render_device, "flip_bind_group_layout");
+    commands.insert_resource(FlipBindGroupLayout(layout));
+}
+
+pub fn init_flip_buffers(
+    mut commands: Commands,
+    render_device: Res<RenderDevice>,
+    grid: Option<Res<ExtractedGrid>>,
+    particles: Option<Res<ExtractedParticleBuffer>>,
+    existing: Option<Res<FlipBuffers>>,
+) {
+    let (Some(grid), Some(particles)) = (grid, particles) else {
+        return;
+    };
+    // a hashed grid has no bounds to put the MAC grid on
+    let capacity = if grid.hashed {
+        1
+    } else {
+        mac_capacity(grid.num_cells) as u32
+    };
+    let num_particles = particles.num_particles;
+    if existing.is_some_and(|b| b.capacity == capacity && b.num_particles == num_particles) {
+        return;
+    }
+
+    let nodes = render_device.create_buffer(&BufferDescriptor {
+        label: Some("flip_mac_nodes"),
+        size: capacity as u64 * std::mem::size_of::<GPUMacNode>() as u64,
+        usage: BufferUsages::STORAGE,
+        mapped_at_creation: false,
+    });
+    // zero at creation, so APIC starts without a gradient
+    let affine = render_device.create_buffer(&BufferDescriptor {
+        label: Some("flip_affine"),
+        size: num_particles.max(1) as u64 * std::mem::size_of::<[f32; 4]>() as u64,
+        usage: BufferUsages::STORAGE,
+        mapped_at_creation: false,
+    });
+    info!("FLIP buffers: nodes={capacity}, particles={num_particles}");
+    commands.insert_resource(FlipBuffers {
+        nodes,
+        affine,
+        capacity,
+        num_particles,
+    });
+}
+
+pub fn prepare_flip_bind_group(
+    mut commands: Commands,
+    render_device: Res<RenderDevice>,
+    layout: Option<Res<FlipBindGroupLayout>>,
+    flip: Option<Res<FlipBuffers>>,
+    inputs: GridSolverInputs,
+) {
+    let (Some(layout), Some(flip)) = (layout, flip) else {
+        return;
+    };
+    let bind_group = inputs.bind_group(
+        &render_device,
+        "flip_bind_group",
+        &layout.0,
+        &flip.nodes,
+        &flip.affine,
+    );
+    commands.insert_resource(FlipBindGroup(bind_group));
+}
@@ -91,6 +91,20 @@ pub struct GridEntriesGpuBuffer {
 #[derive(Resource)]
 pub struct CursorClearBindGroup(pub BindGroup);
 
+// BGL for bounds: 0=particles(ro), 1=grid params out(rw), 2=GridBuildParams(uniform), 3=stats(rw)
+#[derive(Resource, Clone)]
+pub struct GridBoundsBindGroupLayout(pub BindGroupLayout);
+
+// GridParams is a uniform, so the bounds pass writes here and the node copies it over
+#[derive(Resource)]
+pub struct GridBoundsBuffers {
+    pub params_out: Buffer,
+    pub stats: Buffer,
+}
+
+#[derive(Resource)]
+pub struct GridBoundsBindGroup(pub BindGroup);
+
 /// Create the layout in the Render world (runs once)
 pub fn init_grid_build_bind_group_layout(mut commands: Commands, render_device: Res<RenderDevice>) {
     let layout = render_device.create_bind_group_layout(
@@ -130,6 +144,7 @@ pub fn init_grid_build_buffers(
     _queue: Res<RenderQueue>,
     layout: Option<Res<GridBuildBindGroupLayout>>,
     extracted_grid: Option<Res<crate::gpu::buffers::ExtractedGrid>>,
+    existing: Option<Res<GridBuildParamsBuffer>>,
 ) {
     let (Some(layout), Some(grid)) = (layout, extracted_grid) else {
         return; // layout or grid not ready this frame
@@ -138,6 +153,16 @@ pub fn init_grid_build_buffers(
     let num_cells_usize = grid.num_cells;
     let num_cells = num_cells_usize as u32;
 
+    // only reallocate when the capacity grew
+    let hashed = grid.hashed as u32;
+    if existing.is_some_and(|gb| {
+        gb.value.num_cells == num_cells
+            && gb.value.cell_size == grid.cell_size
+            && gb.value.hashed == hashed
+    }) {
+        return;
+    }
+
     let counts_size_bytes = (num_cells_usize.max(1) * std::mem::size_of::<u32>()) as u64;
 
     let counts = render_device.create_buffer(&BufferDescriptor {
@@ -149,7 +174,9 @@ pub fn init_grid_build_buffers(
 
     let gb_val = crate::gpu::ffi::GridBuildParams {
         num_cells,
-        _pad: [0; 7],
+        cell_size: grid.cell_size,
+        hashed,
+        _pad: [0; 5],
     };
     let gb_buf = render_device.create_buffer_with_data(&BufferInitDescriptor {
         label: Some("grid_build_params"),
@@ -554,6 +581,7 @@ pub fn init_cursor_buffer_and_clear_bg(
     gb_layout: Option<Res<GridBuildBindGroupLayout>>,
     grid: Option<Res<crate::gpu::buffers::ExtractedGrid>>,
     params: Option<Res<GridBuildParamsBuffer>>,
+    existing: Option<Res<GridCursorBuffer>>,
 ) {
     let (Some(gb_layout), Some(_grid), Some(params)) = (gb_layout, grid, params) else {
         return;
@@ -562,6 +590,9 @@ pub fn init_cursor_buffer_and_clear_bg(
     if num_cells == 0 {
         return;
     }
+    if existing.is_some_and(|cursor| cursor.num_cells == num_cells) {
+        return;
+    }
 
     let size_bytes = (num_cells.max(1) as usize * std::mem::size_of::<u32>()) as u64;
     let cursor = rd.create_buffer(&BufferDescriptor {
@@ -736,3 +767,118 @@ pub fn init_scatter_bg(
     );
     commands.insert_resource(ScatterBindGroup(bg));
 }
+
+pub fn init_grid_bounds_bgl(mut commands: Commands, rd: Res<RenderDevice>) {
+    let layout = rd.create_bind_group_layout(
+        Some("grid_bounds_bgl"),
+        &[
+            BindGroupLayoutEntry {
+                // particles
+                binding: 0,
+                visibility: ShaderStages::COMPUTE,
+                ty: BindingType::Buffer {
+                    ty: BufferBindingType::Storage { read_only: true },
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            },
+            BindGroupLayoutEntry {
+                // grid params out
+                binding: 1,
+                visibility: ShaderStages::COMPUTE,
+                ty: BindingType::Buffer {
+                    ty: BufferBindingType::Storage { read_only: false },
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            },
+            BindGroupLayoutEntry {
+                // GridBuildParams (capacity, cell size)
+                binding: 2,
+                visibility: ShaderStages::COMPUTE,
+                ty: BindingType::Buffer {
+                    ty: BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            },
+            BindGroupLayoutEntry {
+                // stats
+                binding: 3,
+                visibility: ShaderStages::COMPUTE,
+                ty: BindingType::Buffer {
+                    ty: BufferBindingType::Storage { read_only: false },
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            },
+        ],
+    );
+    commands.insert_resource(GridBoundsBindGroupLayout(layout));
+}
+
+pub fn init_grid_bounds_buffers(
+    mut commands: Commands,
+    rd: Res<RenderDevice>,
+    existing: Option<Res<GridBoundsBuffers>>,
+) {
+    if existing.is_some() {
+        return; // fixed size, created once
+    }
+    let params_out = rd.create_buffer(&BufferDescriptor {
+        label: Some("grid_params_out"),
+        size: std::mem::size_of::<crate::gpu::ffi::GridParams>() as u64,
+        usage: BufferUsages::STORAGE | BufferUsages::COPY_SRC,
+        mapped_at_creation: false,
+    });
+    let stats = rd.create_buffer(&BufferDescriptor {
+        label: Some("grid_bounds_stats"),
+        size: std::mem::size_of::<crate::gpu::ffi::GridBoundsStats>() as u64,
+        usage: BufferUsages::STORAGE | BufferUsages::COPY_SRC,
+        mapped_at_creation: false,
+    });
+    commands.insert_resource(GridBoundsBuffers { params_out, stats });
+}
+
+pub fn init_grid_bounds_bg(
+    mut commands: Commands,
+    rd: Res<RenderDevice>,
+    layout: Option<Res<GridBoundsBindGroupLayout>>,
+    particles: Option<Res<ExtractedParticleBuffer>>,
+    bounds: Option<Res<GridBoundsBuffers>>,
+    params: Option<Res<GridBuildParamsBuffer>>,
+) {
+    let (Some(layout), Some(particles), Some(bounds), Some(params)) =
+        (layout, particles, bounds, params)
+    else {
+        return;
+    };
+
+    let bg = rd.create_bind_group(
+        Some("grid_bounds_bg"),
+        &layout.0,
+        &[
+            BindGroupEntry {
+                binding: 0,
+                resource: particles.buffer.as_entire_binding(),
+            },
+            BindGroupEntry {
+                binding: 1,
+                resource: bounds.params_out.as_entire_binding(),
+            },
+            BindGroupEntry {
+                binding: 2,
+                resource: params.buffer.as_entire_binding(),
+            },
+            BindGroupEntry {
+                binding: 3,
+                resource: bounds.stats.as_entire_binding(),
+            },
+        ],
+    );
+    commands.insert_resource(GridBoundsBindGroup(bg));
+}
@@ -1,4 +1,11 @@
 pub mod buffers;
+pub mod collider;
 pub mod ffi;
+pub mod flip;
 pub mod grid_build;
+pub mod mpm;
 pub mod pipeline;
+pub mod readback;
+pub mod render;
+pub mod solver;
+pub mod timestep;
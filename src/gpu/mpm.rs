@@ -0,0 +1,115 @@
+use bevy::prelude::*;
+use bevy::render::render_resource::{
+    BindGroup, BindGroupLayout, Buffer, BufferDescriptor, BufferInitDescriptor, BufferUsages,
+};
+use bevy::render::renderer::RenderDevice;
+
+use crate::gpu::buffers::{ExtractedGrid, ExtractedParticleBuffer};
+use crate::gpu::ffi::{GPUMpmNode, GPUMpmParticle};
+use crate::gpu::flip::{GridSolverInputs, grid_solver_layout, mac_capacity};
+
+// ==================== resources ======================================
+
+// mpm.wgsl: 0 = particles (rw), 1 = GridParams, 2 = IntegrateParams,
+// 3 = SphParams (uniforms), 4 = MPM nodes (rw, atomics), 5 = GPUMpmParticle
+// (rw), 6 = solver stats (rw). Same slots as the FLIP bind group
+#[derive(Resource, Clone)]
+pub struct MpmBindGroupLayout(pub BindGroupLayout);
+
+// render world only. The particle state starts undeformed and is kept when
+// the grid grows
+#[derive(Resource)]
+pub struct MpmBuffers {
+    pub nodes: Buffer,     // GPUMpmNode
+    pub particles: Buffer, // GPUMpmParticle
+    pub capacity: u32,     // nodes
+    pub num_particles: u32,
+}
+
+#[derive(Resource)]
+pub struct MpmBindGroup(pub BindGroup);
+
+// =====================================================================
+
+// ========================== systems ==================================
+
+pub fn init_mpm_bind_group_layout(
+    mut commands: Commands,
+    render_device: Res<RenderDevice>,
+    existing: Option<Res<MpmBindGroupLayout>>,
+) {
+    if existing.is_some() {
+        return;
+    }
+    let layout = grid_solver_layout(&render_device, "mpm_bind_group_layout");
+    commands.insert_resource(MpmBindGroupLayout(layout));
+}
+
+pub fn init_mpm_buffers(
+    mut commands: Commands,
+    render_device: Res<RenderDevice>,
+    grid: Option<Res<ExtractedGrid>>,
+    particles: Option<Res<ExtractedParticleBuffer>>,
+    existing: Option<ResMut<MpmBuffers>>,
+) {
+    let (Some(grid), Some(particles)) = (grid, particles) else {
+        return;
+    };
+    // the node grid has the same size as the MAC grid (dims + 3 per axis)
+    let capacity = if grid.hashed {
+        1
+    } else {
+        mac_capacity(grid.num_cells) as u32
+    };
+    let num_particles = particles.num_particles;
+    let node_buffer = |capacity: u32| {
+        render_device.create_buffer(&BufferDescriptor {
+            label: Some("mpm_nodes"),
+            size: capacity as u64 * std::mem::size_of::<GPUMpmNode>() as u64,
+            usage: BufferUsages::STORAGE,
+            mapped_at_creation: false,
+        })
+    };
+
+    if let Some(mut buffers) = existing.filter(|b| b.num_particles == num_particles) {
+        if buffers.capacity != capacity {
+            buffers.nodes = node_buffer(capacity);
+            buffers.capacity = capacity;
+        }
+        return;
+    }
+
+    let rest = vec![GPUMpmParticle::default(); num_particles.max(1) as usize];
+    let particle_state = render_device.create_buffer_with_data(&BufferInitDescriptor {
+        label: Some("mpm_particles"),
+        contents: bytemuck::cast_slice(&rest),
+        usage: BufferUsages::STORAGE,
+    });
+    info!("MPM buffers: nodes={capacity}, particles={num_particles}");
+    commands.insert_resource(MpmBuffers {
+        nodes: node_buffer(capacity),
+        particles: particle_state,
+        capacity,
+        num_particles,
+    });
+}
+
+pub fn prepare_mpm_bind_group(
+    mut commands: Commands,
+    render_device: Res<RenderDevice>,
+    layout: Option<Res<MpmBindGroupLayout>>,
+    mpm: Option<Res<MpmBuffers>>,
+    inputs: GridSolverInputs,
+) {
+    let (Some(layout), Some(mpm)) = (layout, mpm) else {
+        return;
+    };
+    let bind_group = inputs.bind_group(
+        &render_device,
+        "mpm_bind_group",
+        &layout.0,
+        &mpm.nodes,
+        &mpm.particles,
+    );
+    commands.insert_resource(MpmBindGroup(bind_group));
+}
@@ -1,27 +1,41 @@
 use std::borrow::Cow;
+use std::collections::HashMap;
 
 use bevy::prelude::*;
 use bevy::render::graph::CameraDriverLabel;
 use bevy::render::render_graph::{
-    Node, NodeRunError, RenderGraph, RenderGraphContext, RenderLabel,
+    Node, NodeRunError, RenderGraph, RenderGraphContext, RenderLabel, RenderSubGraph,
 };
 use bevy::render::render_resource::{
-    CachedComputePipelineId, ComputePassDescriptor, ComputePipeline, ComputePipelineDescriptor,
-    PipelineCache, PushConstantRange, ShaderDefVal,
+    BindGroup, BindGroupLayout, CachedComputePipelineId, ComputePass, ComputePassDescriptor,
+    ComputePipeline, ComputePipelineDescriptor, PipelineCache, PushConstantRange, ShaderDefVal,
 };
 use bevy::render::renderer::RenderContext;
 
+use crate::cpu::sph2d::Solver;
 use crate::gpu::buffers::{
-    ExtractedAllowCopy, ExtractedParticleBuffer, ExtractedReadbackBuffer, ParticleBindGroup,
-    ParticleBindGroupLayout,
+    ExtractedGrid, ExtractedIntegrateParamsBuffer, ExtractedParticleBuffer, ExtractedSimClock,
+    ParticleBindGroup, ParticleBindGroupLayout,
 };
+use crate::gpu::collider::ExtractedColliderBuffers;
+use crate::gpu::ffi::{GridBoundsStats, GridParams};
+use crate::gpu::flip::{FlipBindGroup, FlipBindGroupLayout, FlipBuffers};
 use crate::gpu::grid_build::{
     AddBackBindGroup, AddBackBindGroupLayout, BlockSumsScanBindGroup, BlockSumsScanBindGroupLayout,
     CursorClearBindGroup, GridBlockScanBindGroup, GridBlockScanBindGroupLayout,
-    GridBlockSumsBuffer, GridBuildBindGroup, GridBuildBindGroupLayout, GridBuildParamsBuffer,
+    GridBlockSumsBuffer, GridBoundsBindGroup, GridBoundsBindGroupLayout, GridBoundsBuffers,
+    GridBuildBindGroup, GridBuildBindGroupLayout, GridBuildParamsBuffer,
     GridCountsToStartsBindGroup, GridCountsToStartsBindGroupLayout, GridHistogramBindGroup,
     GridHistogramBindGroupLayout, ScatterBindGroup, ScatterBindGroupLayout,
 };
+use crate::gpu::mpm::{MpmBindGroup, MpmBindGroupLayout, MpmBuffers};
+use crate::gpu::readback::{
+    ExtractedBodyForceReadback, ExtractedGridStatsReadback, ExtractedSolverStatsReadback,
+    ExtractedTimestepReadback,
+};
+use crate::gpu::render::{ExtractedParticleRenderSettings, ParticleColorMode};
+use crate::gpu::solver::ExtractedSolverBuffers;
+use crate::gpu::timestep::{ExtractedTimestep, TimestepBindGroup, TimestepBindGroupLayout};
 
 // ==================== resources ======================================
 #[derive(Resource)]
@@ -33,6 +47,98 @@ pub struct PressurePipeline(pub ComputePipeline);
 #[derive(Resource)]
 pub struct ForcesPipeline(pub ComputePipeline);
 
+// entry points of the iterative pressure solvers, XSPH and the surface
+// normals in sph_density.wgsl
+pub const SOLVER_ENTRY_POINTS: [&str; 30] = [
+    "solver_check_main",
+    "pcisph_init_main",
+    "pcisph_predict_main",
+    "pcisph_density_main",
+    "pcisph_pressure_main",
+    "pcisph_finish_main",
+    "dfsph_alpha_main",
+    "dfsph_divergence_main",
+    "dfsph_divergence_check_main",
+    "dfsph_update_main",
+    "dfsph_predict_main",
+    "dfsph_density_main",
+    "dfsph_finish_main",
+    "pbf_predict_main",
+    "pbf_lambda_main",
+    "pbf_delta_main",
+    "pbf_apply_main",
+    "pbf_velocity_main",
+    "pbf_xsph_main",
+    "pbf_vorticity_main",
+    "pbf_finish_main",
+    "iisph_init_main",
+    "iisph_sum_main",
+    "iisph_pressure_main",
+    "iisph_update_main",
+    "iisph_finish_main",
+    "grid_finish_main",
+    "xsph_main",
+    "xsph_apply_main",
+    "normals_main",
+];
+
+// grid passes of the FLIP solver in flip.wgsl
+pub const FLIP_ENTRY_POINTS: [&str; 8] = [
+    "flip_clear_main",
+    "flip_p2g_main",
+    "flip_grid_main",
+    "flip_pressure_main",
+    "flip_check_main",
+    "flip_update_main",
+    "flip_project_main",
+    "flip_g2p_main",
+];
+
+// MPM passes in mpm.wgsl
+pub const MPM_ENTRY_POINTS: [&str; 4] = [
+    "mpm_clear_main",
+    "mpm_p2g_main",
+    "mpm_grid_main",
+    "mpm_g2p_main",
+];
+
+// adaptive dt in timestep.wgsl
+pub const TIMESTEP_ENTRY_POINTS: [&str; 2] = ["timestep_max_main", "timestep_dt_main"];
+
+// inserted once all of SOLVER_ENTRY_POINTS compiled
+#[derive(Resource)]
+pub struct SolverPipelines(pub HashMap<&'static str, ComputePipeline>);
+
+impl SolverPipelines {
+    fn dispatch(&self, pass: &mut ComputePass, bind_group: &BindGroup, entry: &str, groups: u32) {
+        pass.set_pipeline(&self.0[entry]);
+        pass.set_bind_group(0, bind_group, &[]);
+        pass.dispatch_workgroups(groups, 1, 1);
+    }
+}
+
+// FLIP_ENTRY_POINTS with FlipBindGroupLayout
+#[derive(Resource)]
+pub struct FlipPipelines(pub SolverPipelines);
+
+// MPM_ENTRY_POINTS with MpmBindGroupLayout
+#[derive(Resource)]
+pub struct MpmPipelines(pub SolverPipelines);
+
+// TIMESTEP_ENTRY_POINTS with TimestepBindGroupLayout
+#[derive(Resource)]
+pub struct TimestepPipelines(pub SolverPipelines);
+
+// the passes of one simulation step (grid build, then DensityNode), run
+// ExtractedSimClock::steps times Integrator::stages per frame by SimStepsNode
+#[derive(Debug, Hash, PartialEq, Eq, Clone, RenderSubGraph)]
+pub struct SimStepGraph;
+
+#[derive(Debug, Hash, PartialEq, Eq, Clone, RenderLabel)]
+pub struct SimStepsLabel;
+
+struct SimStepsNode;
+
 #[derive(Debug, Hash, PartialEq, Eq, Clone, RenderLabel)]
 pub struct DensityPassLabel;
 #[derive(Default)]
@@ -41,6 +147,10 @@ struct DensityNode;
 #[derive(Resource)]
 pub struct IntegratePipeline(pub ComputePipeline);
 
+// first stage of Leapfrog and Rk2, integrate_predict_main
+#[derive(Resource)]
+pub struct IntegratePredictPipeline(pub ComputePipeline);
+
 #[derive(Resource)]
 pub struct ClearCountsPipeline(pub CachedComputePipelineId);
 
@@ -108,6 +218,12 @@ pub struct ClearCursorPassLabel;
 #[derive(Default)]
 pub struct ClearCursorNode;
 
+#[derive(Resource)]
+pub struct GridBoundsPipeline(pub CachedComputePipelineId);
+#[derive(Debug, Hash, PartialEq, Eq, Clone, RenderLabel)]
+pub struct GridBoundsPassLabel;
+pub struct GridBoundsNode;
+
 #[derive(Resource)]
 pub struct ScatterPipeline(pub CachedComputePipelineId);
 #[derive(Debug, Hash, PartialEq, Eq, Clone, RenderLabel)]
@@ -214,27 +330,161 @@ pub fn prepare_integrate_pipeline(
     pipeline_cache: Res<PipelineCache>,
     layout: Res<ParticleBindGroupLayout>,
     assets: Res<AssetServer>,
-    mut cached: Local<Option<CachedComputePipelineId>>,
+    mut cached: Local<Option<[CachedComputePipelineId; 2]>>,
 ) {
     if cached.is_none() {
         let shader: Handle<Shader> = assets.load("shaders/sph_density.wgsl");
-        let desc = ComputePipelineDescriptor {
-            label: Some("sph_integrate_pipeline".into()),
+        let desc = |label: &'static str, entry: &'static str| ComputePipelineDescriptor {
+            label: Some(label.into()),
             layout: vec![layout.0.clone()],
             push_constant_ranges: Vec::<PushConstantRange>::new(),
-            shader,
+            shader: shader.clone(),
             shader_defs: Vec::<ShaderDefVal>::new(),
-            entry_point: Cow::from("integrate_main"),
+            entry_point: Cow::from(entry),
             zero_initialize_workgroup_memory: false,
         };
-        *cached = Some(pipeline_cache.queue_compute_pipeline(desc));
+        *cached = Some([
+            pipeline_cache.queue_compute_pipeline(desc("sph_integrate_pipeline", "integrate_main")),
+            pipeline_cache.queue_compute_pipeline(desc(
+                "sph_integrate_predict_pipeline",
+                "integrate_predict_main",
+            )),
+        ]);
         return; // wait for compilation
     }
 
-    if let Some(id) = *cached {
-        if let Some(pipeline) = pipeline_cache.get_compute_pipeline(id) {
+    if let Some([integrate, predict]) = *cached {
+        if let Some(pipeline) = pipeline_cache.get_compute_pipeline(integrate) {
             commands.insert_resource(IntegratePipeline(pipeline.clone()));
         }
+        if let Some(pipeline) = pipeline_cache.get_compute_pipeline(predict) {
+            commands.insert_resource(IntegratePredictPipeline(pipeline.clone()));
+        }
+    }
+}
+
+// queues every entry point of `shader` on the first call, then returns the
+// pipelines once all of them compiled
+fn compile_entry_points(
+    pipeline_cache: &PipelineCache,
+    layout: &BindGroupLayout,
+    shader: Handle<Shader>,
+    entries: &[&'static str],
+    cached: &mut Vec<(&'static str, CachedComputePipelineId)>,
+) -> Option<SolverPipelines> {
+    if cached.is_empty() {
+        for &entry in entries {
+            let desc = ComputePipelineDescriptor {
+                label: Some(format!("sph_{entry}_pipeline").into()),
+                layout: vec![layout.clone()],
+                push_constant_ranges: vec![],
+                shader: shader.clone(),
+                shader_defs: vec![],
+                entry_point: Cow::Borrowed(entry),
+                zero_initialize_workgroup_memory: false,
+            };
+            cached.push((entry, pipeline_cache.queue_compute_pipeline(desc)));
+        }
+        return None; // wait for compilation
+    }
+
+    cached
+        .iter()
+        .map(|(entry, id)| Some((*entry, pipeline_cache.get_compute_pipeline(*id)?.clone())))
+        .collect::<Option<HashMap<_, _>>>()
+        .map(SolverPipelines)
+}
+
+pub fn prepare_solver_pipelines(
+    mut commands: Commands,
+    pipeline_cache: Res<PipelineCache>,
+    layout: Res<ParticleBindGroupLayout>,
+    assets: Res<AssetServer>,
+    ready: Option<Res<SolverPipelines>>,
+    mut cached: Local<Vec<(&'static str, CachedComputePipelineId)>>,
+) {
+    if ready.is_some() {
+        return;
+    }
+    let shader = assets.load("shaders/sph_density.wgsl");
+    let entries = &SOLVER_ENTRY_POINTS;
+    if let Some(pipelines) =
+        compile_entry_points(&pipeline_cache, &layout.0, shader, entries, &mut cached)
+    {
+        info!("solver pipelines are READY");
+        commands.insert_resource(pipelines);
+    }
+}
+
+pub fn prepare_flip_pipelines(
+    mut commands: Commands,
+    pipeline_cache: Res<PipelineCache>,
+    layout: Option<Res<FlipBindGroupLayout>>,
+    assets: Res<AssetServer>,
+    ready: Option<Res<FlipPipelines>>,
+    mut cached: Local<Vec<(&'static str, CachedComputePipelineId)>>,
+) {
+    let Some(layout) = layout else {
+        return;
+    };
+    if ready.is_some() {
+        return;
+    }
+    let shader = assets.load("shaders/flip.wgsl");
+    let entries = &FLIP_ENTRY_POINTS;
+    if let Some(pipelines) =
+        compile_entry_points(&pipeline_cache, &layout.0, shader, entries, &mut cached)
+    {
+        info!("FLIP pipelines are READY");
+        commands.insert_resource(FlipPipelines(pipelines));
+    }
+}
+
+pub fn prepare_mpm_pipelines(
+    mut commands: Commands,
+    pipeline_cache: Res<PipelineCache>,
+    layout: Option<Res<MpmBindGroupLayout>>,
+    assets: Res<AssetServer>,
+    ready: Option<Res<MpmPipelines>>,
+    mut cached: Local<Vec<(&'static str, CachedComputePipelineId)>>,
+) {
+    let Some(layout) = layout else {
+        return;
+    };
+    if ready.is_some() {
+        return;
+    }
+    let shader = assets.load("shaders/mpm.wgsl");
+    let entries = &MPM_ENTRY_POINTS;
+    if let Some(pipelines) =
+        compile_entry_points(&pipeline_cache, &layout.0, shader, entries, &mut cached)
+    {
+        info!("MPM pipelines are READY");
+        commands.insert_resource(MpmPipelines(pipelines));
+    }
+}
+
+pub fn prepare_timestep_pipelines(
+    mut commands: Commands,
+    pipeline_cache: Res<PipelineCache>,
+    layout: Option<Res<TimestepBindGroupLayout>>,
+    assets: Res<AssetServer>,
+    ready: Option<Res<TimestepPipelines>>,
+    mut cached: Local<Vec<(&'static str, CachedComputePipelineId)>>,
+) {
+    let Some(layout) = layout else {
+        return;
+    };
+    if ready.is_some() {
+        return;
+    }
+    let shader = assets.load("shaders/timestep.wgsl");
+    let entries = &TIMESTEP_ENTRY_POINTS;
+    if let Some(pipelines) =
+        compile_entry_points(&pipeline_cache, &layout.0, shader, entries, &mut cached)
+    {
+        info!("timestep pipelines are READY");
+        commands.insert_resource(TimestepPipelines(pipelines));
     }
 }
 // dispatch compute shader
@@ -246,6 +496,18 @@ impl Node for DensityNode {
         render_context: &mut RenderContext,
         world: &World,
     ) -> Result<(), NodeRunError> {
+        // counted before any early return, SimStepsNode runs stages per step
+        let clock = world.get_resource::<ExtractedSimClock>();
+        let stage = clock.map_or(0, |clock| clock.next_stage());
+        let last_stage = clock.is_none_or(|clock| clock.is_last_stage(stage));
+        // the two stage integrators need both of their passes
+        let predict = world.get_resource::<IntegratePredictPipeline>();
+        let integrate_ready = predict.is_some() && world.contains_resource::<IntegratePipeline>();
+        if clock.is_some_and(|clock| clock.stages > 1) && !integrate_ready {
+            info!("Info Node: integrate pipelines not ready");
+            return Ok(());
+        }
+
         // return because the calculations doesn't exist yet
         let Some(pipeline) = world.get_resource::<DensityPipeline>() else {
             return Ok(());
@@ -272,20 +534,108 @@ impl Node for DensityNode {
         }
         // ========================
 
+        // iterative solvers need all of their passes, skip the step until then
+        let solver = world.get_resource::<ExtractedSolverBuffers>();
+        let solver_kind = solver.map(|s| s.solver).unwrap_or_default();
+        let solver_pipelines = world.get_resource::<SolverPipelines>();
+        let solver_pipelines = match (solver_kind, solver_pipelines) {
+            (Solver::Wcsph, _) => None,
+            (_, Some(pipelines)) => Some(pipelines),
+            (_, None) => {
+                info!("Info Node: solver pipelines not ready");
+                return Ok(());
+            }
+        };
+
+        // FLIP and MPM run on their own grid and bind group
+        let grid_solver = match solver_kind {
+            Solver::Flip(_) => {
+                let pipelines = world.get_resource::<FlipPipelines>();
+                let bind_group = world.get_resource::<FlipBindGroup>();
+                let buffers = world.get_resource::<FlipBuffers>();
+                let (Some(pipelines), Some(bind_group), Some(buffers)) =
+                    (pipelines, bind_group, buffers)
+                else {
+                    info!("Info Node: FLIP pipelines not ready");
+                    return Ok(());
+                };
+                Some((&pipelines.0, &bind_group.0, buffers.capacity.div_ceil(256)))
+            }
+            Solver::Mpm(_) => {
+                let pipelines = world.get_resource::<MpmPipelines>();
+                let bind_group = world.get_resource::<MpmBindGroup>();
+                let buffers = world.get_resource::<MpmBuffers>();
+                let (Some(pipelines), Some(bind_group), Some(buffers)) =
+                    (pipelines, bind_group, buffers)
+                else {
+                    info!("Info Node: MPM pipelines not ready");
+                    return Ok(());
+                };
+                Some((&pipelines.0, &bind_group.0, buffers.capacity.div_ceil(256)))
+            }
+            _ => None,
+        };
+
         // how many workgroups do we actually need?
         let n = extracted.num_particles.max(1);
         let workgroups = (n + 255) / 256; // for every 256 -> 1 workgroup
         info!("Info Node: DISPATCH, N = {}, groups = {}", n, workgroups);
 
+        // adaptive dt from the velocities and accelerations of the last step,
+        // read back to pace the next steps (SimClock); this step keeps the dt
+        // the App wrote into IntegrateParams. Once per step, in the first stage
+        let timestep = world
+            .get_resource::<ExtractedTimestep>()
+            .filter(|t| t.adaptive && stage == 0);
+        let timestep_pipelines = world.get_resource::<TimestepPipelines>();
+        let timestep_bind_group = world.get_resource::<TimestepBindGroup>();
+        let integ = world.get_resource::<ExtractedIntegrateParamsBuffer>();
+        if let (Some(_), Some(pipelines), Some(timestep_bg)) =
+            (timestep, timestep_pipelines, timestep_bind_group)
+        {
+            let mut pass = render_context
+                .command_encoder()
+                .begin_compute_pass(&ComputePassDescriptor::default());
+            let bg = &timestep_bg.0;
+            pipelines
+                .0
+                .dispatch(&mut pass, bg, "timestep_max_main", workgroups);
+            pipelines.0.dispatch(&mut pass, bg, "timestep_dt_main", 1);
+        }
+
+        // the forces pass sums the body forces with atomics
+        let colliders = world.get_resource::<ExtractedColliderBuffers>();
+        if let Some(colliders) = colliders {
+            render_context
+                .command_encoder()
+                .clear_buffer(&colliders.body_forces, 0, None);
+        }
+
         let mut pass = render_context
             .command_encoder()
             .begin_compute_pass(&ComputePassDescriptor::default());
 
-        pass.set_pipeline(&pipeline.0); // bind the compiled pipeline
-        pass.set_bind_group(0, &bind_group.0, &[]); // inject the particle buffer
-        pass.dispatch_workgroups(workgroups, 1, 1); // start the shader
+        // FLIP and MPM have no SPH sums
+        if grid_solver.is_none() {
+            pass.set_pipeline(&pipeline.0); // bind the compiled pipeline
+            pass.set_bind_group(0, &bind_group.0, &[]); // inject the particle buffer
+            pass.dispatch_workgroups(workgroups, 1, 1); // start the shader
+        }
+
+        // DFSPH: divergence solve before the non-pressure forces
+        if let (Solver::Dfsph(config), Some(solver)) = (solver_kind, solver_pipelines) {
+            let bg = &bind_group.0;
+            solver.dispatch(&mut pass, bg, "dfsph_alpha_main", workgroups);
+            for _ in 0..config.divergence.max_iterations {
+                solver.dispatch(&mut pass, bg, "dfsph_divergence_main", workgroups);
+                solver.dispatch(&mut pass, bg, "dfsph_divergence_check_main", 1);
+                solver.dispatch(&mut pass, bg, "dfsph_update_main", workgroups);
+            }
+        }
 
-        if let Some(pressure) = world.get_resource::<PressurePipeline>() {
+        if solver_pipelines.is_some() {
+            // pressure comes from the solver
+        } else if let Some(pressure) = world.get_resource::<PressurePipeline>() {
             pass.set_pipeline(&pressure.0);
             pass.set_bind_group(0, &bind_group.0, &[]);
             pass.dispatch_workgroups(workgroups, 1, 1);
@@ -294,7 +644,21 @@ impl Node for DensityNode {
             info!("Info Node: pressure SKIPPED (pipeline not working/not ready)");
         }
 
-        if let Some(forces) = world.get_resource::<ForcesPipeline>() {
+        // surface normals for the surface tension and the Surface colour mode
+        let normals = solver.is_some_and(|s| s.normals)
+            || world
+                .get_resource::<ExtractedParticleRenderSettings>()
+                .is_some_and(|r| r.0.color_mode == ParticleColorMode::Surface);
+        let normals_pipelines = world
+            .get_resource::<SolverPipelines>()
+            .filter(|_| normals && grid_solver.is_none());
+        if let Some(pipelines) = normals_pipelines {
+            pipelines.dispatch(&mut pass, &bind_group.0, "normals_main", workgroups);
+        }
+
+        if grid_solver.is_some() {
+            // velocities come from the grid
+        } else if let Some(forces) = world.get_resource::<ForcesPipeline>() {
             pass.set_pipeline(&forces.0);
             pass.set_bind_group(0, &bind_group.0, &[]);
             pass.dispatch_workgroups(workgroups, 1, 1);
@@ -303,39 +667,154 @@ impl Node for DensityNode {
             info!("Info Node: forces SKIPPED (pipeline not working/not ready)");
         }
 
-        if let Some(integrate) = world.get_resource::<IntegratePipeline>() {
-            pass.set_pipeline(&integrate.0);
+        // fixed numbers of rounds, the shaders skip them once converged
+        if let Some(solver) = solver_pipelines {
+            let bg = &bind_group.0;
+            match solver_kind {
+                Solver::Pcisph(config) => {
+                    solver.dispatch(&mut pass, bg, "pcisph_init_main", workgroups);
+                    for _ in 0..config.max_iterations {
+                        solver.dispatch(&mut pass, bg, "pcisph_predict_main", workgroups);
+                        solver.dispatch(&mut pass, bg, "pcisph_density_main", workgroups);
+                        solver.dispatch(&mut pass, bg, "pcisph_pressure_main", workgroups);
+                        solver.dispatch(&mut pass, bg, "solver_check_main", 1);
+                    }
+                    solver.dispatch(&mut pass, bg, "pcisph_finish_main", workgroups);
+                }
+                Solver::Dfsph(config) => {
+                    solver.dispatch(&mut pass, bg, "dfsph_predict_main", workgroups);
+                    for _ in 0..config.density.max_iterations {
+                        solver.dispatch(&mut pass, bg, "dfsph_density_main", workgroups);
+                        solver.dispatch(&mut pass, bg, "solver_check_main", 1);
+                        solver.dispatch(&mut pass, bg, "dfsph_update_main", workgroups);
+                    }
+                    solver.dispatch(&mut pass, bg, "dfsph_finish_main", workgroups);
+                }
+                Solver::Pbf(config) => {
+                    solver.dispatch(&mut pass, bg, "pbf_predict_main", workgroups);
+                    for _ in 0..config.iterations {
+                        solver.dispatch(&mut pass, bg, "pbf_lambda_main", workgroups);
+                        solver.dispatch(&mut pass, bg, "solver_check_main", 1);
+                        solver.dispatch(&mut pass, bg, "pbf_delta_main", workgroups);
+                        solver.dispatch(&mut pass, bg, "pbf_apply_main", workgroups);
+                    }
+                    solver.dispatch(&mut pass, bg, "pbf_velocity_main", workgroups);
+                    solver.dispatch(&mut pass, bg, "pbf_xsph_main", workgroups);
+                    solver.dispatch(&mut pass, bg, "pbf_vorticity_main", workgroups);
+                    solver.dispatch(&mut pass, bg, "pbf_finish_main", workgroups);
+                }
+                Solver::Iisph(config) => {
+                    solver.dispatch(&mut pass, bg, "iisph_init_main", workgroups);
+                    for _ in 0..config.density.max_iterations {
+                        solver.dispatch(&mut pass, bg, "iisph_sum_main", workgroups);
+                        solver.dispatch(&mut pass, bg, "iisph_pressure_main", workgroups);
+                        solver.dispatch(&mut pass, bg, "solver_check_main", 1);
+                        solver.dispatch(&mut pass, bg, "iisph_update_main", workgroups);
+                    }
+                    solver.dispatch(&mut pass, bg, "iisph_finish_main", workgroups);
+                }
+                Solver::Flip(config) => {
+                    if let Some((grid, grid_bg, node_groups)) = grid_solver {
+                        grid.dispatch(&mut pass, grid_bg, "flip_clear_main", node_groups);
+                        grid.dispatch(&mut pass, grid_bg, "flip_p2g_main", workgroups);
+                        grid.dispatch(&mut pass, grid_bg, "flip_grid_main", node_groups);
+                        for _ in 0..config.pressure.max_iterations {
+                            grid.dispatch(&mut pass, grid_bg, "flip_pressure_main", node_groups);
+                            grid.dispatch(&mut pass, grid_bg, "flip_check_main", 1);
+                            grid.dispatch(&mut pass, grid_bg, "flip_update_main", node_groups);
+                        }
+                        grid.dispatch(&mut pass, grid_bg, "flip_project_main", node_groups);
+                        grid.dispatch(&mut pass, grid_bg, "flip_g2p_main", workgroups);
+                    }
+                    solver.dispatch(&mut pass, bg, "grid_finish_main", workgroups);
+                }
+                Solver::Mpm(_) => {
+                    if let Some((grid, grid_bg, node_groups)) = grid_solver {
+                        grid.dispatch(&mut pass, grid_bg, "mpm_clear_main", node_groups);
+                        grid.dispatch(&mut pass, grid_bg, "mpm_p2g_main", workgroups);
+                        grid.dispatch(&mut pass, grid_bg, "mpm_grid_main", node_groups);
+                        grid.dispatch(&mut pass, grid_bg, "mpm_g2p_main", workgroups);
+                    }
+                    solver.dispatch(&mut pass, bg, "grid_finish_main", workgroups);
+                }
+                Solver::Wcsph => {}
+            }
+            info!("Info Node: DISPATCH solver {solver_kind:?}");
+        }
+
+        // XSPH once per step, on the velocities of the last stage
+        let xsph_pipelines = world
+            .get_resource::<SolverPipelines>()
+            .filter(|_| last_stage && solver.is_some_and(|s| s.xsph));
+        if let Some(pipelines) = xsph_pipelines {
+            let bg = &bind_group.0;
+            pipelines.dispatch(&mut pass, bg, "xsph_main", workgroups);
+            pipelines.dispatch(&mut pass, bg, "xsph_apply_main", workgroups);
+        }
+
+        let integrate = if last_stage {
+            world.get_resource::<IntegratePipeline>().map(|p| &p.0)
+        } else {
+            predict.map(|p| &p.0)
+        };
+        if let Some(integrate) = integrate {
+            pass.set_pipeline(integrate);
             pass.set_bind_group(0, &bind_group.0, &[]);
             pass.dispatch_workgroups(workgroups, 1, 1);
             info!("Info Node: DISPATCH integrate N = {n}, groups = {workgroups}");
         } else {
             info!("Info Node: integrate SKIPPED (pipeline not ready)");
         }
+        drop(pass);
+
+        // report the body forces back to the App world, with the dt this step
+        // integrated with (IntegrateParams::dt, offset 0) after them
+        let readback = world
+            .get_resource::<ExtractedBodyForceReadback>()
+            .filter(|r| r.copy);
+        if let (Some(colliders), Some(readback), Some(integ)) = (colliders, readback, integ) {
+            let forces_size = readback.slot.forces_size();
+            let encoder = render_context.command_encoder();
+            encoder.copy_buffer_to_buffer(
+                &colliders.body_forces,
+                0,
+                &readback.slot.buffer,
+                0,
+                forces_size,
+            );
+            encoder.copy_buffer_to_buffer(
+                &integ.buffer,
+                0,
+                &readback.slot.buffer,
+                forces_size,
+                std::mem::size_of::<f32>() as u64,
+            );
+        }
 
-        drop(pass); // pass must end before encoding copies
-        let Some(readback) = world.get_resource::<ExtractedReadbackBuffer>() else {
-            return Ok(());
-        };
-
-        let allow_copy = world
-            .get_resource::<ExtractedAllowCopy>()
-            .map(|f| f.0)
-            .unwrap_or(true);
-
-        if allow_copy {
+        let readback = world
+            .get_resource::<ExtractedSolverStatsReadback>()
+            .filter(|r| r.copy);
+        if let (Some(solver), Some(readback)) = (solver, readback) {
             render_context.command_encoder().copy_buffer_to_buffer(
-                &extracted.buffer,
+                &solver.stats,
                 0,
-                &readback.buffer,
+                &readback.slot.buffer,
                 0,
-                readback.size_bytes,
+                readback.slot.buffer.size(),
             );
-            info!(
-                "Info Node: COPY particles -> readback ({} bytes)",
-                readback.size_bytes
+        }
+
+        let readback = world
+            .get_resource::<ExtractedTimestepReadback>()
+            .filter(|r| r.copy);
+        if let (Some(timestep), Some(readback)) = (timestep, readback) {
+            render_context.command_encoder().copy_buffer_to_buffer(
+                &timestep.state,
+                0,
+                &readback.slot.buffer,
+                0,
+                readback.slot.buffer.size(),
             );
-        } else {
-            info!("Info Node: copy is SKIPPED");
         }
 
         Ok(())
@@ -343,9 +822,41 @@ impl Node for DensityNode {
 }
 
 pub fn add_density_node_to_graph(render_app: &mut bevy::app::SubApp) {
-    let mut graph = render_app.world_mut().resource_mut::<RenderGraph>();
+    let mut graph = sim_step_graph(render_app);
     graph.add_node(DensityPassLabel, DensityNode::default());
-    graph.add_node_edge(DensityPassLabel, CameraDriverLabel);
+}
+
+// every step runs the whole sub graph in the same command encoder, so each
+// one sees the grid and particles the previous one wrote
+impl Node for SimStepsNode {
+    fn run(
+        &self,
+        graph: &mut RenderGraphContext,
+        _render_context: &mut RenderContext,
+        world: &World,
+    ) -> Result<(), NodeRunError> {
+        let runs = world
+            .get_resource::<ExtractedSimClock>()
+            .map_or(0, |clock| clock.steps * clock.stages);
+        for _ in 0..runs {
+            graph.run_sub_graph(SimStepGraph, vec![], None)?;
+        }
+        Ok(())
+    }
+}
+
+pub fn add_sim_steps_node_to_graph(render_app: &mut bevy::app::SubApp) {
+    let mut graph = render_app.world_mut().resource_mut::<RenderGraph>();
+    graph.add_sub_graph(SimStepGraph, RenderGraph::default());
+    graph.add_node(SimStepsLabel, SimStepsNode);
+    graph.add_node_edge(SimStepsLabel, CameraDriverLabel);
+}
+
+pub fn sim_step_graph(render_app: &mut bevy::app::SubApp) -> Mut<'_, RenderGraph> {
+    render_app
+        .world_mut()
+        .resource_mut::<RenderGraph>()
+        .map_unchanged(|graph| graph.sub_graph_mut(SimStepGraph))
 }
 
 pub fn prepare_clear_counts_pipeline(
@@ -490,7 +1001,7 @@ impl Node for ClearCountsNode {
 }
 
 pub fn add_clear_counts_node_to_graph(render_app: &mut bevy::app::SubApp) {
-    let mut graph = render_app.world_mut().resource_mut::<RenderGraph>();
+    let mut graph = sim_step_graph(render_app);
     graph.add_node(ClearCountsLabel, ClearCountsNode::default());
 
     let _ = graph.add_node_edge(ClearCountsLabel, DensityPassLabel);
@@ -554,7 +1065,7 @@ impl Node for HistogramNode {
 }
 
 pub fn add_histogram_node_to_graph(render_app: &mut bevy::app::SubApp) {
-    let mut graph = render_app.world_mut().resource_mut::<RenderGraph>();
+    let mut graph = sim_step_graph(render_app);
     graph.add_node(HistogramPassLabel, HistogramNode::default());
 
     // Run order: ClearCounts -> Histogram -> Density
@@ -657,7 +1168,7 @@ impl Node for PrefixSumNaiveNode {
     }
 }
 pub fn _add_prefix_sum_naive_node_to_graph(render_app: &mut bevy::app::SubApp) {
-    let mut graph = render_app.world_mut().resource_mut::<RenderGraph>();
+    let mut graph = sim_step_graph(render_app);
     graph.add_node(PrefixSumNaivePassLabel, PrefixSumNaiveNode::default());
 
     // Order: ClearCounts -> Histogram -> PrefixSumNaive -> Density
@@ -754,7 +1265,7 @@ impl Node for BlockScanNode {
 }
 
 pub fn add_block_scan_node_to_graph(render_app: &mut bevy::app::SubApp) {
-    let mut graph = render_app.world_mut().resource_mut::<RenderGraph>();
+    let mut graph = sim_step_graph(render_app);
     graph.add_node(BlockScanPassLabel, BlockScanNode::default());
 
     // Order: ClearCounts -> Histogram -> BlockScan -> PrefixSumNaive (or Density later)
@@ -849,7 +1360,7 @@ impl Node for BlockSumsScanNode {
 }
 
 pub fn add_block_sums_scan_node_to_graph(render_app: &mut bevy::app::SubApp) {
-    let mut graph = render_app.world_mut().resource_mut::<RenderGraph>();
+    let mut graph = sim_step_graph(render_app);
     graph.add_node(BlockSumsScanPassLabel, BlockSumsScanNode::default());
 
     // Order: ClearCounts -> Histogram -> BlockScan -> BlockSumsScan -> Density
@@ -947,7 +1458,7 @@ impl Node for AddBackNode {
 }
 
 pub fn add_add_back_node_to_graph(render_app: &mut bevy::app::SubApp) {
-    let mut graph = render_app.world_mut().resource_mut::<RenderGraph>();
+    let mut graph = sim_step_graph(render_app);
     graph.add_node(AddBackPassLabel, AddBackNode::default());
 
     // Order: ClearCounts -> Histogram -> BlockScan -> BlockSumsScan -> AddBack -> Density
@@ -1032,7 +1543,7 @@ impl Node for WriteSentinelNode {
     }
 }
 pub fn add_write_sentinel_node_to_graph(render_app: &mut bevy::app::SubApp) {
-    let mut graph = render_app.world_mut().resource_mut::<RenderGraph>();
+    let mut graph = sim_step_graph(render_app);
     graph.add_node(WriteSentinelPassLabel, WriteSentinelNode::default());
 
     let _ = graph.add_node_edge(AddBackPassLabel, WriteSentinelPassLabel);
@@ -1080,7 +1591,7 @@ impl Node for ClearCursorNode {
     }
 }
 pub fn add_clear_cursor_node_to_graph(render_app: &mut bevy::app::SubApp) {
-    let mut graph = render_app.world_mut().resource_mut::<RenderGraph>();
+    let mut graph = sim_step_graph(render_app);
     graph.add_node(ClearCursorPassLabel, ClearCursorNode::default());
     // after AddBack & WriteSentinel, before Scatter
     let _ = graph.add_node_edge(AddBackPassLabel, ClearCursorPassLabel);
@@ -1160,7 +1671,7 @@ impl Node for ScatterNode {
     }
 }
 pub fn add_scatter_node_to_graph(render_app: &mut bevy::app::SubApp) {
-    let mut graph = render_app.world_mut().resource_mut::<RenderGraph>();
+    let mut graph = sim_step_graph(render_app);
     graph.add_node(ScatterPassLabel, ScatterNode::default());
     // Order: ... AddBack -> WriteSentinel -> ClearCursor -> Scatter -> Density
     let _ = graph.add_node_edge(AddBackPassLabel, ScatterPassLabel);
@@ -1168,3 +1679,109 @@ pub fn add_scatter_node_to_graph(render_app: &mut bevy::app::SubApp) {
     let _ = graph.add_node_edge(ClearCursorPassLabel, ScatterPassLabel);
     let _ = graph.add_node_edge(ScatterPassLabel, DensityPassLabel);
 }
+
+pub fn prepare_grid_bounds_pipeline(
+    mut commands: Commands,
+    cache: Res<PipelineCache>,
+    layout: Option<Res<GridBoundsBindGroupLayout>>,
+    assets: Res<AssetServer>,
+    mut cached: Local<Option<CachedComputePipelineId>>,
+) {
+    let Some(layout) = layout else {
+        return;
+    };
+    if cached.is_some() {
+        return;
+    }
+
+    let shader: Handle<Shader> = assets.load("shaders/grid_build.wgsl");
+    let desc = ComputePipelineDescriptor {
+        label: Some("grid_bounds_pipeline".into()),
+        layout: vec![layout.0.clone()], // particles(ro), params out(rw), gb(uniform), stats(rw)
+        push_constant_ranges: vec![],
+        shader_defs: vec![],
+        entry_point: Cow::Borrowed("bounds"),
+        shader,
+        zero_initialize_workgroup_memory: true,
+    };
+    let id = cache.queue_compute_pipeline(desc);
+    *cached = Some(id);
+    commands.insert_resource(GridBoundsPipeline(id));
+}
+
+impl Node for GridBoundsNode {
+    fn run(
+        &self,
+        _graph: &mut RenderGraphContext,
+        render_context: &mut RenderContext,
+        world: &World,
+    ) -> Result<(), NodeRunError> {
+        let Some(pip_res) = world.get_resource::<GridBoundsPipeline>() else {
+            info!("Info Node: grid_bounds SKIPPED (pipeline not ready)");
+            return Ok(());
+        };
+        let Some(bg) = world.get_resource::<GridBoundsBindGroup>() else {
+            info!("Info Node: grid_bounds SKIPPED (no bind group)");
+            return Ok(());
+        };
+        let Some(bounds) = world.get_resource::<GridBoundsBuffers>() else {
+            info!("Info Node: grid_bounds SKIPPED (no buffers)");
+            return Ok(());
+        };
+        let Some(grid) = world.get_resource::<ExtractedGrid>() else {
+            info!("Info Node: grid_bounds SKIPPED (no grid params)");
+            return Ok(());
+        };
+
+        let cache = world.resource::<PipelineCache>();
+        let Some(pipeline) = cache.get_compute_pipeline(pip_res.0) else {
+            info!("Info Node: grid_bounds SKIPPED (pipeline compiling)");
+            return Ok(());
+        };
+
+        // Single workgroup reduces over all particles
+        {
+            let mut pass =
+                render_context
+                    .command_encoder()
+                    .begin_compute_pass(&ComputePassDescriptor {
+                        label: Some("GridBoundsPass"),
+                        timestamp_writes: None,
+                    });
+            pass.set_pipeline(pipeline);
+            pass.set_bind_group(0, &bg.0, &[]);
+            pass.dispatch_workgroups(1, 1, 1);
+        }
+
+        // storage -> uniform, read by histogram, scatter and the SPH passes
+        render_context.command_encoder().copy_buffer_to_buffer(
+            &bounds.params_out,
+            0,
+            &grid.params_buf,
+            0,
+            std::mem::size_of::<GridParams>() as u64,
+        );
+
+        let readback = world.get_resource::<ExtractedGridStatsReadback>();
+        if let Some(readback) = readback.filter(|r| r.copy) {
+            render_context.command_encoder().copy_buffer_to_buffer(
+                &bounds.stats,
+                0,
+                &readback.slot.buffer,
+                0,
+                std::mem::size_of::<GridBoundsStats>() as u64,
+            );
+        }
+
+        Ok(())
+    }
+}
+
+pub fn add_grid_bounds_node_to_graph(render_app: &mut bevy::app::SubApp) {
+    let mut graph = sim_step_graph(render_app);
+    graph.add_node(GridBoundsPassLabel, GridBoundsNode);
+
+    graph.add_node_edge(GridBoundsPassLabel, HistogramPassLabel);
+    graph.add_node_edge(GridBoundsPassLabel, ScatterPassLabel);
+    graph.add_node_edge(GridBoundsPassLabel, DensityPassLabel);
+}
@@ -0,0 +1,852 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU8, AtomicU64, Ordering};
+
+use bevy::prelude::*;
+use bevy::render::graph::CameraDriverLabel;
+use bevy::render::render_graph::{
+    Node, NodeRunError, RenderGraph, RenderGraphContext, RenderLabel,
+};
+use bevy::render::render_resource::{Buffer, BufferDescriptor, BufferUsages, Maintain, MapMode};
+use bevy::render::renderer::{RenderContext, RenderDevice};
+use bevy::render::{Extract, ExtractSchedule, Render, RenderApp, RenderSet};
+
+use crate::cpu::sph2d::{SPHState, SolverStats};
+use crate::cpu::timestep::SimClock;
+use crate::gpu::buffers::{ExtractedParticleBuffer, IntegrateConfig, ParticleBuffers};
+use crate::gpu::collider::ColliderBuffers;
+use crate::gpu::ffi::{
+    BODY_FORCE_SCALE, GPUBodyForce, GPUParticle, GPUSolverStats, GPUTimestep, GridBoundsStats,
+};
+use crate::gpu::pipeline::SimStepsLabel;
+
+// 3 buffers: one being copied, one being mapped, one being read
+pub const READBACK_SLOTS: usize = 3;
+
+// slot life cycle: FREE -> QUEUED (app) -> COPIED (extract) -> MAPPING (render cleanup)
+// -> MAPPED / FAILED (map callback) -> FREE (app, after reading)
+const SLOT_FREE: u8 = 0;
+const SLOT_QUEUED: u8 = 1;
+const SLOT_COPIED: u8 = 2;
+const SLOT_MAPPING: u8 = 3;
+const SLOT_MAPPED: u8 = 4;
+const SLOT_FAILED: u8 = 5;
+
+// ==================== resources ======================================
+
+pub struct ReadbackSlot {
+    pub buffer: Buffer,
+    state: AtomicU8,
+    step: AtomicU64, // SimClock::step at the time the copy was requested
+}
+
+// staging ring shared by the App and the Render world
+#[derive(Resource)]
+pub struct ParticleReadback {
+    slots: Arc<Vec<ReadbackSlot>>,
+    size_bytes: u64,
+    pub continuous: bool, // request a copy every frame a slot is free
+    requested: bool,      // one-shot request
+}
+
+// latest finished GPU snapshot
+#[derive(Resource, Default)]
+pub struct ParticleSnapshot {
+    pub step: u64,
+    pub particles: Vec<GPUParticle>,
+}
+
+#[derive(Event, Clone, Copy, Debug)]
+pub struct ParticleSnapshotReady {
+    pub step: u64,
+}
+
+#[derive(Resource, Clone)]
+pub struct ExtractedParticleReadback {
+    slots: Arc<Vec<ReadbackSlot>>,
+    size_bytes: u64,
+    copy_slot: Option<usize>, // slot to copy into this frame
+}
+
+// single staging buffer for the grid stats; 16 bytes, so no ring needed
+pub struct GridStatsSlot {
+    pub buffer: Buffer,
+    state: AtomicU8,
+}
+
+#[derive(Resource)]
+pub struct GridStatsReadback {
+    slot: Arc<GridStatsSlot>,
+}
+
+// last grid stats the GPU reported (a few frames old)
+#[derive(Resource, Default, Clone, Copy, Debug)]
+pub struct GridBoundsReport {
+    pub required_cells: u32,
+    pub clamped: bool,
+}
+
+#[derive(Resource, Clone)]
+pub struct ExtractedGridStatsReadback {
+    pub slot: Arc<GridStatsSlot>,
+    pub copy: bool, // the bounds node copies the stats this frame
+}
+
+// sized for exactly `num_bodies`, then the dt of their step; replaced when
+// the body count changes
+pub struct BodyForceSlot {
+    pub buffer: Buffer,
+    pub num_bodies: u32,
+    state: AtomicU8,
+}
+
+impl BodyForceSlot {
+    // bytes of the forces, the dt follows them
+    pub fn forces_size(&self) -> u64 {
+        self.num_bodies.max(1) as u64 * std::mem::size_of::<GPUBodyForce>() as u64
+    }
+}
+
+#[derive(Resource)]
+pub struct BodyForceReadback {
+    slot: Arc<BodyForceSlot>,
+}
+
+// last fluid force and torque on every FluidBody (a few frames old)
+#[derive(Resource, Default, Clone, Debug)]
+pub struct BodyForceReport {
+    pub forces: Vec<(glam::Vec2, f32)>,
+    pub dt: f32, // of the step the forces come from, 0 before the first report
+}
+
+#[derive(Resource, Clone)]
+pub struct ExtractedBodyForceReadback {
+    pub slot: Arc<BodyForceSlot>,
+    pub copy: bool, // the density node copies the forces this frame
+}
+
+// GPUSolverStats of the last step, fixed size like the grid stats
+pub struct SolverStatsSlot {
+    pub buffer: Buffer,
+    state: AtomicU8,
+}
+
+#[derive(Resource)]
+pub struct SolverStatsReadback {
+    slot: Arc<SolverStatsSlot>,
+}
+
+// iterations and density error of the GPU pressure solver (a few frames old)
+#[derive(Resource, Default, Clone, Copy, Debug)]
+pub struct SolverStatsReport {
+    pub stats: SolverStats,
+}
+
+#[derive(Resource, Clone)]
+pub struct ExtractedSolverStatsReadback {
+    pub slot: Arc<SolverStatsSlot>,
+    pub copy: bool, // the density node copies the stats this frame
+}
+
+// GPUTimestep of the last step, fixed size like the grid stats
+pub struct TimestepSlot {
+    pub buffer: Buffer,
+    state: AtomicU8,
+}
+
+#[derive(Resource)]
+pub struct TimestepReadback {
+    slot: Arc<TimestepSlot>,
+}
+
+// dt the GPU picked and the maxima it came from (a few frames old); all zero
+// while IntegrateConfig::adaptive is off
+#[derive(Resource, Default, Clone, Copy, Debug)]
+pub struct TimestepReport {
+    pub dt: f32,
+    pub max_speed: f32,
+    pub max_accel: f32,
+}
+
+#[derive(Resource, Clone)]
+pub struct ExtractedTimestepReadback {
+    pub slot: Arc<TimestepSlot>,
+    pub copy: bool, // the density node copies the timestep this frame
+}
+
+#[derive(Debug, Hash, PartialEq, Eq, Clone, RenderLabel)]
+pub struct ReadbackPassLabel;
+
+#[derive(Default)]
+pub struct ReadbackNode;
+
+// =====================================================================
+
+// ========================== systems ==================================
+
+fn init_particle_readback(
+    mut commands: Commands,
+    render_device: Res<RenderDevice>,
+    particle_buffers: Option<Res<ParticleBuffers>>,
+) {
+    let Some(particle_buffers) = particle_buffers else {
+        return;
+    };
+    let size_bytes =
+        (particle_buffers.num_particles as u64) * (std::mem::size_of::<GPUParticle>() as u64);
+
+    let slots = (0..READBACK_SLOTS)
+        .map(|_| ReadbackSlot {
+            buffer: render_device.create_buffer(&BufferDescriptor {
+                label: Some("readback_slot"),
+                size: size_bytes.max(4),
+                usage: BufferUsages::COPY_DST | BufferUsages::MAP_READ,
+                mapped_at_creation: false,
+            }),
+            state: AtomicU8::new(SLOT_FREE),
+            step: AtomicU64::new(0),
+        })
+        .collect();
+
+    commands.insert_resource(ParticleReadback {
+        slots: Arc::new(slots),
+        size_bytes,
+        continuous: false,
+        requested: false,
+    });
+}
+
+// read every slot whose map finished; never blocks
+pub fn poll_particle_readback(
+    render_device: Res<RenderDevice>,
+    readback: Option<Res<ParticleReadback>>,
+    mut snapshot: ResMut<ParticleSnapshot>,
+    mut ready: EventWriter<ParticleSnapshotReady>,
+) {
+    let Some(readback) = readback else {
+        return;
+    };
+    render_device.poll(Maintain::Poll);
+
+    for slot in readback.slots.iter() {
+        match slot.state.load(Ordering::Acquire) {
+            SLOT_MAPPED => {
+                let step = slot.step.load(Ordering::Acquire);
+                // keep only the newest one if two finish in the same frame
+                if snapshot.particles.is_empty() || step >= snapshot.step {
+                    let data = slot.buffer.slice(..).get_mapped_range();
+                    snapshot.particles.clear();
+                    snapshot
+                        .particles
+                        .extend_from_slice(bytemuck::cast_slice(&data));
+                    snapshot.step = step;
+                    drop(data);
+                    ready.write(ParticleSnapshotReady { step });
+                }
+                slot.buffer.unmap();
+                slot.state.store(SLOT_FREE, Ordering::Release);
+            }
+            SLOT_FAILED => {
+                error!("particle readback: buffer map failed");
+                slot.buffer.unmap();
+                slot.state.store(SLOT_FREE, Ordering::Release);
+            }
+            _ => {}
+        }
+    }
+}
+
+// hand one free slot to the render world (at most one copy per frame)
+pub fn request_particle_readback(
+    readback: Option<ResMut<ParticleReadback>>,
+    clock: Option<Res<SimClock>>,
+) {
+    let Some(mut readback) = readback else {
+        return;
+    };
+    if !readback.continuous && !readback.requested {
+        return;
+    }
+    // a copy is already waiting for extraction
+    if readback
+        .slots
+        .iter()
+        .any(|s| s.state.load(Ordering::Acquire) == SLOT_QUEUED)
+    {
+        return;
+    }
+
+    let step = clock.map(|c| c.step).unwrap_or(0);
+    for slot in readback.slots.iter() {
+        if slot
+            .state
+            .compare_exchange(SLOT_FREE, SLOT_QUEUED, Ordering::AcqRel, Ordering::Acquire)
+            .is_ok()
+        {
+            slot.step.store(step, Ordering::Release);
+            readback.requested = false;
+            return;
+        }
+    }
+    // all slots busy -> try again next frame
+}
+
+fn extract_particle_readback(
+    mut commands: Commands,
+    readback: Extract<Option<Res<ParticleReadback>>>,
+) {
+    let Some(readback) = readback.as_ref() else {
+        return;
+    };
+
+    let copy_slot = readback.slots.iter().position(|s| {
+        s.state
+            .compare_exchange(
+                SLOT_QUEUED,
+                SLOT_COPIED,
+                Ordering::AcqRel,
+                Ordering::Acquire,
+            )
+            .is_ok()
+    });
+
+    commands.insert_resource(ExtractedParticleReadback {
+        slots: readback.slots.clone(),
+        size_bytes: readback.size_bytes,
+        copy_slot,
+    });
+}
+
+// runs after the frame was submitted, so the copy is in flight before we map
+fn map_particle_readback(readback: Option<Res<ExtractedParticleReadback>>) {
+    let Some(readback) = readback else {
+        return;
+    };
+    let Some(i) = readback.copy_slot else {
+        return;
+    };
+    let slot = &readback.slots[i];
+    if slot
+        .state
+        .compare_exchange(
+            SLOT_COPIED,
+            SLOT_MAPPING,
+            Ordering::AcqRel,
+            Ordering::Acquire,
+        )
+        .is_err()
+    {
+        return;
+    }
+
+    let slots = readback.slots.clone();
+    slot.buffer.slice(..).map_async(MapMode::Read, move |r| {
+        let state = if r.is_ok() { SLOT_MAPPED } else { SLOT_FAILED };
+        slots[i].state.store(state, Ordering::Release);
+    });
+}
+
+pub fn init_grid_stats_readback(mut commands: Commands, render_device: Res<RenderDevice>) {
+    let buffer = render_device.create_buffer(&BufferDescriptor {
+        label: Some("grid_stats_readback"),
+        size: std::mem::size_of::<GridBoundsStats>() as u64,
+        usage: BufferUsages::COPY_DST | BufferUsages::MAP_READ,
+        mapped_at_creation: false,
+    });
+    commands.insert_resource(GridStatsReadback {
+        slot: Arc::new(GridStatsSlot {
+            buffer,
+            state: AtomicU8::new(SLOT_FREE),
+        }),
+    });
+}
+
+pub fn poll_grid_stats_readback(
+    render_device: Res<RenderDevice>,
+    readback: Option<Res<GridStatsReadback>>,
+    mut report: ResMut<GridBoundsReport>,
+) {
+    let Some(readback) = readback else {
+        return;
+    };
+    render_device.poll(Maintain::Poll);
+
+    let slot = &readback.slot;
+    match slot.state.load(Ordering::Acquire) {
+        SLOT_MAPPED => {
+            let data = slot.buffer.slice(..).get_mapped_range();
+            let stats: GridBoundsStats = bytemuck::pod_read_unaligned(&data);
+            drop(data);
+            slot.buffer.unmap();
+            slot.state.store(SLOT_FREE, Ordering::Release);
+
+            report.required_cells = stats.required_cells;
+            report.clamped = stats.clamped != 0;
+        }
+        SLOT_FAILED => {
+            error!("grid stats readback: buffer map failed");
+            slot.buffer.unmap();
+            slot.state.store(SLOT_FREE, Ordering::Release);
+        }
+        _ => {}
+    }
+}
+
+// the stats are copied whenever the previous copy has been read
+pub fn extract_grid_stats_readback(
+    mut commands: Commands,
+    readback: Extract<Option<Res<GridStatsReadback>>>,
+) {
+    let Some(readback) = readback.as_ref() else {
+        return;
+    };
+    let copy = readback
+        .slot
+        .state
+        .compare_exchange(SLOT_FREE, SLOT_COPIED, Ordering::AcqRel, Ordering::Acquire)
+        .is_ok();
+
+    commands.insert_resource(ExtractedGridStatsReadback {
+        slot: readback.slot.clone(),
+        copy,
+    });
+}
+
+pub fn map_grid_stats_readback(readback: Option<Res<ExtractedGridStatsReadback>>) {
+    let Some(readback) = readback else {
+        return;
+    };
+    if !readback.copy {
+        return;
+    }
+    let slot = &readback.slot;
+    if slot
+        .state
+        .compare_exchange(
+            SLOT_COPIED,
+            SLOT_MAPPING,
+            Ordering::AcqRel,
+            Ordering::Acquire,
+        )
+        .is_err()
+    {
+        return;
+    }
+
+    let shared = readback.slot.clone();
+    slot.buffer.slice(..).map_async(MapMode::Read, move |r| {
+        let state = if r.is_ok() { SLOT_MAPPED } else { SLOT_FAILED };
+        shared.state.store(state, Ordering::Release);
+    });
+}
+
+fn body_force_slot(render_device: &RenderDevice, num_bodies: u32) -> Arc<BodyForceSlot> {
+    let forces = num_bodies.max(1) as u64 * std::mem::size_of::<GPUBodyForce>() as u64;
+    let size = forces + std::mem::size_of::<f32>() as u64; // and the dt
+    Arc::new(BodyForceSlot {
+        buffer: render_device.create_buffer(&BufferDescriptor {
+            label: Some("body_force_readback"),
+            size,
+            usage: BufferUsages::COPY_DST | BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        }),
+        num_bodies,
+        state: AtomicU8::new(SLOT_FREE),
+    })
+}
+
+pub fn init_body_force_readback(mut commands: Commands, render_device: Res<RenderDevice>) {
+    commands.insert_resource(BodyForceReadback {
+        slot: body_force_slot(&render_device, 0),
+    });
+}
+
+pub fn poll_body_force_readback(
+    render_device: Res<RenderDevice>,
+    readback: Option<ResMut<BodyForceReadback>>,
+    buffers: Option<Res<ColliderBuffers>>,
+    mut report: ResMut<BodyForceReport>,
+) {
+    let (Some(mut readback), Some(buffers)) = (readback, buffers) else {
+        return;
+    };
+    render_device.poll(Maintain::Poll);
+
+    let slot = &readback.slot;
+    match slot.state.load(Ordering::Acquire) {
+        SLOT_MAPPED => {
+            let data = slot.buffer.slice(..).get_mapped_range();
+            let (forces, dt) = data.split_at(slot.forces_size() as usize);
+            let forces: &[GPUBodyForce] = bytemuck::cast_slice(forces);
+            report.dt = bytemuck::pod_read_unaligned(&dt[..std::mem::size_of::<f32>()]);
+            report.forces = forces[..slot.num_bodies as usize]
+                .iter()
+                .map(|f| {
+                    let force = glam::IVec2::from_array(f.force).as_vec2() / BODY_FORCE_SCALE;
+                    (force, f.torque as f32 / BODY_FORCE_SCALE)
+                })
+                .collect();
+            drop(data);
+            slot.buffer.unmap();
+            slot.state.store(SLOT_FREE, Ordering::Release);
+        }
+        SLOT_FAILED => {
+            error!("body force readback: buffer map failed");
+            slot.buffer.unmap();
+            slot.state.store(SLOT_FREE, Ordering::Release);
+        }
+        _ => {}
+    }
+
+    // resize between copies only
+    let resize =
+        slot.num_bodies != buffers.num_bodies && slot.state.load(Ordering::Acquire) == SLOT_FREE;
+    if resize {
+        readback.slot = body_force_slot(&render_device, buffers.num_bodies);
+        report.forces.clear();
+    }
+}
+
+pub fn extract_body_force_readback(
+    mut commands: Commands,
+    readback: Extract<Option<Res<BodyForceReadback>>>,
+    buffers: Extract<Option<Res<ColliderBuffers>>>,
+) {
+    let (Some(readback), Some(buffers)) = (readback.as_ref(), buffers.as_ref()) else {
+        return;
+    };
+    // nothing to report, or the slot does not match the bodies (yet)
+    let copy = buffers.num_bodies > 0
+        && readback.slot.num_bodies == buffers.num_bodies
+        && readback
+            .slot
+            .state
+            .compare_exchange(SLOT_FREE, SLOT_COPIED, Ordering::AcqRel, Ordering::Acquire)
+            .is_ok();
+
+    commands.insert_resource(ExtractedBodyForceReadback {
+        slot: readback.slot.clone(),
+        copy,
+    });
+}
+
+pub fn map_body_force_readback(readback: Option<Res<ExtractedBodyForceReadback>>) {
+    let Some(readback) = readback else {
+        return;
+    };
+    if !readback.copy {
+        return;
+    }
+    let slot = &readback.slot;
+    if slot
+        .state
+        .compare_exchange(
+            SLOT_COPIED,
+            SLOT_MAPPING,
+            Ordering::AcqRel,
+            Ordering::Acquire,
+        )
+        .is_err()
+    {
+        return;
+    }
+
+    let shared = readback.slot.clone();
+    slot.buffer.slice(..).map_async(MapMode::Read, move |r| {
+        let state = if r.is_ok() { SLOT_MAPPED } else { SLOT_FAILED };
+        shared.state.store(state, Ordering::Release);
+    });
+}
+
+pub fn init_solver_stats_readback(mut commands: Commands, render_device: Res<RenderDevice>) {
+    let buffer = render_device.create_buffer(&BufferDescriptor {
+        label: Some("solver_stats_readback"),
+        size: std::mem::size_of::<GPUSolverStats>() as u64,
+        usage: BufferUsages::COPY_DST | BufferUsages::MAP_READ,
+        mapped_at_creation: false,
+    });
+    commands.insert_resource(SolverStatsReadback {
+        slot: Arc::new(SolverStatsSlot {
+            buffer,
+            state: AtomicU8::new(SLOT_FREE),
+        }),
+    });
+}
+
+pub fn poll_solver_stats_readback(
+    render_device: Res<RenderDevice>,
+    readback: Option<Res<SolverStatsReadback>>,
+    sph: Res<SPHState>,
+    mut report: ResMut<SolverStatsReport>,
+) {
+    let Some(readback) = readback else {
+        return;
+    };
+    render_device.poll(Maintain::Poll);
+
+    let slot = &readback.slot;
+    match slot.state.load(Ordering::Acquire) {
+        SLOT_MAPPED => {
+            let data = slot.buffer.slice(..).get_mapped_range();
+            let stats: GPUSolverStats = bytemuck::pod_read_unaligned(&data);
+            drop(data);
+            slot.buffer.unmap();
+            slot.state.store(SLOT_FREE, Ordering::Release);
+
+            report.stats = SolverStats {
+                iterations: stats.iterations,
+                density_error: stats.density_error,
+                divergence_iterations: stats.divergence_iterations,
+                divergence_error: stats.divergence_error,
+            };
+        }
+        SLOT_FAILED => {
+            error!("solver stats readback: buffer map failed");
+            slot.buffer.unmap();
+            slot.state.store(SLOT_FREE, Ordering::Release);
+        }
+        _ => {}
+    }
+
+    // nothing is copied for the explicit solver
+    if sph.solver.iterative().is_none() {
+        report.stats = SolverStats::default();
+    }
+}
+
+// copied whenever an iterative solver runs and the previous copy has been read
+pub fn extract_solver_stats_readback(
+    mut commands: Commands,
+    readback: Extract<Option<Res<SolverStatsReadback>>>,
+    sph: Extract<Res<SPHState>>,
+) {
+    let Some(readback) = readback.as_ref() else {
+        return;
+    };
+    let copy = sph.solver.iterative().is_some()
+        && readback
+            .slot
+            .state
+            .compare_exchange(SLOT_FREE, SLOT_COPIED, Ordering::AcqRel, Ordering::Acquire)
+            .is_ok();
+
+    commands.insert_resource(ExtractedSolverStatsReadback {
+        slot: readback.slot.clone(),
+        copy,
+    });
+}
+
+pub fn map_solver_stats_readback(readback: Option<Res<ExtractedSolverStatsReadback>>) {
+    let Some(readback) = readback else {
+        return;
+    };
+    if !readback.copy {
+        return;
+    }
+    let slot = &readback.slot;
+    if slot
+        .state
+        .compare_exchange(
+            SLOT_COPIED,
+            SLOT_MAPPING,
+            Ordering::AcqRel,
+            Ordering::Acquire,
+        )
+        .is_err()
+    {
+        return;
+    }
+
+    let shared = readback.slot.clone();
+    slot.buffer.slice(..).map_async(MapMode::Read, move |r| {
+        let state = if r.is_ok() { SLOT_MAPPED } else { SLOT_FAILED };
+        shared.state.store(state, Ordering::Release);
+    });
+}
+
+pub fn init_timestep_readback(mut commands: Commands, render_device: Res<RenderDevice>) {
+    let buffer = render_device.create_buffer(&BufferDescriptor {
+        label: Some("timestep_readback"),
+        size: std::mem::size_of::<GPUTimestep>() as u64,
+        usage: BufferUsages::COPY_DST | BufferUsages::MAP_READ,
+        mapped_at_creation: false,
+    });
+    commands.insert_resource(TimestepReadback {
+        slot: Arc::new(TimestepSlot {
+            buffer,
+            state: AtomicU8::new(SLOT_FREE),
+        }),
+    });
+}
+
+pub fn poll_timestep_readback(
+    render_device: Res<RenderDevice>,
+    readback: Option<Res<TimestepReadback>>,
+    config: Res<IntegrateConfig>,
+    mut report: ResMut<TimestepReport>,
+) {
+    let Some(readback) = readback else {
+        return;
+    };
+    render_device.poll(Maintain::Poll);
+
+    let slot = &readback.slot;
+    match slot.state.load(Ordering::Acquire) {
+        SLOT_MAPPED => {
+            let data = slot.buffer.slice(..).get_mapped_range();
+            let timestep: GPUTimestep = bytemuck::pod_read_unaligned(&data);
+            drop(data);
+            slot.buffer.unmap();
+            slot.state.store(SLOT_FREE, Ordering::Release);
+
+            *report = TimestepReport {
+                dt: timestep.dt,
+                max_speed: timestep.max_speed,
+                max_accel: timestep.max_accel,
+            };
+        }
+        SLOT_FAILED => {
+            error!("timestep readback: buffer map failed");
+            slot.buffer.unmap();
+            slot.state.store(SLOT_FREE, Ordering::Release);
+        }
+        _ => {}
+    }
+
+    // nothing is copied for a fixed dt
+    if config.adaptive.is_none() {
+        *report = TimestepReport::default();
+    }
+}
+
+// copied whenever dt is adaptive and the previous copy has been read
+pub fn extract_timestep_readback(
+    mut commands: Commands,
+    readback: Extract<Option<Res<TimestepReadback>>>,
+    config: Extract<Res<IntegrateConfig>>,
+) {
+    let Some(readback) = readback.as_ref() else {
+        return;
+    };
+    let copy = config.adaptive.is_some()
+        && readback
+            .slot
+            .state
+            .compare_exchange(SLOT_FREE, SLOT_COPIED, Ordering::AcqRel, Ordering::Acquire)
+            .is_ok();
+
+    commands.insert_resource(ExtractedTimestepReadback {
+        slot: readback.slot.clone(),
+        copy,
+    });
+}
+
+pub fn map_timestep_readback(readback: Option<Res<ExtractedTimestepReadback>>) {
+    let Some(readback) = readback else {
+        return;
+    };
+    if !readback.copy {
+        return;
+    }
+    let slot = &readback.slot;
+    if slot
+        .state
+        .compare_exchange(
+            SLOT_COPIED,
+            SLOT_MAPPING,
+            Ordering::AcqRel,
+            Ordering::Acquire,
+        )
+        .is_err()
+    {
+        return;
+    }
+
+    let shared = readback.slot.clone();
+    slot.buffer.slice(..).map_async(MapMode::Read, move |r| {
+        let state = if r.is_ok() { SLOT_MAPPED } else { SLOT_FAILED };
+        shared.state.store(state, Ordering::Release);
+    });
+}
+
+// Implementations
+
+impl ParticleReadback {
+    // ask for a single snapshot of the current GPU state
+    pub fn request(&mut self) {
+        self.requested = true;
+    }
+
+    // true while a requested copy has not been read back yet
+    pub fn in_flight(&self) -> bool {
+        self.requested
+            || self
+                .slots
+                .iter()
+                .any(|s| s.state.load(Ordering::Acquire) != SLOT_FREE)
+    }
+}
+
+impl Node for ReadbackNode {
+    fn run(
+        &self,
+        _graph: &mut RenderGraphContext,
+        render_context: &mut RenderContext,
+        world: &World,
+    ) -> Result<(), NodeRunError> {
+        let Some(readback) = world.get_resource::<ExtractedParticleReadback>() else {
+            return Ok(());
+        };
+        let Some(i) = readback.copy_slot else {
+            return Ok(());
+        };
+        let Some(extracted) = world.get_resource::<ExtractedParticleBuffer>() else {
+            return Ok(());
+        };
+
+        render_context.command_encoder().copy_buffer_to_buffer(
+            &extracted.buffer,
+            0,
+            &readback.slots[i].buffer,
+            0,
+            readback.size_bytes,
+        );
+        info!(
+            "Info Node: COPY particles -> readback slot {} ({} bytes)",
+            i, readback.size_bytes
+        );
+
+        Ok(())
+    }
+}
+
+pub fn add_readback_node_to_graph(render_app: &mut bevy::app::SubApp) {
+    let mut graph = render_app.world_mut().resource_mut::<RenderGraph>();
+    graph.add_node(ReadbackPassLabel, ReadbackNode);
+    // after the SPH steps, before anything is drawn
+    graph.add_node_edge(SimStepsLabel, ReadbackPassLabel);
+    graph.add_node_edge(ReadbackPassLabel, CameraDriverLabel);
+}
+
+// =====================================================================
+
+// Plugin
+
+// Added by GPUSPHPlugin. Set `ParticleReadback::continuous` or call `request()`,
+// then read `ParticleSnapshot` when `ParticleSnapshotReady` fires.
+pub struct ParticleReadbackPlugin;
+
+impl Plugin for ParticleReadbackPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ParticleSnapshot>()
+            .add_event::<ParticleSnapshotReady>()
+            .add_systems(PostStartup, init_particle_readback)
+            .add_systems(First, poll_particle_readback)
+            .add_systems(PostUpdate, request_particle_readback);
+
+        let render_app = app.sub_app_mut(RenderApp);
+        render_app
+            .add_systems(ExtractSchedule, extract_particle_readback)
+            .add_systems(Render, map_particle_readback.in_set(RenderSet::Cleanup));
+        add_readback_node_to_graph(render_app);
+    }
+}
@@ -0,0 +1,405 @@
+use std::borrow::Cow;
+
+use bevy::core_pipeline::core_2d::graph::{Core2d, Node2d};
+use bevy::ecs::query::QueryItem;
+use bevy::image::BevyDefault;
+use bevy::prelude::*;
+use bevy::render::extract_resource::ExtractResource;
+use bevy::render::render_graph::{
+    NodeRunError, RenderGraphApp, RenderGraphContext, RenderLabel, ViewNode, ViewNodeRunner,
+};
+use bevy::render::render_resource::{
+    BindGroup, BindGroupEntry, BindGroupLayout, BindGroupLayoutEntry, BindingType, BlendState,
+    Buffer, BufferBindingType, BufferInitDescriptor, BufferUsages, CachedRenderPipelineId,
+    ColorTargetState, ColorWrites, FragmentState, MultisampleState, PipelineCache, PrimitiveState,
+    RenderPassDescriptor, RenderPipelineDescriptor, ShaderStages, ShaderType,
+    SpecializedRenderPipeline, SpecializedRenderPipelines, TextureFormat, VertexState,
+};
+use bevy::render::renderer::{RenderContext, RenderDevice, RenderQueue};
+use bevy::render::view::{
+    ExtractedView, Msaa, ViewTarget, ViewUniform, ViewUniformOffset, ViewUniforms,
+};
+use bevy::render::{Extract, ExtractSchedule, Render, RenderApp, RenderSet};
+
+use crate::gpu::buffers::ExtractedParticleBuffer;
+use crate::gpu::ffi::ParticleRenderParams;
+
+// ==================== resources ======================================
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ParticleColorMode {
+    #[default]
+    Solid,
+    Density,
+    Pressure,
+    Speed,
+    Surface, // length of the surface normal, runs the normals pass
+}
+
+impl ParticleColorMode {
+    // cycle through the modes (handy for a key binding)
+    pub fn next(self) -> Self {
+        match self {
+            ParticleColorMode::Solid => ParticleColorMode::Density,
+            ParticleColorMode::Density => ParticleColorMode::Pressure,
+            ParticleColorMode::Pressure => ParticleColorMode::Speed,
+            ParticleColorMode::Speed => ParticleColorMode::Surface,
+            ParticleColorMode::Surface => ParticleColorMode::Solid,
+        }
+    }
+}
+
+#[derive(Resource, Clone, Debug, ExtractResource)]
+pub struct ParticleRenderSettings {
+    pub color_mode: ParticleColorMode,
+    pub solid_color: LinearRgba,
+    pub radius: f32,       // world units (meters)
+    pub render_scale: f32, // world -> pixels
+    // (min, max) mapped onto the colour ramp for each mode
+    pub density_range: Vec2,
+    pub pressure_range: Vec2,
+    pub speed_range: Vec2,
+    pub surface_range: Vec2,
+}
+
+impl Default for ParticleRenderSettings {
+    fn default() -> Self {
+        Self {
+            color_mode: ParticleColorMode::Solid,
+            solid_color: LinearRgba::rgb(0.0, 1.0, 1.0),
+            radius: 0.075,
+            render_scale: 100.0,
+            density_range: Vec2::new(900.0, 1400.0),
+            pressure_range: Vec2::new(0.0, 1200.0),
+            speed_range: Vec2::new(0.0, 3.0),
+            surface_range: Vec2::new(0.0, 1.0),
+        }
+    }
+}
+
+impl ParticleRenderSettings {
+    pub fn params(&self) -> ParticleRenderParams {
+        let (mode, range) = match self.color_mode {
+            ParticleColorMode::Solid => (0, Vec2::ZERO),
+            ParticleColorMode::Density => (1, self.density_range),
+            ParticleColorMode::Pressure => (2, self.pressure_range),
+            ParticleColorMode::Speed => (3, self.speed_range),
+            ParticleColorMode::Surface => (4, self.surface_range),
+        };
+        ParticleRenderParams {
+            color: self.solid_color.to_f32_array(),
+            range: [range.x, range.y],
+            radius: self.radius,
+            scale: self.render_scale,
+            mode,
+            _pad: [0; 3],
+        }
+    }
+}
+
+// render-world copy of the settings
+#[derive(Resource, Clone)]
+pub struct ExtractedParticleRenderSettings(pub ParticleRenderSettings);
+
+#[derive(Resource)]
+pub struct ParticleRenderPipeline {
+    pub layout: BindGroupLayout,
+    pub shader: Handle<Shader>,
+}
+
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+pub struct ParticleRenderPipelineKey {
+    pub hdr: bool,
+    pub samples: u32,
+}
+
+// per view, the pipeline matching its target format and MSAA
+#[derive(Component)]
+pub struct ParticleRenderPipelineId(pub CachedRenderPipelineId);
+
+#[derive(Resource)]
+pub struct ParticleRenderParamsBuffer {
+    pub buffer: Buffer,
+}
+
+#[derive(Resource)]
+pub struct ParticleRenderBindGroup(pub BindGroup);
+
+#[derive(Debug, Hash, PartialEq, Eq, Clone, RenderLabel)]
+pub struct ParticleDrawLabel;
+
+#[derive(Default)]
+pub struct ParticleDrawNode;
+
+// =====================================================================
+
+// ========================== systems ==================================
+
+fn extract_particle_render_settings(
+    mut commands: Commands,
+    settings: Extract<Res<ParticleRenderSettings>>,
+) {
+    commands.insert_resource(ExtractedParticleRenderSettings(settings.clone()));
+}
+
+fn prepare_particle_render_pipelines(
+    mut commands: Commands,
+    pipeline_cache: Res<PipelineCache>,
+    mut pipelines: ResMut<SpecializedRenderPipelines<ParticleRenderPipeline>>,
+    particle_pipeline: Res<ParticleRenderPipeline>,
+    views: Query<(Entity, &ExtractedView, &Msaa)>,
+) {
+    for (entity, view, msaa) in &views {
+        let key = ParticleRenderPipelineKey {
+            hdr: view.hdr,
+            samples: msaa.samples(),
+        };
+        let id = pipelines.specialize(&pipeline_cache, &particle_pipeline, key);
+        commands.entity(entity).insert(ParticleRenderPipelineId(id));
+    }
+}
+
+fn prepare_particle_render_params(
+    mut commands: Commands,
+    render_device: Res<RenderDevice>,
+    render_queue: Res<RenderQueue>,
+    settings: Option<Res<ExtractedParticleRenderSettings>>,
+    params_buffer: Option<Res<ParticleRenderParamsBuffer>>,
+) {
+    let Some(settings) = settings else {
+        return;
+    };
+    let params = settings.0.params();
+
+    if let Some(existing) = params_buffer {
+        render_queue.write_buffer(&existing.buffer, 0, bytemuck::bytes_of(&params));
+        return;
+    }
+    let buffer = render_device.create_buffer_with_data(&BufferInitDescriptor {
+        label: Some("particle_render_params"),
+        contents: bytemuck::bytes_of(&params),
+        usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+    });
+    commands.insert_resource(ParticleRenderParamsBuffer { buffer });
+}
+
+fn prepare_particle_render_bind_group(
+    mut commands: Commands,
+    render_device: Res<RenderDevice>,
+    pipeline: Res<ParticleRenderPipeline>,
+    view_uniforms: Res<ViewUniforms>,
+    particles: Option<Res<ExtractedParticleBuffer>>,
+    params: Option<Res<ParticleRenderParamsBuffer>>,
+) {
+    let (Some(particles), Some(params)) = (particles, params) else {
+        return;
+    };
+    let Some(view_binding) = view_uniforms.uniforms.binding() else {
+        return;
+    };
+
+    let bind_group = render_device.create_bind_group(
+        Some("particle_render_bind_group"),
+        &pipeline.layout,
+        &[
+            // binding(0): view uniform (dynamic offset per camera)
+            BindGroupEntry {
+                binding: 0,
+                resource: view_binding,
+            },
+            // binding(1): particles SSBO, read straight from the simulation buffer
+            BindGroupEntry {
+                binding: 1,
+                resource: particles.buffer.as_entire_binding(),
+            },
+            // binding(2): ParticleRenderParams UBO
+            BindGroupEntry {
+                binding: 2,
+                resource: params.buffer.as_entire_binding(),
+            },
+        ],
+    );
+    commands.insert_resource(ParticleRenderBindGroup(bind_group));
+}
+
+// Implementations
+
+impl FromWorld for ParticleRenderPipeline {
+    fn from_world(world: &mut World) -> Self {
+        let render_device = world.resource::<RenderDevice>();
+        let layout = render_device.create_bind_group_layout(
+            Some("particle_render_bind_group_layout"),
+            &[
+                // binding 0: view (uniform, dynamic offset)
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::VERTEX_FRAGMENT,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Uniform,
+                        has_dynamic_offset: true,
+                        min_binding_size: Some(ViewUniform::min_size()),
+                    },
+                    count: None,
+                },
+                // binding 1: particles (read-only)
+                BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: ShaderStages::VERTEX,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Storage { read_only: true },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                // binding 2: render params (uniform)
+                BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: ShaderStages::VERTEX_FRAGMENT,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        );
+        let shader = world
+            .resource::<AssetServer>()
+            .load("shaders/particle_render.wgsl");
+
+        Self { layout, shader }
+    }
+}
+
+impl SpecializedRenderPipeline for ParticleRenderPipeline {
+    type Key = ParticleRenderPipelineKey;
+
+    fn specialize(&self, key: Self::Key) -> RenderPipelineDescriptor {
+        let format = if key.hdr {
+            ViewTarget::TEXTURE_FORMAT_HDR
+        } else {
+            TextureFormat::bevy_default()
+        };
+
+        RenderPipelineDescriptor {
+            label: Some("particle_render_pipeline".into()),
+            layout: vec![self.layout.clone()],
+            push_constant_ranges: vec![],
+            vertex: VertexState {
+                shader: self.shader.clone(),
+                shader_defs: vec![],
+                entry_point: Cow::Borrowed("vertex"),
+                buffers: vec![], // quads are generated from vertex_index
+            },
+            primitive: PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: MultisampleState {
+                count: key.samples,
+                ..default()
+            },
+            fragment: Some(FragmentState {
+                shader: self.shader.clone(),
+                shader_defs: vec![],
+                entry_point: Cow::Borrowed("fragment"),
+                targets: vec![Some(ColorTargetState {
+                    format,
+                    blend: Some(BlendState::ALPHA_BLENDING),
+                    write_mask: ColorWrites::ALL,
+                })],
+            }),
+            zero_initialize_workgroup_memory: false,
+        }
+    }
+}
+
+// draw call
+
+impl ViewNode for ParticleDrawNode {
+    type ViewQuery = (
+        &'static ViewTarget,
+        &'static ViewUniformOffset,
+        &'static ParticleRenderPipelineId,
+    );
+
+    fn run<'w>(
+        &self,
+        _graph: &mut RenderGraphContext,
+        render_context: &mut RenderContext<'w>,
+        (target, view_offset, pipeline_id): QueryItem<'w, Self::ViewQuery>,
+        world: &'w World,
+    ) -> Result<(), NodeRunError> {
+        let Some(bind_group) = world.get_resource::<ParticleRenderBindGroup>() else {
+            return Ok(());
+        };
+        let Some(extracted) = world.get_resource::<ExtractedParticleBuffer>() else {
+            return Ok(());
+        };
+        if extracted.num_particles == 0 {
+            return Ok(());
+        }
+
+        let pipeline_cache = world.resource::<PipelineCache>();
+        let Some(pipeline) = pipeline_cache.get_render_pipeline(pipeline_id.0) else {
+            return Ok(()); // still compiling
+        };
+
+        let mut pass = render_context.begin_tracked_render_pass(RenderPassDescriptor {
+            label: Some("particle_draw_pass"),
+            color_attachments: &[Some(target.get_color_attachment())],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+
+        pass.set_render_pipeline(pipeline);
+        pass.set_bind_group(0, &bind_group.0, &[view_offset.offset]);
+        // 6 vertices per quad, one instance per particle
+        pass.draw(0..6, 0..extracted.num_particles);
+
+        Ok(())
+    }
+}
+
+// =====================================================================
+
+// Plugin
+
+// Draws the particles straight from the simulation storage buffer (no readback).
+// Needs GPUSPHPlugin for ExtractedParticleBuffer.
+pub struct ParticleRenderPlugin;
+
+impl Plugin for ParticleRenderPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ParticleRenderSettings>();
+
+        let render_app = app.sub_app_mut(RenderApp);
+        render_app
+            .init_resource::<SpecializedRenderPipelines<ParticleRenderPipeline>>()
+            .add_systems(ExtractSchedule, extract_particle_render_settings)
+            .add_systems(
+                Render,
+                (
+                    prepare_particle_render_pipelines.in_set(RenderSet::Prepare),
+                    prepare_particle_render_params.in_set(RenderSet::Prepare),
+                    prepare_particle_render_bind_group.in_set(RenderSet::PrepareBindGroups),
+                ),
+            )
+            .add_render_graph_node::<ViewNodeRunner<ParticleDrawNode>>(Core2d, ParticleDrawLabel)
+            .add_render_graph_edges(
+                Core2d,
+                (
+                    Node2d::MainTransparentPass,
+                    ParticleDrawLabel,
+                    Node2d::EndMainPass,
+                ),
+            );
+    }
+
+    fn finish(&self, app: &mut App) {
+        // the pipeline needs the RenderDevice, which only exists once the renderer is up
+        app.sub_app_mut(RenderApp)
+            .init_resource::<ParticleRenderPipeline>();
+    }
+}
@@ -0,0 +1,66 @@
+use bevy::prelude::*;
+use bevy::render::Extract;
+use bevy::render::render_resource::{Buffer, BufferDescriptor, BufferUsages};
+use bevy::render::renderer::RenderDevice;
+
+use crate::cpu::sph2d::{SPHState, Solver};
+use crate::gpu::buffers::ParticleBuffers;
+use crate::gpu::ffi::{GPUSolverScratch, GPUSolverStats};
+
+// ==================== resources ======================================
+
+// working memory of the iterative pressure solvers (all but Wcsph). Always
+// allocated so the solver can be switched at runtime
+#[derive(Resource)]
+pub struct SolverBuffers {
+    pub scratch: Buffer, // GPUSolverScratch per particle
+    pub stats: Buffer,   // GPUSolverStats, read back into SolverStatsReport
+}
+
+#[derive(Resource, Clone)]
+pub struct ExtractedSolverBuffers {
+    pub scratch: Buffer,
+    pub stats: Buffer,
+    pub solver: Solver, // which passes the density node dispatches
+    pub xsph: bool,     // SPHState::xsph_active
+    pub normals: bool,  // surface tension needs the normals pass
+}
+
+// =====================================================================
+
+// ========================== systems ==================================
+
+pub fn init_solver_buffers(
+    mut commands: Commands,
+    render_device: Res<RenderDevice>,
+    particle_buffers: Res<ParticleBuffers>,
+) {
+    let particles = particle_buffers.num_particles.max(1) as u64;
+    let scratch = render_device.create_buffer(&BufferDescriptor {
+        label: Some("solver_scratch_buffer"),
+        size: particles * std::mem::size_of::<GPUSolverScratch>() as u64,
+        usage: BufferUsages::STORAGE,
+        mapped_at_creation: false,
+    });
+    let stats = render_device.create_buffer(&BufferDescriptor {
+        label: Some("solver_stats_buffer"),
+        size: std::mem::size_of::<GPUSolverStats>() as u64,
+        usage: BufferUsages::STORAGE | BufferUsages::COPY_SRC,
+        mapped_at_creation: false,
+    });
+    commands.insert_resource(SolverBuffers { scratch, stats });
+}
+
+pub fn extract_solver_buffers(
+    mut commands: Commands,
+    buffers: Extract<Res<SolverBuffers>>,
+    sph: Extract<Res<SPHState>>,
+) {
+    commands.insert_resource(ExtractedSolverBuffers {
+        scratch: buffers.scratch.clone(),
+        stats: buffers.stats.clone(),
+        solver: sph.solver,
+        xsph: sph.xsph_active(),
+        normals: sph.surface_tension > 0.0,
+    });
+}
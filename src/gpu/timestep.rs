@@ -0,0 +1,108 @@
+use bevy::prelude::*;
+use bevy::render::Extract;
+use bevy::render::render_resource::{
+    BindGroup, BindGroupLayout, BindGroupLayoutEntry, BindingType, Buffer, BufferBindingType,
+    BufferInitDescriptor, BufferUsages, ShaderStages,
+};
+use bevy::render::renderer::RenderDevice;
+
+use crate::gpu::buffers::IntegrateConfig;
+use crate::gpu::ffi::GPUTimestep;
+use crate::gpu::flip::GridSolverInputs;
+
+// ==================== resources ======================================
+
+// GPUTimestep, always allocated so IntegrateConfig::adaptive can be switched
+// at runtime; read back into TimestepReport
+#[derive(Resource)]
+pub struct TimestepBuffers {
+    pub state: Buffer,
+}
+
+#[derive(Resource, Clone)]
+pub struct ExtractedTimestep {
+    pub state: Buffer,
+    pub adaptive: bool, // the density node runs the timestep passes
+}
+
+// timestep.wgsl: 0 = particles (read), 1 = GridParams, 2 = IntegrateParams,
+// 3 = SphParams (uniforms), 4 = GPUTimestep (rw)
+#[derive(Resource, Clone)]
+pub struct TimestepBindGroupLayout(pub BindGroupLayout);
+
+#[derive(Resource)]
+pub struct TimestepBindGroup(pub BindGroup);
+
+// =====================================================================
+
+// ========================== systems ==================================
+
+pub fn init_timestep_buffers(mut commands: Commands, render_device: Res<RenderDevice>) {
+    let state = render_device.create_buffer_with_data(&BufferInitDescriptor {
+        label: Some("timestep_buffer"),
+        contents: bytemuck::bytes_of(&GPUTimestep::default()),
+        usage: BufferUsages::STORAGE | BufferUsages::COPY_SRC,
+    });
+    commands.insert_resource(TimestepBuffers { state });
+}
+
+pub fn extract_timestep(
+    mut commands: Commands,
+    buffers: Extract<Res<TimestepBuffers>>,
+    config: Extract<Res<IntegrateConfig>>,
+) {
+    commands.insert_resource(ExtractedTimestep {
+        state: buffers.state.clone(),
+        adaptive: config.adaptive.is_some(),
+    });
+}
+
+pub fn init_timestep_bind_group_layout(
+    mut commands: Commands,
+    render_device: Res<RenderDevice>,
+    existing: Option<Res<TimestepBindGroupLayout>>,
+) {
+    if existing.is_some() {
+        return;
+    }
+    let entry = |binding: u32, ty: BufferBindingType| BindGroupLayoutEntry {
+        binding,
+        visibility: ShaderStages::COMPUTE,
+        ty: BindingType::Buffer {
+            ty,
+            has_dynamic_offset: false,
+            min_binding_size: None,
+        },
+        count: None,
+    };
+    let layout = render_device.create_bind_group_layout(
+        Some("timestep_bind_group_layout"),
+        &[
+            entry(0, BufferBindingType::Storage { read_only: true }),
+            entry(1, BufferBindingType::Uniform),
+            entry(2, BufferBindingType::Uniform),
+            entry(3, BufferBindingType::Uniform),
+            entry(4, BufferBindingType::Storage { read_only: false }),
+        ],
+    );
+    commands.insert_resource(TimestepBindGroupLayout(layout));
+}
+
+pub fn prepare_timestep_bind_group(
+    mut commands: Commands,
+    render_device: Res<RenderDevice>,
+    layout: Option<Res<TimestepBindGroupLayout>>,
+    timestep: Option<Res<ExtractedTimestep>>,
+    inputs: GridSolverInputs,
+) {
+    let (Some(layout), Some(timestep)) = (layout, timestep) else {
+        return;
+    };
+    let bind_group = inputs.bind_group_with(
+        &render_device,
+        "timestep_bind_group",
+        &layout.0,
+        &[&timestep.state],
+    );
+    commands.insert_resource(TimestepBindGroup(bind_group));
+}
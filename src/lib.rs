@@ -3,14 +3,34 @@ use bevy::prelude::*;
 pub mod solid_color;
 
 pub mod cpu {
+    pub mod body;
+    pub mod boundary;
+    pub mod collider;
+    pub mod dfsph;
+    pub mod domain;
+    pub mod flip;
+    pub mod iisph;
+    pub mod integrator;
+    pub mod mpm;
+    pub mod pbf;
+    pub mod pcisph;
     pub mod sph2d;
+    pub mod surface;
+    pub mod timestep;
 }
 
 pub mod gpu {
     pub mod buffers;
+    pub mod collider;
     pub mod ffi;
+    pub mod flip;
     pub mod grid_build;
+    pub mod mpm;
     pub mod pipeline;
+    pub mod readback;
+    pub mod render;
+    pub mod solver;
+    pub mod timestep;
 }
 
 #[derive(Component)]
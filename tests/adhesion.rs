@@ -0,0 +1,65 @@
+use bevy_gpu_fluid::cpu::collider::{Collider, ColliderShape};
+use bevy_gpu_fluid::cpu::domain::{Domain, WallMode};
+use bevy_gpu_fluid::cpu::sph2d::SPHState;
+use glam::Vec2;
+
+const H: f32 = 0.045;
+
+// one particle, so only gravity and the boundaries act on it
+fn lone_particle(pos: Vec2) -> SPHState {
+    let mut sph = SPHState::new(H, 1000.0, 3.0, 0.1, 1.6);
+    sph.init_grid(1, 1, 0.02);
+    sph.particles[0].pos = pos;
+    sph
+}
+
+fn box_domain(adhesion: [f32; 4]) -> Domain {
+    let mut domain = Domain::new(
+        Vec2::ZERO,
+        Vec2::ONE,
+        WallMode::Reflect { restitution: 0.5 },
+    );
+    domain.adhesion = adhesion;
+    domain
+}
+
+#[test]
+fn sticky_ceiling_holds_a_drop() {
+    // 3/4 h below the ceiling, where the adhesion kernel peaks
+    let pos = Vec2::new(0.5, 1.0 - 0.75 * H);
+    let mut sph = lone_particle(pos);
+    sph.step_domain(1e-6, &box_domain([0.0, 0.0, 0.0, 1.0]));
+    let acc = sph.particles[0].acc;
+    assert!(acc.y > 0.0, "{acc}");
+    assert!(acc.x.abs() < 1e-4);
+
+    // no adhesion, or the same wall made open: it just falls
+    for domain in [box_domain([0.0; 4]), {
+        let mut domain = box_domain([0.0, 0.0, 0.0, 1.0]);
+        domain.top = WallMode::Open;
+        domain
+    }] {
+        let mut sph = lone_particle(pos);
+        sph.step_domain(1e-6, &domain);
+        assert_eq!(sph.particles[0].acc, sph.gravity);
+    }
+}
+
+#[test]
+fn sticky_collider_pulls_fluid_to_its_surface() {
+    let pos = Vec2::new(0.5 + 0.2 + 0.75 * H, 0.5);
+    let mut sph = lone_particle(pos);
+    sph.gravity = Vec2::ZERO;
+    let mut collider = Collider::new(ColliderShape::Circle { radius: 0.2 }, Vec2::splat(0.5));
+    collider.adhesion = 1.0;
+    sph.colliders.push(collider);
+    sph.step_domain(1e-6, &box_domain([0.0; 4]));
+
+    let acc = sph.particles[0].acc;
+    assert!(acc.x < 0.0 && acc.y.abs() < 1e-3 * acc.x.abs(), "{acc}");
+
+    // out of reach
+    sph.particles[0].pos = pos + Vec2::new(H, 0.0);
+    sph.step_domain(1e-6, &box_domain([0.0; 4]));
+    assert_eq!(sph.particles[0].acc, Vec2::ZERO);
+}
@@ -0,0 +1,117 @@
+use bevy::ecs::system::RunSystemOnce;
+use bevy::prelude::*;
+use bevy_gpu_fluid::cpu::body::{
+    FluidBody, RigidBody, inertia_of, sync_fluid_bodies, write_back_fluid_bodies,
+};
+use bevy_gpu_fluid::cpu::collider::{Collider, ColliderShape};
+use bevy_gpu_fluid::cpu::domain::Domain;
+use bevy_gpu_fluid::cpu::sph2d::SPHState;
+use glam::Vec2;
+
+fn unit_box() -> ColliderShape {
+    ColliderShape::Box {
+        half_extents: Vec2::splat(0.1),
+    }
+}
+
+#[test]
+fn inertia_of_simple_shapes() {
+    let circle = inertia_of(&ColliderShape::Circle { radius: 2.0 }, 3.0);
+    assert!((circle - 6.0).abs() < 1e-6);
+
+    // a square polygon is the same as the box
+    let square = ColliderShape::Polygon {
+        points: vec![
+            Vec2::new(-0.1, -0.1),
+            Vec2::new(0.1, -0.1),
+            Vec2::new(0.1, 0.1),
+            Vec2::new(-0.1, 0.1),
+        ],
+    };
+    let a = inertia_of(&square, 2.0);
+    let b = inertia_of(&unit_box(), 2.0);
+    assert!((a - b).abs() < 1e-6, "{a} vs {b}");
+}
+
+#[test]
+fn pressure_pushes_particle_and_body_apart() {
+    let sph = SPHState::new(0.045, 1000.0, 3.0, 0.2, 1.6);
+    let mut body = RigidBody::new(Collider::new(unit_box(), Vec2::ZERO), 1.0);
+
+    // particle above the right half of the top face
+    let pos = Vec2::new(0.05, 0.12);
+    let a = body
+        .fluid_accel(&sph, pos, Vec2::ZERO, 1000.0, 50.0)
+        .unwrap();
+    assert!(a.y > 0.0 && a.x.abs() < 1e-3);
+
+    body.add_reaction(sph.m, pos, a);
+    assert_eq!(body.force, -sph.m * a); // equal and opposite
+    assert!(body.torque < 0.0); // pushed down right of the centre -> clockwise
+
+    // out of reach
+    assert!(
+        body.fluid_accel(&sph, Vec2::new(0.0, 0.2), Vec2::ZERO, 1000.0, 50.0)
+            .is_none()
+    );
+}
+
+#[test]
+fn fluid_slows_down_a_falling_body() {
+    let mut sph = SPHState::new(0.045, 1000.0, 3.0, 0.2, 1.6);
+    sph.init_grid(30, 8, 0.04);
+    for p in &mut sph.particles {
+        p.pos.x -= 0.6;
+    }
+
+    let mut collider = Collider::new(unit_box(), Vec2::new(0.0, 0.45));
+    collider.velocity = Vec2::new(0.0, -1.0);
+    sph.bodies.push(RigidBody::new(collider, 0.5));
+
+    let domain = Domain::floor_and_walls(-0.62, 0.62, -0.5);
+    let mut max_up = 0.0f32;
+    for _ in 0..200 {
+        sph.step_domain(0.0005, &domain);
+        max_up = max_up.max(sph.bodies[0].force.y);
+    }
+    assert!(max_up > 0.0, "fluid never pushed back");
+
+    // the fluid took some of the body's momentum
+    let falling = sph.bodies[0].collider.velocity.y;
+    let free_fall = -1.0 + sph.gravity.y * 0.1;
+    assert!(falling > free_fall, "{falling} vs {free_fall}");
+}
+
+#[test]
+fn fluid_body_round_trips_through_transform() {
+    let mut app = App::new();
+    app.insert_resource(SPHState::new(0.045, 1000.0, 3.0, 0.2, 1.6))
+        .add_systems(Update, (sync_fluid_bodies, write_back_fluid_bodies).chain());
+
+    let mut fb = FluidBody::new(unit_box(), 2.0);
+    fb.velocity = Vec2::new(0.5, 0.0);
+    let entity = app
+        .world_mut()
+        .spawn((fb, Transform::from_xyz(1.0, 2.0, 0.0)))
+        .id();
+    app.update();
+
+    let sph = app.world().resource::<SPHState>();
+    assert_eq!(sph.bodies.len(), 1);
+    assert_eq!(sph.bodies[0].collider.position, Vec2::new(1.0, 2.0));
+    assert_eq!(sph.bodies[0].collider.velocity, Vec2::new(0.5, 0.0));
+
+    // a simulation step moved the body; the next frame writes it back
+    let mut sph = app.world_mut().resource_mut::<SPHState>();
+    sph.bodies[0].collider.position = Vec2::new(1.5, 2.5);
+    sph.bodies[0].collider.rotation = 0.25;
+    app.world_mut()
+        .run_system_once(write_back_fluid_bodies)
+        .unwrap();
+
+    let transform = app.world().get::<Transform>(entity).unwrap();
+    assert_eq!(transform.translation.x, 1.5);
+    assert_eq!(transform.translation.y, 2.5);
+    let (_, _, angle) = transform.rotation.to_euler(EulerRot::XYZ);
+    assert!((angle - 0.25).abs() < 1e-6);
+}
@@ -0,0 +1,138 @@
+use bevy_gpu_fluid::cpu::boundary::{BoundaryParticles, sample_walls};
+use bevy_gpu_fluid::cpu::collider::{Collider, ColliderShape};
+use bevy_gpu_fluid::cpu::domain::{Domain, WallMode};
+use bevy_gpu_fluid::cpu::sph2d::{
+    EquationOfState, IterativeConfig, PressureForce, SPHState, Solver,
+};
+use glam::Vec2;
+
+const H: f32 = 0.045;
+
+// 30 x 30 block one spacing above the floor of a tank it fills, the mass set
+// so the inside is at rest density
+fn tank(boundary: bool) -> (SPHState, Domain) {
+    let mut sph = SPHState::new(H, 1000.0, 3.0, 0.0, 1.6);
+    sph.eos = EquationOfState::Tait {
+        speed_of_sound: 20.0,
+    };
+    sph.pressure_force = PressureForce::Symmetric;
+    sph.init_grid(30, 30, 0.02);
+    for p in &mut sph.particles {
+        p.pos += Vec2::splat(0.02);
+    }
+    sph.density_pressure_calc();
+    sph.m *= 1000.0 / sph.particles[465].rho;
+
+    let domain = Domain::floor_and_walls(0.0, 0.62, -0.5);
+    if boundary {
+        sph.sample_boundary(&domain, 0.02);
+    }
+    (sph, domain)
+}
+
+fn on_floor(sph: &SPHState) -> usize {
+    sph.particles.iter().filter(|p| p.pos.y <= 0.0).count()
+}
+
+#[test]
+fn boundary_particles_hold_the_fluid_off_the_floor() {
+    // without them the bottom row is short of neighbours, sinks and piles
+    // up on the clamp
+    let (mut sph, domain) = tank(false);
+    for _ in 0..300 {
+        sph.step_domain(5e-4, &domain);
+    }
+    assert!(on_floor(&sph) > 10, "{}", on_floor(&sph));
+
+    let (mut sph, domain) = tank(true);
+    for _ in 0..300 {
+        sph.step_domain(5e-4, &domain);
+    }
+    assert_eq!(on_floor(&sph), 0);
+    let bottom: Vec<f32> = sph
+        .particles
+        .iter()
+        .filter(|p| p.pos.y < 0.05)
+        .map(|p| p.rho)
+        .collect();
+    let mean = bottom.iter().sum::<f32>() / bottom.len() as f32;
+    assert!((mean - 1000.0).abs() < 50.0, "{mean}");
+
+    // the pressure solvers keep the clamps
+    sph.solver = Solver::Pcisph(IterativeConfig::default());
+    assert!(sph.active_boundary().is_none());
+}
+
+#[test]
+fn psi_evens_out_the_sampling() {
+    // the same floor sampled twice as densely weighs about the same
+    let domain = Domain::floor_and_walls(0.0, 1.0, -0.5);
+    let floor = |spacing: f32| {
+        let points = sample_walls(&domain, spacing, (Vec2::ZERO, Vec2::ONE))
+            .into_iter()
+            .filter(|p| p.y == 0.0)
+            .collect();
+        BoundaryParticles::new(points, H, 1000.0)
+    };
+    let x = Vec2::new(0.5, 0.5 * H);
+    let weight = |b: &BoundaryParticles| {
+        let mut sum = 0.0;
+        b.for_each_near(x, H, |k| {
+            sum += b.psi[k] * (1.0 - (x - b.pos[k]).length_squared() / (H * H)).powi(3);
+        });
+        sum
+    };
+    let (coarse, fine) = (floor(0.02), floor(0.01));
+    assert!(fine.pos.len() as f32 > 1.9 * coarse.pos.len() as f32);
+    let (w_coarse, w_fine) = (weight(&coarse), weight(&fine));
+    assert!(
+        (w_fine / w_coarse - 1.0).abs() < 0.05,
+        "{w_fine} vs {w_coarse}"
+    );
+}
+
+#[test]
+fn sampling_covers_solid_walls_and_colliders() {
+    let mut sph = SPHState::new(H, 1000.0, 3.0, 0.1, 1.6);
+    sph.init_grid(10, 10, 0.02);
+    let collider = Collider::new(ColliderShape::Circle { radius: 0.1 }, Vec2::new(0.5, 0.3));
+    sph.colliders.push(collider.clone());
+    let mut domain = Domain::floor_and_walls(0.0, 1.0, -0.5);
+    domain.left = WallMode::Open;
+    sph.sample_boundary(&domain, 0.02);
+
+    let b = sph.boundary.as_ref().unwrap();
+    // nothing on the open wall but the end of the floor
+    assert!(
+        b.pos
+            .iter()
+            .all(|p| p.is_finite() && (p.x > 0.0 || p.y == 0.0))
+    );
+    // the right wall ends above the particles, grown by their size
+    let right = b.pos.iter().filter(|p| p.x == 1.0);
+    let top = right.map(|p| p.y).fold(0.0, f32::max);
+    assert!((top - 0.36).abs() < 1e-4, "{top}");
+
+    let on_circle: Vec<Vec2> = b
+        .pos
+        .iter()
+        .copied()
+        .filter(|p| collider.distance(*p).abs() < 1e-3)
+        .collect();
+    // 2 pi r / spacing, give or take the thinning
+    assert!((25..=35).contains(&on_circle.len()), "{}", on_circle.len());
+    // about one spacing to the next sample, no holes
+    for p in &on_circle {
+        let next = on_circle
+            .iter()
+            .filter(|q| *q != p)
+            .map(|q| p.distance(*q))
+            .fold(f32::INFINITY, f32::min);
+        assert!((0.018..0.03).contains(&next), "{next}");
+    }
+    for (i, p) in b.pos.iter().enumerate() {
+        for q in &b.pos[i + 1..] {
+            assert!(p.distance(*q) >= 0.01);
+        }
+    }
+}
@@ -0,0 +1,183 @@
+use std::time::Duration;
+
+use bevy::prelude::*;
+use bevy_gpu_fluid::cpu::collider::{
+    Collider, ColliderShape, FluidCollider, SdfGrid, sync_fluid_colliders,
+};
+use bevy_gpu_fluid::cpu::domain::Domain;
+use bevy_gpu_fluid::cpu::sph2d::SPHState;
+use glam::Vec2;
+
+fn close(a: f32, b: f32) -> bool {
+    (a - b).abs() < 1e-4
+}
+
+#[test]
+fn primitive_distances() {
+    let circle = ColliderShape::Circle { radius: 1.0 };
+    assert!(close(circle.distance(Vec2::new(2.0, 0.0)), 1.0));
+    assert!(close(circle.distance(Vec2::ZERO), -1.0));
+
+    let b = ColliderShape::Box {
+        half_extents: Vec2::new(2.0, 1.0),
+    };
+    assert!(close(b.distance(Vec2::new(3.0, 0.0)), 1.0));
+    assert!(close(b.distance(Vec2::new(0.0, 0.5)), -0.5));
+    assert!(close(b.distance(Vec2::new(5.0, 5.0)), 5.0)); // corner (3, 4)
+
+    let capsule = ColliderShape::Capsule {
+        a: Vec2::new(-1.0, 0.0),
+        b: Vec2::new(1.0, 0.0),
+        radius: 0.5,
+    };
+    assert!(close(capsule.distance(Vec2::new(0.0, 2.0)), 1.5));
+    assert!(close(capsule.distance(Vec2::new(3.0, 0.0)), 1.5));
+}
+
+#[test]
+fn concave_polygon_cup() {
+    // U-shaped cup, wall thickness 0.2, opening at the top
+    let cup = ColliderShape::Polygon {
+        points: vec![
+            Vec2::new(-1.0, 0.0),
+            Vec2::new(1.0, 0.0),
+            Vec2::new(1.0, 1.0),
+            Vec2::new(0.8, 1.0),
+            Vec2::new(0.8, 0.2),
+            Vec2::new(-0.8, 0.2),
+            Vec2::new(-0.8, 1.0),
+            Vec2::new(-1.0, 1.0),
+        ],
+    };
+    assert!(close(cup.distance(Vec2::new(0.0, 0.1)), -0.1)); // in the floor
+    assert!(close(cup.distance(Vec2::new(0.0, 0.5)), 0.3)); // inside the cup, fluid side
+    assert!(cup.distance(Vec2::new(-0.9, 0.5)) < 0.0); // left wall
+}
+
+#[test]
+fn baked_grid_matches_shape() {
+    let circle = ColliderShape::Circle { radius: 1.0 };
+    let grid = SdfGrid::bake(&circle, Vec2::splat(-2.0), Vec2::splat(2.0), 0.05);
+    for p in [
+        Vec2::new(0.3, -0.2),
+        Vec2::new(1.1, 0.4),
+        Vec2::new(-1.5, 1.5),
+    ] {
+        assert!((grid.sample(p) - circle.distance(p)).abs() < 0.01);
+    }
+    assert_eq!(grid.sample(Vec2::new(3.0, 0.0)), f32::INFINITY);
+}
+
+#[test]
+fn projection_with_restitution_and_friction() {
+    let mut ramp = Collider::new(
+        ColliderShape::Box {
+            half_extents: Vec2::new(2.0, 0.5),
+        },
+        Vec2::ZERO,
+    );
+    ramp.rotation = std::f32::consts::FRAC_PI_4;
+    ramp.restitution = 0.5;
+    ramp.friction = 0.5;
+
+    let n = Vec2::new(-1.0, 1.0).normalize(); // ramp's upper surface
+    let mut pos = n * 0.4;
+    let mut vel = Vec2::new(1.0, -3.0);
+    assert!(ramp.apply(&mut pos, &mut vel));
+    assert!(ramp.distance(pos).abs() < 1e-3);
+
+    // normal part (-2.83) flipped and halved, tangential (-1.41) halved
+    let t = Vec2::new(1.0, 1.0).normalize();
+    assert!((vel.dot(n) - 0.5 * 4.0 / 2f32.sqrt()).abs() < 1e-2);
+    assert!((vel.dot(t) + 0.5 * 2.0 / 2f32.sqrt()).abs() < 1e-2);
+
+    // outside: untouched
+    let mut far = Vec2::new(0.0, 5.0);
+    assert!(!ramp.apply(&mut far, &mut vel));
+}
+
+#[test]
+fn particles_stay_out_of_colliders() {
+    let mut sph = SPHState::new(0.045, 1000.0, 3.0, 0.2, 1.0);
+    sph.init_grid(20, 5, 0.04);
+    for p in &mut sph.particles {
+        p.pos += Vec2::new(-0.4, 0.6);
+    }
+    sph.colliders.push(Collider::new(
+        ColliderShape::Circle { radius: 0.3 },
+        Vec2::new(0.0, 0.3),
+    ));
+
+    let domain = Domain::floor_and_walls(-1.0, 1.0, -0.5);
+    for _ in 0..300 {
+        sph.step_domain(0.001, &domain);
+    }
+    for p in &sph.particles {
+        assert!(sph.colliders[0].distance(p.pos) > -1e-3, "{:?}", p.pos);
+    }
+}
+
+#[test]
+fn moving_wall_pushes_particles() {
+    let mut piston = Collider::new(
+        ColliderShape::Box {
+            half_extents: Vec2::new(0.1, 1.0),
+        },
+        Vec2::ZERO,
+    );
+    piston.velocity = Vec2::new(2.0, 0.0);
+
+    // particle at rest just inside the piston face
+    let mut pos = Vec2::new(0.09, 0.0);
+    let mut vel = Vec2::ZERO;
+    assert!(piston.apply(&mut pos, &mut vel));
+    assert!((pos.x - 0.1).abs() < 1e-4);
+    assert!((vel - Vec2::new(2.0, 0.0)).length() < 1e-4);
+
+    // a spinning rod moves its tip sideways
+    let mut rod = Collider::new(
+        ColliderShape::Capsule {
+            a: Vec2::ZERO,
+            b: Vec2::new(1.0, 0.0),
+            radius: 0.05,
+        },
+        Vec2::ZERO,
+    );
+    rod.angular_velocity = 1.0;
+    assert!((rod.velocity_at(Vec2::new(1.0, 0.0)) - Vec2::new(0.0, 1.0)).length() < 1e-6);
+}
+
+#[test]
+fn fluid_collider_follows_transform() {
+    let mut app = App::new();
+    app.init_resource::<Time>()
+        .insert_resource(SPHState::new(0.045, 1000.0, 3.0, 0.2, 1.0))
+        .add_systems(Update, sync_fluid_colliders);
+
+    let mut paddle = FluidCollider::new(ColliderShape::Circle { radius: 0.2 });
+    paddle.friction = 0.3;
+    let entity = app
+        .world_mut()
+        .spawn((paddle, Transform::from_xyz(1.0, 0.5, 0.0)))
+        .id();
+    app.update();
+
+    let sph = app.world().resource::<SPHState>();
+    assert_eq!(sph.moving_colliders.len(), 1);
+    assert_eq!(sph.moving_colliders[0].position, Vec2::new(1.0, 0.5));
+    assert_eq!(sph.moving_colliders[0].friction, 0.3);
+
+    // move 0.1 to the right and turn a bit in 0.1 s
+    app.world_mut()
+        .resource_mut::<Time>()
+        .advance_by(Duration::from_millis(100));
+    let mut transform = app.world_mut().get_mut::<Transform>(entity).unwrap();
+    transform.translation.x += 0.1;
+    transform.rotation = Quat::from_rotation_z(0.05);
+    app.update();
+
+    let moving = &app.world().resource::<SPHState>().moving_colliders[0];
+    assert!((moving.velocity - Vec2::new(1.0, 0.0)).length() < 1e-3);
+    assert!((moving.angular_velocity - 0.5).abs() < 1e-3);
+    assert!((moving.rotation - 0.05).abs() < 1e-5);
+}
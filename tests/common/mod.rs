@@ -0,0 +1,20 @@
+// fixture shared by the pressure solver tests
+use bevy_gpu_fluid::cpu::domain::Domain;
+use bevy_gpu_fluid::cpu::sph2d::{SPHState, Solver};
+
+// two particles per smoothing length; with the demo spacing a particle's own
+// kernel weight is already above rho_0 and no incompressible state exists
+pub fn column(solver: Solver) -> SPHState {
+    let mut sph = SPHState::new(0.045, 1000.0, 3.0, 0.02, 0.4);
+    sph.solver = solver;
+    sph.init_grid(10, 30, 0.02);
+    sph
+}
+
+// the column between two walls just around it
+pub fn run(sph: &mut SPHState, dt: f32, steps: usize) {
+    let domain = Domain::floor_and_walls(-0.01, 0.19, -0.1);
+    for _ in 0..steps {
+        sph.step_domain(dt, &domain);
+    }
+}
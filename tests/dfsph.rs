@@ -0,0 +1,52 @@
+mod common;
+
+use bevy_gpu_fluid::cpu::sph2d::{DfsphConfig, IterativeConfig, SPHState, Solver};
+use common::{column, run};
+
+fn max_density(sph: &SPHState) -> f32 {
+    sph.particles.iter().map(|p| p.rho).fold(0.0, f32::max) / sph.rho_0
+}
+
+#[test]
+fn reports_both_solves() {
+    // a loose tolerance stops both at min_iterations
+    let loose = IterativeConfig {
+        tolerance: 1.0,
+        min_iterations: 3,
+        max_iterations: 10,
+    };
+    let mut sph = column(Solver::Dfsph(DfsphConfig {
+        density: loose,
+        divergence: loose,
+    }));
+    run(&mut sph, 0.002, 5);
+    assert_eq!(sph.stats.iterations, 3);
+    assert_eq!(sph.stats.divergence_iterations, 3);
+    assert!(sph.stats.density_error.is_finite() && sph.stats.divergence_error.is_finite());
+}
+
+#[test]
+fn resting_fluid_needs_no_divergence_work() {
+    let mut sph = column(Solver::Dfsph(DfsphConfig::default()));
+    run(&mut sph, 0.002, 1);
+    // at rest there is nothing to correct
+    assert_eq!(sph.stats.divergence_iterations, 1);
+    assert_eq!(sph.stats.divergence_error, 0.0);
+}
+
+#[test]
+fn large_steps_stay_incompressible() {
+    // twice the step WCSPH survives in this column
+    let mut dfsph = column(Solver::Dfsph(DfsphConfig::default()));
+    run(&mut dfsph, 0.004, 150);
+    let d = max_density(&dfsph);
+    assert!(d < 1.2, "{d}");
+    assert!(dfsph.stats.density_error < 0.01, "{:?}", dfsph.stats);
+
+    // nothing flew off
+    for p in &dfsph.particles {
+        assert!(p.pos.is_finite() && p.vel.length() < 10.0, "{p:?}");
+    }
+    let top = dfsph.particles.iter().map(|p| p.pos.y).fold(0.0, f32::max);
+    assert!(top < 1.0, "{top}");
+}
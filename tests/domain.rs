@@ -0,0 +1,111 @@
+use bevy_gpu_fluid::cpu::domain::{Domain, WallMode};
+use bevy_gpu_fluid::cpu::sph2d::{GridMode, SPHState};
+use glam::Vec2;
+
+fn unit_box(mode: WallMode) -> Domain {
+    Domain::new(Vec2::ZERO, Vec2::ONE, mode)
+}
+
+#[test]
+fn reflect_uses_restitution() {
+    let d = unit_box(WallMode::Reflect { restitution: 0.5 });
+    let mut pos = Vec2::new(1.2, 0.5);
+    let mut vel = Vec2::new(2.0, 1.0);
+    assert!(d.apply(&mut pos, &mut vel));
+    assert_eq!(pos, Vec2::new(1.0, 0.5));
+    assert_eq!(vel, Vec2::new(-1.0, 1.0));
+}
+
+#[test]
+fn slip_and_no_slip() {
+    let mut pos = Vec2::new(0.5, -0.1);
+    let mut vel = Vec2::new(1.0, -2.0);
+    assert!(unit_box(WallMode::FreeSlip).apply(&mut pos, &mut vel));
+    assert_eq!(pos, Vec2::new(0.5, 0.0));
+    assert_eq!(vel, Vec2::new(1.0, 0.0)); // tangential kept
+
+    let mut pos = Vec2::new(0.5, -0.1);
+    let mut vel = Vec2::new(1.0, -2.0);
+    assert!(unit_box(WallMode::NoSlip).apply(&mut pos, &mut vel));
+    assert_eq!(vel, Vec2::ZERO);
+}
+
+#[test]
+fn periodic_wraps_position() {
+    let d = unit_box(WallMode::Periodic);
+    let mut pos = Vec2::new(1.25, -0.25);
+    let mut vel = Vec2::new(1.0, -1.0);
+    assert!(d.apply(&mut pos, &mut vel));
+    assert!((pos - Vec2::new(0.25, 0.75)).length() < 1e-6);
+    assert_eq!(vel, Vec2::new(1.0, -1.0));
+}
+
+#[test]
+fn open_wall_deletes_particles() {
+    let mut d = unit_box(WallMode::Reflect { restitution: 1.0 });
+    d.top = WallMode::Open;
+
+    let mut sph = SPHState::new(0.045, 1000.0, 3.0, 0.1, 1.0);
+    sph.init_grid(2, 1, 0.5);
+    sph.particles[1].pos.y = 1.5; // above the ceiling
+    sph.apply_domain(&d);
+    assert_eq!(sph.particles.len(), 1);
+}
+
+#[test]
+fn floor_and_walls_matches_old_boundaries() {
+    let d = Domain::floor_and_walls(-3.0, 3.0, -0.5);
+    let mut pos = Vec2::new(3.5, -0.1);
+    let mut vel = Vec2::new(1.0, -2.0);
+    assert!(d.apply(&mut pos, &mut vel));
+    assert_eq!(pos, Vec2::new(3.0, 0.0));
+    assert_eq!(vel, Vec2::new(-0.5, 1.0));
+
+    // no ceiling
+    let mut pos = Vec2::new(0.0, 1e6);
+    assert!(d.apply(&mut pos, &mut vel));
+    assert_eq!(pos.y, 1e6);
+}
+
+#[test]
+fn periodic_images_near_corner() {
+    let p = unit_box(WallMode::Periodic).periodicity();
+    let (images, n) = p.images(Vec2::new(0.05, 0.97), 0.1);
+    assert_eq!(n, 4);
+    assert_eq!(images[0], Vec2::ZERO);
+    assert!(images[..n].contains(&Vec2::new(1.0, -1.0)));
+
+    let (_, n) = p.images(Vec2::new(0.5, 0.5), 0.1);
+    assert_eq!(n, 1);
+
+    // one periodic wall is not enough
+    let mut d = unit_box(WallMode::Periodic);
+    d.left = WallMode::FreeSlip;
+    let (_, n) = d.periodicity().images(Vec2::new(0.05, 0.5), 0.1);
+    assert_eq!(n, 1);
+}
+
+#[test]
+fn periodic_lattice_has_uniform_density() {
+    // 10 x 10 lattice that exactly tiles a periodic box: every particle
+    // sees the same neighbourhood, also across the edges
+    let spacing = 0.02;
+    let d = Domain::new(
+        Vec2::splat(-0.5 * spacing),
+        Vec2::splat(9.5 * spacing),
+        WallMode::Periodic,
+    );
+
+    for mode in [GridMode::Dense, GridMode::hashed_for(100)] {
+        let mut sph = SPHState::new(0.045, 1000.0, 3.0, 0.1, 1.0);
+        sph.grid_mode = mode;
+        sph.init_grid(10, 10, spacing);
+        sph.periodic = d.periodicity();
+        sph.density_pressure_calc();
+
+        let rho0 = sph.particles[0].rho;
+        for p in &sph.particles {
+            assert!((p.rho - rho0).abs() < 1e-3 * rho0, "{} vs {}", p.rho, rho0);
+        }
+    }
+}
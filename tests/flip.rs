@@ -0,0 +1,78 @@
+mod common;
+
+use bevy_gpu_fluid::cpu::domain::{Domain, WallMode};
+use bevy_gpu_fluid::cpu::sph2d::{FlipConfig, IterativeConfig, Particle, SPHState, Solver};
+use common::{column, run};
+use glam::Vec2;
+
+#[test]
+fn reports_pressure_iterations() {
+    let config = FlipConfig {
+        pressure: IterativeConfig {
+            tolerance: 0.0,
+            min_iterations: 1,
+            max_iterations: 7,
+        },
+        ..Default::default()
+    };
+    let mut sph = column(Solver::Flip(config));
+    run(&mut sph, 0.004, 1);
+    assert_eq!(sph.stats.iterations, 7); // tolerance never met
+    assert!(sph.stats.density_error.is_finite());
+    assert_eq!(sph.solver.iterative(), Some(config.pressure));
+}
+
+#[test]
+fn column_holds_its_height() {
+    // about five particles per grid cell
+    let config = FlipConfig::default();
+    let mut sph = column(Solver::Flip(config));
+    for _ in 0..150 {
+        run(&mut sph, 0.004, 1);
+        for p in &sph.particles {
+            assert!(p.pos.is_finite() && p.vel.length() < 5.0, "{p:?}");
+        }
+    }
+    // no collapse under its own weight
+    let top = sph.particles.iter().map(|p| p.pos.y).fold(0.0, f32::max);
+    assert!(top > 0.5, "{top}");
+    assert!(sph.stats.density_error < 0.01, "{:?}", sph.stats);
+}
+
+#[test]
+fn apic_keeps_rotation_that_pic_loses() {
+    // a spinning disc without gravity or walls
+    let spin = |flip_ratio: f32, apic: bool| {
+        let mut sph = SPHState::new(0.045, 1000.0, 3.0, 0.02, 0.4);
+        sph.solver = Solver::Flip(FlipConfig {
+            flip_ratio,
+            apic,
+            ..Default::default()
+        });
+        sph.gravity = Vec2::ZERO;
+        for y in -10..=10 {
+            for x in -10..=10 {
+                let pos = Vec2::new(x as f32, y as f32) * 0.02;
+                if pos.length() < 0.2 {
+                    let mut p = Particle::new(pos);
+                    p.vel = pos.perp();
+                    sph.particles.push(p);
+                }
+            }
+        }
+        let angular = |sph: &SPHState| -> f32 {
+            sph.particles.iter().map(|p| p.pos.perp_dot(p.vel)).sum()
+        };
+        let before = angular(&sph);
+        let open = Domain::new(Vec2::splat(-1.0), Vec2::splat(1.0), WallMode::Open);
+        for _ in 0..20 {
+            sph.step_domain(0.004, &open);
+        }
+        angular(&sph) / before
+    };
+
+    let pic = spin(0.0, false);
+    let apic = spin(0.0, true);
+    assert!(pic < 0.9, "{pic}");
+    assert!(apic > 0.95, "{apic}");
+}
@@ -0,0 +1,295 @@
+use bevy_gpu_fluid::cpu::body::RigidBody;
+use bevy_gpu_fluid::cpu::collider::{Collider, ColliderShape};
+use bevy_gpu_fluid::cpu::domain::{Domain, WallMode};
+use bevy_gpu_fluid::cpu::integrator::Integrator;
+use bevy_gpu_fluid::cpu::mpm::sand_alpha;
+use bevy_gpu_fluid::cpu::pcisph::{pcisph_delta, prototype_grad_sq};
+use bevy_gpu_fluid::cpu::sph2d::{
+    DfsphConfig, EquationOfState, FlipConfig, IisphConfig, IterativeConfig, MpmConfig, MpmMaterial,
+    PbfConfig, PressureForce, SPHState, Solver, Viscosity,
+};
+use bevy_gpu_fluid::cpu::timestep::AdaptiveDt;
+use bevy_gpu_fluid::gpu::buffers::{
+    GRID_CAPACITY_HEADROOM, IntegrateConfig, MAX_GRID_CELLS, MIN_GRID_CELLS, grid_capacity_for,
+};
+use bevy_gpu_fluid::gpu::collider::{pack_boundary, pack_colliders, pack_scene};
+use bevy_gpu_fluid::gpu::ffi::{
+    COLLIDER_CIRCLE, COLLIDER_POLYGON, GPUCollider, GPUMacNode, GPUMpmNode, GPUMpmParticle,
+    GPUParticle, GPUSolverScratch, GPUSolverStats, GPUTimestep, GridBoundsStats, GridBuildParams,
+    IntegrateParams, SphParams,
+};
+
+#[test]
+fn sph_params_match_state() {
+    let mut sph = SPHState::new(0.05, 998.0, 7.0, 0.4, 2.5);
+    sph.gravity = glam::Vec2::new(0.0, -3.7);
+
+    let params = SphParams::from_state(&sph);
+    assert_eq!(params.mass, 2.5);
+    assert_eq!(params.rho_0, 998.0);
+    assert_eq!(params.k, 7.0);
+    assert_eq!(params.mu, 0.4);
+    assert_eq!(params.gravity, [0.0, -3.7]);
+    assert_eq!(params.eos, 0);
+
+    sph.eos = EquationOfState::Tait {
+        speed_of_sound: 40.0,
+    };
+    let params = SphParams::from_state(&sph);
+    assert_eq!(params.eos, 2);
+    assert_eq!(params.speed_of_sound, 40.0);
+    assert_eq!((params.pressure_force, params.viscosity), (0, 0));
+
+    sph.pressure_force = PressureForce::Symmetric;
+    sph.viscosity = Viscosity::Artificial {
+        alpha: 0.05,
+        speed_of_sound: 30.0,
+    };
+    let params = SphParams::from_state(&sph);
+    assert_eq!((params.pressure_force, params.viscosity), (1, 1));
+    assert_eq!((params.av_alpha, params.av_speed_of_sound), (0.05, 30.0));
+    assert_eq!(params.integrator, 0);
+
+    sph.integrator = Integrator::VelocityVerlet;
+    assert_eq!(SphParams::from_state(&sph).integrator, 2);
+
+    sph.xsph = 0.1;
+    assert_eq!(SphParams::from_state(&sph).xsph, 0.1);
+    sph.solver = Solver::Pbf(PbfConfig::default());
+    let params = SphParams::from_state(&sph);
+    assert_eq!((params.integrator, params.xsph), (0, 0.0));
+
+    sph.surface_tension = 0.3;
+    assert_eq!(SphParams::from_state(&sph).surface_tension, 0.3);
+}
+
+#[test]
+fn solver_params_match_state() {
+    let mut sph = SPHState::new(0.045, 1000.0, 3.0, 0.2, 1.6);
+    let params = SphParams::from_state(&sph);
+    assert_eq!(params.solver, 0);
+    assert_eq!(params.pcisph_delta, pcisph_delta(0.045, 1.6, 1000.0));
+
+    sph.solver = Solver::Pcisph(IterativeConfig {
+        tolerance: 0.02,
+        min_iterations: 2,
+        max_iterations: 8,
+    });
+    let params = SphParams::from_state(&sph);
+    assert_eq!(params.solver, 1);
+    assert_eq!(params.solver_tolerance, 0.02);
+    assert_eq!(params.solver_min_iterations, 2);
+
+    let config = DfsphConfig::default();
+    sph.solver = Solver::Dfsph(config);
+    let params = SphParams::from_state(&sph);
+    assert_eq!(params.solver, 2);
+    assert_eq!(params.solver_tolerance, config.density.tolerance);
+    assert_eq!(params.divergence_tolerance, config.divergence.tolerance);
+    assert_eq!(
+        params.divergence_min_iterations,
+        config.divergence.min_iterations
+    );
+
+    let config = PbfConfig {
+        iterations: 6,
+        vorticity: 0.2,
+        ..Default::default()
+    };
+    sph.solver = Solver::Pbf(config);
+    let params = SphParams::from_state(&sph);
+    assert_eq!(params.solver, 3);
+    // every round runs
+    assert_eq!(params.solver_min_iterations, 6);
+    assert_eq!(params.solver_tolerance, 0.0);
+    assert_eq!(params.pbf_relaxation, config.relaxation);
+    assert_eq!(params.pbf_vorticity, 0.2);
+    assert_eq!(params.pbf_scale, prototype_grad_sq(0.045, 1.6, 1000.0));
+
+    let config = IisphConfig {
+        relaxation: 0.3,
+        ..Default::default()
+    };
+    sph.solver = Solver::Iisph(config);
+    let params = SphParams::from_state(&sph);
+    assert_eq!(params.solver, 4);
+    assert_eq!(params.solver_min_iterations, config.density.min_iterations);
+    assert_eq!(params.solver_tolerance, config.density.tolerance);
+    assert_eq!(params.iisph_relaxation, 0.3);
+
+    let config = FlipConfig {
+        flip_ratio: 0.9,
+        apic: true,
+        ..Default::default()
+    };
+    sph.solver = Solver::Flip(config);
+    let params = SphParams::from_state(&sph);
+    assert_eq!(params.solver, 5);
+    assert_eq!(params.solver_min_iterations, config.pressure.min_iterations);
+    assert_eq!(params.solver_tolerance, config.pressure.tolerance);
+    assert_eq!(params.flip_ratio, 0.9);
+    assert_eq!(params.flip_relaxation, config.relaxation);
+    assert_eq!(params.flip_apic, 1);
+
+    let config = MpmConfig {
+        material: MpmMaterial::sand(),
+        ..Default::default()
+    };
+    sph.solver = Solver::Mpm(config);
+    let params = SphParams::from_state(&sph);
+    assert_eq!(params.solver, 6);
+    assert_eq!(params.mpm_material, 3);
+    assert_eq!((params.mpm_mu, params.mpm_lambda), config.lame());
+    assert_eq!(params.mpm_friction, sand_alpha(30.0));
+
+    sph.solver = Solver::Mpm(MpmConfig {
+        material: MpmMaterial::snow(),
+        ..Default::default()
+    });
+    let params = SphParams::from_state(&sph);
+    assert_eq!(params.mpm_material, 2);
+    assert_eq!(params.mpm_critical_compression, 0.025);
+    assert_eq!(params.mpm_hardening, 10.0);
+}
+
+#[test]
+fn solver_structs_layout() {
+    // vec2 fields in WGSL, and a 16B aligned stats struct
+    assert_eq!(std::mem::size_of::<GPUSolverScratch>(), 64);
+    assert_eq!(std::mem::size_of::<GPUSolverStats>(), 32);
+    assert_eq!(std::mem::size_of::<GPUMacNode>(), 48);
+    assert_eq!(std::mem::size_of::<GPUMpmNode>(), 24);
+    assert_eq!(std::mem::size_of::<GPUMpmParticle>(), 48);
+    assert_eq!(std::mem::size_of::<GPUTimestep>(), 32);
+}
+
+#[test]
+fn sph_params_uniform_size() {
+    // uniform buffers need a multiple of 16 bytes
+    assert_eq!(std::mem::size_of::<SphParams>() % 16, 0);
+}
+
+#[test]
+fn grid_structs_uniform_size() {
+    assert_eq!(std::mem::size_of::<GridBuildParams>() % 16, 0);
+    assert_eq!(std::mem::size_of::<GridBoundsStats>(), 16);
+}
+
+#[test]
+fn grid_capacity_grows_in_powers_of_two() {
+    assert_eq!(grid_capacity_for(0), MIN_GRID_CELLS);
+    assert_eq!(grid_capacity_for(5000), 16384);
+
+    let cap = grid_capacity_for(70_000);
+    assert!(cap.is_power_of_two());
+    assert!(cap >= 70_000 * GRID_CAPACITY_HEADROOM);
+
+    // exploded particles must not allocate without limit
+    assert_eq!(grid_capacity_for(usize::MAX / 4), MAX_GRID_CELLS);
+}
+
+#[test]
+fn integrate_params_pack_domain() {
+    let mut config = IntegrateConfig::default();
+    config.domain.top = WallMode::Open;
+    config.domain.left = WallMode::Periodic;
+    config.domain.adhesion = [1.0, 2.0, 3.0, 4.0];
+
+    let params = IntegrateParams::from_config(&config);
+    assert_eq!(std::mem::size_of::<IntegrateParams>() % 16, 0);
+    assert_eq!(params.modes, [4, 0, 0, 3]);
+    assert_eq!(params.restitution, [0.0, 3.0, 3.0, 0.0]);
+    assert_eq!(params.adhesion, [0.0, 2.0, 3.0, 0.0]);
+    assert_eq!(params.min, [-5.0, 0.0]);
+
+    config.adaptive = Some(AdaptiveDt {
+        cfl: 0.3,
+        ..Default::default()
+    });
+    let params = IntegrateParams::from_config(&config);
+    assert_eq!(params.dt, config.dt);
+    assert_eq!(params.dt_safety, [0.3, 0.25, 0.125, 0.0]);
+    assert_eq!((params.dt_min, params.dt_max), (1e-5, 0.005));
+}
+
+#[test]
+fn particle_stride_matches_wgsl() {
+    // vec2 members give the WGSL struct an 8 byte alignment
+    assert_eq!(std::mem::size_of::<GPUParticle>(), 48);
+}
+
+#[test]
+fn colliders_pack_into_flat_data() {
+    // matches `struct Collider` in sph_density.wgsl
+    assert_eq!(std::mem::size_of::<GPUCollider>(), 80);
+
+    let triangle = vec![glam::Vec2::ZERO, glam::Vec2::X, glam::Vec2::Y];
+    let mut sph = SPHState::new(0.05, 998.0, 7.0, 0.4, 2.5);
+    sph.colliders = vec![
+        Collider::new(ColliderShape::Polygon { points: triangle }, glam::Vec2::ONE),
+        Collider::new(ColliderShape::Circle { radius: 0.5 }, glam::Vec2::ZERO),
+    ];
+    sph.colliders[1].adhesion = 0.5;
+
+    let (packed, data) = pack_colliders(sph.all_colliders());
+    assert_eq!(packed[0].kind, COLLIDER_POLYGON);
+    assert_eq!((packed[0].data_start, packed[0].data_len), (0, 6));
+    assert_eq!(packed[0].position, [1.0, 1.0]);
+    assert_eq!(packed[1].kind, COLLIDER_CIRCLE);
+    assert_eq!((packed[1].data_start, packed[1].data_len), (6, 0));
+    assert_eq!(packed[1].adhesion, 0.5);
+    assert_eq!(data, [0.0, 0.0, 1.0, 0.0, 0.0, 1.0]);
+    assert_eq!(SphParams::from_state(&sph).num_colliders, 2);
+}
+
+#[test]
+fn bodies_get_force_slots() {
+    let circle = || Collider::new(ColliderShape::Circle { radius: 0.5 }, glam::Vec2::ZERO);
+    let mut sph = SPHState::new(0.05, 998.0, 7.0, 0.4, 2.5);
+    sph.colliders.push(circle());
+    sph.bodies.push(RigidBody::new(circle(), 1.0));
+    sph.bodies.push(RigidBody::new(circle(), 2.0));
+
+    let (packed, _) = pack_scene(&sph);
+    let slots: Vec<i32> = packed.iter().map(|c| c.body).collect();
+    assert_eq!(slots, [-1, 0, 1]);
+    assert_eq!(SphParams::from_state(&sph).num_colliders, 3);
+}
+
+#[test]
+fn boundary_packs_in_front_of_the_collider_data() {
+    let triangle = vec![glam::Vec2::ZERO, glam::Vec2::X, glam::Vec2::Y];
+    let mut sph = SPHState::new(0.05, 998.0, 7.0, 0.4, 2.5);
+    sph.init_grid(4, 4, 0.025);
+    sph.colliders.push(Collider::new(
+        ColliderShape::Polygon { points: triangle },
+        glam::Vec2::ONE,
+    ));
+    sph.sample_boundary(&Domain::floor_and_walls(0.0, 0.5, -0.5), 0.025);
+    let boundary = sph.boundary.clone().unwrap();
+    let table = boundary.grid.table_size as usize;
+
+    let data = pack_boundary(&boundary);
+    assert_eq!(data.len(), table + 1 + 3 * boundary.pos.len());
+    assert_eq!(data[table], boundary.pos.len() as f32);
+    // the first particle of a bucket is where its start points
+    let b = (0..table).find(|&b| data[b + 1] > data[b]).unwrap();
+    let k = boundary.grid.entries[boundary.grid.starts[b] as usize] as usize;
+    let at = table + 1 + 3 * data[b] as usize;
+    assert_eq!(
+        data[at..at + 3],
+        [boundary.pos[k].x, boundary.pos[k].y, boundary.psi[k]]
+    );
+
+    let (packed, scene) = pack_scene(&sph);
+    assert_eq!(packed[0].data_start as usize, data.len());
+    assert_eq!(scene[..data.len()], data[..]);
+    let params = SphParams::from_state(&sph);
+    assert_eq!(params.num_boundary as usize, boundary.pos.len());
+    assert_eq!(params.boundary_table as usize, table);
+
+    // only Wcsph uses them
+    sph.solver = Solver::Pcisph(IterativeConfig::default());
+    assert_eq!(pack_scene(&sph).0[0].data_start, 0);
+    assert_eq!(SphParams::from_state(&sph).num_boundary, 0);
+}
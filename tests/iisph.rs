@@ -0,0 +1,67 @@
+mod common;
+
+use bevy_gpu_fluid::cpu::sph2d::{GridMode, IisphConfig, IterativeConfig, Solver};
+use common::{column, run};
+
+#[test]
+fn reports_iterations_and_error() {
+    let config = IisphConfig {
+        density: IterativeConfig {
+            tolerance: 0.0,
+            min_iterations: 1,
+            max_iterations: 5,
+        },
+        ..Default::default()
+    };
+    let mut sph = column(Solver::Iisph(config));
+    run(&mut sph, 0.002, 1);
+    assert_eq!(sph.stats.iterations, 5); // tolerance never met
+    assert!(sph.stats.density_error.is_finite());
+    assert_eq!(sph.solver.iterative(), Some(config.density));
+
+    sph.solver = Solver::Wcsph;
+    run(&mut sph, 0.002, 1);
+    assert_eq!(sph.stats.iterations, 0);
+}
+
+#[test]
+fn converges_every_step() {
+    let config = IisphConfig::default();
+    let mut sph = column(Solver::Iisph(config));
+    for _ in 0..100 {
+        run(&mut sph, 0.004, 1);
+        let stats = sph.stats;
+        assert!(
+            stats.iterations >= config.density.min_iterations,
+            "{stats:?}"
+        );
+        assert!(
+            stats.density_error <= config.density.tolerance
+                || stats.iterations == config.density.max_iterations,
+            "{stats:?}"
+        );
+    }
+    for p in &sph.particles {
+        assert!(p.pos.is_finite() && p.vel.length() < 10.0, "{p:?}");
+        assert!(p.p >= 0.0);
+    }
+}
+
+#[test]
+fn hashed_grid_gives_the_same_pressures() {
+    let mut dense = column(Solver::Iisph(IisphConfig::default()));
+    let mut hashed = column(Solver::Iisph(IisphConfig::default()));
+    hashed.grid_mode = GridMode::hashed_for(hashed.particles.len());
+    run(&mut dense, 0.002, 3);
+    run(&mut hashed, 0.002, 3);
+
+    assert_eq!(dense.stats.iterations, hashed.stats.iterations);
+    for (a, b) in dense.particles.iter().zip(&hashed.particles) {
+        assert!(
+            (a.p - b.p).abs() <= 1e-3 * a.p.abs().max(1.0),
+            "{} {}",
+            a.p,
+            b.p
+        );
+    }
+}
@@ -0,0 +1,89 @@
+use bevy_gpu_fluid::cpu::domain::Domain;
+use bevy_gpu_fluid::cpu::integrator::Integrator;
+use bevy_gpu_fluid::cpu::sph2d::{IterativeConfig, Particle, SPHState, Solver};
+use glam::Vec2;
+
+// one particle far above the floor: only gravity acts on it
+fn free_fall(integrator: Integrator, dt: f32, steps: usize) -> (Vec2, Vec2) {
+    let mut sph = SPHState::new(0.045, 1000.0, 3.0, 0.1, 1.6);
+    sph.integrator = integrator;
+    sph.init_grid(1, 1, 0.04);
+    sph.particles[0].pos = Vec2::new(0.5, 10.0);
+    let domain = Domain::floor_and_walls(0.0, 1.0, -0.5);
+    for _ in 0..steps {
+        sph.step_domain(dt, &domain);
+    }
+    (sph.particles[0].pos, sph.particles[0].vel)
+}
+
+#[test]
+fn second_order_integrators_fall_exactly() {
+    let (dt, steps) = (0.01, 50);
+    let t = dt * steps as f32;
+    let y = 10.0 - 0.5 * 9.81 * t * t;
+    for integrator in [
+        Integrator::Leapfrog,
+        Integrator::VelocityVerlet,
+        Integrator::Rk2,
+    ] {
+        let (pos, vel) = free_fall(integrator, dt, steps);
+        assert!((pos.y - y).abs() < 1e-4, "{integrator:?}: y = {}", pos.y);
+        assert!(
+            (vel.y + 9.81 * t).abs() < 1e-4,
+            "{integrator:?}: v = {}",
+            vel.y
+        );
+    }
+
+    // symplectic Euler is off by g t dt / 2
+    let (pos, vel) = free_fall(Integrator::SymplecticEuler, dt, steps);
+    assert!((pos.y - y + 0.5 * 9.81 * t * dt).abs() < 1e-4);
+    assert!((vel.y + 9.81 * t).abs() < 1e-4);
+}
+
+#[test]
+fn velocity_verlet_finishes_with_the_previous_dt() {
+    // two steps from rest; the step size changes, and so does the force
+    let (dt1, dt2) = (0.01, 0.02);
+    let a2 = Vec2::new(0.0, -3.0);
+    let two_steps = |a1: Vec2| {
+        let mut sph = SPHState::new(0.045, 1000.0, 3.0, 0.1, 1.6);
+        sph.integrator = Integrator::VelocityVerlet;
+        sph.particles.push(Particle::new(Vec2::ZERO));
+        sph.particles[0].acc = a1;
+        sph.integrate(dt1, &[]);
+        sph.particles[0].acc = a2;
+        sph.integrate(dt2, &[]);
+        sph
+    };
+
+    // a force free first step still has its half kick to finish
+    for a1 in [Vec2::new(0.0, -1.0), Vec2::ZERO] {
+        let sph = two_steps(a1);
+        // v = (a1 + a2) / 2 dt1 + a2 dt2
+        let vel = 0.5 * (a1 + a2) * dt1 + a2 * dt2;
+        assert!((sph.particles[0].vel - vel).length() < 1e-6, "{a1}");
+        let pos = 0.5 * a1 * dt1 * dt1 + 0.5 * (a1 + a2) * dt1 * dt2 + 0.5 * a2 * dt2 * dt2;
+        assert!((sph.particles[0].pos - pos).length() < 1e-6, "{a1}");
+    }
+
+    // another integrator in between leaves nothing to finish
+    let mut sph = two_steps(Vec2::ZERO);
+    sph.integrator = Integrator::SymplecticEuler;
+    sph.integrate(dt1, &[]);
+    assert_eq!(sph.particles[0].dt_prev, 0.0);
+}
+
+#[test]
+fn integrator_follows_the_solver() {
+    let mut sph = SPHState::new(0.045, 1000.0, 3.0, 0.1, 1.6);
+    assert_eq!(sph.active_integrator(), Integrator::SymplecticEuler);
+    sph.integrator = Integrator::Rk2;
+    assert_eq!(sph.active_integrator(), Integrator::Rk2);
+    assert_eq!(sph.active_integrator().stages(), 2);
+
+    // the pressure solvers keep symplectic Euler
+    sph.solver = Solver::Pcisph(IterativeConfig::default());
+    assert_eq!(sph.active_integrator(), Integrator::SymplecticEuler);
+    assert_eq!(sph.active_integrator().stages(), 1);
+}
@@ -0,0 +1,93 @@
+use bevy_gpu_fluid::cpu::domain::{Domain, WallMode};
+use bevy_gpu_fluid::cpu::sph2d::{MpmConfig, MpmMaterial, SPHState, Solver};
+use glam::Vec2;
+
+// about five particles per grid cell, like tests/flip.rs
+fn block(material: MpmMaterial, nx: usize, ny: usize, corner: Vec2) -> SPHState {
+    let mut sph = SPHState::new(0.045, 1000.0, 3.0, 0.02, 0.4);
+    sph.solver = Solver::Mpm(MpmConfig {
+        material,
+        ..Default::default()
+    });
+    sph.init_grid(nx, ny, 0.02);
+    for p in &mut sph.particles {
+        p.pos += corner;
+    }
+    sph
+}
+
+// floor that holds on to what lands on it
+fn sticky_floor(x_max: f32) -> Domain {
+    let mut domain = Domain::floor_and_walls(0.0, x_max, -0.1);
+    domain.bottom = WallMode::NoSlip;
+    domain
+}
+
+#[test]
+fn every_material_stays_finite() {
+    let materials = [
+        MpmConfig::default().material,
+        MpmMaterial::Jelly,
+        MpmMaterial::snow(),
+        MpmMaterial::sand(),
+    ];
+    for material in materials {
+        let mut sph = block(material, 10, 20, Vec2::ZERO);
+        let domain = Domain::floor_and_walls(0.0, 0.6, -0.1);
+        for _ in 0..200 {
+            sph.step_domain(0.002, &domain);
+        }
+        assert_eq!(sph.stats.iterations, 0); // explicit
+        for p in &sph.particles {
+            assert!(
+                p.pos.is_finite() && p.vel.length() < 5.0,
+                "{material:?} {p:?}"
+            );
+            assert!(p.rho > 0.0 && p.deformation.determinant() > 0.0);
+        }
+    }
+}
+
+#[test]
+fn sand_piles_up_where_fluid_spreads() {
+    let spread = |material: MpmMaterial| {
+        let mut sph = block(material, 10, 20, Vec2::ZERO);
+        let domain = sticky_floor(1.2);
+        for _ in 0..400 {
+            sph.step_domain(0.002, &domain);
+        }
+        sph.particles.iter().map(|p| p.pos.x).fold(0.0, f32::max)
+    };
+    let fluid = spread(MpmConfig::default().material);
+    let sand = spread(MpmMaterial::sand());
+    assert!(sand < 0.5, "{sand}");
+    assert!(fluid > 0.6, "{fluid}");
+}
+
+#[test]
+fn jelly_bounces_and_snow_stays_packed() {
+    // dropped from 0.3 m, lands after about 0.25 s
+    let drop = |material: MpmMaterial| {
+        let mut sph = block(material, 10, 10, Vec2::new(0.5, 0.3));
+        let domain = sticky_floor(1.2);
+        let mut rebound: f32 = 0.0; // highest bottom after the landing
+        for step in 0..500 {
+            sph.step_domain(0.002, &domain);
+            if step >= 150 {
+                let bottom = sph.particles.iter().map(|p| p.pos.y).fold(1.0, f32::min);
+                rebound = rebound.max(bottom);
+            }
+        }
+        let plastic_j =
+            sph.particles.iter().map(|p| p.plastic_j).sum::<f32>() / sph.particles.len() as f32;
+        (rebound, plastic_j)
+    };
+
+    let (jelly_rebound, jelly_j) = drop(MpmMaterial::Jelly);
+    assert!(jelly_rebound > 0.03, "{jelly_rebound}");
+    assert_eq!(jelly_j, 1.0); // purely elastic
+
+    let (snow_rebound, snow_j) = drop(MpmMaterial::snow());
+    assert!(snow_rebound < 0.01, "{snow_rebound}");
+    assert!(snow_j < 0.9, "{snow_j}"); // compacted for good
+}
@@ -0,0 +1,55 @@
+mod common;
+
+use bevy_gpu_fluid::cpu::domain::Domain;
+use bevy_gpu_fluid::cpu::sph2d::{Particle, PbfConfig, Solver};
+use common::{column, run};
+use glam::Vec2;
+
+#[test]
+fn runs_a_fixed_number_of_iterations() {
+    let mut sph = column(Solver::Pbf(PbfConfig {
+        iterations: 7,
+        ..Default::default()
+    }));
+    run(&mut sph, 0.004, 1);
+    assert_eq!(sph.stats.iterations, 7);
+    assert!(sph.stats.density_error.is_finite());
+    assert_eq!(sph.solver.iterative().unwrap().max_iterations, 7);
+}
+
+#[test]
+fn large_steps_do_not_explode() {
+    // a dam break at four times the step WCSPH survives
+    let mut sph = column(Solver::Pbf(PbfConfig::default()));
+    let domain = Domain::floor_and_walls(-0.01, 0.37, -0.1);
+    let dt = 0.016;
+    for _ in 0..125 {
+        sph.step_domain(dt, &domain);
+        for p in &sph.particles {
+            assert!(p.pos.is_finite() && p.vel.length() < 15.0, "{p:?}");
+        }
+    }
+    assert_eq!(sph.particles.len(), 300);
+    let top = sph.particles.iter().map(|p| p.pos.y).fold(0.0, f32::max);
+    assert!(top < 1.5, "{top}");
+}
+
+#[test]
+fn artificial_pressure_separates_clumps() {
+    // too few particles to be compressed, only s_corr pushes them apart
+    let pair = |artificial_pressure: f32| {
+        let mut sph = column(Solver::Pbf(PbfConfig {
+            artificial_pressure,
+            ..Default::default()
+        }));
+        sph.gravity = Vec2::ZERO;
+        sph.particles = vec![
+            Particle::new(Vec2::new(0.1, 0.1)),
+            Particle::new(Vec2::new(0.11, 0.1)),
+        ];
+        sph.step_domain(0.004, &Domain::floor_and_walls(-1.0, 1.0, -0.1));
+        sph.particles[1].pos.x - sph.particles[0].pos.x
+    };
+    assert!(pair(0.1) > 0.01, "{}", pair(0.1));
+    assert!((pair(0.0) - 0.01).abs() < 1e-6, "{}", pair(0.0));
+}
@@ -0,0 +1,70 @@
+use bevy_gpu_fluid::cpu::domain::Domain;
+use bevy_gpu_fluid::cpu::pcisph::pcisph_delta;
+use bevy_gpu_fluid::cpu::sph2d::{IterativeConfig, SPHState, Solver};
+
+fn column(solver: Solver) -> SPHState {
+    let mut sph = SPHState::new(0.045, 1000.0, 3.0, 0.2, 1.6);
+    sph.solver = solver;
+    sph.init_grid(10, 30, 0.04);
+    sph
+}
+
+// mean density over rho_0 after letting the column settle
+fn settle(sph: &mut SPHState, dt: f32, steps: usize) -> f32 {
+    let domain = Domain::floor_and_walls(-0.01, 0.37, -0.1);
+    for _ in 0..steps {
+        sph.step_domain(dt, &domain);
+    }
+    let mean = sph.particles.iter().map(|p| p.rho).sum::<f32>() / sph.particles.len() as f32;
+    mean / sph.rho_0
+}
+
+#[test]
+fn delta_scales_with_the_lattice() {
+    let delta = pcisph_delta(0.045, 1.6, 1000.0);
+    assert!(delta.is_finite() && delta > 0.0);
+
+    // a kernel smaller than the spacing has no neighbours to push
+    assert_eq!(pcisph_delta(0.01, 1.6, 1000.0), 0.0);
+}
+
+#[test]
+fn reports_iterations_and_error() {
+    let config = IterativeConfig {
+        tolerance: 0.0,
+        min_iterations: 1,
+        max_iterations: 4,
+    };
+    let mut sph = column(Solver::Pcisph(config));
+    settle(&mut sph, 0.002, 1);
+    assert_eq!(sph.stats.iterations, 4); // tolerance never met
+    assert!(sph.stats.density_error.is_finite());
+
+    // a loose tolerance stops at min_iterations
+    sph.solver = Solver::Pcisph(IterativeConfig {
+        tolerance: 1.0,
+        min_iterations: 2,
+        max_iterations: 10,
+    });
+    settle(&mut sph, 0.002, 1);
+    assert_eq!(sph.stats.iterations, 2);
+
+    sph.solver = Solver::Wcsph;
+    settle(&mut sph, 0.002, 1);
+    assert_eq!(sph.stats.iterations, 0);
+}
+
+#[test]
+fn compresses_less_than_wcsph() {
+    let mut wcsph = column(Solver::Wcsph);
+    let mut pcisph = column(Solver::Pcisph(IterativeConfig::default()));
+
+    let dt = 0.002;
+    let w = settle(&mut wcsph, dt, 300);
+    let p = settle(&mut pcisph, dt, 300);
+    assert!(p < w, "pcisph {p} vs wcsph {w}");
+    assert!(pcisph.stats.density_error < 0.05, "{:?}", pcisph.stats);
+    for particle in &pcisph.particles {
+        assert!(particle.pos.is_finite());
+    }
+}
@@ -1,4 +1,6 @@
-use bevy_gpu_fluid::cpu::sph2d::SPHState;
+use bevy_gpu_fluid::cpu::domain::{Domain, WallMode};
+use bevy_gpu_fluid::cpu::sph2d::{EquationOfState, GridMode, PressureForce, SPHState, Viscosity};
+use glam::Vec2;
 
 #[test]
 fn init_grid_n() {
@@ -70,4 +72,109 @@ fn integral_no_nan() {
     for _ in 0..50 { sph.step(0.001, x_max, x_min, bounce); }
     assert!(sph.particles.iter().all(|p| p.pos.is_finite()));
 
-}
\ No newline at end of file
+}
+
+#[test]
+fn hashed_grid_contains_all_particles() {
+    let h = 0.045;
+    let spacing = 0.04;
+    let rho_0 = 1000.0;
+    let m = rho_0 * spacing * spacing;
+
+    let mut sph = SPHState::new(h, rho_0, 3.0, 0.1, m);
+    sph.init_grid(8, 8, spacing);
+    let table = sph.build_hashed_grid(16); // small table -> lots of collisions
+
+    assert_eq!(table.starts.len(), 17);
+    assert_eq!(*table.starts.last().unwrap() as usize, sph.particles.len());
+    let mut seen = table.entries.clone();
+    seen.sort();
+    assert!(seen.iter().enumerate().all(|(i, &j)| i as u32 == j));
+}
+
+#[test]
+fn hashed_matches_dense() {
+    let h = 0.045;
+    let spacing = 0.04;
+    let rho_0 = 1000.0;
+    let m = rho_0 * spacing * spacing;
+
+    let mut dense = SPHState::new(h, rho_0, 3.0, 0.1, m);
+    dense.init_grid(10, 10, spacing);
+    let mut hashed = SPHState::new(h, rho_0, 3.0, 0.1, m);
+    hashed.init_grid(10, 10, spacing);
+    hashed.grid_mode = GridMode::Hashed { table_size: 32 };
+
+    for _ in 0..5 {
+        dense.step(0.001, 3.0, -3.0, -0.5);
+        hashed.step(0.001, 3.0, -3.0, -0.5);
+    }
+    for (a, b) in dense.particles.iter().zip(&hashed.particles) {
+        assert!((a.rho - b.rho).abs() <= 1e-3 * a.rho.abs().max(1.0));
+        assert!((a.pos - b.pos).length() < 1e-5);
+    }
+}
+
+#[test]
+fn equations_of_state() {
+    let (rho_0, k) = (1000.0, 3.0);
+
+    let clamped = EquationOfState::LinearClamped;
+    assert_eq!(clamped.pressure(1010.0, rho_0, k), 30.0);
+    assert_eq!(clamped.pressure(990.0, rho_0, k), 0.0);
+
+    // tension below rest density
+    assert_eq!(EquationOfState::Linear.pressure(990.0, rho_0, k), -30.0);
+
+    // B = rho_0 c^2 / 7; near rest density p ~ c^2 (rho - rho_0)
+    let tait = EquationOfState::Tait {
+        speed_of_sound: 20.0,
+    };
+    assert_eq!(tait.pressure(rho_0, rho_0, k), 0.0);
+    let p = tait.pressure(1001.0, rho_0, k);
+    assert!((p - 400.0).abs() < 2.0, "{p}");
+    assert!(tait.pressure(999.0, rho_0, k) < 0.0);
+}
+
+#[test]
+fn tait_is_used_by_the_solver() {
+    let mut sph = SPHState::new(0.045, 1000.0, 3.0, 0.1, 1.6);
+    sph.eos = EquationOfState::Tait {
+        speed_of_sound: 30.0,
+    };
+    sph.init_grid(5, 5, 0.04);
+    sph.density_pressure_calc();
+    for p in &sph.particles {
+        assert_eq!(p.p, sph.eos.pressure(p.rho, sph.rho_0, sph.k));
+    }
+}
+
+#[test]
+fn symmetric_forces_conserve_momentum() {
+    let mut sph = SPHState::new(0.045, 1000.0, 2000.0, 0.1, 1.6);
+    sph.gravity = Vec2::ZERO;
+    sph.eos = EquationOfState::Linear;
+    sph.pressure_force = PressureForce::Symmetric;
+    sph.viscosity = Viscosity::Artificial {
+        alpha: 0.1,
+        speed_of_sound: 20.0,
+    };
+    // compressed, converging block so both terms are active
+    sph.init_grid(12, 9, 0.03);
+    for p in &mut sph.particles {
+        p.vel = Vec2::new(-p.pos.x, 0.5 * p.pos.x - p.pos.y);
+    }
+    let before: Vec<Vec2> = sph.particles.iter().map(|p| p.vel).collect();
+
+    let open = Domain::new(Vec2::splat(-10.0), Vec2::splat(10.0), WallMode::FreeSlip);
+    sph.step_domain(0.0005, &open);
+
+    let mut total = Vec2::ZERO;
+    let mut moved = 0.0;
+    for (p, v) in sph.particles.iter().zip(&before) {
+        total += p.vel - *v;
+        moved += (p.vel - *v).length();
+    }
+    assert!(moved > 1e-3, "no forces acted");
+    assert!(total.length() < 1e-4 * moved, "{total:?} of {moved}");
+}
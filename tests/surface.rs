@@ -0,0 +1,58 @@
+use bevy_gpu_fluid::cpu::domain::Domain;
+use bevy_gpu_fluid::cpu::sph2d::SPHState;
+use glam::Vec2;
+
+// 10 x 10 block without gravity or pressure (rho_0 above its density), so
+// surface tension is the only force
+fn blob(surface_tension: f32) -> SPHState {
+    let mut sph = SPHState::new(0.045, 5000.0, 3.0, 0.1, 1.6);
+    sph.gravity = Vec2::ZERO;
+    sph.surface_tension = surface_tension;
+    sph.init_grid(10, 10, 0.02);
+    for p in &mut sph.particles {
+        p.pos += Vec2::splat(0.5);
+    }
+    sph
+}
+
+fn centroid(sph: &SPHState) -> Vec2 {
+    sph.particles.iter().map(|p| p.pos).sum::<Vec2>() / sph.particles.len() as f32
+}
+
+#[test]
+fn normals_point_into_the_fluid() {
+    let mut sph = blob(0.0);
+    sph.density_pressure_calc();
+    let normals = sph.normals();
+
+    // init_grid fills rows of 10: 40 is on the left edge, 45 inside, 49 on the right
+    assert!(normals[40].x > 0.2 && normals[40].y.abs() < 0.05 * normals[40].x);
+    assert!(normals[49].x < -0.2);
+    assert!(normals[95].y < -0.2); // top row
+    assert!(normals[45].length() < 0.05 * normals[40].length());
+}
+
+#[test]
+fn tension_pulls_the_corners_in() {
+    let mut sph = blob(0.5);
+    sph.step_domain(1e-6, &Domain::floor_and_walls(0.0, 2.0, -0.5));
+    let center = centroid(&sph);
+
+    for corner in [0, 9, 90, 99] {
+        let p = &sph.particles[corner];
+        assert!(
+            p.acc.dot(center - p.pos) > 0.0,
+            "corner {corner}: {}",
+            p.acc
+        );
+    }
+    // pairwise symmetric, no net force on the blob
+    let net: Vec2 = sph.particles.iter().map(|p| p.acc).sum();
+    let total: f32 = sph.particles.iter().map(|p| p.acc.length()).sum();
+    assert!(net.length() < 1e-3 * total, "{net} of {total}");
+
+    // no tension, nothing moves
+    let mut sph = blob(0.0);
+    sph.step_domain(1e-6, &Domain::floor_and_walls(0.0, 2.0, -0.5));
+    assert!(sph.particles.iter().all(|p| p.acc == Vec2::ZERO));
+}
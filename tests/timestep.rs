@@ -0,0 +1,141 @@
+use bevy_gpu_fluid::cpu::domain::Domain;
+use bevy_gpu_fluid::cpu::sph2d::{
+    EquationOfState, IisphConfig, MpmConfig, SPHState, Solver, Viscosity,
+};
+use bevy_gpu_fluid::cpu::timestep::{AdaptiveDt, SimClock};
+use glam::Vec2;
+
+#[test]
+fn tightest_limit_wins() {
+    let config = AdaptiveDt::default();
+    let h = 0.04;
+    let close = |a: f32, b: f32| (a - b).abs() < 1e-7;
+
+    // nothing moves: the cap
+    assert_eq!(config.dt(h, 0.0, 0.0, 0.0, 0.0), config.max_dt);
+    // 0.4 * 0.04 / (2 + 8)
+    assert!(close(config.dt(h, 2.0, 0.0, 8.0, 0.0), 0.0016));
+    // 0.25 * sqrt(0.04 / 400)
+    assert!(close(config.dt(h, 0.0, 400.0, 0.0, 0.0), 0.0025));
+    // 0.125 * 0.04^2 / 0.1
+    assert!(close(config.dt(h, 0.0, 0.0, 0.0, 0.1), 0.002));
+    // all three at once
+    assert!(close(config.dt(h, 2.0, 400.0, 8.0, 0.1), 0.0016));
+    // a blown-up particle: the floor
+    assert_eq!(config.dt(h, f32::INFINITY, 0.0, 0.0, 0.0), config.min_dt);
+}
+
+#[test]
+fn limits_follow_the_solver() {
+    let mut sph = SPHState::new(0.045, 1000.0, 4.0, 0.1, 1.6);
+    assert_eq!(sph.sound_speed(), 2.0); // sqrt(k)
+    assert_eq!(sph.kinematic_viscosity(), 0.1);
+
+    sph.eos = EquationOfState::Tait {
+        speed_of_sound: 20.0,
+    };
+    assert_eq!(sph.sound_speed(), 20.0);
+    sph.viscosity = Viscosity::Artificial {
+        alpha: 0.1,
+        speed_of_sound: 20.0,
+    };
+    assert!((sph.kinematic_viscosity() - 0.1 * 20.0 * 0.045 / 8.0).abs() < 1e-7);
+
+    // the pressure solvers keep the density, only the flow limits dt
+    sph.solver = Solver::Iisph(IisphConfig::default());
+    assert_eq!(sph.sound_speed(), 0.0);
+    sph.solver = Solver::Mpm(MpmConfig::default()); // bulk modulus 50000
+    assert!((sph.sound_speed() - 50.0f32.sqrt()).abs() < 1e-5);
+}
+
+#[test]
+fn splash_takes_smaller_steps_than_rest() {
+    let mut sph = SPHState::new(0.045, 1000.0, 3.0, 0.01, 1.6);
+    sph.init_grid(10, 20, 0.04);
+    for p in &mut sph.particles {
+        p.pos += Vec2::new(0.02, 0.02);
+    }
+    let domain = Domain::floor_and_walls(0.0, 1.6, -0.1);
+    let config = AdaptiveDt::default();
+    let rest = sph.adaptive_dt(&config);
+
+    let mut time = 0.0;
+    let mut smallest = rest;
+    while time < 0.6 {
+        let dt = sph.adaptive_dt(&config);
+        smallest = smallest.min(dt);
+        sph.step_domain(dt, &domain);
+        time += dt;
+    }
+    // the collapsing column speeds up, so the steps shrink
+    assert!(smallest < 0.9 * rest, "{smallest} {rest}");
+    for p in &sph.particles {
+        assert!(p.pos.is_finite() && p.vel.length() < 10.0, "{p:?}");
+    }
+}
+
+#[test]
+fn clock_time_does_not_depend_on_frame_rate() {
+    let dt = 0.004;
+    let mut slow = SimClock::default();
+    let mut fast = SimClock::default();
+    for _ in 0..30 {
+        slow.advance(1.0 / 30.0, dt);
+    }
+    for _ in 0..144 {
+        fast.advance(1.0 / 144.0, dt);
+    }
+    // one second each, within a step
+    assert!((slow.time - 1.0).abs() <= dt as f64, "{}", slow.time);
+    assert!((fast.time - 1.0).abs() <= dt as f64, "{}", fast.time);
+    assert!((8..=9).contains(&slow.steps_this_frame));
+
+    let mut half = SimClock {
+        time_scale: 0.5,
+        ..Default::default()
+    };
+    assert_eq!(half.advance(0.02, dt), 2);
+}
+
+#[test]
+fn clock_pause_single_step_and_backlog() {
+    let dt = 0.001;
+    let mut clock = SimClock {
+        paused: true,
+        ..Default::default()
+    };
+    assert_eq!(clock.advance(0.1, dt), 0);
+    clock.step_once();
+    assert_eq!(clock.advance(0.1, dt), 1);
+    assert_eq!(clock.advance(0.1, dt), 0);
+    assert_eq!(clock.step, 1);
+
+    // a long frame is capped and its backlog dropped
+    clock.paused = false;
+    assert_eq!(clock.advance(1.0, dt), clock.max_steps_per_frame);
+    assert_eq!(clock.advance(0.0, dt), 0);
+
+    // variable steps out of the same accumulator
+    clock.accumulate(0.0035);
+    assert!(clock.consume(0.002) && clock.consume(0.001));
+    assert!(!clock.consume(0.001));
+
+    let mut lockstep = SimClock::lockstep();
+    assert_eq!(lockstep.advance(1.0, dt), 1);
+    assert_eq!(lockstep.advance(0.0, dt), 1);
+}
+
+#[test]
+fn clock_time_is_the_steps_it_paced() {
+    // an adaptive dt that changes from frame to frame: the GPU runs
+    // steps_this_frame steps of SimClock::dt
+    let mut clock = SimClock::default();
+    let mut simulated = 0.0;
+    for dt in [0.004, 0.001, 0.0025, 0.004, 0.0005] {
+        let steps = clock.advance(1.0 / 60.0, dt);
+        assert!(steps > 0);
+        assert_eq!(clock.dt, dt);
+        simulated += steps as f64 * clock.dt as f64;
+    }
+    assert!((clock.time - simulated).abs() < 1e-9, "{}", clock.time);
+}
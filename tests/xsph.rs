@@ -0,0 +1,53 @@
+use bevy_gpu_fluid::cpu::sph2d::{FlipConfig, PbfConfig, SPHState, Solver};
+use glam::Vec2;
+
+// a block with alternating velocities, the noise XSPH should damp
+fn noisy_block() -> SPHState {
+    let mut sph = SPHState::new(0.045, 1000.0, 3.0, 0.1, 1.6);
+    sph.xsph = 0.5;
+    sph.init_grid(8, 8, 0.02);
+    for (i, p) in sph.particles.iter_mut().enumerate() {
+        // checkerboard, init_grid fills rows of 8
+        let sign = if (i % 8 + i / 8) % 2 == 0 { 1.0 } else { -1.0 };
+        p.vel = Vec2::new(0.3, 0.1) + sign * Vec2::new(0.2, -0.4);
+    }
+    sph.density_pressure_calc();
+    sph
+}
+
+fn momentum(sph: &SPHState) -> Vec2 {
+    sph.particles.iter().map(|p| sph.m * p.vel).sum()
+}
+
+fn spread(sph: &SPHState) -> f32 {
+    let mean = momentum(sph) / (sph.m * sph.particles.len() as f32);
+    sph.particles
+        .iter()
+        .map(|p| (p.vel - mean).length_squared())
+        .sum()
+}
+
+#[test]
+fn xsph_smooths_and_keeps_momentum() {
+    let mut sph = noisy_block();
+    let (momentum_0, spread_0) = (momentum(&sph), spread(&sph));
+    sph.xsph_smooth();
+
+    // symmetric in i and j, so the momentum exchange cancels
+    assert!((momentum(&sph) - momentum_0).length() < 1e-4);
+    let spread_1 = spread(&sph);
+    assert!(spread_1 < 0.5 * spread_0, "{spread_1} vs {spread_0}");
+}
+
+#[test]
+fn xsph_follows_the_solver() {
+    let mut sph = noisy_block();
+    assert!(sph.xsph_active());
+    sph.solver = Solver::Pbf(PbfConfig::default()); // its own XSPH pass
+    assert!(!sph.xsph_active());
+    sph.solver = Solver::Flip(FlipConfig::default());
+    assert!(!sph.xsph_active());
+    sph.solver = Solver::Wcsph;
+    sph.xsph = 0.0;
+    assert!(!sph.xsph_active());
+}